@@ -0,0 +1,78 @@
+use std::{
+    collections::VecDeque,
+    fmt::Write as _,
+    fs,
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+    };
+
+use crate::options::Cli;
+use crate::save::output_suffixes;
+use crate::{create_owned_dir, ExecutionArtifact, Verdict};
+
+/// Writes everything about every round into `<dir>/round_0007/`, passing rounds included,
+/// removing the oldest round directories beyond `--keep-last`.
+pub struct ArtifactKeeper {
+    dir: PathBuf,
+    keep_last: Option<usize>,
+    kept: VecDeque<PathBuf>,
+}
+
+/// what gets kept of a round
+pub struct KeptRound<'a> {
+    pub round: u64,
+    pub seed: u64,
+    pub verdict: Verdict,
+    pub duration: Duration,
+    pub input: Option<&'a str>,
+    pub executions: Vec<ExecutionArtifact>,
+}
+
+impl ArtifactKeeper {
+    pub fn new(dir: &Path, keep_last: Option<usize>) -> io::Result<Self> {
+        create_owned_dir(dir)?;
+        Ok(ArtifactKeeper { dir: dir.to_path_buf(), keep_last, kept: VecDeque::new() })
+    }
+
+    /// writes the directory of the round, replacing the one of an earlier session, and returns its path
+    pub fn keep(&mut self, args: &Cli, round: &KeptRound) -> io::Result<PathBuf> {
+        let dir = self.dir.join(format!("round_{:04}", round.round));
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        fs::create_dir(&dir)?;
+
+        if let Some(input) = round.input {
+            fs::write(dir.join("input.in"), input)?;
+        }
+        // retried executions get numbered files as their names repeat
+        let names = output_suffixes(round.executions.iter().map(|e| args.source_of(&e.stats.path)));
+        let mut commands = String::new();
+        let mut timing = format!("round {}: verdict {}, seed {}, took {:.3}s\n",
+            round.round, round.verdict.code(), round.seed, round.duration.as_secs_f64());
+        for (execution, name) in round.executions.iter().zip(names) {
+            execution.stdout.save(&dir.join(&name))?;
+            fs::write(dir.join(name.replace(".out", ".err")), &execution.stderr)?;
+            let _ = writeln!(commands, "{}", execution.command);
+            let stats = &execution.stats;
+            let status = match (stats.killed, stats.exit_code, stats.signal) {
+                (true, ..) => "killed".to_string(),
+                (_, _, Some(signal)) => format!("signal {}", signal),
+                (_, Some(code), _) => format!("exit code {}", code),
+                _ => "unknown status".to_string(),
+            };
+            let _ = writeln!(timing, "{}: {:.3}s, {}", args.source_of(&stats.path).display(), stats.seconds, status);
+        }
+        fs::write(dir.join("cmd.txt"), commands)?;
+        fs::write(dir.join("timing.txt"), timing)?;
+
+        self.kept.push_back(dir.clone());
+        while self.keep_last.is_some_and(|n| self.kept.len() > n) {
+            if let Some(old) = self.kept.pop_front() {
+                fs::remove_dir_all(old)?;
+            }
+        }
+        Ok(dir)
+    }
+}
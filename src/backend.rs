@@ -0,0 +1,210 @@
+use std::{
+    env,
+    ffi::{OsStr, OsString},
+    path::{Path, PathBuf},
+    process::Command,
+    };
+use std::error::Error;
+use string_error::{into_err, static_err};
+
+use crate::runner::Limits;
+use crate::say;
+
+/// Where the compilers and the programmes run: on this machine, or in containers of the `--docker` image.
+#[derive(Clone, Debug, Default)]
+pub enum Backend {
+    #[default]
+    Local,
+    Docker {
+        docker: PathBuf,
+        image: String,
+        /// uid:gid, so that the binaries compiled in the containers belong to the user
+        user: Option<String>,
+    },
+    /// another machine reached with ssh, see `Backend::open_remote`
+    Remote {
+        ssh: PathBuf,
+        host: String,
+        dir: String,
+        /// the files copied into `dir`, with their paths there
+        files: Vec<(PathBuf, String)>,
+    },
+}
+
+/// what ssh exits with when it couldn't connect, rather than the exit code of the command
+const SSH_FAILURE: i32 = 255;
+/// the options of every ssh and scp, which never ask for a password
+const SSH_OPTIONS: [&str; 4] = ["-o", "BatchMode=yes", "-o", "ConnectTimeout=10"];
+
+/// the word quoted for the remote shell
+fn quoted(word: &OsStr) -> String {
+    format!("'{}'", word.to_string_lossy().replace('\'', r"'\''"))
+}
+
+/// the shell command bounding the memory of what it runs next, in bytes
+fn ulimit(bytes: usize) -> String {
+    format!("ulimit -v {} && ", bytes / 1000)
+}
+
+/// The command run by `sh` once its memory is bounded, for a programme which can't be bounded after it starts.
+pub fn ulimited(cmd: &Command, bytes: usize) -> Command {
+    let mut limited = Command::new("sh");
+    limited.arg("-c").arg(format!("{}exec \"$0\" \"$@\"", ulimit(bytes))).arg(cmd.get_program()).args(cmd.get_args());
+    for (name, value) in cmd.get_envs() {
+        match value {
+            Some(value) => limited.env(name, value),
+            None => limited.env_remove(name),
+        };
+    }
+    if let Some(dir) = cmd.get_current_dir() {
+        limited.current_dir(dir);
+    }
+    limited
+}
+
+impl Backend {
+    /// Checks that docker is installed and has the image, pulling it when it hasn't, so that neither
+    /// fails only once the rounds run.
+    pub fn open(image: Option<&str>) -> Result<Self, Box<dyn Error>> {
+        let Some(image) = image else { return Ok(Backend::Local) };
+        let docker = which::which("docker").map_err(|_| static_err("couldn't find docker, which --docker runs the programmes with"))?;
+        let inspected = Command::new(&docker).args(["image", "inspect", image]).output()?;
+        if !inspected.status.success() {
+            say!("pulling the docker image {}", image);
+            let pulled = Command::new(&docker).args(["pull", image]).output()?;
+            if !pulled.status.success() {
+                return Err(into_err(format!("couldn't pull the docker image {}: {}", image, String::from_utf8_lossy(&pulled.stderr).trim())));
+            }
+        }
+        let id = |flag| Command::new("id").arg(flag).output().ok()
+            .filter(|out| out.status.success())
+            .map(|out| String::from_utf8_lossy(&out.stdout).trim().to_string());
+        let user = id("-u").zip(id("-g")).map(|(uid, gid)| format!("{}:{}", uid, gid));
+        Ok(Backend::Docker { docker, image: image.to_string(), user })
+    }
+
+    /// Copies the files into the directory of the host once, where they then run, reporting a host
+    /// which can't be reached before the first round.
+    pub fn open_remote(host: &str, dir: &str, files: &[&Path]) -> Result<Self, Box<dyn Error>> {
+        let find = |name| which::which(name).map_err(|_| into_err(format!("couldn't find {}, which --remote runs the programme with", name)));
+        let (ssh, scp) = (find("ssh")?, find("scp")?);
+        let failed = |what: &str, out: std::process::Output| into_err(format!("couldn't {} {}: {}", what, host, String::from_utf8_lossy(&out.stderr).trim()));
+        let out = Command::new(&ssh).args(SSH_OPTIONS).arg(host).arg(format!("mkdir -p {}", quoted(OsStr::new(dir)))).output()?;
+        if !out.status.success() {
+            return Err(failed("reach", out));
+        }
+        let mut copied = vec![];
+        for file in files {
+            let name = file.file_name().ok_or_else(|| into_err(format!("{} isn't a file", file.display())))?;
+            let remote = format!("{}/{}", dir.trim_end_matches('/'), name.to_string_lossy());
+            let out = Command::new(&scp).args(SSH_OPTIONS).arg("-q").arg(file).arg(format!("{}:{}", host, remote)).output()?;
+            if !out.status.success() {
+                return Err(failed(&format!("copy {} to", file.display()), out));
+            }
+            copied.push((file.to_path_buf(), remote));
+        }
+        Ok(Backend::Remote { ssh, host: host.to_string(), dir: dir.to_string(), files: copied })
+    }
+
+    /// the programme looked up in PATH, in a container or on a host it's looked up when it runs
+    pub fn which(&self, name: impl AsRef<OsStr>) -> Option<PathBuf> {
+        match self {
+            Backend::Local => which::which(name).ok(),
+            Backend::Docker { .. } | Backend::Remote { .. } => Some(PathBuf::from(name.as_ref())),
+        }
+    }
+
+    /// The command which runs `cmd` in the backend. A container gets the working directory and the
+    /// directories of the files of the command mounted read-only at the same paths, the `writable`
+    /// directories read-write, and is bound by the limits.
+    pub fn command(&self, cmd: Command, writable: &[&Path], limits: Limits) -> Command {
+        let (docker, image, user) = match self {
+            Backend::Local => return cmd,
+            Backend::Docker { docker, image, user } => (docker, image, user),
+            Backend::Remote { .. } => return self.remote_command(&cmd, limits),
+        };
+        let cwd = cmd.get_current_dir().map(Path::to_path_buf)
+            .or_else(|| env::current_dir().ok())
+            .unwrap_or_default();
+        let program = PathBuf::from(cmd.get_program());
+        let files = std::iter::once(program.as_path())
+            .chain(cmd.get_args().map(Path::new))
+            .filter(|file| file.is_absolute() && file.exists())
+            .filter_map(Path::parent);
+        let mut mounts = writable.iter().map(|dir| (dir.to_path_buf(), true)).collect::<Vec<_>>();
+        for dir in std::iter::once(cwd.as_path()).chain(files) {
+            if !mounts.iter().any(|(mounted, _)| dir.starts_with(mounted)) {
+                mounts.push((dir.to_path_buf(), false));
+            }
+        }
+
+        let mut wrapped = Command::new(docker);
+        wrapped.args(["run", "--rm", "-i"]);
+        if let Some(user) = user {
+            wrapped.args(["--user", user]);
+        }
+        for (dir, rw) in &mounts {
+            wrapped.arg("-v").arg(format!("{0}:{0}{1}", dir.display(), if *rw { "" } else { ":ro" }));
+        }
+        wrapped.arg("-w").arg(&cwd);
+        for (name, value) in cmd.get_envs() {
+            if let Some(value) = value {
+                let mut variable = OsString::from(name);
+                variable.push("=");
+                variable.push(value);
+                wrapped.arg("-e").arg(variable);
+            }
+        }
+        if let Some(bytes) = limits.memory {
+            wrapped.arg(format!("--memory={}", bytes)).arg(format!("--memory-swap={}", bytes));
+        }
+        wrapped.arg(image);
+        // killing docker leaves the container running, so it's bound from the inside as well
+        if let Some(time) = limits.time {
+            wrapped.args(["timeout", "--signal=KILL", &format!("{:.3}", time.as_secs_f64())]);
+        }
+        wrapped.arg(program).args(cmd.get_args()).current_dir(&cwd);
+        wrapped
+    }
+
+    /// The ssh command running `cmd` in the directory of the host, its copied files replaced by the copies
+    /// and programmes looked up in the PATH of the host. The limits are those of `ulimit` and `timeout` there.
+    fn remote_command(&self, cmd: &Command, limits: Limits) -> Command {
+        let Backend::Remote { ssh, host, dir, files } = self else { unreachable!("only remote commands run over ssh") };
+        let remote = |word: &OsStr| match files.iter().find(|(file, _)| file.as_os_str() == word) {
+            Some((_, copy)) => quoted(OsStr::new(copy)),
+            None => quoted(word),
+        };
+        let program = Path::new(cmd.get_program());
+        let program = match files.iter().any(|(file, _)| file == program) {
+            false if program.is_absolute() => quoted(program.file_name().unwrap_or_default()),
+            _ => remote(program.as_os_str()),
+        };
+        let mut script = format!("cd {} && ", quoted(OsStr::new(dir)));
+        if let Some(bytes) = limits.memory {
+            script += &ulimit(bytes);
+        }
+        for (name, value) in cmd.get_envs() {
+            if let Some(value) = value {
+                script += &format!("export {}={} && ", name.to_string_lossy(), quoted(value));
+            }
+        }
+        if let Some(time) = limits.time {
+            script += &format!("timeout --signal=KILL {:.3} ", time.as_secs_f64());
+        }
+        script += &std::iter::once(program).chain(cmd.get_args().map(remote)).collect::<Vec<_>>().join(" ");
+
+        let mut wrapped = Command::new(ssh);
+        wrapped.args(SSH_OPTIONS).arg(host).arg(script);
+        wrapped
+    }
+
+    /// whether the execution failed because the host couldn't be reached, not because of the programme
+    pub fn lost_connection(&self, status: &process_control::ExitStatus) -> bool {
+        matches!(self, Backend::Remote { .. }) && status.code() == Some(i64::from(SSH_FAILURE))
+    }
+
+    pub fn is_local(&self) -> bool {
+        matches!(self, Backend::Local)
+    }
+}
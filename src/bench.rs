@@ -0,0 +1,241 @@
+use std::{
+    error::Error,
+    io,
+    path::PathBuf,
+    process::{Child, ExitStatus},
+    thread,
+    time::{Duration, Instant},
+    };
+use string_error::into_err;
+
+use crate::options::Cli;
+use crate::runner::{Invocation, Processes};
+use crate::session::planned_rounds;
+use crate::{drain, generate_input, generated_input, get_execution_limits, start_prog, CancellationToken, Role, RoundCtx, CANCEL_POLL};
+
+/// What a run of a programme took, the cpu time and the memory only being known of a local process on unix.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Measured {
+    pub wall: Duration,
+    /// user and system time
+    pub cpu: Option<Duration>,
+    /// peak resident memory
+    pub memory_kb: Option<u64>,
+}
+
+/// a measure of a run, and the seed of the input it ran on
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sample {
+    pub seed: u64,
+    pub value: f64,
+}
+
+/// The distribution of a measure over the runs, with the seeds of the inputs of its extremes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stats {
+    pub count: usize,
+    pub mean: f64,
+    pub median: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub min: Sample,
+    pub max: Sample,
+}
+
+impl Stats {
+    /// none without samples, the percentiles are the nearest ranks
+    pub fn of(samples: &[Sample]) -> Option<Self> {
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(|a, b| a.value.total_cmp(&b.value));
+        let (min, max) = (*sorted.first()?, *sorted.last()?);
+        let rank = |p: f64| sorted[((p * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len()) - 1].value;
+        Some(Stats {
+            count: sorted.len(),
+            mean: sorted.iter().map(|s| s.value).sum::<f64>() / sorted.len() as f64,
+            median: rank(0.5),
+            p90: rank(0.9),
+            p99: rank(0.99),
+            min,
+            max,
+        })
+    }
+}
+
+/// The runs of a programme over the benchmark: its wall and cpu times in seconds and its peak memory in
+/// kilobytes, and how many runs failed.
+#[derive(Clone, Debug, Default)]
+pub struct Samples {
+    pub path: PathBuf,
+    pub wall: Vec<Sample>,
+    pub cpu: Vec<Sample>,
+    pub memory: Vec<Sample>,
+    pub failed: u64,
+    /// why the first failing run failed
+    pub first_error: Option<String>,
+}
+
+impl Samples {
+    fn add(&mut self, seed: u64, measured: Result<Measured, Box<dyn Error>>) {
+        match measured {
+            Ok(measured) => {
+                self.wall.push(Sample { seed, value: measured.wall.as_secs_f64() });
+                self.cpu.extend(measured.cpu.map(|cpu| Sample { seed, value: cpu.as_secs_f64() }));
+                self.memory.extend(measured.memory_kb.map(|kb| Sample { seed, value: kb as f64 }));
+            },
+            Err(e) => {
+                self.failed += 1;
+                self.first_error.get_or_insert_with(|| e.to_string());
+            },
+        }
+    }
+}
+
+/// The programme and then the references, as they were benchmarked.
+#[derive(Clone, Debug, Default)]
+pub struct Bench {
+    pub programmes: Vec<Samples>,
+    /// the inputs the programmes ran on
+    pub inputs: u64,
+    /// the rounds whose generator failed, which nothing ran in
+    pub skipped: u64,
+}
+
+/// Runs the programme and the references one after the other on the input of every round, `--warmup`
+/// times unmeasured and then `--runs-per-input` times, without comparing their outputs. `progress` is
+/// called after every round, until the rounds are over or the token is cancelled.
+pub fn bench(args: &Cli, cancellation: &CancellationToken, mut progress: impl FnMut(u64, &Bench)) -> Bench {
+    let paths: Vec<(&PathBuf, Role)> = std::iter::once((&args.programs.program, Role::Program))
+        .chain(args.programs.reference.iter().map(|r| (r, Role::Reference)))
+        .collect();
+    let mut bench = Bench { programmes: paths.iter().map(|(path, _)| Samples { path: path.to_path_buf(), ..Default::default() }).collect(), ..Default::default() };
+    let mut round = 0;
+    while planned_rounds(args).is_none_or(|rounds| round < rounds) && !cancellation.is_cancelled() {
+        let ctx = RoundCtx::new(args, round).with_cancellation(cancellation.clone());
+        match generate_input(&ctx, &Processes).and_then(|generated| generated_input(generated, &ctx)) {
+            Ok(input) => {
+                for ((path, role), samples) in paths.iter().zip(&mut bench.programmes) {
+                    let mut invocation = Invocation::new(path, *role).with_input(&input);
+                    if *role == Role::Program {
+                        invocation.limits = get_execution_limits(args);
+                    }
+                    for _ in 0..args.warmup {
+                        let _ = measure(&invocation, &ctx);
+                    }
+                    for _ in 0..args.runs_per_input.max(1) {
+                        samples.add(ctx.seed, measure(&invocation, &ctx));
+                    }
+                }
+                bench.inputs += 1;
+            },
+            Err(_) => bench.skipped += 1,
+        }
+        progress(round, &bench);
+        round += 1;
+    }
+    bench
+}
+
+/// Runs the programme once, killing it once it exceeds its time limit or the session is cancelled. It fails
+/// unless it exits successfully.
+pub fn measure(invocation: &Invocation, ctx: &RoundCtx) -> Result<Measured, Box<dyn Error>> {
+    let started = Instant::now();
+    let (mut child, _) = start_prog(invocation, &ctx.args.programs)?;
+    let deadline = invocation.limits.time.map(|t| started + t);
+    // the pipes are emptied so that the programme never blocks on them, the outputs don't matter
+    drain(child.stdout.take());
+    drain(child.stderr.take());
+    let (status, mut measured) = match wait_measured(&mut child, started, deadline, ctx)? {
+        Some(waited) => waited,
+        None if ctx.cancelled() => return Err(into_err("the benchmark was cancelled".to_string())),
+        None => return Err(into_err(format!("killed after exceeding the time limit of {:?}", invocation.limits.time.unwrap_or_default()))),
+    };
+    if !status.success() {
+        return Err(into_err(format!("{}", status)));
+    }
+    // the resources of docker or ssh aren't those of the programme
+    if !ctx.args.programs.backend_of(invocation.role).is_local() {
+        (measured.cpu, measured.memory_kb) = (None, None);
+    }
+    Ok(measured)
+}
+
+/// Reaps the programme on a thread of its own along with the resources it used, and kills it once the
+/// deadline passes or the session is cancelled, returning none then.
+#[cfg(unix)]
+pub(crate) fn wait_measured(child: &mut Child, started: Instant, deadline: Option<Instant>, ctx: &RoundCtx) -> io::Result<Option<(ExitStatus, Measured)>> {
+    let pid = child.id();
+    let (sender, receiver) = std::sync::mpsc::channel();
+    thread::spawn(move || sender.send(reap(pid, started)));
+    loop {
+        let left = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+        if ctx.cancelled() || left == Some(Duration::ZERO) {
+            let _ = child.kill();
+            return receiver.recv().map_err(io::Error::other)?.map(|_| None);
+        }
+        match receiver.recv_timeout(left.map_or(CANCEL_POLL, |left| left.min(CANCEL_POLL))) {
+            Ok(reaped) => return reaped.map(Some),
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {},
+            Err(e) => return Err(io::Error::other(e)),
+        }
+    }
+}
+
+/// waits for the process with `wait4`, which tells the resources it used as it reaps it
+#[cfg(unix)]
+fn reap(pid: u32, started: Instant) -> io::Result<(ExitStatus, Measured)> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let mut status = 0;
+    // SAFETY: rusage is plain data, which wait4 fills in
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    while unsafe { libc::wait4(pid as libc::pid_t, &mut status, 0, &mut usage) } == -1 {
+        let e = io::Error::last_os_error();
+        if e.kind() != io::ErrorKind::Interrupted {
+            return Err(e);
+        }
+    }
+    let wall = started.elapsed();
+    let time = |t: libc::timeval| Duration::from_secs(t.tv_sec as u64) + Duration::from_micros(t.tv_usec as u64);
+    // macos counts it in bytes
+    let max_rss = usage.ru_maxrss as u64;
+    let memory_kb = if cfg!(target_os = "macos") { max_rss / 1024 } else { max_rss };
+    Ok((ExitStatus::from_raw(status), Measured { wall, cpu: Some(time(usage.ru_utime) + time(usage.ru_stime)), memory_kb: Some(memory_kb) }))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn wait_measured(child: &mut Child, started: Instant, deadline: Option<Instant>, ctx: &RoundCtx) -> io::Result<Option<(ExitStatus, Measured)>> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some((status, Measured { wall: started.elapsed(), cpu: None, memory_kb: None })));
+        }
+        if ctx.cancelled() || deadline.is_some_and(|d| Instant::now() >= d) {
+            let _ = child.kill();
+            child.wait()?;
+            return Ok(None);
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+/// Keeps compdiff, and so every programme it starts from now on, on the cpu, for steadier times.
+#[cfg(target_os = "linux")]
+pub fn pin_to_cpu(cpu: usize) -> io::Result<()> {
+    // SAFETY: the set is plain data, CPU_SET only writes inside it for the cpus it can hold
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        if cpu >= 8 * std::mem::size_of::<libc::cpu_set_t>() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("there's no cpu {}", cpu)));
+        }
+        libc::CPU_SET(cpu, &mut set);
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn pin_to_cpu(_cpu: usize) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "the cpu can only be chosen on linux"))
+}
+
@@ -0,0 +1,216 @@
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::Arc,
+    sync::atomic::{AtomicUsize, Ordering},
+    };
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+/// What a programme wrote to its stdout. It's kept in memory up to `--spill-threshold`, past which it's
+/// written to a temporary file instead, removed once the last clone of it is dropped. Serialized, an output
+/// in a file is its preview.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum Captured {
+    Text(String),
+    Spilled(Arc<Spill>),
+}
+
+/// The temporary file of an output too long to be kept in memory.
+#[derive(Debug)]
+pub struct Spill {
+    path: PathBuf,
+    /// in bytes
+    len: u64,
+    /// the first `PREVIEW_BYTES` of the output
+    preview: String,
+}
+
+impl Drop for Spill {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+impl Default for Captured {
+    fn default() -> Self {
+        Captured::Text(String::new())
+    }
+}
+
+impl From<String> for Captured {
+    fn from(text: String) -> Self {
+        Captured::Text(text)
+    }
+}
+
+impl From<Captured> for String {
+    fn from(captured: Captured) -> Self {
+        captured.preview().to_string()
+    }
+}
+
+impl PartialEq for Captured {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Captured::Text(a), Captured::Text(b)) => a == b,
+            (Captured::Spilled(a), Captured::Spilled(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl PartialEq<&str> for Captured {
+    fn eq(&self, other: &&str) -> bool {
+        self.text() == Some(*other)
+    }
+}
+
+impl Captured {
+    /// the whole output, unless it's in a file
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            Captured::Text(text) => Some(text),
+            Captured::Spilled(_) => None,
+        }
+    }
+
+    /// the output as it's shown: all of it, or its beginning when it's in a file
+    pub fn preview(&self) -> &str {
+        match self {
+            Captured::Text(text) => text,
+            Captured::Spilled(spill) => &spill.preview,
+        }
+    }
+
+    pub fn is_spilled(&self) -> bool {
+        matches!(self, Captured::Spilled(_))
+    }
+
+    /// in bytes
+    pub fn len(&self) -> u64 {
+        match self {
+            Captured::Text(text) => text.len() as u64,
+            Captured::Spilled(spill) => spill.len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// reads the output from the start, from memory or from its file
+    pub fn reader(&self) -> io::Result<Box<dyn BufRead + '_>> {
+        Ok(match self {
+            Captured::Text(text) => Box::new(text.as_bytes()),
+            Captured::Spilled(spill) => Box::new(BufReader::new(File::open(&spill.path)?)),
+        })
+    }
+
+    /// the whole output, read from its file when it's in one
+    pub fn into_string(self) -> io::Result<String> {
+        match self {
+            Captured::Text(text) => Ok(text),
+            Captured::Spilled(spill) => Ok(String::from_utf8_lossy(&fs::read(&spill.path)?).into_owned()),
+        }
+    }
+
+    /// writes the whole output into the file
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        match self {
+            Captured::Text(text) => fs::write(path, text),
+            Captured::Spilled(spill) => fs::copy(&spill.path, path).map(|_| ()),
+        }
+    }
+}
+
+/// how much of an output in a file is kept in memory to be shown
+pub const PREVIEW_BYTES: usize = 64 * 1024;
+
+/// the size of the chunks an output is read in once it's written to a file
+pub const CHUNK_BYTES: usize = 64 * 1024;
+
+/// An output longer than the threshold, written to its temporary file as it's read. When no file can be
+/// created it's kept in memory after all, and when the file can't be written to the rest of it is dropped.
+pub struct Spilling {
+    sink: Sink,
+    spill: Spill,
+}
+
+enum Sink {
+    File(File),
+    Memory(Vec<u8>),
+    Failed,
+}
+
+impl Spilling {
+    /// starts the output with what was read of it
+    pub fn start(head: Vec<u8>) -> Self {
+        static SPILLS: AtomicUsize = AtomicUsize::new(0);
+        let path = env::temp_dir().join(format!("compdiff-output-{}-{}", process::id(), SPILLS.fetch_add(1, Ordering::Relaxed)));
+        let preview = String::from_utf8_lossy(&head[..PREVIEW_BYTES.min(head.len())]).into_owned();
+        let mut spilling = Spilling { sink: Sink::Memory(vec![]), spill: Spill { path, len: 0, preview } };
+        match File::create(&spilling.spill.path) {
+            Ok(file) => {
+                spilling.sink = Sink::File(file);
+                spilling.append(&head);
+            },
+            Err(e) => {
+                warn!("couldn't create {} for an output of more than {} bytes, keeping it in memory: {}", spilling.spill.path.display(), head.len() - 1, e);
+                spilling.sink = Sink::Memory(head);
+            },
+        }
+        spilling
+    }
+
+    pub fn append(&mut self, chunk: &[u8]) {
+        match &mut self.sink {
+            Sink::File(file) => match file.write_all(chunk) {
+                Ok(()) => self.spill.len += chunk.len() as u64,
+                Err(e) => {
+                    warn!("couldn't write all of an output to {}, the rest of it is dropped: {}", self.spill.path.display(), e);
+                    self.sink = Sink::Failed;
+                },
+            },
+            Sink::Memory(bytes) => bytes.extend_from_slice(chunk),
+            Sink::Failed => {},
+        }
+    }
+
+    pub fn finish(self) -> Captured {
+        match self.sink {
+            Sink::Memory(bytes) => captured_text(&bytes),
+            Sink::File(_) | Sink::Failed => Captured::Spilled(Arc::new(self.spill)),
+        }
+    }
+}
+
+/// the output, all of which is `bytes`
+pub fn captured_text(bytes: &[u8]) -> Captured {
+    Captured::Text(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// Reads the pipe to its end, keeping what's read in memory up to `threshold` bytes and writing all of it
+/// into a temporary file past that.
+pub fn capture<R: Read>(mut pipe: R, threshold: usize) -> Captured {
+    let mut head = vec![];
+    let _ = pipe.by_ref().take(threshold as u64 + 1).read_to_end(&mut head);
+    if head.len() <= threshold {
+        return captured_text(&head);
+    }
+    let mut spilling = Spilling::start(head);
+    let mut chunk = vec![0; CHUNK_BYTES];
+    loop {
+        match pipe.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => spilling.append(&chunk[..n]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {},
+            Err(_) => break,
+        }
+    }
+    spilling.finish()
+}
@@ -0,0 +1,173 @@
+use std::{
+    env,
+    fs,
+    path::{Path, PathBuf},
+    process,
+    };
+use toml::Value;
+
+use crate::cli::{EXIT_INFRA, EXIT_OK};
+use crate::config;
+use crate::output;
+use crate::resume::SessionFile;
+use crate::{cache_dir, is_owned_dir, say, stale_builds, OWNED_MARKER};
+
+/// the size of a file, or of a directory with everything in it
+fn size_of(path: &Path) -> u64 {
+    let Ok(meta) = fs::symlink_metadata(path) else { return 0 };
+    if !meta.is_dir() {
+        return meta.len();
+    }
+    fs::read_dir(path)
+        .map(|entries| entries.filter_map(Result::ok).map(|e| size_of(&e.path())).sum())
+        .unwrap_or(0)
+}
+
+/// Why the directory mustn't be removed: it holds the working directory or the home directory, or
+/// compdiff didn't create it (unless `trusted`). None when it may be.
+fn refusal(dir: &Path, trusted: bool) -> Option<String> {
+    let canonical = |path: PathBuf| fs::canonicalize(path).ok();
+    let dir = canonical(dir.to_path_buf())?;
+    let holds = |other: Option<PathBuf>| other.is_some_and(|other| other.starts_with(&dir));
+    if dir.parent().is_none() || holds(env::current_dir().ok().and_then(canonical)) || holds(env::var_os("HOME").map(PathBuf::from).and_then(canonical)) {
+        return Some("it holds the working or the home directory".to_string());
+    }
+    if !trusted && !is_owned_dir(&dir) {
+        return Some(format!("compdiff didn't create it, there's no {} in it", OWNED_MARKER));
+    }
+    None
+}
+
+/// the directory of a session built with --no-cache whose process is gone
+fn abandoned_session_dir(dir: &Path) -> bool {
+    let pid = dir.file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_prefix("compdiff-session-"))
+        .and_then(|pid| pid.parse::<u32>().ok());
+    // without /proc there's no telling whether the session still runs
+    pid.is_some_and(|pid| pid != process::id() && Path::new("/proc/self").exists() && !Path::new("/proc").join(pid.to_string()).exists())
+}
+
+/// Removes only what compdiff wrote, adding up what it reclaims.
+#[derive(Default)]
+struct Cleaner {
+    reclaimed: u64,
+    removed: usize,
+    refused: bool,
+}
+
+impl Cleaner {
+    fn removed(&mut self, path: &Path, size: u64) {
+        say!("removed {} ({})", path.display(), output::human_size(size));
+        self.reclaimed += size;
+        self.removed += 1;
+    }
+
+    fn refuse(&mut self, path: &Path, why: &str) {
+        eprintln!("refusing to remove {}: {}", path.display(), why);
+        self.refused = true;
+    }
+
+    fn remove_file(&mut self, path: &Path) {
+        let size = size_of(path);
+        match fs::remove_file(path) {
+            Ok(()) => self.removed(path, size),
+            Err(e) => self.refuse(path, &e.to_string()),
+        }
+    }
+
+    /// Removes the directory when compdiff created it, or else the directories it created in it, such as
+    /// the sessions of --save-failures in a directory which already existed.
+    fn remove_dir(&mut self, dir: &Path, trusted: bool) {
+        if !dir.is_dir() {
+            return;
+        }
+        let Some(why) = refusal(dir, trusted) else {
+            let size = size_of(dir);
+            return match fs::remove_dir_all(dir) {
+                Ok(()) => self.removed(dir, size),
+                Err(e) => self.refuse(dir, &e.to_string()),
+            };
+        };
+        let mut owned = fs::read_dir(dir)
+            .map(|entries| entries.filter_map(Result::ok).map(|e| e.path()).filter(|p| p.is_dir() && is_owned_dir(p)).collect::<Vec<_>>())
+            .unwrap_or_default();
+        if owned.is_empty() {
+            return self.refuse(dir, &why);
+        }
+        owned.sort();
+        for dir in owned {
+            self.remove_dir(&dir, false);
+        }
+    }
+
+    /// removes the file of `--session` after making sure that it is one
+    fn remove_session_file(&mut self, path: &Path) {
+        let Ok(text) = fs::read_to_string(path) else { return };
+        if serde_json::from_str::<SessionFile>(&text).is_err() {
+            return self.refuse(path, "it isn't a session file");
+        }
+        self.remove_file(path);
+    }
+}
+
+/// `compdiff clean`: the build directories left behind, and the caches, the stale binaries, the saved
+/// rounds and the session files when asked for, as configured by `--config` or `compdiff.toml`
+pub fn clean(stale: bool, cache: bool, relocated: Option<&Path>, artifacts: bool, config: Option<&Path>, profile: Option<&str>) -> i32 {
+    let table = match config::load(config, profile) {
+        Ok(loaded) => loaded.map(|(_, table)| table).unwrap_or_default(),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return EXIT_INFRA;
+        },
+    };
+    let configured = |key: &str| match table.get(key) {
+        Some(Value::String(path)) => Some(PathBuf::from(path)),
+        _ => None,
+    };
+    let mut cleaner = Cleaner::default();
+
+    if let Some(dir) = configured("build-dir") {
+        cleaner.remove_dir(&dir, false);
+    }
+    let mut sessions = fs::read_dir(env::temp_dir())
+        .map(|entries| entries.filter_map(Result::ok).map(|e| e.path()).filter(|p| abandoned_session_dir(p)).collect::<Vec<_>>())
+        .unwrap_or_default();
+    sessions.sort();
+    for dir in sessions {
+        cleaner.remove_dir(&dir, false);
+    }
+
+    let relocated = relocated.map(Path::to_path_buf).or_else(|| configured("cache-dir"));
+    let dir = cache_dir(relocated.as_deref());
+    if cache {
+        // the default cache is compdiff's, even when an older version created it without marking it
+        cleaner.remove_dir(&dir, relocated.is_none());
+        if let Some(dir) = configured("ref-cache") {
+            cleaner.remove_dir(&dir, false);
+        }
+    } else if stale && dir.is_dir() {
+        match refusal(&dir, relocated.is_none()).map_or_else(|| stale_builds(&dir).map_err(|e| e.to_string()), Err) {
+            Ok(paths) => paths.iter().for_each(|path| cleaner.remove_file(path)),
+            Err(why) => cleaner.refuse(&dir, &why),
+        }
+    }
+
+    if artifacts {
+        for key in ["save-failures", "keep-artifacts", "save-slowest", "save-perf", "save-mem"] {
+            if let Some(dir) = configured(key) {
+                cleaner.remove_dir(&dir, false);
+            }
+        }
+        if let Some(session) = configured("session") {
+            cleaner.remove_session_file(&session);
+        }
+    }
+
+    if cleaner.removed == 0 {
+        say!("nothing to clean");
+    } else {
+        say!("reclaimed {} in total", output::human_size(cleaner.reclaimed));
+    }
+    if cleaner.refused { EXIT_INFRA } else { EXIT_OK }
+}
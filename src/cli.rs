@@ -1,144 +1,1342 @@
-use clap::{command, arg, Parser};
-use std::{path::PathBuf, env};
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap::{builder::Resettable, Id};
+use clap_complete::Shell;
+use log::debug;
+use similar::{DiffTag, TextDiff};
+use std::{path::{Path, PathBuf}, env, fs, borrow::Cow, error::Error, time::Duration, io::{self, IsTerminal}, backtrace::Backtrace, panic, thread};
+use string_error::into_err;
 
-use crate::{run_round, Failure, test_mismatch, Success, preprocess_command};
+pub use crate::options::{Cli, Programs, Show, Format, SeedVia, Generator, GenPick, OnGenFail, RelativeTimeLimit, Compare, BuildProfile, Sanitizer, CompileCmd, LanguageFlags, DEFAULT_CPP_FLAGS};
+pub use crate::options::{parse_compile_cmd, parse_standard, parse_language_flags, parse_gen_args, split_flags, parse_seconds};
+use crate::save::{FailureSaver, SavedRound, output_suffixes};
+use crate::artifacts::{ArtifactKeeper, KeptRound};
+use crate::corpus::Corpus;
+use crate::resume::{PastFailure, SessionFile};
+use crate::slowest::{self, SlowRound, SlowestRounds};
+use crate::perf::{self, ComparedRound, Measure, PerfComparison, WorstRounds};
+use crate::memory::MemoryComparison;
+use crate::bench::{self, Sample, Stats};
+use crate::report::{self, DistinctFailure, FailureMode, Report, RoundRecord};
+use crate::markdown::MarkdownReport;
+use crate::csv::CsvReport;
+use crate::db::{self, ResultsDb};
+use crate::notify::Notifier;
+use crate::html;
+use crate::clean;
+use crate::refcache::RefCache;
+pub use crate::session::{EXIT_OK, EXIT_FAILURE, EXIT_INFRA};
+use crate::session::{planned_rounds, RoundEnd, Session, SessionReport, SessionView, Stop};
+use crate::backend::Backend;
+use crate::capture::{Captured, PREVIEW_BYTES};
+use crate::compare::{compare_captured, compare_outputs, Comparison};
+use crate::minimize::{minimize, Minimized};
+use crate::explore::explore;
+use crate::runner::Processes;
+use crate::github;
+use crate::watch::watch;
+use crate::events::{Event, EventStream};
+use crate::{say, output};
+use crate::output::{ColorChoice, Mark, Style};
+use crate::{run_round_with_input, RoundCtx, random_seed, round_seed, generator_invocation, generate_input, command_line, Failure, failed_references, Execution, ExecutionResult, preprocess_commands, Toolchain, CompileError, UnsupportedFlags, session_build_dir, remove_session_build_dir, Diagnostic, Role, Signature, Verdict, catch_interrupts, interrupted};
 
 
 
-#[derive(Parser)]
-#[command(author, version, about)]
-pub struct Cli {
-    /// the test-case generator programme
-    #[arg(short, long, value_name = "FILE")]
-    pub generator: PathBuf,
+/// The whole command line: either a subcommand or the options of a session,
+/// so that plain `compdiff -g gen.py -p sol.cpp ...` keeps working.
+#[derive(Parser, Debug)]
+#[command(author, version, about, args_conflicts_with_subcommands = true)]
+// clap leaves the group of a struct with flattened fields empty, without the options of `Cli`
+// in its group `run` would never be set
+#[command(mut_group("Cli", |g| g.args(Cli::command().get_arguments().map(|a| a.get_id().clone()))))]
+pub struct App {
+    #[command(subcommand)]
+    pub command: Option<Command>,
 
-    /// the programme to be examined
-    #[arg(short, long, value_name = "FILE")]
-    pub program: PathBuf,
+    #[command(flatten)]
+    pub run: Option<Cli>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// run a session, the same as giving its options without a subcommand
+    Run(Cli),
+    /// only build the programmes and print where their binaries are
+    Compile(CompileArgs),
+    /// run a single round on a saved input
+    Replay(ReplayArgs),
+    /// remove what compdiff wrote: the build directories, and with the options the cache and the saved rounds,
+    /// never a directory it didn't create
+    Clean {
+        /// remove the cached binaries whose source changed or was removed since
+        #[arg(long, default_value = "false", conflicts_with = "cache")]
+        stale: bool,
+
+        /// empty the cache, and remove the --ref-cache of the configuration
+        #[arg(long, default_value = "false")]
+        cache: bool,
+
+        /// the cache, when relocated with --cache-dir
+        #[arg(long, value_name = "DIR")]
+        cache_dir: Option<PathBuf>,
+
+        /// remove the directories of --save-failures, --keep-artifacts, --save-slowest, --save-perf and
+        /// --save-mem and the --session file of the configuration
+        #[arg(long, default_value = "false")]
+        artifacts: bool,
+
+        /// the configuration which says where those are (compdiff.toml by default)
+        #[arg(long, value_name = "FILE")]
+        config: Option<PathBuf>,
+
+        /// take the options of the `[profile.NAME]` section of the configuration over its top-level ones
+        #[arg(long, value_name = "NAME")]
+        profile: Option<String>,
+    },
+    /// print the pass rate over time and how the speed of the programme changed, from a `--db` database
+    Stats(StatsArgs),
+    /// print the completion script for the shell to stdout
+    Completions {
+        shell: Shell,
+    },
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct CompileArgs {
+    /// read the options which aren't given on the command line from this file (compdiff.toml by default)
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// take the options of the `[profile.NAME]` section of the configuration over its top-level ones
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// the test-case generator programmes, built as well when given
+    #[arg(short, long, value_name = "FILE[:WEIGHT]")]
+    pub generator: Vec<Generator>,
+
+    #[command(flatten)]
+    pub programs: Programs,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct StatsArgs {
+    /// the database written by `--db`
+    #[arg(long, value_name = "FILE")]
+    pub db: PathBuf,
+}
+
+/// A session on a single input, so the generator isn't needed.
+#[derive(Args, Debug, Clone)]
+#[command(
+    mut_arg("generator", |a| a.required_unless_present(Resettable::<Id>::Reset).hide(true)),
+    mut_arg("input_file", |a| a.hide(true)),
+    mut_arg("gen_spec", |a| a.hide(true)),
+    mut_arg("generator_file", |a| a.hide(true)),
+    mut_arg("program_file", |a| a.hide(true)),
+    mut_arg("reference_files", |a| a.hide(true)),
+)]
+pub struct ReplayArgs {
+    /// the input, e.g. one saved by --save-failures or written into --corpus-dir
+    #[arg(value_name = "INPUT_FILE")]
+    pub input: PathBuf,
+
+    #[command(flatten)]
+    pub session: Cli,
+}
+
+impl App {
+    /// how many times --verbose was given to the session
+    pub fn verbosity(&self) -> u8 {
+        match (&self.command, &self.run) {
+            (Some(Command::Run(args)), _) | (None, Some(args)) => args.verbose,
+            (Some(Command::Replay(args)), _) => args.session.verbose,
+            _ => 0,
+        }
+    }
+}
+
+/// runs the subcommand, or the session, and returns the exit code
+pub fn handle_app(app: App) -> i32 {
+    let exit_code = match (app.command, app.run) {
+        (Some(Command::Run(args)), _) | (None, Some(args)) => session(args),
+        (Some(Command::Compile(args)), _) => compile(args),
+        (Some(Command::Replay(mut args)), _) => {
+            args.session.generator = vec![];
+            args.session.gen_spec = None;
+            args.session.input_file = Some(args.input);
+            session(args.session)
+        },
+        (Some(Command::Clean { stale, cache, cache_dir, artifacts, config, profile }), _) =>
+            clean::clean(stale, cache, cache_dir.as_deref(), artifacts, config.as_deref(), profile.as_deref()),
+        (Some(Command::Stats(args)), _) => match db::stats(&args.db) {
+            Ok(()) => EXIT_OK,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                EXIT_INFRA
+            },
+        },
+        (Some(Command::Completions { shell }), _) => {
+            clap_complete::generate(shell, &mut App::command(), "compdiff", &mut io::stdout());
+            EXIT_OK
+        },
+        (None, None) => unreachable!("clap requires the session options without a subcommand"),
+    };
+    if let Err(e) = remove_session_build_dir() {
+        eprintln!("couldn't remove the build directory {}: {}", session_build_dir().display(), e);
+    }
+    exit_code
+}
+
+fn session(mut args: Cli) -> i32 {
+    args.take_positional();
+    if let Err(e) = args.programs.expand_references().and_then(|()| args.programs.expand_program()) {
+        eprintln!("error: {}", e);
+        return EXIT_INFRA;
+    }
+    if let (Some(relative), None) = (&args.time_limit_rel, args.relative_reference()) {
+        eprintln!("error: {} of --time-limit-rel isn't one of the references", relative.reference.display());
+        return EXIT_INFRA;
+    }
+    if args.watch { watch(args) } else { handle_cli(args) }
+}
+
+/// builds every source, fails when one of them doesn't compile
+fn compile(mut args: CompileArgs) -> i32 {
+    let style = Style::detect(false, ColorChoice::Auto);
+    if let Err(e) = args.programs.expand_references().and_then(|()| args.programs.expand_program()) {
+        eprintln!("error: {}", e);
+        return EXIT_INFRA;
+    }
+    if let (Some(profile), Some(config)) = (&args.profile, &args.config) {
+        say!("using the profile {} from {}", profile, config.display());
+    }
+    warn_overridden_std(style, &args.programs);
+    args.programs.backend = match Backend::open(args.programs.docker.as_deref()) {
+        Ok(backend) => backend,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return EXIT_INFRA;
+        },
+    };
+    let sources = args.programs.distinct_sources(&args.generator);
+    let mut exit_code = EXIT_OK;
+    // the version of the compiler is asked for once, not for every source it builds
+    let toolchain = sources.iter().any(|(role, s)| args.programs.is_compiled(*role, s)).then(|| Toolchain::detect(&args.programs).ok()).flatten();
+    for ((_, source), binary) in sources.iter().zip(preprocess_commands(&sources, &args.programs, toolchain.as_ref())) {
+        match binary {
+            Ok(built) => {
+                say!("{} {} -> {}", style.mark(Mark::Ok), source.display(), built.binary.display());
+                display_compile_warnings(style, &args.programs, source, &built.warnings);
+            },
+            Err(e) => {
+                display_compile_error(style, source, e.as_ref());
+                exit_code = EXIT_FAILURE;
+            },
+        }
+    }
+    exit_code
+}
 
-    /// the reference programme/programmes
-    #[arg(short, long, alias = "ref", action = clap::ArgAction::Append)]
-    pub reference: Vec<PathBuf>,
+/// lines of unchanged output shown around every difference
+const DIFF_CONTEXT: usize = 3;
 
-    /// for how many rounds should the programme be ran
-    #[arg(short = 'c', long)]
-    pub rounds: Option<u64>,
+fn width(args: &Cli) -> usize {
+    args.width.unwrap_or(output::DEFAULT_WIDTH)
+}
 
-    /// time limit (s) for the programme excluding references
-    #[arg(short = 't', long)]
-    pub time_limit: Option<f64>,
+/// the text as it should be displayed, long texts and lines are truncated unless --full-output is given
+fn shown<'a>(args: &Cli, text: &'a str) -> Cow<'a, str> {
+    if args.full_output {
+        return Cow::Borrowed(text);
+    }
+    let note = if args.save_failures.is_some() { ", see the saved files for the full text" } else { "" };
+    let text = output::truncate_middle(text, args.max_lines, args.max_bytes, note);
+    match output::fit(&text, width(args), args.style.ellipsis()) {
+        Cow::Owned(fitted) => Cow::Owned(fitted),
+        Cow::Borrowed(_) => text,
+    }
+}
 
-    /// memory limit (kB) for the programme excluding references
-    #[arg(short = 'm', long)]
-    pub memory_limit: Option<usize>,
+/// says the text as it should be displayed, the log gets it in full
+fn say_text(args: &Cli, text: &str) {
+    output::say_with_log(&shown(args, text), text);
+}
 
-    /// print additional information
-    #[arg(short = 'v', long, default_value = "false")]
-    pub verbose: bool,
+/// says the output like `say_text`, only its beginning when it was written to a file
+fn say_output(args: &Cli, out: &Captured) {
+    say_text(args, out.preview());
+    if out.is_spilled() {
+        say!("::: (the first {} of {} are shown)", output::human_size(out.preview().len() as u64), output::human_size(out.len()));
+    }
+}
 
-    /// options for c++ compiler
-    #[arg(long, default_value = "-std=c++20")]
-    pub cpp_compiler_flags: String,
+/// notes that outputs written to files are only shown as far as their beginnings go
+fn say_previewed(outputs: &[&Captured]) {
+    if outputs.iter().any(|out| out.is_spilled()) {
+        say!("::: (the outputs are only shown up to their first {})", output::human_size(PREVIEW_BYTES as u64));
+    }
 }
 
+fn display_mismatches(args: &Cli, inp: &str, prog: &ExecutionResult, refs: &[ExecutionResult]) {
+    cli_section(args, format!("there are {} mismatched testcases!", refs.len()).as_str(), false);
 
+    say!("\n::: input:");
+    say_text(args, inp);
 
-fn display_mismatches(inp: &String, prog: &Success, refs: &Vec<Success>) {
-    cli_section(format!("there are {} mismatched testcases!", refs.len()).as_str(), false);
+    // the diff shows every differing line, even those the comparison accepts
+    if args.compare != Compare::Exact {
+        let comparator = args.comparator();
+        for r in refs {
+            if let Comparison::Differ { line, explanation } = compare_captured(comparator.as_ref(), &prog.stdout, &r.stdout) {
+                let at = line.map(|line| format!(" at line {}", line)).unwrap_or_default();
+                say!("\n::: compared with the reference program ({}){}: {}", args.source_of(&r.path).display(), at, explanation);
+            }
+        }
+    }
 
-    println!("\n::: input:");
-    println!("{}", inp);
+    match args.show {
+        Show::DiffOnly => return refs.iter().for_each(|r| display_diff(args, prog, r)),
+        Show::SideBySide => return refs.iter().for_each(|r| display_side_by_side(args, prog, r)),
+        Show::Full => {},
+    }
 
-    println!("\n::: program ({}) output:", prog.0.display());
-    println!("{}", prog.1);
+    say!("\n::: program ({}) output:", args.source_of(&prog.path).display());
+    say_output(args, &prog.stdout);
         
-    for (p, out) in refs {
-        println!("\n::: reference program ({}) output:", p.display());
-        println!("{}", out);            
+    for r in refs {
+        say!("\n::: reference program ({}) output:", args.source_of(&r.path).display());
+        say_output(args, &r.stdout);
+    }
+}
+
+/// the differing lines of the outputs, as a unified diff from the reference to the program
+fn display_diff(args: &Cli, prog: &ExecutionResult, reference: &ExecutionResult) {
+    let (prog_name, ref_name) = (args.source_of(&prog.path).display().to_string(), args.source_of(&reference.path).display().to_string());
+    say!("\n::: diff of the program ({}) output against the reference program ({}):", prog_name, ref_name);
+    let diff = TextDiff::from_lines(reference.stdout.preview(), prog.stdout.preview())
+        .unified_diff()
+        .context_radius(DIFF_CONTEXT)
+        .header(&ref_name, &prog_name)
+        .to_string();
+    let shown_diff = shown(args, &diff);
+    output::say_with_log(&shown_diff.lines().map(|l| args.style.diff_line(l)).collect::<Vec<_>>().join("\n"), &diff);
+    say_previewed(&[&reference.stdout, &prog.stdout]);
+}
+
+/// the compiler output of a source which didn't build, or why it couldn't be built at all
+fn display_compile_error(style: Style, source: &Path, e: &(dyn Error + 'static)) {
+    if let Some(e) = e.downcast_ref::<UnsupportedFlags>() {
+        say!("{} failed to compile {}: {}", style.mark(Mark::Fail), source.display(), e);
+        return say!("\n::: compiler output:\n{}", e.stderr.trim_end());
+    }
+    let Some(e) = e.downcast_ref::<CompileError>() else {
+        return say!("{} failed to prepare {}: {}", style.mark(Mark::Fail), source.display(), e);
+    };
+    say!("{} failed to compile {}", style.mark(Mark::Fail), source.display());
+    if let Some(grader) = &e.grader {
+        let in_solution = e.erring.iter().any(|s| s != grader);
+        match (e.erring.contains(grader), in_solution) {
+            (true, false) => say!("::: the errors are in the grader {}, not in the solution", grader.display()),
+            (false, true) => say!("::: the errors are in the solution, not in the grader"),
+            (true, true) => say!("::: there are errors in both the solution and the grader {}", grader.display()),
+            (false, false) => say!("::: linking with the grader {} failed, does the solution define every function it calls?", grader.display()),
+        }
+    }
+    say!("\n::: command:\n{}", e.command);
+    if !e.stderr.trim().is_empty() {
+        say!("\n::: compiler output:\n{}", e.stderr.trim_end());
     }
 }
 
-fn display_ref_mismatches(inp: &String, refs: &Vec<Success>) {
-    cli_section(format!("🚧 CRITICAL ERROR 🚧 there are {} mismatched references!!!!", refs.len()).as_str(), false);
+fn warn_overridden_std(style: Style, programs: &Programs) {
+    if programs.std_overridden() {
+        say!("  {} warning : --std {} is ignored, the -std= of the c++ flags given explicitly wins", style.mark(Mark::Warn), programs.std.as_deref().unwrap_or_default());
+    }
+}
 
-    println!("\n::: input:");
-    println!("{}", inp);
+/// with --show-compile-warnings, what the compiler said about a source which compiled
+fn display_compile_warnings(style: Style, programs: &Programs, source: &Path, warnings: &str) {
+    if programs.show_compile_warnings && !warnings.trim().is_empty() {
+        say!("{} warnings compiling {}:\n{}", style.mark(Mark::Warn), source.display(), warnings.trim_end());
+    }
+}
+
+/// The differing lines of the reference output on the left and of the program output on the right, marked
+/// like sdiff does: `|` for changed lines, `<` and `>` for lines only on one side. The columns share the width.
+fn display_side_by_side(args: &Cli, prog: &ExecutionResult, reference: &ExecutionResult) {
+    let (prog_name, ref_name) = (args.source_of(&prog.path).display().to_string(), args.source_of(&reference.path).display().to_string());
+    say!("\n::: the reference program ({}) output next to the program ({}) output:", ref_name, prog_name);
+    let diff = TextDiff::from_lines(reference.stdout.preview(), prog.stdout.preview());
+    let (old, new) = (diff.old_slices(), diff.new_slices());
+    let line = |slices: &[&str], i: usize| slices.get(i).map(|l| l.trim_end_matches(['\n', '\r']).to_string());
+    let column = match width(args) {
+        0 => old.iter().map(|l| l.trim_end().chars().count()).max().unwrap_or(0),
+        w => w.saturating_sub(3) / 2,
+    };
+    let ellipsis = args.style.ellipsis();
+
+    let mut rows = vec![format!("{:<column$}   {}", output::fit_line(&ref_name, column, ellipsis), output::fit_line(&prog_name, column, ellipsis))];
+    for (i, group) in diff.grouped_ops(DIFF_CONTEXT).iter().enumerate() {
+        if i > 0 {
+            rows.push(ellipsis.to_string());
+        }
+        for op in group {
+            let (tag, olds, news) = op.as_tag_tuple();
+            for k in 0..olds.len().max(news.len()) {
+                let left = (k < olds.len()).then(|| line(old, olds.start + k)).flatten();
+                let right = (k < news.len()).then(|| line(new, news.start + k)).flatten();
+                let marker = match (tag, &left, &right) {
+                    (DiffTag::Equal, ..) => ' ',
+                    (_, Some(_), Some(_)) => '|',
+                    (_, Some(_), None) => '<',
+                    _ => '>',
+                };
+                let left = output::fit_line(left.as_deref().unwrap_or(""), column, ellipsis).into_owned();
+                let right = output::fit_line(right.as_deref().unwrap_or(""), column, ellipsis).into_owned();
+                rows.push(format!("{:<column$} {} {}", left, marker, right).trim_end().to_string());
+            }
+        }
+    }
+    let table = rows.join("\n");
+    output::say_with_log(&shown(args, &table), &table);
+    say_previewed(&[&reference.stdout, &prog.stdout]);
+}
+
+fn display_ref_mismatches(args: &Cli, inp: &str, refs: &[ExecutionResult]) {
+    let warn = args.style.mark(Mark::Warn);
+    cli_section(args, format!("{} CRITICAL ERROR {} there are {} mismatched references!!!!", warn, warn, refs.len()).as_str(), false);
+
+    say!("\n::: input:");
+    say_text(args, inp);
         
-    for (p, out) in refs {
-        println!("\n::: reference program ({}) output:", p.display());
-        println!("{}", out);            
+    for r in refs {
+        say!("\n::: reference program ({}) output:", args.source_of(&r.path).display());
+        say_output(args, &r.stdout);
     }
 }
 
-fn cli_section(s: &str, ok: bool) {
-    println!("{} -- {}", if ok {"✔"} else {"❌"}, s)
+fn cli_section(args: &Cli, s: &str, ok: bool) {
+    say!("{} -- {}", args.style.mark(if ok { Mark::Ok } else { Mark::Fail }), s)
 }
 
-fn display_failure(fail: &Failure) {
+fn display_failure(args: &Cli, fail: &Failure) {
+    let bad = args.style.mark(Mark::Bad);
+    let status = |x: &ExecutionResult| x.status.clone().unwrap_or_default();
     match fail {
-        Failure::Prog(path, status, err) => 
-            println!("  👎 program \"{}\" failed with status \"{}\" and the error: {}", path.display(), status, err),
-        Failure::TimeLimit(path) => 
-            println!("  👎 program \"{}\" exceeded the time limit!", path.display()),
+        Failure::Prog(x) if is_sanitizer_report(&x.stderr) => {
+            let text = |err: &str| format!("  {} program \"{}\" failed with status \"{}\" and the sanitizer report:\n{}", bad, args.source_of(&x.path).display(), status(x), err.trim_end());
+            output::say_with_log(&text(&shown(args, &x.stderr)), &text(&x.stderr))
+        },
+        Failure::Prog(x) if x.stderr.is_empty() =>
+            say!("  {} program \"{}\" failed with status \"{}\" after {:.3}s", bad, args.source_of(&x.path).display(), status(x), x.duration.as_secs_f64()),
+        Failure::Prog(x) => {
+            let line = |err: &str| format!("  {} program \"{}\" failed with status \"{}\" and the error: {}", bad, args.source_of(&x.path).display(), status(x), err);
+            output::say_with_log(&line(&shown(args, &x.stderr)), &line(&x.stderr))
+        },
+        Failure::TimeLimit(x) => 
+            say!("  {} program \"{}\" exceeded the time limit, killed after {:.3}s!", bad, args.source_of(&x.path).display(), x.duration.as_secs_f64()),
+        Failure::Spawn(e) => 
+            say!("  {} {}", bad, e.describe(args, args.verbose > 0)),
+        Failure::RoundTimeout(x) => 
+            say!("  {} program \"{}\" was killed as the round exceeded its timeout!", bad, args.source_of(&x.path).display()),
+    }
+}
+
+/// which of the references failed and how, along with those which succeeded
+fn display_references(args: &Cli, refs: &[Execution]) {
+    say!("  {} {}", args.style.mark(Mark::Fail), failed_references(args, refs));
+    for (i, r) in refs.iter().enumerate() {
+        match r {
+            Err(fail) => display_failure(args, fail),
+            Ok(x) => say!("  {} reference {} of {} \"{}\" succeeded after {:.3}s", args.style.mark(Mark::Ok), i + 1, refs.len(),
+                args.source_of(&x.path).display(), x.duration.as_secs_f64()),
+        }
+    }
+}
+
+/// the error output of a programme built with --sanitize holds the report of what went wrong
+fn is_sanitizer_report(err: &str) -> bool {
+    err.contains("Sanitizer") || err.contains(": runtime error: ")
+}
+
+/// (file suffix, stderr) of the failed executions which have any error output
+fn failure_files<'a>(args: &Cli, fails: &[&'a Failure]) -> Vec<(String, Cow<'a, Captured>)> {
+    let names = output_suffixes(fails.iter().map(|f| args.source_of(f.path())));
+    fails.iter().zip(names)
+        .filter_map(|(f, name)| match f {
+            Failure::Prog(x) => Some((name.replace(".out", ".err"), Cow::Owned(x.stderr.clone().into()))),
+            _ => None,
+        })
+        .collect()
+}
+
+/// returns the paths of the saved files
+fn save_failure(args: &Cli, saver: &FailureSaver, round: SavedRound) -> Vec<PathBuf> {
+    match saver.save(&round) {
+        Ok(paths) => {
+            say!("  {} saved to {}", args.style.mark(Mark::Saved), paths.iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join(", "));
+            paths
+        },
+        Err(e) => {
+            say!("  {} warning : couldn't save the failing round: {}", args.style.mark(Mark::Warn), e);
+            vec![]
+        },
+    }
+}
+
+fn display_seed(args: &Cli, round: u64, seed: u64) {
+    if let Some(file) = &args.input_file {
+        say!("::: input read from {}", file.display());
+        return;
     }
+    say!("::: seed {}, reproduce the input with: {}", seed, generator_invocation(args, round, seed));
 }
 
-pub fn handle_cli(mut args: Cli){
+/// `--minimize`: the smallest input found which fails the same way, saved next to the input of the round
+fn display_minimized(args: &Cli, round: u64, input: &str, signature: Signature, saver: Option<&FailureSaver>) -> Minimized {
+    let minimized = minimize(args, round, input, signature);
+    let size = |text: &str| {
+        let lines = text.lines().count();
+        format!("{} ({} line{})", output::human_size(text.len() as u64), lines, if lines == 1 { "" } else { "s" })
+    };
+    say!("\n::: minimized the input from {} to {} in {} runs:", size(input), size(&minimized.input), minimized.runs);
+    say_text(args, &minimized.input);
+    if let Some(saver) = saver {
+        let path = saver.dir().join(format!("round_{}.min.in", round));
+        match fs::write(&path, &minimized.input) {
+            Ok(()) => say!("  {} saved the minimized input to {}", args.style.mark(Mark::Saved), path.display()),
+            Err(e) => say!("  {} warning : couldn't save the minimized input to {}: {}", args.style.mark(Mark::Warn), path.display(), e),
+        }
+    }
+    minimized
+}
+
+/// `--explore`: how many mutants of the input fail too and how, the distinct failing ones saved next to the input of the round
+fn display_explored(args: &Cli, round: u64, seed: u64, input: &str, count: usize, saver: Option<&FailureSaver>) {
+    let explored = explore(args, round, seed, input, count);
+    let rejected = if explored.rejected == 0 { String::new() } else { format!(", {} rejected by the validator", explored.rejected) };
+    say!("\n::: explored {} mutants of the input{}: {} of them failed too", explored.mutants, rejected, explored.failing);
+    let mut signatures: Vec<(Signature, usize)> = vec![];
+    for (_, signature) in &explored.distinct {
+        match signatures.iter_mut().find(|(s, _)| s == signature) {
+            Some((_, n)) => *n += 1,
+            None => signatures.push((*signature, 1)),
+        }
+    }
+    for (signature, n) in signatures {
+        say!("  {} {} ({} distinct mutant{})", args.style.mark(Mark::Fail), signature, n, if n == 1 { "" } else { "s" });
+    }
+    let Some(saver) = saver.filter(|_| !explored.distinct.is_empty()) else { return };
+    let written = explored.distinct.iter().enumerate()
+        .try_for_each(|(i, (mutant, _))| fs::write(saver.dir().join(format!("round_{}.mutant_{}.in", round, i + 1)), mutant));
+    match written {
+        Ok(()) => say!("  {} saved the failing mutants to {}", args.style.mark(Mark::Saved), saver.dir().join(format!("round_{}.mutant_*.in", round)).display()),
+        Err(e) => say!("  {} warning : couldn't save the failing mutants: {}", args.style.mark(Mark::Warn), e),
+    }
+}
+
+fn display_diagnostics(args: &Cli, diagnostics: &[Diagnostic]) {
+    for diagnostic in diagnostics {
+        say!("\n::: {}:", diagnostic.title);
+        say_text(args, &diagnostic.body);
+    }
+}
+
+/// Asks what to do after a failing round until the answer is to continue (true) or to quit (false).
+fn ask_after_failure(args: &Cli, round: u64, verdict: Verdict, input: Option<&str>, saver: Option<&FailureSaver>) -> bool {
     use crate::Round as R;
-    use crate::Mismatch as M;
+    loop {
+        let answer = output::ask("[c]ontinue / [q]uit / [s]ave input / [r]erun?");
+        if interrupted() {
+            return false;
+        }
+        match answer.as_deref() {
+            None | Some("c" | "") => return true,
+            Some("q") => return false,
+            Some("s" | "r") if input.is_none() => say!("  {} the round has no input", args.style.mark(Mark::Warn)),
+            Some("s") => {
+                let dir = saver.map(|s| s.dir().to_path_buf()).unwrap_or_default();
+                let path = dir.join(format!("round_{}.in", round));
+                match fs::write(&path, input.unwrap_or_default()) {
+                    Ok(()) => say!("  {} saved the input to {}", args.style.mark(Mark::Saved), path.display()),
+                    Err(e) => say!("  {} couldn't save the input to {}: {}", args.style.mark(Mark::Warn), path.display(), e),
+                }
+            },
+            Some("r") => {
+                let again = RoundCtx::new(args, round);
+                let again_verdict = match run_round_with_input(&again, input.unwrap_or_default().to_string(), &Processes) {
+                    R::Success { prog, refs, .. } if !refs.is_empty() => Verdict::of_mismatch(&compare_outputs(&prog, &refs, &*args.comparator())),
+                    outs => Verdict::of_round(&outs),
+                };
+                if again_verdict == verdict {
+                    say!("  {} rerun: {} again", args.style.mark(Mark::Retry), again_verdict.describe());
+                } else {
+                    say!("  {} rerun: {} this time, the failure may be nondeterministic", args.style.mark(Mark::Retry), again_verdict.describe());
+                }
+            },
+            Some(other) => say!("  unknown answer `{}`", other),
+        }
+    }
+}
 
-    if args.verbose {
-        env::set_var("RUST_BACKTRACE", "1");
+/// Shows the groups of `report::failure_modes`, and an input of each.
+fn display_failure_modes(args: &Cli, groups: Vec<FailureMode>) {
+    if groups.is_empty() {
+        return;
     }
+    let list = groups.iter()
+        .map(|g| format!("{} ({} round{})", g.first.signature, output::thousands(g.rounds as u64), if g.rounds == 1 { "" } else { "s" }))
+        .collect::<Vec<_>>();
+    say!("\n {} {} distinct failure mode{}: {}", args.style.mark(Mark::Warn), groups.len(), if groups.len() == 1 { "" } else { "s" }, list.join(", "));
+    for FailureMode { first, .. } in &groups {
+        say!("\n::: {}, first in round {}, with the input:", first.signature, first.round);
+        match &first.input {
+            Some(input) => say_text(args, input),
+            None => say!("(no input, the generator didn't produce one)"),
+        }
+        display_seed(args, first.round, first.seed);
+    }
+}
 
-    args.program = preprocess_command(args.program.clone(), &args).expect("failed preprocessing program!");
-    args.generator = preprocess_command(args.generator.clone(), &args).expect("failed preprocessing generator!");
-    args.reference = args.reference.iter().map(|s|
-        preprocess_command(s, &args).expect("failed preprocessing reference!")
-    ).collect();
+/// `--dry-run`: what the session would run and with which limits, and the input of its first round
+fn dry_run(args: &Cli) -> i32 {
+    let duration = |seconds: Option<f64>| seconds.map_or("none".to_string(), |s| format!("{:?}", Duration::from_secs_f64(s)));
+    let command = |path: &Path, role| command_line(path, role, &args.programs).unwrap_or_else(|e| format!("can't be run: {}", e));
+    say!("== dry run, only the generator is run");
+    say!("\n::: commands:");
+    match (&args.input_file, &args.gen_spec) {
+        (Some(file), _) => say!("  input: read from {}", file.display()),
+        (None, Some(spec)) => say!("  input: written by the spec {}", spec.source()),
+        (None, None) => for generator in &args.generator {
+            let weight = if args.generator.len() > 1 { format!(", weight {}", generator.weight) } else { String::new() };
+            let gen_args = args.gen_args.as_ref().map_or(String::new(), |a| format!(", with the arguments {}", a));
+            say!("  generator: {}{}, receiving the seed via {:?}{}", command(&generator.path, Role::Generator), gen_args, args.gen_seed_via, weight);
+        },
+    }
+    say!("  program: {}", command(&args.programs.program, Role::Program));
+    for reference in &args.programs.reference {
+        say!("  reference: {}", command(reference, Role::Reference));
+    }
+    say!("\n::: limits:");
+    say!("  rounds: {}", planned_rounds(args).map_or("until the programme fails".to_string(), |n| n.to_string()));
+    match &args.time_limit_rel {
+        Some(relative) => say!("  time limit: {}x the time of {} on the same input, at least {}", relative.factor, relative.reference.display(), duration(Some(args.time_limit_floor))),
+        None => say!("  time limit: {}", duration(args.time_limit)),
+    }
+    say!("  memory limit: {}", args.memory_limit.map_or("none".to_string(), |m| format!("{}kB", m)));
+    say!("  round timeout: {}", duration(args.round_timeout));
+    say!("\n::: comparison:");
+    let agreeing = match args.compare {
+        Compare::Exact => "identical to".to_string(),
+        Compare::Trimmed => "identical, but for trailing whitespace, to".to_string(),
+        Compare::Tokens => "the same words as".to_string(),
+        Compare::Float => format!("the same words, numbers within {} absolute or relative, as", args.float_tolerance),
+    };
+    say!("  the programme's output has to be {} that of every reference, mismatches are shown as {:?}", agreeing, args.show);
 
-    let mut fails = vec![];
-    for round in 0..args.rounds.unwrap_or(1) {
-        println!("== starting round {}", round);
+    let ctx = RoundCtx::new(args, 0);
+    say!("\n::: input of the first round ({}):", generator_invocation(args, 0, ctx.seed));
+    match generate_input(&ctx, &Processes) {
+        Ok(input) => {
+            say_output(args, &input.stdout);
+            EXIT_OK
+        },
+        Err(fail) => {
+            display_failure(args, &fail);
+            EXIT_INFRA
+        },
+    }
+}
 
-        let outs = run_round(&args);
-        
-        match outs {
-            R::GeneratorFail(fail) => display_failure(&fail),
-            R::ProgramFail(inp, fail) => {
-                display_failure(&fail);
-                println!("with the following input: \n{}", inp);
+/// how many of the slowest rounds are listed without --top
+const DEFAULT_TOP: usize = 5;
+
+fn display_slowest(args: &Cli, slowest: Vec<SlowRound>) {
+    if slowest.is_empty() {
+        return;
+    }
+    say!("\n {} the {} slowest rounds of the programme:", args.style.mark(Mark::Timeout), slowest.len());
+    for slow in &slowest {
+        say!("  round {}: {:.3}s, {}, seed {}", slow.round, slow.time.as_secs_f64(), slow.verdict.describe(), slow.seed);
+    }
+    if let Some(dir) = &args.save_slowest {
+        match slowest::save(dir, &slowest) {
+            Ok(()) => say!("  {} saved their inputs to {}", args.style.mark(Mark::Saved), dir.display()),
+            Err(e) => say!("  {} warning : couldn't save the slowest inputs to {}: {}", args.style.mark(Mark::Warn), dir.display(), e),
+        }
+    }
+}
+
+/// how the command line words the comparison of `--perf-compare` or of `--mem-compare`
+struct Wording {
+    /// what the programme did, e.g. took
+    verb: &'static str,
+    /// e.g. the time of the fastest reference
+    against: &'static str,
+    /// what its worst rounds are, e.g. the slowest
+    worst: &'static str,
+    mark: Mark,
+}
+
+const TIME: Wording = Wording { verb: "took", against: "the time of the fastest reference", worst: "the slowest", mark: Mark::Timeout };
+const MEMORY: Wording = Wording { verb: "used", against: "the memory of the leanest reference", worst: "the greediest", mark: Mark::Warn };
+
+/// counts the round, telling when the programme was flagged in it
+fn display_compared<M: Measure>(args: &Cli, comparison: &mut WorstRounds<M>, compared: ComparedRound<M>, wording: &Wording) {
+    let (program, reference, ratio) = (compared.program, compared.reference, compared.ratio());
+    if comparison.add(compared) {
+        say!("  {} the programme {} {:.1}x {}: {} against {}", args.style.mark(wording.mark), wording.verb, ratio, wording.against, program.show(), reference.show());
+    }
+}
+
+/// how often the programme was flagged and its worst rounds, whose inputs are saved into the directory with
+/// their measures in the file when any of them was flagged
+fn display_comparison<M: Measure>(args: &Cli, comparison: &WorstRounds<M>, wording: &Wording, save: Option<(&Path, &str)>) {
+    let factor = comparison.factor();
+    if comparison.flagged == 0 {
+        say!("\n {} the programme {} at most {}x {} in all {} compared rounds, {}:",
+            args.style.mark(Mark::Ok), wording.verb, factor, wording.against, output::thousands(comparison.compared), wording.worst);
+    } else {
+        say!("\n {} the programme {} more than {}x {} in {} of {} compared rounds, {}:", args.style.mark(wording.mark),
+            wording.verb, factor, wording.against, output::thousands(comparison.flagged), output::thousands(comparison.compared), wording.worst);
+    }
+    say!("  {:>8} {:>10} {:>10} {:>7}  {:<14} seed", "round", "programme", "reference", "ratio", "verdict");
+    for round in comparison.worst() {
+        let ratio = format!("{:.1}x", round.ratio());
+        say!("  {:>8} {:>10} {:>10} {:>7}  {:<14} {}{}", round.round, round.program.show(), round.reference.show(),
+            ratio, round.verdict.code(), round.seed, if comparison.is_flagged(round) { "  <- flagged" } else { "" });
+    }
+    let flagged: Vec<_> = comparison.worst().iter().filter(|round| comparison.is_flagged(round)).collect();
+    if let (Some((dir, file)), false) = (save, flagged.is_empty()) {
+        match perf::save(dir, &flagged, file) {
+            Ok(()) => say!("  {} saved the inputs of the flagged rounds to {}", args.style.mark(Mark::Saved), dir.display()),
+            Err(e) => say!("  {} warning : couldn't save the inputs of the flagged rounds to {}: {}", args.style.mark(Mark::Warn), dir.display(), e),
+        }
+    }
+}
+
+fn display_perf(args: &Cli, perf: &PerfComparison) {
+    if perf.compared == 0 {
+        say!("\n  {} warning : no round timed both the programme and a reference to compare them", args.style.mark(Mark::Warn));
+        return;
+    }
+    display_comparison(args, perf, &TIME, args.save_perf.as_deref().map(|dir| (dir, "perf.txt")));
+}
+
+fn display_memory(args: &Cli, memory: &MemoryComparison) {
+    let kb = |kb: u64| output::human_size(kb * 1024);
+    let binaries: Vec<_> = memory.binaries().filter_map(|(path, stats)| Some((args.source_of(path).display().to_string(), stats?))).collect();
+    if binaries.is_empty() {
+        say!("\n  {} warning : --mem-compare measured no programme, which it only can of those running on this machine", args.style.mark(Mark::Warn));
+        return;
+    }
+    say!("\n::: peak memory:");
+    let width = binaries.iter().map(|(name, _)| name.len()).max().unwrap_or_default();
+    for (name, stats) in &binaries {
+        say!("  {:<width$}  median {:>10}, max {:>10} with the seed {}", name, kb(stats.median as u64), kb(stats.max.value as u64), stats.max.seed, width = width);
+    }
+    if memory.rounds.compared > 0 {
+        display_comparison(args, &memory.rounds, &MEMORY, args.save_mem.as_deref().map(|dir| (dir, "memory.txt")));
+    }
+}
+
+/// `--bench`: the times and the memory of the programmes on the inputs of the rounds, their outputs unchecked
+fn run_bench(args: &Cli) -> i32 {
+    if let Some(cpu) = args.pin_cpu {
+        match bench::pin_to_cpu(cpu) {
+            Ok(()) => debug!("running on the cpu {}", cpu),
+            Err(e) => say!("  {} warning : couldn't run on the cpu {}: {}", args.style.mark(Mark::Warn), cpu, e),
+        }
+    }
+    let runs = if args.warmup > 0 { format!("{} warm-up and {} timed", args.warmup, args.runs_per_input) } else { args.runs_per_input.to_string() };
+    say!("== benchmarking on the input of {}, {} run(s) of every programme on each",
+        planned_rounds(args).map_or("every round until Ctrl+C".to_string(), |n| format!("{} round(s)", n)), runs);
+    let bench = bench::bench(args, &catch_interrupts(), |round, bench| if args.verbose > 0 {
+        let last = |samples: &[Sample]| samples.last().map_or("-".to_string(), |s| format!("{:.3}s", s.value));
+        let times: Vec<_> = bench.programmes.iter().map(|p| format!("{} {}", args.source_of(&p.path).display(), last(&p.wall))).collect();
+        say!("round {} (seed {}): {}", round, round_seed(args.seed.unwrap_or_default(), round), times.join(", "));
+    });
+    say!("== {} inputs benchmarked", output::thousands(bench.inputs));
+    if bench.skipped > 0 {
+        say!("  {} warning : skipped {} round(s) whose generator failed", args.style.mark(Mark::Warn), output::thousands(bench.skipped));
+    }
+    let of = |samples: fn(&bench::Samples) -> &[Sample]| bench.programmes.iter().map(|p| (p, Stats::of(samples(p)))).collect::<Vec<_>>();
+    let width = bench.programmes.iter().map(|p| args.source_of(&p.path).display().to_string().len()).max().unwrap_or_default().max(9);
+    for (measure, stats, memory) in [("wall time", of(|p| &p.wall), false), ("cpu time", of(|p| &p.cpu), false), ("peak memory", of(|p| &p.memory), true)] {
+        if stats.iter().all(|(_, stats)| stats.is_none()) {
+            continue;
+        }
+        let show = |value: f64| if memory { output::human_size(value as u64 * 1024) } else { format!("{:.3}s", value) };
+        say!("\n::: {}:", measure);
+        say!("  {:<width$} {:>10} {:>10} {:>10} {:>10} {:>10}  extremes", "programme", "mean", "median", "p90", "p99", "max", width = width);
+        for (programme, stats) in stats {
+            let name = args.source_of(&programme.path).display().to_string();
+            match stats {
+                Some(s) => say!("  {:<width$} {:>10} {:>10} {:>10} {:>10} {:>10}  max with the seed {}, min {} with the seed {}", name,
+                    show(s.mean), show(s.median), show(s.p90), show(s.p99), show(s.max.value), s.max.seed, show(s.min.value), s.min.seed, width = width),
+                None => say!("  {:<width$} {:>10}", name, "-", width = width),
+            }
+        }
+    }
+    let mut failed = false;
+    for programme in bench.programmes.iter().filter(|p| p.failed > 0) {
+        failed = true;
+        say!("\n {} {} failed {} run(s), which aren't counted, the first: {}", args.style.mark(Mark::Fail),
+            args.source_of(&programme.path).display(), output::thousands(programme.failed), programme.first_error.as_deref().unwrap_or_default());
+    }
+    if failed { EXIT_FAILURE } else { EXIT_OK }
+}
+
+/// a panic shows where it happened like with RUST_BACKTRACE=1, without changing the environment of the process
+fn show_backtraces() {
+    panic::set_hook(Box::new(|info| {
+        let thread = thread::current();
+        eprintln!("\nthread '{}' {}\nstack backtrace:\n{}", thread.name().unwrap_or("<unnamed>"), info, Backtrace::force_capture());
+    }));
+}
+
+/// runs the whole session and returns the exit code
+pub fn handle_cli(mut args: Cli) -> i32 {
+    if args.verbose > 0 {
+        show_backtraces();
+    }
+    args.style = Style::detect(args.ascii, args.color);
+    if let Some(path) = &args.log_file {
+        if let Err(e) = output::open_log(path) {
+            eprintln!("error: couldn't create the log file {}: {}", path.display(), e);
+            return EXIT_INFRA;
+        }
+    }
+    if let (Some(profile), Some(config)) = (&args.profile, &args.config) {
+        say!("using the profile {} from {}", profile, config.display());
+    }
+    if args.verbose > 0 {
+        if let Some(config) = &args.config {
+            say!("using the configuration from {}", config.display());
+        }
+        say!("effective configuration: {:#?}", args);
+    }
+
+    warn_overridden_std(args.style, &args.programs);
+    args.programs.backend = match Backend::open(args.programs.docker.as_deref()) {
+        Ok(backend) => backend,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return EXIT_INFRA;
+        },
+    };
+
+    args.detect_toolchain();
+    if let (Some(toolchain), true) = (&args.toolchain, args.verbose > 0) {
+        say!("compiling with {} ({})", toolchain.compiler.display(), toolchain.version);
+        if let (None, Some(cxx)) = (&args.programs.cpp_compiler, args.programs.env_cxx()) {
+            say!("the c++ compiler {} comes from the CXX variable", cxx.display());
+        }
+        if let Some(flags) = args.programs.env_cxx_flags() {
+            say!("the flags {} come from the CXXFLAGS variable", flags);
+        }
+    }
+    if args.verbose > 0 {
+        for (role, source) in args.distinct_sources().iter().filter(|(role, s)| args.programs.is_compiled(*role, s)) {
+            let flags = format!("{} {}", args.programs.flags_of("cpp"), args.programs.extra_flags(*role));
+            say!("{} is built as the {} with the {} profile: {}", source.display(), role,
+                args.programs.profile_of(*role).to_possible_value().expect("profiles have names").get_name(), flags.trim());
+        }
+    }
+    // every source gets its status line, and no round runs unless all of them are ready
+    let prepared = args.prepare();
+    let mut failed = 0;
+    for (source, binary) in &prepared {
+        match binary {
+            Ok(binary) => {
+                let status = match &binary.interpreter {
+                    _ if binary.compiled.is_some() => format!("compiled -> {}", binary.binary.display()),
+                    Some(interpreter) => format!("runs with {}", interpreter.display()),
+                    None => "ok".to_string(),
+                };
+                if !args.quiet {
+                    say!("{} {} {}", args.style.mark(Mark::Ok), source.display(), status);
+                }
+                display_compile_warnings(args.style, &args.programs, source, &binary.warnings);
+            },
+            Err(e) => {
+                display_compile_error(args.style, source, e.as_ref());
+                failed += 1;
             },
-            R::ReferenceFails(inp, fails) => {
-                fails.iter().for_each(display_failure);
-                println!("with the following input: \n{}", inp);   
+        }
+    }
+    if failed > 0 {
+        say!("== no rounds ran, {} of the {} files couldn't be prepared", failed, prepared.len());
+        return EXIT_INFRA;
+    }
+    if let Some(host) = &args.programs.remote {
+        let program = args.programs.program.clone();
+        args.programs.program_backend = match Backend::open_remote(host, &args.programs.remote_dir, &[&program]) {
+            Ok(backend) => Some(backend),
+            Err(e) => {
+                eprintln!("error: {}", e);
+                return EXIT_INFRA;
             },
-            R::Success(inp, prog, refs) => if refs.is_empty() {
-                println!("  🚧 warning : skipping reference checks as no references were supplied...")        
-            } else { 
-                if args.verbose { println!("running comparisons of output..."); }
-
-                let test = test_mismatch(prog, refs);
-                match test {
-                    M::AllMatch => cli_section("Awesome! All references match the output!", true),
-                    M::ProgMismatch(ref prog, ref refs) => display_mismatches(&inp, &prog, &refs),
-                    M::RefMismatch(ref refs) => display_ref_mismatches(&inp, &refs),
+        };
+    }
+
+    if let Some(dir) = &args.ref_cache {
+        match RefCache::open(dir, args.ref_cache_size * 1024 * 1024, &args.programs) {
+            Ok(cache) => args.opened_ref_cache = Some(cache),
+            Err(e) => {
+                eprintln!("error: couldn't open the reference cache {}: {}", dir.display(), e);
+                return EXIT_INFRA;
+            },
+        }
+    }
+
+    // a session started with other options or another seed is refused, the error says which changed
+    let resumed = match args.session.as_ref().map(|path| (path, SessionFile::resume(path, &args))) {
+        Some((path, Err(e))) => {
+            eprintln!("error: couldn't resume the session {}: {}", path.display(), e);
+            return EXIT_INFRA;
+        },
+        Some((_, Ok(resumed))) => resumed,
+        None => None,
+    };
+    if let Some(resumed) = &resumed {
+        args.seed = Some(resumed.seed);
+    }
+    let seed = *args.seed.get_or_insert_with(random_seed);
+    if args.dry_run {
+        return dry_run(&args);
+    }
+    if args.bench {
+        return run_bench(&args);
+    }
+    if let (Some(file), true) = (&args.input_file, args.verbose > 0) {
+        say!("running a single round on the input from {}", file.display());
+    } else if args.verbose > 0 {
+        say!("session seed {}, the generator receives the seed of each round via {:?}", seed, args.gen_seed_via);
+    }
+
+    // keep stdout for the machine readable output
+    let stdout = Some(Path::new("-"));
+    if args.events_ndjson.as_deref() == stdout || args.report_json.as_deref() == stdout {
+        output::human_to_stderr(true);
+    }
+    output::set_quiet(args.quiet);
+    output::set_pager(args.pager);
+    // a file or a CI log gets the fixed width, not that of the terminal the session was started from
+    args.width.get_or_insert_with(output::detect_width);
+    let first_round = resumed.as_ref().map_or(0, |r| r.rounds_done);
+    let session = Session::of_cli(args, catch_interrupts());
+    let args = session.args();
+    let mut presenter = match Presenter::open(args, seed, resumed.as_ref()) {
+        Ok(presenter) => presenter,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return EXIT_INFRA;
+        },
+    };
+    // the bar replaces the output of passing rounds, so it's only useful on an interactive terminal
+    if !args.no_progress && args.verbose == 0 && !log::log_enabled!(log::Level::Info) && io::stderr().is_terminal() {
+        output::start_progress(planned_rounds(args), args.style);
+    }
+    let report = session.run(resumed.as_ref(), &mut presenter);
+    presenter.finish(report, first_round)
+}
+
+/// How the command line shows the rounds of its `Session`: it prints them as they end, saves and keeps their
+/// files and writes them into the reports.
+struct Presenter<'a> {
+    args: &'a Cli,
+    /// the session seed
+    seed: u64,
+    /// the failing rounds are annotated for GitHub Actions
+    github: bool,
+    /// the user is asked what to do after a failing round
+    interactive: bool,
+    saver: Option<FailureSaver>,
+    keeper: Option<ArtifactKeeper>,
+    corpus: Option<Corpus>,
+    events: Option<EventStream>,
+    markdown: Option<MarkdownReport>,
+    csv: Option<CsvReport>,
+    results_db: Option<ResultsDb>,
+    notifier: Option<Notifier>,
+    slowest: Option<SlowestRounds>,
+    perf: Option<PerfComparison>,
+    memory: Option<MemoryComparison>,
+    /// the session file, rewritten after every round
+    progress: Option<(&'a PathBuf, SessionFile)>,
+    /// the rounds of the JSON and HTML reports, written once the session is over
+    records: Vec<RoundRecord>,
+}
+
+impl<'a> Presenter<'a> {
+    /// Opens the files and directories which the rounds are written into and writes the session file, the
+    /// error says which of them couldn't be.
+    fn open(args: &'a Cli, seed: u64, resumed: Option<&SessionFile>) -> Result<Self, Box<dyn Error>> {
+        let saver = args.save_failures.as_ref()
+            .map(|dir| FailureSaver::new(dir, args.environment.as_ref())
+                .map_err(|e| into_err(format!("couldn't create the directory for failures in {}: {}", dir.display(), e))))
+            .transpose()?;
+        if let (Some(saver), true) = (&saver, args.verbose > 0) {
+            say!("failing rounds will be saved into {}", saver.dir().display());
+        }
+        let keeper = args.keep_artifacts.as_ref()
+            .map(|dir| ArtifactKeeper::new(dir, args.keep_last).map_err(|e| into_err(format!("couldn't create the directory for artifacts {}: {}", dir.display(), e))))
+            .transpose()?;
+        let corpus = args.corpus_dir.as_ref()
+            .map(|dir| Corpus::open(dir, args.corpus_size.map(|mb| mb * 1024 * 1024)).map_err(|e| into_err(format!("couldn't open the corpus {}: {}", dir.display(), e))))
+            .transpose()?;
+
+        let stdout = Some(Path::new("-"));
+        let github = match args.format {
+            Some(format) => format == Format::Github,
+            None => env::var("GITHUB_ACTIONS").is_ok_and(|v| v == "true"),
+        };
+        // the annotations would corrupt the machine readable output on stdout
+        let github = github && args.events_ndjson.as_deref() != stdout && args.report_json.as_deref() != stdout;
+        let events = args.events_ndjson.as_ref()
+            .map(|dest| EventStream::new(dest).map_err(|e| into_err(format!("couldn't open the event stream {}: {}", dest.display(), e))))
+            .transpose()?;
+
+        let markdown = args.report_md.as_ref()
+            .map(|path| MarkdownReport::new(path).map_err(|e| into_err(format!("couldn't create the Markdown report {}: {}", path.display(), e))))
+            .transpose()?;
+        let csv = args.report_csv.as_ref()
+            .map(|path| CsvReport::new(path, args).map_err(|e| into_err(format!("couldn't create the CSV report {}: {}", path.display(), e))))
+            .transpose()?;
+        let results_db = args.db.as_ref()
+            .map(|path| ResultsDb::start(path, args).map_err(|e| into_err(format!("couldn't open the database {}: {}", path.display(), e))))
+            .transpose()?;
+        let notifier = args.notify_url.as_ref().map(|url| Notifier::new(url, &args.notify_on, args.style));
+        let slowest = (args.top.is_some() || args.save_slowest.is_some()).then(|| SlowestRounds::new(args.top.unwrap_or(DEFAULT_TOP)));
+        let perf = args.perf_compare.then(|| PerfComparison::new(args.perf_factor, args.top.unwrap_or(DEFAULT_TOP)));
+        let memory = args.mem_compare.then(|| MemoryComparison::new(args.mem_factor, args.top.unwrap_or(DEFAULT_TOP), &args.programs.program, &args.programs.reference));
+        if let (Some(path), Some(resumed)) = (&args.session, resumed) {
+            say!("resuming the session from {}: {} rounds done, {} failed, going on from round {}",
+                path.display(), output::thousands(resumed.rounds_done), output::thousands(resumed.failed_rounds), resumed.rounds_done);
+        }
+        let progress = args.session.as_ref().map(|path| (path, resumed.cloned().unwrap_or_else(|| SessionFile::new(args, seed))));
+        if let Some((path, progress)) = &progress {
+            progress.save(path).map_err(|e| into_err(format!("couldn't write the session file {}: {}", path.display(), e)))?;
+        }
+        Ok(Presenter {
+            args, seed, github, interactive: !args.no_interactive && io::stdin().is_terminal(),
+            saver, keeper, corpus, events, markdown, csv, results_db, notifier, slowest, perf, memory, progress, records: vec![],
+        })
+    }
+}
+
+impl SessionView for Presenter<'_> {
+    fn round_start(&mut self, round: u64) {
+        output::begin_round();
+        say!("== starting round {}", round);
+        self.emit(Event::RoundStart { round });
+
+        if self.args.verbose > 0 {
+            let seed = round_seed(self.seed, round);
+            say!("round seed {}: {}", seed, generator_invocation(self.args, round, seed));
+        }
+    }
+
+    fn round_end(&mut self, end: &mut RoundEnd, report: &SessionReport) {
+        use crate::Round as R;
+        use crate::Mismatch as M;
+
+        let args = self.args;
+        let result = &mut end.result;
+        let (round, seed, verdict, duration, meta) = (result.round, result.seed, result.verdict, result.duration, result.meta.clone());
+        let reproduce = generator_invocation(args, round, seed);
+        let saved = |verdict, input, files| SavedRound { round, verdict, seed, duration, meta: &meta, input, reproduce: &reproduce, files };
+        if result.retries > 0 {
+            say!("  {} {} execution(s) had to be retried in this round", args.style.mark(Mark::Retry), result.retries);
+        }
+
+        let input = result.outcome.input().map(str::to_string);
+        let annotation = self.github.then(|| github::round_message(args, &result.outcome));
+        let reported = args.report_json.is_some() || args.report_html.is_some() || self.markdown.is_some() || self.csv.is_some() || self.results_db.is_some();
+        let mut record = reported.then(|| RoundRecord::new(args, result));
+        let mut saved_paths = vec![];
+        output::begin_page();
+        let artifacts = std::mem::take(&mut result.artifacts);
+        let saver = self.saver.as_ref();
+        match &result.outcome {
+            R::GeneratorFail(fail) => {
+                display_failure(args, fail);
+                display_seed(args, round, seed);
+                if let Some(saver) = saver {
+                    saved_paths = save_failure(args, saver, saved(verdict.describe(), None, failure_files(args, &[fail])));
+                }
+            },
+            R::ProgramFail { input: inp, fail, diagnostics } => {
+                display_failure(args, fail);
+                say!("with the following input: ");
+                say_text(args, inp);
+                display_seed(args, round, seed);
+                display_diagnostics(args, diagnostics);
+                if let Some(saver) = saver {
+                    let files = failure_files(args, &[fail]).into_iter()
+                        .map(|(_, err)| ("program.err".to_string(), err))
+                        .collect();
+                    saved_paths = save_failure(args, saver, saved(verdict.describe(), Some(inp), files));
+                }
+            },
+            R::ReferenceFails { input: inp, refs } => {
+                display_references(args, refs);
+                say!("with the following input: ");
+                say_text(args, inp);
+                display_seed(args, round, seed);
+                if let Some(saver) = saver {
+                    let fails: Vec<_> = refs.iter().filter_map(|r| r.as_ref().err()).collect();
+                    let mut files = failure_files(args, &fails);
+                    // the outputs of the references which succeeded are saved with the errors of the others
+                    let names = output_suffixes(refs.iter().map(|r| args.source_of(r.as_ref().map_or_else(Failure::path, |x| &x.path))));
+                    files.extend(refs.iter().zip(names).filter_map(|(r, name)| r.as_ref().ok().map(|x| (name, Cow::Borrowed(&x.stdout)))));
+                    saved_paths = save_failure(args, saver, saved(verdict.describe(), Some(inp), files));
+                }
+            },
+            R::Timeout { stage, input: inp } => {
+                say!("  {} round exceeded the round timeout of {:?} while running the {}, skipping...", args.style.mark(Mark::Timeout), Duration::from_secs_f64(args.round_timeout.unwrap_or_default()), stage);
+                if let Some(inp) = &inp {
+                    say!("with the following input: ");
+                    say_text(args, inp);
+                }
+                display_seed(args, round, seed);
+                if let Some(saver) = saver {
+                    saved_paths = save_failure(args, saver, saved(verdict.describe(), inp.as_deref(), vec![]));
+                }
+            },
+            R::Success { input: inp, .. } => match &end.mismatch {
+                None => say!("  {} warning : skipping reference checks as no references were supplied...", args.style.mark(Mark::Warn)),
+                Some(M::AllMatch) => cli_section(args, "Awesome! All references match the output!", true),
+                Some(mismatch) => {
+                    let (prog, refs) = match mismatch {
+                        M::ProgMismatch { prog, refs } => {
+                            display_mismatches(args, inp, prog, refs);
+                            (Some(prog), refs)
+                        },
+                        M::RefMismatch(refs) => {
+                            display_ref_mismatches(args, inp, refs);
+                            (None, refs)
+                        },
+                        M::AllMatch => unreachable!(),
+                    };
+                    display_seed(args, round, seed);
+                    if let Some(saver) = saver {
+                        let mut files: Vec<_> = prog.iter().map(|p| ("program.out".to_string(), Cow::Borrowed(&p.stdout))).collect();
+                        files.extend(output_suffixes(refs.iter().map(|r| args.source_of(&r.path)))
+                            .into_iter()
+                            .zip(refs.iter().map(|r| Cow::Borrowed(&r.stdout))));
+                        saved_paths = save_failure(args, saver, saved(verdict.describe(), Some(inp), files));
+                    }
+                },
+            },
+        }
+        let failed = end.exit_code != EXIT_OK;
+        let minimized = match (&input, args.minimize && failed) {
+            (Some(input), true) => Some(display_minimized(args, round, input, end.signature, saver)),
+            _ => None,
+        };
+        if let (Some(input), Some(count), true) = (&input, args.explore, failed) {
+            display_explored(args, round, seed, input, count, saver);
+        }
+        if let (Some(perf), Some(input)) = (&mut self.perf, &input) {
+            if let Some(timed) = PerfComparison::timed(round, seed, verdict, &meta, input) {
+                display_compared(args, perf, timed, &TIME);
+            }
+        }
+        if let (Some(memory), Some(input)) = (&mut self.memory, &input) {
+            memory.record(seed, &meta);
+            if let Some(measured) = MemoryComparison::measured(round, seed, verdict, &meta, input) {
+                display_compared(args, &mut memory.rounds, measured, &MEMORY);
+            }
+        }
+        output::end_page(failed);
+
+        if let (Some(message), true) = (&annotation, verdict != Verdict::Ok) {
+            github::annotate_round(round, seed, verdict, message);
+        }
+        self.emit(Event::Verdict { round, verdict, seed, time_ms: duration.as_millis() });
+        let program_time = end.result.executions.iter().rev().find(|e| e.role == Role::Program).map(|e| Duration::from_secs_f64(e.seconds));
+        if let (Some(slowest), Some(time), Some(input)) = (&mut self.slowest, program_time, &input) {
+            slowest.add(SlowRound { time, round, seed, verdict, input: input.clone() });
+        }
+        if let Some(keeper) = &mut self.keeper {
+            let kept = KeptRound { round, seed, verdict, duration, input: input.as_deref(), executions: artifacts };
+            match keeper.keep(args, &kept) {
+                Ok(dir) => debug!("kept the artifacts of round {} in {}", round, dir.display()),
+                Err(e) => say!("  {} warning : couldn't keep the artifacts of the round: {}", args.style.mark(Mark::Warn), e),
+            }
+        }
+        if let (Some(corpus), Some(input)) = (&mut self.corpus, &input) {
+            match corpus.add(seed, verdict, &meta, input) {
+                Ok(added) => debug!("{} the input of round {} to the corpus", if added { "added" } else { "didn't add the known" }, round),
+                Err(e) => say!("  {} warning : couldn't add the input of the round to the corpus: {}", args.style.mark(Mark::Warn), e),
+            }
+        }
+        if let Some(mut record) = record.take() {
+            record.verdict = verdict;
+            record.signature = end.signature;
+            record.input_path = saved_paths.into_iter().find(|p| p.extension().is_some_and(|e| e == "in"));
+            record.minimized_input = minimized.map(|m| report::Blob::new(&m.input, args.report_max_output));
+            if let (Some(md), true) = (&mut self.markdown, verdict != Verdict::Ok) {
+                if let Err(e) = md.add(args, &record) {
+                    say!("  {} warning : couldn't write the round into the Markdown report: {}", args.style.mark(Mark::Warn), e);
                 }
-                if !matches!(test, M::AllMatch) {
-                    fails.push((inp, test))
+            }
+            if let Some(db) = &mut self.results_db {
+                if let Err(e) = db.add(&record) {
+                    say!("  {} warning : couldn't write the round into the database: {}", args.style.mark(Mark::Warn), e);
                 }
             }
+            if let Some(csv) = &mut self.csv {
+                if let Err(e) = csv.add(&record) {
+                    say!("  {} warning : couldn't write the round into the CSV report: {}", args.style.mark(Mark::Warn), e);
+                }
+            }
+            if args.report_json.is_some() || args.report_html.is_some() {
+                self.records.push(record);
+            }
+        }
+
+        if let (Some(notifier), true) = (&mut self.notifier, failed) {
+            notifier.failure(round, seed, verdict, input.as_deref(), report.elapsed);
         }
+        if let Some((path, progress)) = &mut self.progress {
+            progress.rounds_done = report.rounds_done;
+            progress.summary = report.summary.clone();
+            progress.failed_rounds = report.failed_rounds;
+            progress.exit_code = report.summary.exit_code;
+            if failed {
+                progress.failures.push(PastFailure { round, seed, verdict });
+            }
+            if let Err(e) = progress.save(path) {
+                say!("  {} warning : couldn't record the progress into {}: {}", args.style.mark(Mark::Warn), path.display(), e);
+            }
+        }
+        output::show_progress(end.progress);
+        output::end_round(failed);
     }
 
-    if fails.is_empty() { return; }
-    println!(" 🚧 Summary of all fails: ");
+    fn go_on(&mut self, end: &RoundEnd, report: &SessionReport) -> bool {
+        let (args, round) = (self.args, end.result.round);
+        if self.interactive && end.exit_code != EXIT_OK && !ask_after_failure(args, round, end.result.verdict, end.result.outcome.input(), self.saver.as_ref()) {
+            return false;
+        }
+        if args.heartbeat > 0 && (round + 1).is_multiple_of(args.heartbeat) && !output::has_progress() {
+            say!("== {} rounds passed, {} failed, {} elapsed", output::thousands(report.summary.passed as u64),
+                output::thousands(report.summary.failed as u64), output::format_elapsed(report.elapsed));
+        }
+        true
+    }
+}
 
-    for (inp, mismatch) in fails {
-        match mismatch {
-            M::ProgMismatch(prog, refs) => display_mismatches(&inp, &prog, &refs),
-            M::RefMismatch(refs) => display_ref_mismatches(&inp, &refs),
-            _ => panic!("internal error, unrecognized mismatch"),
+impl Presenter<'_> {
+    fn emit(&mut self, event: Event) {
+        if let Some(events) = &mut self.events {
+            if let Err(e) = events.emit(&event) {
+                say!("  {} warning : couldn't write an event: {}", self.args.style.mark(Mark::Warn), e);
+            }
         }
     }
-}
\ No newline at end of file
+
+    /// shows why the session stopped and what it came to, writes the reports and returns the exit code
+    fn finish(mut self, session: SessionReport, first_round: u64) -> i32 {
+        use crate::Mismatch as M;
+
+        let args = self.args;
+        match session.stopped {
+            Some(Stop::Interrupted) => {
+                // the round which was stopped says nothing
+                output::end_round(false);
+                say!(" {} interrupted, stopping", args.style.mark(Mark::Warn));
+            },
+            Some(Stop::LostRemote) => say!(" {} stopping, the programme can't run on {}", args.style.mark(Mark::Warn), args.programs.remote.as_deref().unwrap_or_default()),
+            Some(Stop::FailFast) => say!(" {} stopping at the first failing round (--fail-fast)", args.style.mark(Mark::Warn)),
+            Some(Stop::FoundFailure) => say!(" {} found a failing round after {} rounds", args.style.mark(Mark::Warn), output::thousands(session.rounds_done)),
+            Some(Stop::GeneratorFailed) => say!(" {} stopping, a generator failed (--on-gen-fail stop)", args.style.mark(Mark::Warn)),
+            Some(Stop::Asked | Stop::MaxFailures) | None => {},
+        }
+
+        output::finish_progress();
+        let summary = session.summary;
+        let exit_code = summary.exit_code;
+        let times = session.times;
+        let counts = format!("   the generator ran for {:.2}s, the programme for {:.2}s and the references for {:.2}s\n== {} rounds: {} passed, {} failed",
+            times.generator.as_secs_f64(), times.program.as_secs_f64(), times.references.as_secs_f64(), summary.rounds, summary.passed, summary.failed);
+        self.emit(Event::Summary(&summary));
+        if self.github {
+            github::annotate_summary(&summary);
+        }
+        if let Some(notifier) = self.notifier {
+            notifier.finish(&summary, session.elapsed);
+        }
+        if let Some(db) = &mut self.results_db {
+            if let Err(e) = db.finish(&summary) {
+                say!("  {} warning : couldn't write the session into the database: {}", args.style.mark(Mark::Warn), e);
+            }
+        }
+        if let Some(md) = self.markdown {
+            if let Err(e) = md.finish(&summary, args.environment.as_ref()) {
+                say!("  {} warning : couldn't write the Markdown report: {}", args.style.mark(Mark::Warn), e);
+            }
+        }
+        let report = Report::new(args, self.records, summary);
+        if let Some(dest) = &args.report_json {
+            if let Err(e) = report.write(dest) {
+                say!("  {} warning : couldn't write the JSON report to {}: {}", args.style.mark(Mark::Warn), dest.display(), e);
+            }
+        }
+        if let Some(dest) = &args.report_html {
+            if let Err(e) = html::write(&report, dest) {
+                say!("  {} warning : couldn't write the HTML report to {}: {}", args.style.mark(Mark::Warn), dest.display(), e);
+            }
+        }
+        if let Some(slowest) = self.slowest {
+            display_slowest(args, slowest.into_sorted());
+        }
+        if let Some(perf) = &self.perf {
+            display_perf(args, perf);
+        }
+        if let Some(memory) = &self.memory {
+            display_memory(args, memory);
+        }
+        if matches!(session.stopped, Some(Stop::FailFast | Stop::LostRemote)) {
+            say!("{}", counts);
+            return exit_code;
+        }
+        if session.skipped_rounds > 0 {
+            say!(" {} skipped {} round{} whose generator failed", args.style.mark(Mark::Warn), output::thousands(session.skipped_rounds),
+                if session.skipped_rounds == 1 { "" } else { "s" });
+        }
+        if session.stopped == Some(Stop::MaxFailures) {
+            say!(" {} stopped after {} failing rounds as set by --max-failures", args.style.mark(Mark::Warn), session.failed_rounds);
+        }
+        if let Some((_, progress)) = &self.progress {
+            let past = progress.failures.iter()
+                .filter(|f| f.round < first_round)
+                .map(|f| format!("round {} ({}, seed {})", f.round, f.verdict.code(), f.seed))
+                .collect::<Vec<_>>();
+            if !past.is_empty() {
+                say!(" {} {} failing rounds before the session was resumed: {}", args.style.mark(Mark::Warn), past.len(), past.join(", "));
+            }
+        }
+        if !session.mismatches.is_empty() {
+            say!(" {} Summary of all fails: ", args.style.mark(Mark::Warn));
+        }
+        // identical failures are shown once
+        for DistinctFailure { first, occurrences } in report::distinct_failures(session.mismatches, !args.no_dedup) {
+            if occurrences > 1 {
+                say!("\n  this failure occurred {} times, first at round {}", output::thousands(occurrences), first.round);
+            }
+            match first.mismatch {
+                M::ProgMismatch { prog, refs } => display_mismatches(args, &first.input, &prog, &refs),
+                M::RefMismatch(refs) => display_ref_mismatches(args, &first.input, &refs),
+                _ => panic!("internal error, unrecognized mismatch"),
+            }
+            display_seed(args, first.round, first.seed);
+        }
+        display_failure_modes(args, report::failure_modes(session.failures));
+        say!("{}", counts);
+        exit_code
+    }
+}
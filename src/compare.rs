@@ -0,0 +1,249 @@
+use std::io::{self, BufRead};
+
+use crate::capture::Captured;
+use crate::{ExecutionResult, Mismatch};
+
+/// Whether the output of the programme agrees with the output of a reference, as judged by a `Comparator`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    Match,
+    /// the line (from 1) of the programme's output where they first differ, and why they differ
+    Differ { line: Option<usize>, explanation: String },
+}
+
+impl Comparison {
+    pub fn is_match(&self) -> bool {
+        matches!(self, Comparison::Match)
+    }
+}
+
+/// Judges the output of the programme against the output of a reference. `--compare` picks one of the
+/// built-in ones, a `Session` may be given any other. Sessions run on any thread, so comparators can be shared.
+pub trait Comparator: Send + Sync {
+    fn compare(&self, program: &[u8], reference: &[u8]) -> Comparison;
+
+    /// Compares outputs too long to be kept in memory, read from their files. They're read into memory
+    /// all the same unless the comparator reads them a chunk at a time, as the built-in ones do.
+    fn compare_readers(&self, program: &mut dyn BufRead, reference: &mut dyn BufRead) -> io::Result<Comparison> {
+        let (mut p, mut r) = (vec![], vec![]);
+        program.read_to_end(&mut p)?;
+        reference.read_to_end(&mut r)?;
+        Ok(self.compare(&p, &r))
+    }
+}
+
+/// Compares the outputs in memory when they both are, reading them from their files otherwise.
+pub fn compare_captured<C: Comparator + ?Sized>(comparator: &C, program: &Captured, reference: &Captured) -> Comparison {
+    if let (Some(p), Some(r)) = (program.text(), reference.text()) {
+        return comparator.compare(p.as_bytes(), r.as_bytes());
+    }
+    let compared = program.reader().and_then(|mut p| reference.reader().and_then(|mut r| comparator.compare_readers(&mut p, &mut r)));
+    compared.unwrap_or_else(|e| Comparison::Differ { line: None, explanation: format!("couldn't read the outputs: {}", e) })
+}
+
+/// the line (from 1) where the lines of the outputs first differ, reading them from their files when they're in any
+pub fn differing_line(reference: &Captured, program: &Captured) -> Option<usize> {
+    let (mut r, mut p) = (reference.reader().ok()?, program.reader().ok()?);
+    match line_difference(lines(&mut p), lines(&mut r)) {
+        Ok(Comparison::Differ { line, .. }) => line,
+        _ => None,
+    }
+}
+
+/// Judges the output of the programme against those of the references with the comparator, as the rounds
+/// do. The references it doesn't match must agree among themselves, or it's the references which are at fault.
+pub fn compare_outputs<C: Comparator + ?Sized>(program: &ExecutionResult, references: &[ExecutionResult], comparator: &C) -> Mismatch {
+    let agrees = |a: &ExecutionResult, b: &ExecutionResult| compare_captured(comparator, &a.stdout, &b.stdout).is_match();
+    let differing: Vec<_> = references.iter().filter(|r| !agrees(program, r)).collect();
+    if differing.is_empty() {
+        return Mismatch::AllMatch;
+    }
+    if differing.windows(2).any(|w| !agrees(w[1], w[0])) {
+        Mismatch::RefMismatch(references.to_vec())
+    } else {
+        Mismatch::ProgMismatch { prog: program.clone(), refs: differing.into_iter().cloned().collect() }
+    }
+}
+
+/// the outputs are the same bytes
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Exact;
+
+/// the outputs have the same lines once the whitespace at their ends and the blank lines at the end are dropped
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Trimmed;
+
+/// the outputs are the same words, however they're separated by whitespace
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Tokens;
+
+/// like `Tokens`, but numbers only have to be within this absolute or relative tolerance of the reference's
+#[derive(Clone, Copy, Debug)]
+pub struct FloatTolerance(pub f64);
+
+/// the comparison of outputs in memory, which can't fail to be read
+fn in_memory(compared: io::Result<Comparison>) -> Comparison {
+    compared.expect("outputs in memory are always read")
+}
+
+/// the lines of the output without their line endings, read one at a time
+fn lines(reader: &mut dyn BufRead) -> impl Iterator<Item = io::Result<String>> + '_ {
+    let mut line = vec![];
+    std::iter::from_fn(move || {
+        line.clear();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => None,
+            Ok(_) => Some(Ok(String::from_utf8_lossy(without_line_ending(&line)).into_owned())),
+            Err(e) => Some(Err(e)),
+        }
+    })
+}
+
+fn without_line_ending(line: &[u8]) -> &[u8] {
+    match line.strip_suffix(b"\n") {
+        Some(line) => line.strip_suffix(b"\r").unwrap_or(line),
+        None => line,
+    }
+}
+
+/// the expected line and the one the programme wrote instead
+fn lines_differ(line: usize, expected: Option<&str>, got: Option<&str>) -> Comparison {
+    let end = "<end of output>";
+    Comparison::Differ { line: Some(line), explanation: format!("expected `{}`, got `{}`", expected.unwrap_or(end), got.unwrap_or(end)) }
+}
+
+/// where the lines of the outputs first differ
+fn line_difference(
+    mut program: impl Iterator<Item = io::Result<String>>,
+    mut reference: impl Iterator<Item = io::Result<String>>,
+) -> io::Result<Comparison> {
+    for line in 1.. {
+        match (reference.next().transpose()?, program.next().transpose()?) {
+            (None, None) => break,
+            (expected, got) if expected != got => return Ok(lines_differ(line, expected.as_deref(), got.as_deref())),
+            _ => {},
+        }
+    }
+    Ok(Comparison::Match)
+}
+
+impl Comparator for Exact {
+    fn compare(&self, program: &[u8], reference: &[u8]) -> Comparison {
+        if program == reference {
+            return Comparison::Match;
+        }
+        in_memory(self.compare_readers(&mut &*program, &mut &*reference))
+    }
+
+    fn compare_readers(&self, program: &mut dyn BufRead, reference: &mut dyn BufRead) -> io::Result<Comparison> {
+        let (mut p, mut r) = (vec![], vec![]);
+        let mut same_bytes = true;
+        for line in 1.. {
+            p.clear();
+            r.clear();
+            let (got, expected) = (program.read_until(b'\n', &mut p)?, reference.read_until(b'\n', &mut r)?);
+            if got == 0 && expected == 0 {
+                break;
+            }
+            if p == r {
+                continue;
+            }
+            same_bytes = false;
+            let text = |read: usize, line: &[u8]| (read > 0).then(|| String::from_utf8_lossy(without_line_ending(line)).into_owned());
+            let (got, expected) = (text(got, &p), text(expected, &r));
+            if got != expected {
+                return Ok(lines_differ(line, expected.as_deref(), got.as_deref()));
+            }
+        }
+        Ok(match same_bytes {
+            true => Comparison::Match,
+            // the lines are the same, it's the line endings or the newline at the end which aren't
+            false => Comparison::Differ { line: None, explanation: "the outputs differ in their whitespace".to_string() },
+        })
+    }
+}
+
+/// the lines without their trailing whitespace, nor the blank lines at the end
+fn trimmed(reader: &mut dyn BufRead) -> impl Iterator<Item = io::Result<String>> + '_ {
+    let mut lines = lines(reader);
+    // the blank lines are held back until a line which isn't blank comes after them
+    let (mut blanks, mut after) = (0, None);
+    std::iter::from_fn(move || loop {
+        if after.is_some() && blanks > 0 {
+            blanks -= 1;
+            return Some(Ok(String::new()));
+        }
+        if let Some(line) = after.take() {
+            return Some(Ok(line));
+        }
+        match lines.next()? {
+            Ok(line) if line.trim_end().is_empty() => blanks += 1,
+            Ok(line) => after = Some(line.trim_end().to_string()),
+            Err(e) => return Some(Err(e)),
+        }
+    })
+}
+
+impl Comparator for Trimmed {
+    fn compare(&self, program: &[u8], reference: &[u8]) -> Comparison {
+        in_memory(self.compare_readers(&mut &*program, &mut &*reference))
+    }
+
+    fn compare_readers(&self, program: &mut dyn BufRead, reference: &mut dyn BufRead) -> io::Result<Comparison> {
+        line_difference(trimmed(program), trimmed(reference))
+    }
+}
+
+/// the words of the output, with the lines (from 1) they're on
+fn words(reader: &mut dyn BufRead) -> impl Iterator<Item = io::Result<(usize, String)>> + '_ {
+    lines(reader).enumerate().flat_map(|(i, line)| match line {
+        Ok(line) => line.split_whitespace().map(|word| Ok((i + 1, word.to_string()))).collect(),
+        Err(e) => vec![Err(e)],
+    })
+}
+
+/// compares the outputs word by word, the words being equal when `same` says so
+fn word_difference(program: &mut dyn BufRead, reference: &mut dyn BufRead, same: impl Fn(&str, &str) -> bool) -> io::Result<Comparison> {
+    let (mut got, mut expected) = (words(program), words(reference));
+    // the line of the last word of the programme, where a missing word is missing from
+    let mut last_line = None;
+    for i in 0.. {
+        let (g, e) = (got.next().transpose()?, expected.next().transpose()?);
+        if let Some((line, _)) = &g {
+            last_line = Some(*line);
+        }
+        let explanation = match (g, e) {
+            (Some((_, g)), Some((_, e))) if same(&g, &e) => continue,
+            (Some((_, g)), Some((_, e))) => format!("word {}: expected `{}`, got `{}`", i + 1, e, g),
+            (Some((_, g)), None) => format!("word {}: expected the end of the output, got `{}`", i + 1, g),
+            (None, Some((_, e))) => format!("word {}: expected `{}`, got the end of the output", i + 1, e),
+            (None, None) => break,
+        };
+        return Ok(Comparison::Differ { line: last_line, explanation });
+    }
+    Ok(Comparison::Match)
+}
+
+impl Comparator for Tokens {
+    fn compare(&self, program: &[u8], reference: &[u8]) -> Comparison {
+        in_memory(self.compare_readers(&mut &*program, &mut &*reference))
+    }
+
+    fn compare_readers(&self, program: &mut dyn BufRead, reference: &mut dyn BufRead) -> io::Result<Comparison> {
+        word_difference(program, reference, |got, expected| got == expected)
+    }
+}
+
+impl Comparator for FloatTolerance {
+    fn compare(&self, program: &[u8], reference: &[u8]) -> Comparison {
+        in_memory(self.compare_readers(&mut &*program, &mut &*reference))
+    }
+
+    fn compare_readers(&self, program: &mut dyn BufRead, reference: &mut dyn BufRead) -> io::Result<Comparison> {
+        let tolerance = self.0;
+        word_difference(program, reference, |got, expected| match (got.parse::<f64>(), expected.parse::<f64>()) {
+            (Ok(g), Ok(e)) if g.is_finite() && e.is_finite() => (g - e).abs() <= tolerance * e.abs().max(1.0),
+            _ => got == expected,
+        })
+    }
+}
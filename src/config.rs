@@ -0,0 +1,150 @@
+use std::{
+    env,
+    fs,
+    process,
+    ffi::OsString,
+    path::{Path, PathBuf},
+    };
+use std::error::Error;
+use clap::{ArgAction, CommandFactory, Parser};
+use clap::parser::ValueSource;
+use string_error::into_err;
+use toml::{Table, Value};
+
+use crate::cli::{App, Cli, Command};
+
+/// the configuration file looked up in the current directory
+pub const CONFIG_FILE: &str = "compdiff.toml";
+
+/// configuration keys which are named differently from the `Cli` fields
+const KEY_ALIASES: &[(&str, &str)] = &[("references", "reference")];
+
+/// subcommands which take their options from the configuration as well
+const CONFIGURED: &[&str] = &["run", "replay", "compile"];
+
+/// Parses the command line, options of a session which it doesn't set are taken from `--config` or `compdiff.toml`.
+pub fn parse_args() -> App {
+    let argv: Vec<OsString> = env::args_os().collect();
+    let subcommand = argv.get(1)
+        .and_then(|arg| arg.to_str())
+        .filter(|arg| *arg == "help" || App::command().get_subcommands().any(|c| c.get_name() == *arg));
+    let cmd = match subcommand {
+        None => Cli::command(),
+        Some(name) if CONFIGURED.contains(&name) => App::command().find_subcommand(name).expect("a known subcommand").clone(),
+        Some(_) => return App::parse_from(argv),
+    };
+
+    // the configuration goes in front of the options, after the subcommand's name
+    let split = if subcommand.is_some() { 2 } else { 1 };
+    let (config, file_args) = config_args(cmd, argv.iter().take(1).chain(argv.iter().skip(split))).unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        process::exit(2);
+    });
+    let mut app = App::parse_from(argv.iter().take(split).cloned().chain(file_args).chain(argv.iter().skip(split).cloned()));
+    let used = match &mut app.command {
+        Some(Command::Run(args)) => Some(&mut args.config),
+        Some(Command::Replay(args)) => Some(&mut args.session.config),
+        Some(Command::Compile(args)) => Some(&mut args.config),
+        _ => app.run.as_mut().map(|args| &mut args.config),
+    };
+    if let Some(used) = used {
+        *used = config;
+    }
+    app
+}
+
+/// The configuration in use, `--config` or else `compdiff.toml` when there's one, with the keys of the profile
+/// replacing the top-level ones.
+pub fn load(config: Option<&Path>, profile: Option<&str>) -> Result<Option<(PathBuf, Table)>, Box<dyn Error>> {
+    let path = match config {
+        Some(path) => path.to_path_buf(),
+        None if PathBuf::from(CONFIG_FILE).is_file() => PathBuf::from(CONFIG_FILE),
+        None if profile.is_some() => return Err(into_err(format!("--profile needs a configuration, but there's no {}", CONFIG_FILE))),
+        None => return Ok(None),
+    };
+
+    let text = fs::read_to_string(&path)
+        .map_err(|e| into_err(format!("couldn't read the configuration {}: {}", path.display(), e)))?;
+    let mut table: Table = text.parse()
+        .map_err(|e| into_err(format!("couldn't parse the configuration {}: {}", path.display(), e)))?;
+
+    // defaults < profile < command line: the keys of the profile replace the top-level ones
+    let profiles = match table.remove("profile") {
+        Some(Value::Table(profiles)) => profiles,
+        Some(_) => return Err(into_err(format!("`profile` in {} should be a table of [profile.NAME] sections", path.display()))),
+        None => Table::new(),
+    };
+    if let Some(name) = profile {
+        let Some(Value::Table(keys)) = profiles.get(name) else {
+            let available = profiles.keys().map(String::as_str).collect::<Vec<_>>();
+            let available = if available.is_empty() { "none".to_string() } else { available.join(", ") };
+            return Err(into_err(format!("unknown profile `{}` in {}, the available ones are: {}", name, path.display(), available)));
+        };
+        table.extend(keys.clone());
+    }
+    Ok(Some((path, table)))
+}
+
+/// the configuration file in use and the arguments of `cmd` equivalent to its keys,
+/// keys set on the command line are left out so that the command line wins
+fn config_args<'a>(cmd: clap::Command, argv: impl Iterator<Item = &'a OsString>) -> Result<(Option<PathBuf>, Vec<OsString>), Box<dyn Error>> {
+    // a lenient pass to find --config and what is set on the command line
+    let matches = cmd.clone()
+        .ignore_errors(true)
+        .disable_help_flag(true)
+        .disable_version_flag(true)
+        .get_matches_from(argv);
+    let profile = matches.get_one::<String>("profile");
+    let Some((path, table)) = load(matches.get_one::<PathBuf>("config").map(PathBuf::as_path), profile.map(String::as_str))? else {
+        return Ok((None, vec![]));
+    };
+
+    let mut args = vec![];
+    for (key, value) in &table {
+        let id = KEY_ALIASES.iter()
+            .find(|(alias, _)| alias == key)
+            .map(|(_, id)| id.to_string())
+            .unwrap_or_else(|| key.replace('-', "_"));
+        let arg = cmd.get_arguments().find(|a| a.get_id() == id.as_str() && a.get_long().is_some() && id != "config" && id != "profile");
+        let Some(arg) = arg else {
+            // keys of a session are fine, the subcommand just doesn't use them
+            if !Cli::command().get_arguments().any(|a| a.get_id() == id.as_str()) {
+                eprintln!("warning: unknown key `{}` in {}, ignoring it", key, path.display());
+            }
+            continue;
+        };
+        // nor are the keys of options which can't be used with those of the command line, such as
+        // `program` with the files given by position
+        let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+        let conflicts = |a: &clap::Arg, b: &clap::Arg| cmd.get_arg_conflicts_with(a).iter().any(|c| c.get_id() == b.get_id());
+        if given(&id) || cmd.get_arguments().any(|other| given(other.get_id().as_str()) && (conflicts(arg, other) || conflicts(other, arg))) {
+            continue;
+        }
+
+        let long = arg.get_long().expect("only long options are configurable");
+        let values = match value {
+            Value::Array(values) => values.iter().collect(),
+            value => vec![value],
+        };
+        for value in values {
+            let value = match value {
+                Value::Boolean(on) if matches!(arg.get_action(), ArgAction::SetTrue) => {
+                    if *on { args.push(format!("--{}", long).into()); }
+                    continue;
+                },
+                // `verbose = 2` is -vv
+                Value::Boolean(_) | Value::Integer(_) if matches!(arg.get_action(), ArgAction::Count) => {
+                    let n = match value { Value::Integer(n) => *n, _ => value.as_bool().map_or(0, i64::from) };
+                    args.extend((0..n).map(|_| format!("--{}", long).into()));
+                    continue;
+                },
+                Value::String(s) => s.clone(),
+                Value::Integer(_) | Value::Float(_) | Value::Boolean(_) => value.to_string(),
+                _ => return Err(into_err(format!("unsupported value of `{}` in {}", key, path.display()))),
+            };
+            // `=` keeps values such as compiler flags from being taken for options
+            args.push(format!("--{}={}", long, value).into());
+        }
+    }
+    Ok((Some(path), args))
+}
@@ -0,0 +1,141 @@
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+    };
+
+use crate::session::RoundMeta;
+use crate::{create_owned_dir, hash_str, Verdict};
+
+/// the index of a corpus, a line for each of its inputs
+pub const INDEX: &str = "index.tsv";
+
+const HEADER: &str = "seed\tverdict\thash\tbytes\tgenerator\tprogram\treferences";
+
+/// An input of the corpus, and what its round came to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CorpusEntry {
+    pub seed: u64,
+    /// the code of the verdict, e.g. WA
+    pub verdict: String,
+    pub hash: u64,
+    pub bytes: u64,
+    /// the times of the round, as in meta.txt
+    pub times: String,
+}
+
+impl CorpusEntry {
+    pub fn passed(&self) -> bool {
+        self.verdict == Verdict::Ok.code()
+    }
+
+    /// the file of the input in the corpus
+    pub fn file_name(&self) -> String {
+        format!("{}.in", self.seed)
+    }
+
+    fn line(&self) -> String {
+        format!("{}\t{}\t{:016x}\t{}\t{}", self.seed, self.verdict, self.hash, self.bytes, self.times)
+    }
+
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(5, '\t');
+        Some(CorpusEntry {
+            seed: fields.next()?.parse().ok()?,
+            verdict: fields.next()?.to_string(),
+            hash: u64::from_str_radix(fields.next()?, 16).ok()?,
+            bytes: fields.next()?.parse().ok()?,
+            times: fields.next().unwrap_or_default().to_string(),
+        })
+    }
+}
+
+/// The inputs of every round of `--corpus-dir`, written as `<seed>.in` whatever their verdict, each distinct
+/// input once. The corpus grows over the sessions which write to it: the oldest passing inputs are removed
+/// once it's over `--corpus-size`, the failing ones are always kept.
+pub struct Corpus {
+    dir: PathBuf,
+    /// in bytes
+    max_size: Option<u64>,
+    /// the oldest first
+    entries: Vec<CorpusEntry>,
+    hashes: HashSet<u64>,
+}
+
+impl Corpus {
+    /// Opens the corpus in the directory, creating it, and goes on with the inputs its index lists.
+    pub fn open(dir: &Path, max_size: Option<u64>) -> io::Result<Self> {
+        create_owned_dir(dir)?;
+        let entries: Vec<CorpusEntry> = match fs::read_to_string(dir.join(INDEX)) {
+            Ok(index) => index.lines().skip(1).filter_map(CorpusEntry::parse).collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e),
+        };
+        let hashes = entries.iter().map(|e| e.hash).collect();
+        let corpus = Corpus { dir: dir.to_path_buf(), max_size, entries, hashes };
+        corpus.write_index()?;
+        Ok(corpus)
+    }
+
+    pub fn entries(&self) -> &[CorpusEntry] {
+        &self.entries
+    }
+
+    /// in bytes
+    pub fn size(&self) -> u64 {
+        self.entries.iter().map(|e| e.bytes).sum()
+    }
+
+    /// Writes the input unless the corpus has it already, returning whether it was added.
+    pub fn add(&mut self, seed: u64, verdict: Verdict, meta: &RoundMeta, input: &str) -> io::Result<bool> {
+        let hash = hash_str(input);
+        if !self.hashes.insert(hash) {
+            return Ok(false);
+        }
+        let seconds = |d: Option<Duration>| d.map_or("-".to_string(), |d| format!("{:.3}", d.as_secs_f64()));
+        let references: Vec<String> = meta.references.iter().map(|&d| seconds(d)).collect();
+        let times = format!("{}\t{}\t{}", seconds(meta.generator), seconds(meta.program), references.join(" "));
+        let entry = CorpusEntry { seed, verdict: verdict.code().to_string(), hash, bytes: input.len() as u64, times };
+        // the input of an earlier round with the same seed is replaced, along with its line
+        let replaced = self.entries.iter().position(|e| e.seed == seed).map(|i| self.entries.remove(i));
+        if let Some(replaced) = &replaced {
+            self.hashes.remove(&replaced.hash);
+        }
+        fs::write(self.dir.join(entry.file_name()), input)?;
+        let line = entry.line();
+        self.entries.push(entry);
+        if self.prune()? > 0 || replaced.is_some() {
+            self.write_index()?;
+        } else {
+            let mut index = fs::OpenOptions::new().append(true).open(self.dir.join(INDEX))?;
+            writeln!(index, "{}", line)?;
+        }
+        Ok(true)
+    }
+
+    /// removes the oldest passing inputs while the corpus is over its size, returning how many
+    fn prune(&mut self) -> io::Result<usize> {
+        let Some(max_size) = self.max_size else { return Ok(0) };
+        let mut size = self.size();
+        let mut removed = 0;
+        while size > max_size {
+            let Some(oldest) = self.entries.iter().position(CorpusEntry::passed) else { break };
+            let entry = self.entries.remove(oldest);
+            match fs::remove_file(self.dir.join(entry.file_name())) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {},
+            }
+            self.hashes.remove(&entry.hash);
+            size -= entry.bytes;
+            removed += 1;
+        }
+        Ok(removed)
+    }
+
+    fn write_index(&self) -> io::Result<()> {
+        let lines: String = self.entries.iter().map(|e| e.line() + "\n").collect();
+        fs::write(self.dir.join(INDEX), format!("{}\n{}", HEADER, lines))
+    }
+}
@@ -0,0 +1,58 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    };
+
+use crate::options::Cli;
+use crate::report::RoundRecord;
+use crate::Role;
+
+/// The `--report-csv` table: a row per round, written out as soon as the round ends.
+pub struct CsvReport {
+    out: BufWriter<File>,
+    /// a time column is kept for every reference, in the order they were given
+    references: Vec<PathBuf>,
+}
+
+/// quotes the field when it contains a separator, a quote or a line break
+fn field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+impl CsvReport {
+    /// creates the file and writes the header, `args` are those of the session with the sources already built
+    pub fn new(path: &Path, args: &Cli) -> io::Result<Self> {
+        let references: Vec<PathBuf> = args.programs.reference.iter().map(|r| args.source_of(r).to_path_buf()).collect();
+        let mut header = vec!["round".to_string(), "seed".to_string(), "verdict".to_string(), "program_ms".to_string()];
+        header.extend(references.iter().map(|r| field(&format!("{}_ms", r.display()))));
+        header.extend(["peak_memory_kb", "input_bytes", "output_bytes"].map(String::from));
+
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "{}", header.join(","))?;
+        out.flush()?;
+        Ok(CsvReport { out, references })
+    }
+
+    /// appends the row of the round, times are those of the last execution of each programme
+    pub fn add(&mut self, record: &RoundRecord) -> io::Result<()> {
+        let last = |role: Role, path: Option<&Path>| record.executions.iter().rev().find(|e| e.role == role && path.is_none_or(|p| e.path == p));
+        let ms = |role, path| optional(last(role, path).map(|e| format!("{:.3}", e.seconds * 1000.0)));
+
+        let mut row = vec![record.round.to_string(), record.seed.to_string(), record.verdict.code().to_string(), ms(Role::Program, None)];
+        row.extend(self.references.iter().map(|r| ms(Role::Reference, Some(r))));
+        row.push(optional(record.executions.iter().filter(|e| e.role == Role::Program).filter_map(|e| e.memory_kb).max()));
+        row.push(optional(record.input.as_ref().map(|i| i.bytes)));
+        row.push(optional(record.program.as_ref().map(|p| p.output.bytes)));
+        writeln!(self.out, "{}", row.join(","))?;
+        self.out.flush()
+    }
+}
@@ -0,0 +1,142 @@
+use std::{
+    fs,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+    };
+use std::error::Error;
+use rusqlite::{params, Connection, OptionalExtension};
+use string_error::into_err;
+
+use crate::options::Cli;
+use crate::report::{RoundRecord, Summary};
+use crate::{hash_str, say, Role};
+
+const SCHEMA: &str = "
+PRAGMA synchronous = NORMAL;
+CREATE TABLE IF NOT EXISTS sessions (
+    id INTEGER PRIMARY KEY,
+    started INTEGER NOT NULL,
+    program TEXT NOT NULL,
+    -- the same for the same source of the programme compiled with the same flags
+    build TEXT NOT NULL,
+    seed TEXT,
+    config TEXT NOT NULL,
+    rounds INTEGER,
+    passed INTEGER,
+    failed INTEGER,
+    exit_code INTEGER
+);
+CREATE TABLE IF NOT EXISTS rounds (
+    session INTEGER NOT NULL REFERENCES sessions(id),
+    round INTEGER NOT NULL,
+    seed TEXT NOT NULL,
+    verdict TEXT NOT NULL,
+    seconds REAL NOT NULL,
+    program_ms REAL,
+    input_hash TEXT,
+    input_bytes INTEGER,
+    PRIMARY KEY (session, round)
+);
+";
+
+fn open(path: &Path) -> rusqlite::Result<Connection> {
+    let conn = Connection::open(path)?;
+    // the write-ahead log keeps the inserts of the rounds cheap, it answers with the new mode
+    conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+    conn.execute_batch(SCHEMA)?;
+    Ok(conn)
+}
+
+/// The `--db` history: a row per session and a row per round, kept across sessions.
+pub struct ResultsDb {
+    conn: Connection,
+    session: i64,
+}
+
+impl ResultsDb {
+    /// creates the database on first use and records the start of the session,
+    /// `args` are those of the session with the sources already built
+    pub fn start(path: &Path, args: &Cli) -> rusqlite::Result<Self> {
+        let conn = open(path)?;
+        let program = args.source_of(&args.programs.program).to_path_buf();
+        let source = fs::read(&program).unwrap_or_default();
+        let build = format!("{:016x}", hash_str(&format!("{}\0{}", String::from_utf8_lossy(&source), args.programs.flags_of("cpp"))));
+        let started = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
+        let seed = args.input_file.is_none().then(|| args.seed.map(|s| s.to_string())).flatten();
+        conn.execute("INSERT INTO sessions (started, program, build, seed, config) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![started, program.display().to_string(), build, seed, format!("{:#?}", args)])?;
+        let session = conn.last_insert_rowid();
+        Ok(ResultsDb { conn, session })
+    }
+
+    /// a single insert, so every round is its own small transaction
+    pub fn add(&mut self, record: &RoundRecord) -> rusqlite::Result<()> {
+        let program_ms = record.executions.iter().rev()
+            .find(|e| e.role == Role::Program)
+            .map(|e| e.seconds * 1000.0);
+        let input_hash = record.input.as_ref().map(|i| format!("{:016x}", hash_str(&i.text)));
+        self.conn.execute("INSERT INTO rounds (session, round, seed, verdict, seconds, program_ms, input_hash, input_bytes) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![self.session, record.round as i64, record.seed.to_string(), record.verdict.code(), record.seconds,
+                program_ms, input_hash, record.input.as_ref().map(|i| i.bytes as i64)])?;
+        Ok(())
+    }
+
+    pub fn finish(&mut self, summary: &Summary) -> rusqlite::Result<()> {
+        self.conn.execute("UPDATE sessions SET rounds = ?1, passed = ?2, failed = ?3, exit_code = ?4 WHERE id = ?5",
+            params![summary.rounds as i64, summary.passed as i64, summary.failed as i64, summary.exit_code, self.session])?;
+        Ok(())
+    }
+}
+
+/// Prints the pass rate of every day and whether the latest build of the programme got slower
+/// than the previous one, on the inputs both were run on.
+pub fn stats(path: &Path) -> Result<(), Box<dyn Error>> {
+    if !path.is_file() {
+        return Err(into_err(format!("there's no database {}", path.display())));
+    }
+    let conn = open(path)?;
+
+    say!("== pass rate by day in {}", path.display());
+    let mut days = conn.prepare("
+        SELECT date(s.started, 'unixepoch'), COUNT(DISTINCT s.id), COUNT(r.round), COALESCE(SUM(r.verdict = 'OK'), 0)
+        FROM sessions s LEFT JOIN rounds r ON r.session = s.id
+        GROUP BY 1 ORDER BY 1")?;
+    let days = days.query_map(params![], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?, row.get::<_, i64>(2)?, row.get::<_, i64>(3)?)))?;
+    for day in days {
+        let (day, sessions, rounds, passed) = day?;
+        let rate = if rounds > 0 { format!("{:.1}%", 100.0 * passed as f64 / rounds as f64) } else { "-".to_string() };
+        say!("  {}: {} sessions, {} rounds, {} passed", day, sessions, rounds, rate);
+    }
+
+    let latest = conn.query_row("SELECT id, program, build FROM sessions ORDER BY id DESC LIMIT 1", params![],
+        |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))).optional()?;
+    let Some((latest, program, build)) = latest else {
+        return Ok(());
+    };
+    let previous = conn.query_row("SELECT id FROM sessions WHERE program = ?1 AND build != ?2 AND id < ?3 ORDER BY id DESC LIMIT 1",
+        params![program, build, latest], |row| row.get::<_, i64>(0)).optional()?;
+    let Some(previous) = previous else {
+        say!("== no earlier build of {} to compare the latest session with", program);
+        return Ok(());
+    };
+    let (now, before, inputs) = conn.query_row("
+        SELECT AVG(a.ms), AVG(b.ms), COUNT(*) FROM
+            (SELECT input_hash, AVG(program_ms) AS ms FROM rounds WHERE session = ?1 AND program_ms IS NOT NULL GROUP BY input_hash) a
+            JOIN (SELECT input_hash, AVG(program_ms) AS ms FROM rounds WHERE session = ?2 AND program_ms IS NOT NULL GROUP BY input_hash) b
+            USING (input_hash)",
+        params![latest, previous], |row| Ok((row.get::<_, Option<f64>>(0)?, row.get::<_, Option<f64>>(1)?, row.get::<_, i64>(2)?)))?;
+    match (now, before) {
+        (Some(now), Some(before)) if inputs > 0 => {
+            let change = (now - before) / before * 100.0;
+            let verdict = match change {
+                c if c > 5.0 => format!("{:.0}% slower than", c),
+                c if c < -5.0 => format!("{:.0}% faster than", -c),
+                _ => "about as fast as".to_string(),
+            };
+            say!("== the latest build of {} (session {}) is {} the previous one (session {}): {:.3} ms against {:.3} ms on average over {} common inputs",
+                program, latest, verdict, previous, now, before, inputs);
+        },
+        _ => say!("== sessions {} and {} ran {} on no common inputs, use the same --seed to compare builds", latest, previous, program),
+    }
+    Ok(())
+}
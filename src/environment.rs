@@ -0,0 +1,79 @@
+use std::{
+    collections::HashMap,
+    env,
+    path::PathBuf,
+    };
+use serde::Serialize;
+
+use crate::options::Cli;
+use crate::{tool_version, Prepared};
+
+/// Where and with what the session ran, gathered once its sources are prepared so that a report or a saved
+/// failure read much later still tells which compiler, flags and version of compdiff produced it.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Environment {
+    /// of compdiff
+    pub version: String,
+    pub os: String,
+    pub arch: String,
+    /// what runs each of the sources
+    pub tools: Vec<Tool>,
+    /// the effective options of the session, as --verbose shows them
+    pub config: String,
+}
+
+/// The compiler which built a source, and with which flags, or the interpreter which runs it.
+#[derive(Clone, Debug, Serialize)]
+pub struct Tool {
+    pub source: PathBuf,
+    /// the compiler or the interpreter, none for a binary which runs on its own
+    pub path: Option<PathBuf>,
+    pub compiled: bool,
+    pub flags: Vec<String>,
+    /// the first line of its `--version`
+    pub version: String,
+}
+
+impl Environment {
+    /// Runs `--version` once for every compiler and interpreter of the prepared sources, the c++ compiler
+    /// of the session being known already.
+    pub fn gather(args: &Cli, prepared: &[Prepared]) -> Self {
+        let mut versions: HashMap<PathBuf, String> = args.toolchain.iter()
+            .map(|toolchain| (toolchain.compiler.clone(), toolchain.version.clone()))
+            .collect();
+        let tools = prepared.iter()
+            .filter_map(|(source, built)| built.as_ref().ok().map(|built| (source, built)))
+            .map(|(source, built)| {
+                let (path, flags) = match (&built.compiled, &built.interpreter) {
+                    (Some(compiled), _) => (Some(compiled.compiler.clone()), compiled.flags.clone()),
+                    (None, interpreter) => (interpreter.clone(), vec![]),
+                };
+                let version = path.as_ref()
+                    .map(|tool| versions.entry(tool.clone()).or_insert_with(|| tool_version(tool, &args.programs.backend)).clone())
+                    .unwrap_or_default();
+                Tool { source: source.clone(), path, compiled: built.compiled.is_some(), flags, version }
+            })
+            .collect();
+        Environment {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            os: env::consts::OS.to_string(),
+            arch: env::consts::ARCH.to_string(),
+            tools,
+            config: format!("{:#?}", args),
+        }
+    }
+
+    /// the environment in a few lines, without the configuration
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("compdiff {} on {} {}", self.version, self.os, self.arch)];
+        for tool in &self.tools {
+            let version = if tool.version.is_empty() { String::new() } else { format!(" ({})", tool.version) };
+            lines.push(match (&tool.path, tool.compiled) {
+                (Some(path), true) => format!("{}: compiled by {}{} with {}", tool.source.display(), path.display(), version, tool.flags.join(" ")),
+                (Some(path), false) => format!("{}: run by {}{}", tool.source.display(), path.display(), version),
+                (None, _) => format!("{}: runs on its own", tool.source.display()),
+            });
+        }
+        lines
+    }
+}
@@ -0,0 +1,41 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+    };
+use serde::Serialize;
+
+use crate::Verdict;
+use crate::report::Summary;
+
+/// A single line of the `--events-ndjson` stream.
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    RoundStart { round: u64 },
+    Verdict { round: u64, verdict: Verdict, seed: u64, time_ms: u128 },
+    Summary(&'a Summary),
+}
+
+/// Writes events as newline delimited JSON, flushing every line so that the stream can be tailed.
+pub struct EventStream {
+    out: Box<dyn Write>,
+}
+
+impl EventStream {
+    /// streams into the file, or to stdout when the path is `-`
+    pub fn new(dest: &Path) -> io::Result<Self> {
+        let out: Box<dyn Write> = if dest == Path::new("-") {
+            Box::new(io::stdout())
+        } else {
+            Box::new(fs::File::create(dest)?)
+        };
+        Ok(EventStream { out })
+    }
+
+    pub fn emit(&mut self, event: &Event) -> io::Result<()> {
+        serde_json::to_writer(&mut self.out, event)?;
+        writeln!(self.out)?;
+        self.out.flush()
+    }
+}
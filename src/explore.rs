@@ -0,0 +1,95 @@
+use std::collections::HashSet;
+
+use crate::minimize::candidate_signature;
+use crate::options::Cli;
+use crate::{Rng, Signature, Verdict};
+
+/// What running the mutants of a failing input found.
+#[derive(Clone, Debug, Default)]
+pub struct Explored {
+    /// the mutants the validator accepted, which were run
+    pub mutants: usize,
+    /// the mutants the validator rejected, neither run nor counted
+    pub rejected: usize,
+    /// how many of the mutants which were run failed too
+    pub failing: usize,
+    /// the failing mutants, each once, in the order they were found
+    pub distinct: Vec<(String, Signature)>,
+}
+
+/// Runs `count` mutants of the failing input as rounds with the given input, each of them the input with a
+/// number perturbed, a line duplicated or deleted, or the words of a line shuffled. The mutants only depend
+/// on the seed of the round, so exploring the same round again runs the same ones.
+pub fn explore(args: &Cli, round: u64, seed: u64, input: &str, count: usize) -> Explored {
+    let mut explored = Explored::default();
+    let mut rng = Rng::new(seed);
+    let mut seen = HashSet::new();
+    for _ in 0..count {
+        let mutant = mutate(input, &mut rng);
+        let Some(signature) = candidate_signature(args, round, &mutant) else {
+            explored.rejected += 1;
+            continue;
+        };
+        explored.mutants += 1;
+        if signature.verdict == Verdict::Ok {
+            continue;
+        }
+        explored.failing += 1;
+        if seen.insert(mutant.clone()) {
+            explored.distinct.push((mutant, signature));
+        }
+    }
+    explored
+}
+
+/// the input changed in one way, chosen at random among those which apply to it
+fn mutate(input: &str, rng: &mut Rng) -> String {
+    let mut lines: Vec<String> = input.lines().map(str::to_string).collect();
+    let numbers: Vec<(usize, usize)> = lines.iter().enumerate()
+        .flat_map(|(l, line)| line.split_whitespace().enumerate().filter(|(_, w)| w.parse::<i64>().is_ok()).map(move |(w, _)| (l, w)))
+        .collect();
+    let shuffled: Vec<usize> = (0..lines.len()).filter(|&l| lines[l].split_whitespace().nth(1).is_some()).collect();
+    let mut kinds = vec![];
+    if !numbers.is_empty() { kinds.push(0); }
+    if !lines.is_empty() { kinds.extend([1, 2]); }
+    if !shuffled.is_empty() { kinds.push(3); }
+    if kinds.is_empty() {
+        return input.to_string();
+    }
+    match kinds[rng.below(kinds.len())] {
+        0 => {
+            let (l, w) = numbers[rng.below(numbers.len())];
+            let mut words: Vec<String> = lines[l].split_whitespace().map(str::to_string).collect();
+            let n: i64 = words[w].parse().unwrap_or_default();
+            words[w] = match rng.below(5) {
+                0 => n.saturating_add(1),
+                1 => n.saturating_sub(1),
+                2 => n.saturating_mul(2),
+                3 => n / 2,
+                _ => 0,
+            }.to_string();
+            lines[l] = words.join(" ");
+        },
+        1 => {
+            let l = rng.below(lines.len());
+            lines.insert(l, lines[l].clone());
+        },
+        2 => {
+            lines.remove(rng.below(lines.len()));
+        },
+        _ => {
+            let l = shuffled[rng.below(shuffled.len())];
+            let mut words: Vec<&str> = lines[l].split_whitespace().collect();
+            // Fisher-Yates
+            for i in (1..words.len()).rev() {
+                words.swap(i, rng.below(i + 1));
+            }
+            lines[l] = words.join(" ");
+        },
+    }
+    let mut mutant = lines.join("\n");
+    if input.ends_with('\n') && !mutant.is_empty() {
+        mutant.push('\n');
+    }
+    mutant
+}
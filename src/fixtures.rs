@@ -0,0 +1,99 @@
+use std::{
+    env,
+    fs,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+    };
+
+// Throwaway programmes for the tests of compdiff and of the crates using it, written as python scripts into
+// directories of their own, so that a test doesn't have to ship them as files. They're run by the python
+// found on the PATH, like any other script.
+
+/// A programme written for a test, removed along with its directory once it's dropped.
+#[derive(Debug)]
+pub struct Fixture {
+    dir: PathBuf,
+    path: PathBuf,
+}
+
+impl Fixture {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// the path as a command line argument
+    pub fn arg(&self) -> &str {
+        self.path.to_str().expect("the temporary directory has a unicode path")
+    }
+}
+
+impl AsRef<Path> for Fixture {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl From<&Fixture> for PathBuf {
+    fn from(fixture: &Fixture) -> Self {
+        fixture.path.clone()
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// writes the file with the source into a new directory, panicking when it can't as a test has nothing to run then
+pub fn script(name: &str, source: &str) -> Fixture {
+    static FIXTURES: AtomicUsize = AtomicUsize::new(0);
+    let dir = env::temp_dir().join(format!("compdiff-fixture-{}-{}", process::id(), FIXTURES.fetch_add(1, Ordering::Relaxed)));
+    fs::create_dir_all(&dir).unwrap_or_else(|e| panic!("Cannot create {}, error: {}", dir.display(), e));
+    let path = dir.join(name);
+    fs::write(&path, source).unwrap_or_else(|e| panic!("Cannot write {}, error: {}", path.display(), e));
+    Fixture { dir, path }
+}
+
+/// prints its input back
+pub fn echo_program() -> Fixture {
+    script("echo.py", "import sys\nsys.stdout.write(sys.stdin.read())\n")
+}
+
+/// prints its input, a number, times the factor
+pub fn multiplying_program(factor: i64) -> Fixture {
+    script("multiply.py", &format!("print(int(input()) * {})\n", factor))
+}
+
+/// kills itself with the signal, after reading its input
+pub fn crashing_program(signal: i32) -> Fixture {
+    script("crash.py", &format!("import os, sys\nsys.stdin.read()\nos.kill(os.getpid(), {})\n", signal))
+}
+
+/// writes the message to its stderr and exits with the code
+pub fn failing_program(code: i32, message: &str) -> Fixture {
+    script("fail.py", &format!("import sys\nsys.stderr.write({})\nsys.exit({})\n", python_string(message), code))
+}
+
+/// prints its input back once it has slept for the duration
+pub fn slow_program(duration: Duration) -> Fixture {
+    script("slow.py", &format!("import sys, time\ntime.sleep({})\nsys.stdout.write(sys.stdin.read())\n", duration.as_secs_f64()))
+}
+
+/// prints the text whichever seed it's given
+pub fn generator_with_output(text: &str) -> Fixture {
+    script("gen.py", &format!("import sys\nsys.stdout.write({})\n", python_string(text)))
+}
+
+/// prints the seed it's given as its first argument, modulo the modulus
+pub fn seeded_generator(modulus: u64) -> Fixture {
+    script("gen.py", &format!("import sys\nprint(int(sys.argv[1]) % {})\n", modulus))
+}
+
+/// the text as a python string literal, its bytes escaped so that nothing in it is read as python
+fn python_string(text: &str) -> String {
+    let escaped: String = text.bytes().map(|b| format!("\\x{:02x}", b)).collect();
+    format!("b'{}'.decode()", escaped)
+}
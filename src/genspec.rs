@@ -0,0 +1,590 @@
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt,
+    str::FromStr,
+    };
+
+use crate::Rng;
+
+// The language of `--gen-spec`, for the inputs which don't need a generator of their own:
+//
+//     n = int(1, 1e5); print(n)
+//     repeat(n) { a = ints(2, -10, 10); print(a) }
+//     print(string(int(1, 20), "ab")); lines(perm(n))
+//
+// The statements are separated by `;` or by new lines, `#` starts a comment. A value is a number, a list of
+// numbers or a string, the variables are assigned with `=`. Numbers are integers, `1e5` included, with
+// `+ - * / %` between them. `print` writes its values on a line, separated by spaces, `println` is the same,
+// `lines` writes each number of its values on a line of its own, and `repeat(n) { ... }` runs its block
+// n times. The random functions draw from the seed of the round, so that a round always has the same input.
+// A spec writes at most `MAX_INPUT` bytes, and one repeating for long is stopped with its round.
+
+/// the source of the `--gen-spec` in the paths of the round, in place of a generator's
+pub const GEN_SPEC: &str = "--gen-spec";
+
+/// the longest list or string a spec can make, so that a mistake in it doesn't exhaust the memory
+pub const MAX_LEN: i64 = 10_000_000;
+
+/// the largest input a spec can write, in bytes, however many times its lists are repeated
+pub const MAX_INPUT: usize = 256 * 1024 * 1024;
+
+/// the functions of the language, with their arguments
+const FUNCTIONS: &[(&str, &[&str])] = &[
+    ("int", &["lo", "hi"]),
+    ("ints", &["n", "lo", "hi"]),
+    ("distinct", &["n", "lo", "hi"]),
+    ("perm", &["n"]),
+    ("string", &["n", "alphabet"]),
+    ("sorted", &["list"]),
+    ("len", &["list"]),
+];
+
+/// A parsed `--gen-spec`, which writes the input of a round from its seed.
+#[derive(Clone)]
+pub struct GenSpec {
+    source: String,
+    statements: Vec<Statement>,
+}
+
+/// What's wrong with a spec, or with what it was asked to draw.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpecError {
+    /// from 1, in characters
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SpecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at column {}: {}", self.column, self.message)
+    }
+}
+
+impl Error for SpecError {}
+
+fn error<T>(column: usize, message: impl Into<String>) -> Result<T, SpecError> {
+    Err(SpecError { column, message: message.into() })
+}
+
+#[derive(Clone, Debug)]
+enum Statement {
+    Assign(String, Expr),
+    Print { each_on_a_line: bool, values: Vec<Expr>, column: usize },
+    Repeat(Expr, Vec<Statement>),
+}
+
+#[derive(Clone, Debug)]
+struct Expr {
+    column: usize,
+    kind: ExprKind,
+}
+
+#[derive(Clone, Debug)]
+enum ExprKind {
+    Int(i64),
+    Str(String),
+    Var(String),
+    Neg(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Int(i64),
+    Str(String),
+    Ident(String),
+    Sym(char),
+    /// `;` or a new line
+    Sep,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Int(n) => write!(f, "`{}`", n),
+            Token::Str(s) => write!(f, "\"{}\"", s),
+            Token::Ident(name) => write!(f, "`{}`", name),
+            Token::Sym(c) => write!(f, "`{}`", c),
+            Token::Sep => write!(f, "the end of the statement"),
+        }
+    }
+}
+
+/// the tokens of the source, each with its column
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, SpecError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let (c, column) = (chars[i], i + 1);
+        let start = i;
+        i += 1;
+        match c {
+            ';' | '\n' => tokens.push((Token::Sep, column)),
+            '#' => while i < chars.len() && chars[i] != '\n' { i += 1 },
+            c if c.is_whitespace() => {},
+            '0'..='9' => {
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') { i += 1 }
+                let word: String = chars[start..i].iter().collect();
+                tokens.push((Token::Int(number(&word, column)?), column));
+            },
+            c if c.is_alphabetic() || c == '_' => {
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') { i += 1 }
+                tokens.push((Token::Ident(chars[start..i].iter().collect()), column));
+            },
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.get(i) {
+                        None => return error(column, "the string isn't closed with a `\"`"),
+                        Some('"') => break,
+                        Some('\\') if matches!(chars.get(i + 1), Some('"' | '\\')) => {
+                            text.push(chars[i + 1]);
+                            i += 1;
+                        },
+                        Some(&c) => text.push(c),
+                    }
+                    i += 1;
+                }
+                i += 1;
+                tokens.push((Token::Str(text), column));
+            },
+            '(' | ')' | '{' | '}' | ',' | '=' | '+' | '-' | '*' | '/' | '%' => tokens.push((Token::Sym(c), column)),
+            c => return error(column, format!("unexpected `{}`", c)),
+        }
+    }
+    Ok(tokens)
+}
+
+/// digits, or digits times a power of ten like `1e5`, with `_` between them to group them
+fn number(word: &str, column: usize) -> Result<i64, SpecError> {
+    let digits = |s: &str| s.replace('_', "").parse::<i64>().ok().filter(|_| !s.starts_with('_') && !s.ends_with('_'));
+    let value = match word.split_once(['e', 'E']) {
+        None => digits(word),
+        Some((mantissa, exponent)) => digits(mantissa)
+            .zip(digits(exponent).and_then(|e| u32::try_from(e).ok()))
+            .and_then(|(mantissa, exponent)| 10i64.checked_pow(exponent).and_then(|power| mantissa.checked_mul(power))),
+    };
+    value.ok_or_else(|| SpecError { column, message: format!("`{}` isn't a number, or doesn't fit in 64 bits", word) })
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    /// where the source ends, for the errors at its end
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn column(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.end, |(_, column)| *column)
+    }
+
+    fn found(&self) -> String {
+        self.peek().map_or("the end of the spec".to_string(), Token::to_string)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let eaten = self.peek() == Some(&Token::Sym(c));
+        if eaten {
+            self.pos += 1;
+        }
+        eaten
+    }
+
+    fn expect(&mut self, c: char, after: &str) -> Result<(), SpecError> {
+        if self.eat(c) {
+            return Ok(());
+        }
+        error(self.column(), format!("expected `{}` {}, found {}", c, after, self.found()))
+    }
+
+    /// the statements up to the end of the spec, or of the block which was opened
+    fn block(&mut self, opened: bool) -> Result<Vec<Statement>, SpecError> {
+        let mut statements = vec![];
+        loop {
+            while self.peek() == Some(&Token::Sep) {
+                self.pos += 1;
+            }
+            match self.peek() {
+                None if opened => return error(self.column(), "the block isn't closed with a `}`"),
+                None => return Ok(statements),
+                Some(Token::Sym('}')) if opened => {
+                    self.pos += 1;
+                    return Ok(statements);
+                },
+                _ => {},
+            }
+            statements.push(self.statement()?);
+            match self.peek() {
+                None | Some(Token::Sep) => {},
+                Some(Token::Sym('}')) if opened => {},
+                _ => return error(self.column(), format!("expected `;` or a new line after the statement, found {}", self.found())),
+            }
+        }
+    }
+
+    fn statement(&mut self) -> Result<Statement, SpecError> {
+        let column = self.column();
+        let Some(Token::Ident(name)) = self.peek().cloned() else {
+            return error(column, format!("expected a statement, found {}", self.found()));
+        };
+        self.pos += 1;
+        match name.as_str() {
+            "print" | "println" | "lines" if self.eat('(') => Ok(Statement::Print {
+                each_on_a_line: name == "lines",
+                values: self.arguments(&name)?,
+                column,
+            }),
+            "repeat" if self.eat('(') => {
+                let count = self.expr()?;
+                self.expect(')', "after the count of `repeat`")?;
+                self.expect('{', "to open the block of `repeat`")?;
+                Ok(Statement::Repeat(count, self.block(true)?))
+            },
+            _ if self.eat('=') => Ok(Statement::Assign(name, self.expr()?)),
+            _ => error(self.column(), format!("expected `=` after `{}`, or one of print, println, lines and repeat, found {}", name, self.found())),
+        }
+    }
+
+    /// the arguments of the call up to its `)`, the `(` being eaten
+    fn arguments(&mut self, name: &str) -> Result<Vec<Expr>, SpecError> {
+        let mut arguments = vec![];
+        if self.eat(')') {
+            return Ok(arguments);
+        }
+        loop {
+            arguments.push(self.expr()?);
+            if self.eat(')') {
+                return Ok(arguments);
+            }
+            self.expect(',', &format!("or `)` between the arguments of `{}`", name))?;
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, SpecError> {
+        let mut left = self.term()?;
+        while let Some(&Token::Sym(op @ ('+' | '-'))) = self.peek() {
+            self.pos += 1;
+            left = Expr { column: left.column, kind: ExprKind::Binary(op, Box::new(left), Box::new(self.term()?)) };
+        }
+        Ok(left)
+    }
+
+    fn term(&mut self) -> Result<Expr, SpecError> {
+        let mut left = self.unary()?;
+        while let Some(&Token::Sym(op @ ('*' | '/' | '%'))) = self.peek() {
+            self.pos += 1;
+            left = Expr { column: left.column, kind: ExprKind::Binary(op, Box::new(left), Box::new(self.unary()?)) };
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, SpecError> {
+        let column = self.column();
+        if self.eat('-') {
+            return Ok(Expr { column, kind: ExprKind::Neg(Box::new(self.unary()?)) });
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<Expr, SpecError> {
+        let column = self.column();
+        let kind = match self.peek().cloned() {
+            Some(Token::Int(n)) => ExprKind::Int(n),
+            Some(Token::Str(s)) => ExprKind::Str(s),
+            Some(Token::Ident(name)) => {
+                self.pos += 1;
+                if !self.eat('(') {
+                    return Ok(Expr { column, kind: ExprKind::Var(name) });
+                }
+                let Some((_, parameters)) = FUNCTIONS.iter().find(|(f, _)| *f == name) else {
+                    let known = FUNCTIONS.iter().map(|(f, _)| *f).collect::<Vec<_>>();
+                    return error(column, format!("there's no function `{}`, only {}", name, known.join(", ")));
+                };
+                let arguments = self.arguments(&name)?;
+                if arguments.len() != parameters.len() {
+                    return error(column, format!("`{}` takes {} argument{} ({}), not {}",
+                        name, parameters.len(), if parameters.len() == 1 { "" } else { "s" }, parameters.join(", "), arguments.len()));
+                }
+                return Ok(Expr { column, kind: ExprKind::Call(name, arguments) });
+            },
+            Some(Token::Sym('(')) => {
+                self.pos += 1;
+                let inner = self.expr()?;
+                self.expect(')', "to close the `(`")?;
+                return Ok(inner);
+            },
+            _ => return error(column, format!("expected a value, found {}", self.found())),
+        };
+        self.pos += 1;
+        Ok(Expr { column, kind })
+    }
+}
+
+impl FromStr for GenSpec {
+    type Err = SpecError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { tokens: tokenize(source)?, pos: 0, end: source.chars().count() + 1 };
+        let statements = parser.block(false)?;
+        if statements.is_empty() {
+            return error(1, "the spec has no statements");
+        }
+        Ok(GenSpec { source: source.to_string(), statements })
+    }
+}
+
+// the configuration of a session shows the spec as it was written
+impl fmt::Debug for GenSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("GenSpec").field(&self.source).finish()
+    }
+}
+
+impl fmt::Display for GenSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Value {
+    Int(i64),
+    List(Vec<i64>),
+    Str(String),
+}
+
+impl Value {
+    fn kind(&self) -> &'static str {
+        match self {
+            Value::Int(_) => "a number",
+            Value::List(_) => "a list",
+            Value::Str(_) => "a string",
+        }
+    }
+}
+
+struct Evaluation<'a> {
+    variables: HashMap<String, Value>,
+    rng: Rng,
+    input: String,
+    /// asked before every turn of a `repeat`, the evaluation stops once it's true
+    stop: &'a dyn Fn() -> bool,
+    stopped: bool,
+}
+
+impl GenSpec {
+    /// the spec as it was written
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// the input of the round with the seed
+    pub fn generate(&self, seed: u64) -> Result<String, SpecError> {
+        self.generate_until(seed, &|| false).expect("the evaluation isn't stopped")
+    }
+
+    /// The input of the round with the seed, None when `stop` said to stop before it was written, as the round
+    /// was cancelled or timed out.
+    pub fn generate_until(&self, seed: u64, stop: &dyn Fn() -> bool) -> Option<Result<String, SpecError>> {
+        let mut evaluation = Evaluation { variables: HashMap::new(), rng: Rng::new(seed), input: String::new(), stop, stopped: false };
+        match evaluation.run(&self.statements) {
+            _ if evaluation.stopped => None,
+            Err(e) => Some(Err(e)),
+            Ok(()) => Some(Ok(evaluation.input)),
+        }
+    }
+}
+
+impl Evaluation<'_> {
+    fn run(&mut self, statements: &[Statement]) -> Result<(), SpecError> {
+        for statement in statements {
+            match statement {
+                Statement::Assign(name, value) => {
+                    let value = self.eval(value)?;
+                    self.variables.insert(name.clone(), value);
+                },
+                Statement::Print { each_on_a_line: false, values, column } => {
+                    let values = values.iter().map(|value| self.eval(value).map(|v| render(&v, " "))).collect::<Result<Vec<_>, _>>()?;
+                    self.input += &values.join(" ");
+                    self.input.push('\n');
+                    self.written(*column)?;
+                },
+                Statement::Print { each_on_a_line: true, values, column } => {
+                    for value in values {
+                        let value = self.eval(value)?;
+                        if !matches!(&value, Value::List(list) if list.is_empty()) {
+                            self.input += &render(&value, "\n");
+                            self.input.push('\n');
+                        }
+                    }
+                    self.written(*column)?;
+                },
+                Statement::Repeat(count, block) => {
+                    for _ in 0..self.count(count, "the count of `repeat`")? {
+                        if (self.stop)() {
+                            self.stopped = true;
+                            return error(count.column, "stopped");
+                        }
+                        self.run(block)?;
+                    }
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// the input written so far is within `MAX_INPUT`
+    fn written(&self, column: usize) -> Result<(), SpecError> {
+        match self.input.len() {
+            0..=MAX_INPUT => Ok(()),
+            _ => error(column, format!("the input is longer than {} bytes", MAX_INPUT)),
+        }
+    }
+
+    fn int(&mut self, expr: &Expr) -> Result<i64, SpecError> {
+        match self.eval(expr)? {
+            Value::Int(n) => Ok(n),
+            other => error(expr.column, format!("expected a number, found {}", other.kind())),
+        }
+    }
+
+    /// a length, of a list or a string, or how many times to repeat
+    fn count(&mut self, expr: &Expr, what: &str) -> Result<i64, SpecError> {
+        match self.int(expr)? {
+            n @ 0..=MAX_LEN => Ok(n),
+            n => error(expr.column, format!("{} is {}, it must be from 0 to {}", what, n, MAX_LEN)),
+        }
+    }
+
+    /// the bounds of a random number, lo being at most hi
+    fn range(&mut self, lo: &Expr, hi: &Expr) -> Result<(i64, i64), SpecError> {
+        match (self.int(lo)?, self.int(hi)?) {
+            (lo, hi) if lo <= hi => Ok((lo, hi)),
+            (l, h) => error(lo.column, format!("the range from {} to {} is empty", l, h)),
+        }
+    }
+
+    fn eval(&mut self, expr: &Expr) -> Result<Value, SpecError> {
+        let column = expr.column;
+        let value = match &expr.kind {
+            ExprKind::Int(n) => Value::Int(*n),
+            ExprKind::Str(s) => Value::Str(s.clone()),
+            ExprKind::Var(name) => match self.variables.get(name) {
+                Some(value) => value.clone(),
+                None => return error(column, format!("`{}` isn't assigned before it's used", name)),
+            },
+            ExprKind::Neg(inner) => match self.int(inner)?.checked_neg() {
+                Some(n) => Value::Int(n),
+                None => return error(column, "the number doesn't fit in 64 bits"),
+            },
+            ExprKind::Binary(op, left, right) => {
+                let (a, b) = (self.int(left)?, self.int(right)?);
+                let result = match op {
+                    '+' => a.checked_add(b),
+                    '-' => a.checked_sub(b),
+                    '*' => a.checked_mul(b),
+                    _ if b == 0 => return error(right.column, "division by 0"),
+                    '/' => a.checked_div(b),
+                    _ => a.checked_rem(b),
+                };
+                match result {
+                    Some(n) => Value::Int(n),
+                    None => return error(column, format!("{} {} {} doesn't fit in 64 bits", a, op, b)),
+                }
+            },
+            ExprKind::Call(name, args) => self.call(name, args, column)?,
+        };
+        Ok(value)
+    }
+
+    fn call(&mut self, name: &str, args: &[Expr], column: usize) -> Result<Value, SpecError> {
+        let value = match name {
+            "int" => {
+                let (lo, hi) = self.range(&args[0], &args[1])?;
+                Value::Int(self.rng.between(lo, hi))
+            },
+            "ints" => {
+                let n = self.count(&args[0], "the length of the list")?;
+                let (lo, hi) = self.range(&args[1], &args[2])?;
+                Value::List((0..n).map(|_| self.rng.between(lo, hi)).collect())
+            },
+            "distinct" => {
+                let n = self.count(&args[0], "the length of the list")?;
+                let (lo, hi) = self.range(&args[1], &args[2])?;
+                let span = hi as i128 - lo as i128 + 1;
+                if span < n as i128 {
+                    return error(column, format!("there aren't {} distinct numbers from {} to {}", n, lo, hi));
+                }
+                // a few draws when the range is much larger than the list, else a part of the range shuffled
+                if span > 2 * n as i128 {
+                    let mut seen = HashSet::new();
+                    let mut list = vec![];
+                    while list.len() < n as usize {
+                        let x = self.rng.between(lo, hi);
+                        if seen.insert(x) {
+                            list.push(x);
+                        }
+                    }
+                    Value::List(list)
+                } else {
+                    let mut range: Vec<i64> = (lo..=hi).collect();
+                    self.shuffle(&mut range);
+                    range.truncate(n as usize);
+                    Value::List(range)
+                }
+            },
+            "perm" => {
+                let n = self.count(&args[0], "the length of the permutation")?;
+                let mut list: Vec<i64> = (1..=n).collect();
+                self.shuffle(&mut list);
+                Value::List(list)
+            },
+            "string" => {
+                let n = self.count(&args[0], "the length of the string")?;
+                let alphabet: Vec<char> = match self.eval(&args[1])? {
+                    Value::Str(s) if !s.is_empty() => s.chars().collect(),
+                    Value::Str(_) => return error(args[1].column, "the alphabet is empty"),
+                    other => return error(args[1].column, format!("expected the alphabet as a string, found {}", other.kind())),
+                };
+                Value::Str((0..n).map(|_| alphabet[self.rng.below(alphabet.len())]).collect())
+            },
+            "sorted" => match self.eval(&args[0])? {
+                Value::List(mut list) => {
+                    list.sort_unstable();
+                    Value::List(list)
+                },
+                other => return error(args[0].column, format!("expected a list, found {}", other.kind())),
+            },
+            _ => match self.eval(&args[0])? {
+                Value::List(list) => Value::Int(list.len() as i64),
+                Value::Str(s) => Value::Int(s.chars().count() as i64),
+                other => return error(args[0].column, format!("expected a list or a string, found {}", other.kind())),
+            },
+        };
+        Ok(value)
+    }
+
+    /// Fisher-Yates
+    fn shuffle(&mut self, list: &mut [i64]) {
+        for i in (1..list.len()).rev() {
+            list.swap(i, self.rng.below(i + 1));
+        }
+    }
+}
+
+/// the value as it's printed, the numbers of a list separated by the separator
+fn render(value: &Value, separator: &str) -> String {
+    match value {
+        Value::Int(n) => n.to_string(),
+        Value::List(list) => list.iter().map(i64::to_string).collect::<Vec<_>>().join(separator),
+        Value::Str(s) => s.clone(),
+    }
+}
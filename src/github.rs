@@ -0,0 +1,66 @@
+use crate::options::Cli;
+use crate::report::Summary;
+use crate::{output, failed_references, first_difference, Failure, Round, Verdict};
+
+/// escapes the message of a workflow command
+fn escape_data(text: &str) -> String {
+    text.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+/// escapes a property such as `title`, which additionally can't contain `:` or `,`
+fn escape_property(text: &str) -> String {
+    escape_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
+fn failure_message(args: &Cli, fail: &Failure) -> String {
+    match fail {
+        Failure::Prog(x) => {
+            let first = x.stderr.lines().find(|l| !l.trim().is_empty()).unwrap_or("no error output");
+            format!("{} failed with status {}: {}", args.source_of(&x.path).display(), x.status.as_deref().unwrap_or_default(), first)
+        },
+        Failure::TimeLimit(x) => format!("{} exceeded the time limit", args.source_of(&x.path).display()),
+        Failure::RoundTimeout(x) => format!("{} was killed by the round timeout", args.source_of(&x.path).display()),
+        Failure::Spawn(e) => e.describe(args, true),
+    }
+}
+
+/// what went wrong in the round, in a line
+pub fn round_message(args: &Cli, outs: &Round) -> String {
+    match outs {
+        Round::GeneratorFail(fail) | Round::ProgramFail { fail, .. } => failure_message(args, fail),
+        Round::ReferenceFails { refs, .. } => {
+            let fails = refs.iter().filter_map(|r| r.as_ref().err()).map(|f| failure_message(args, f));
+            format!("{}: {}", failed_references(args, refs), fails.collect::<Vec<_>>().join("; "))
+        },
+        Round::Timeout { stage, .. } => format!("the round timeout was exceeded while running the {}", stage),
+        Round::Success { prog, refs, .. } => refs.iter()
+            .find_map(|r| first_difference(r.stdout.preview(), prog.stdout.preview()).map(|d| (&r.path, d)))
+            .map(|(path, (line, expected, got))| format!("first difference at line {} against {}: expected `{}`, got `{}`",
+                line, args.source_of(path).display(), expected, got))
+            .unwrap_or_else(|| "the references disagree with each other".to_string()),
+    }
+}
+
+/// prints an `::error` annotation for the failing round
+pub fn annotate_round(round: u64, seed: u64, verdict: Verdict, message: &str) {
+    let what = match verdict {
+        Verdict::WrongAnswer => "mismatch",
+        v => v.describe(),
+    };
+    let title = format!("Round {} {}", round, what);
+    let message = format!("{} (seed {})", message, seed);
+    output::raw(&format!("::error title={}::{}", escape_property(&title), escape_data(&message)));
+}
+
+/// prints a `::notice` annotation with the counts of the session
+pub fn annotate_summary(summary: &Summary) {
+    let verdicts = summary.verdicts.iter()
+        .filter(|(v, _)| **v != Verdict::Ok)
+        .map(|(v, n)| format!("{} {}", n, v.describe()))
+        .collect::<Vec<_>>();
+    let mut message = format!("{} rounds: {} passed, {} failed", summary.rounds, summary.passed, summary.failed);
+    if !verdicts.is_empty() {
+        message += &format!(" ({})", verdicts.join(", "));
+    }
+    output::raw(&format!("::notice title={}::{}", escape_property("compdiff summary"), escape_data(&message)));
+}
@@ -0,0 +1,151 @@
+use std::{
+    fs,
+    io,
+    path::Path,
+    };
+use similar::TextDiff;
+
+use crate::report::{Report, RoundRecord, Blob};
+use crate::Verdict;
+
+/// lines of a text shown before the "show more" toggle
+const SHOWN_LINES: usize = 40;
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; }
+td, th { border: 1px solid #ccc; padding: 0.2em 0.6em; text-align: left; }
+.ok { color: #1a7f37; }
+.fail { color: #cf222e; }
+details { margin: 0.5em 0; border: 1px solid #ccc; padding: 0.5em; }
+summary { cursor: pointer; font-weight: bold; }
+pre { background: #f6f8fa; padding: 0.5em; overflow-x: auto; }
+.add { background: #dafbe1; }
+.del { background: #ffebe9; }
+.hunk { color: #0969da; }
+";
+
+const SCRIPT: &str = "
+function more(button) {
+    var rest = button.previousElementSibling.querySelector('.rest');
+    rest.hidden = !rest.hidden;
+    button.textContent = rest.hidden ? 'show more' : 'show less';
+}
+";
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// a block of text, only the first lines are shown until "show more" is clicked
+fn block(title: &str, blob: &Blob) -> String {
+    let lines: Vec<&str> = blob.text.split_inclusive('\n').collect();
+    let note = if blob.truncated { format!(" (truncated, {} bytes in total)", blob.bytes) } else { String::new() };
+    let mut html = format!("<h4>{}{}</h4>\n<pre>", escape(title), note);
+    if lines.len() <= SHOWN_LINES {
+        html += &escape(&blob.text);
+        html += "</pre>\n";
+    } else {
+        html += &escape(&lines[..SHOWN_LINES].concat());
+        html += &format!("<span class=\"rest\" hidden>{}</span></pre>\n", escape(&lines[SHOWN_LINES..].concat()));
+        html += "<button onclick=\"more(this)\">show more</button>\n";
+    }
+    html
+}
+
+fn diff(title: &str, reference: &str, program: &str) -> String {
+    let diff = TextDiff::from_lines(reference, program).unified_diff().to_string();
+    let lines = diff.lines().map(|line| {
+        let class = match line.as_bytes() {
+            [b'+', b'+', b'+', ..] | [b'-', b'-', b'-', ..] => "",
+            [b'+', ..] => "add",
+            [b'-', ..] => "del",
+            [b'@', b'@', ..] => "hunk",
+            _ => "",
+        };
+        format!("<span class=\"{}\">{}</span>", class, escape(line))
+    }).collect::<Vec<_>>().join("\n");
+    format!("<h4>{}</h4>\n<pre>{}</pre>\n", escape(title), lines)
+}
+
+fn round_panel(record: &RoundRecord) -> String {
+    let mut html = format!("<details id=\"round-{}\">\n<summary class=\"fail\">round {}: {} ({})</summary>\n<p>seed {}, {:.3}s",
+        record.round, record.round, record.verdict.code(), record.verdict.describe(), record.seed, record.seconds);
+    if let Some(generator) = &record.generator {
+        html += &format!(", input written by <code>{}</code>", escape(&generator.display().to_string()));
+    }
+    if let Some(path) = &record.input_path {
+        html += &format!(", input saved in <code>{}</code>", escape(&path.display().to_string()));
+    }
+    html += "</p>\n";
+    if !record.reproduce.is_empty() {
+        html += &format!("<p>reproduce the input with <code>{}</code></p>\n", escape(&record.reproduce));
+    }
+    if let Some(input) = &record.input {
+        html += &block("input", input);
+    }
+    for fail in &record.failures {
+        html += &format!("<p><code>{}</code> failed: {}{}</p>\n", escape(&fail.path.display().to_string()), fail.verdict.describe(),
+            fail.status.as_ref().map(|s| format!(", {}", escape(s))).unwrap_or_default());
+        if let Some(stderr) = fail.stderr.as_ref().filter(|e| !e.text.is_empty()) {
+            html += &block("error output", stderr);
+        }
+    }
+    if let Some(prog) = &record.program {
+        html += &block(&format!("program ({}) output", prog.path.display()), &prog.output);
+        for r in &record.references {
+            html += &block(&format!("reference ({}) output", r.path.display()), &r.output);
+            if record.verdict == Verdict::WrongAnswer && r.output.text != prog.output.text {
+                html += &diff(&format!("diff of the program output against {}", r.path.display()), &r.output.text, &prog.output.text);
+            }
+        }
+    }
+    html += "</details>\n";
+    html
+}
+
+/// Renders the report as a single HTML page without any external assets.
+pub fn render(report: &Report) -> String {
+    let summary = &report.summary;
+    let mut html = format!("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>compdiff report</title>\n<style>{}</style>\n<script>{}</script>\n</head>\n<body>\n", STYLE, SCRIPT);
+    html += "<h1>compdiff report</h1>\n";
+    html += &format!("<p>{} rounds: <span class=\"ok\">{} passed</span>, <span class=\"fail\">{} failed</span>, exit code {}",
+        summary.rounds, summary.passed, summary.failed, summary.exit_code);
+    if let Some(seed) = report.seed {
+        html += &format!(", session seed {}", seed);
+    }
+    if let Some(toolchain) = &report.toolchain {
+        html += &format!(", built with {} ({})", escape(&toolchain.compiler.display().to_string()), escape(&toolchain.version));
+    }
+    html += "</p>\n";
+    if let Some(environment) = &report.environment {
+        html += "<details>\n<summary>environment</summary>\n<ul>\n";
+        html += &environment.lines().iter().map(|line| format!("<li>{}</li>\n", escape(line))).collect::<String>();
+        html += &format!("</ul>\n<pre>{}</pre>\n</details>\n", escape(&environment.config));
+    }
+    html += "<p>";
+    html += &summary.verdicts.iter().map(|(v, n)| format!("{}: {}", v.describe(), n)).collect::<Vec<_>>().join(", ");
+    html += "</p>\n";
+
+    html += "<h2>rounds</h2>\n<table>\n<tr><th>round</th><th>verdict</th><th>seed</th><th>time (s)</th></tr>\n";
+    for r in &report.rounds {
+        let verdict = if r.verdict == Verdict::Ok {
+            format!("<span class=\"ok\">{}</span>", r.verdict.code())
+        } else {
+            format!("<a class=\"fail\" href=\"#round-{}\">{}</a>", r.round, r.verdict.code())
+        };
+        html += &format!("<tr><td>{}</td><td>{}</td><td>{}</td><td>{:.3}</td></tr>\n", r.round, verdict, r.seed, r.seconds);
+    }
+    html += "</table>\n";
+
+    html += "<h2>failures</h2>\n";
+    for r in report.rounds.iter().filter(|r| r.verdict != Verdict::Ok) {
+        html += &round_panel(r);
+    }
+    html += "</body>\n</html>\n";
+    html
+}
+
+pub fn write(report: &Report, path: &Path) -> io::Result<()> {
+    fs::write(path, render(report))
+}
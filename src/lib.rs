@@ -1,80 +1,616 @@
+pub mod output;
+pub mod options;
+#[cfg(feature = "cli")]
 pub mod cli;
+#[cfg(feature = "cli")]
+pub mod config;
+pub mod save;
+pub mod report;
+pub mod events;
+pub mod markdown;
+pub mod html;
+#[cfg(feature = "cli")]
+pub mod watch;
+pub mod github;
+pub mod csv;
+pub mod db;
+pub mod notify;
+pub mod artifacts;
+pub mod corpus;
+pub mod resume;
+pub mod slowest;
+pub mod perf;
+pub mod memory;
+pub mod bench;
+#[cfg(feature = "cli")]
+pub mod clean;
+pub mod refcache;
+pub mod backend;
+pub mod session;
+pub mod compare;
+pub mod runner;
+pub mod capture;
+pub mod environment;
+pub mod minimize;
+pub mod explore;
+pub mod genspec;
+#[cfg(feature = "async")]
+pub mod nonblocking;
+#[cfg(feature = "testing")]
+pub mod fixtures;
 
 use std::{
-    process::{Command, Stdio, Child, Output}, 
-    io::Write, 
+    process::{self, Command, Stdio, Child}, 
+    io::{self, Read, Write}, 
+    thread,
     path::{Path, PathBuf}, 
-    env::current_dir, 
-    time::Duration,
+    env::{self, current_dir}, 
+    fs,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    fmt,
+    borrow::Cow,
     };
 use std::ffi::OsStr;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
 use std::error::Error;
-use cli::Cli;
+use std::collections::{BTreeMap, HashMap};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use backend::Backend;
+use options::{Cli, CompileCmd, Programs, SeedVia, split_flags};
+use refcache::RefCache;
+use capture::{capture, Captured};
+use compare::differing_line;
+use genspec::{GenSpec, GEN_SPEC};
+use runner::{ExecutionBackend, Invocation, Limits};
 use process_control::ChildExt;
-use process_control::Control;
+use process_control::{Control, Output};
 use string_error::{into_err, static_err};
 use binaryornot::is_binary;
+use serde::{Deserialize, Serialize};
+use log::{debug, info, trace, warn};
 
-fn get_command<P>(path: P) -> Result<Command, Box<dyn Error>>
+/// what runs the source, scripts by their interpreter with the flags of their language
+fn get_command<P>(path: P, backend: &Backend, flags: &BTreeMap<String, String>) -> Result<Command, Box<dyn Error>>
 where P: AsRef<Path>
 {
-    let bin = is_binary(path.as_ref()).unwrap_or_else(|e| panic!("Cannot open {}, error: {}", path.as_ref().to_string_lossy(), e));
-    match path.as_ref().extension().and_then(OsStr::to_str).and_then(|s| s.split('.').last()) {
-        Some("py") => get_python_command(path),
+    let bin = is_binary(path.as_ref())?;
+    match path.as_ref().extension().and_then(OsStr::to_str).and_then(|s| s.split('.').next_back()) {
+        Some("py") => get_python_command(path, backend, flags.get("py").map_or("", String::as_str)),
         Some("cpp") | Some("cxx") if !bin => panic!("internal error"),
         Some("exe") | Some("o") | None if bin => Ok(get_bin_command(path)),
         Some(x) if bin => {
             // Err(into_err(format!("unsupported binary file type {}", x)))
-            println!("Warning! Running unconventional type extensions \"{}\"", x);
+            say!("Warning! Running unconventional type extensions \"{}\"", x);
             Ok(get_bin_command(path))
         },
-        Some(_) | None => Err(into_err(format!("textual input is not yet supported.")))
+        Some(_) | None => Err(static_err("textual input is not yet supported."))
     }
 }
 
-fn preprocess_command<P>(path: P, args: &Cli) -> Result<PathBuf, Box<dyn Error>>
+/// How a source is preprocessed: its compiler and flags, and where its binary goes. `Programs::preprocess_options`
+/// gives those of the command line.
+#[derive(Clone, Debug, Default)]
+pub struct PreprocessOptions {
+    /// the c++ compiler, a name looked up by the backend or a path, g++ or clang when None
+    pub cpp_compiler: Option<PathBuf>,
+    /// the c++ compiler of the session with its version, which isn't asked for again when it builds the source
+    pub toolchain: Option<Toolchain>,
+    /// what the source is built as, the binaries of a source in two roles being kept apart
+    pub role: Option<Role>,
+    /// the flags of every language, by its extension
+    pub flags: BTreeMap<String, String>,
+    /// appended to the c++ flags, such as those of a build profile
+    pub extra_flags: String,
+    /// the macros defined, as NAME or NAME=VALUE
+    pub defines: Vec<String>,
+    pub include_dirs: Vec<PathBuf>,
+    /// the sources compiled into the binary, the grader last, only the source itself when empty
+    pub linked_sources: Vec<PathBuf>,
+    /// the grader among the linked sources
+    pub grader: Option<PathBuf>,
+    /// the `--compile-cmd` templates, the last one of an extension wins
+    pub compile_cmd: Vec<CompileCmd>,
+    pub build_dir: PathBuf,
+    /// a binary already built in `build_dir` from the same sources, flags and compiler is reused
+    pub reuse: bool,
+    /// the size in bytes `build_dir` is kept under as a cache, once a binary is added to it
+    pub max_cache_size: Option<u64>,
+    pub warnings_as_errors: bool,
+    /// where the compiler runs
+    pub backend: Backend,
+}
+
+/// A preprocessed source: what runs in its place, and how it was built.
+#[derive(Clone, Debug)]
+pub struct PreparedProgram {
+    /// the binary, or the source itself when it isn't compiled
+    pub binary: PathBuf,
+    /// what runs the script, None for a binary
+    pub interpreter: Option<PathBuf>,
+    /// how the binary was compiled, None when the source isn't
+    pub compiled: Option<Compiled>,
+    /// what the compiler warned about
+    pub warnings: String,
+}
+
+/// how a `PreparedProgram` was compiled
+#[derive(Clone, Debug)]
+pub struct Compiled {
+    pub compiler: PathBuf,
+    /// the flags, the include directories and the macros it was given
+    pub flags: Vec<String>,
+    /// the binary was built by an earlier session and taken from the build directory
+    pub reused: bool,
+}
+
+/// a source and how preprocessing it went
+pub type Prepared = (PathBuf, Result<PreparedProgram, Box<dyn Error>>);
+
+/// Compiles the source when it's compiled, or checks that it can run otherwise, its errors being a `CompileError`
+/// or an `UnsupportedFlags` when the compiler refuses it.
+pub fn preprocess(path: &Path, opts: &PreprocessOptions) -> Result<PreparedProgram, Box<dyn Error>> {
+    if opts.linked_sources.len() > 1 || path.is_dir() {
+        return preprocess_cpp_command(path, opts);
+    }
+    let bin = is_binary(path).map_err(|e| into_err(format!("couldn't open {}: {}", path.display(), e)))?;
+    match path.extension().and_then(OsStr::to_str).and_then(|s| s.split('.').next_back()) {
+        Some("cpp") | Some("cxx") if !bin => preprocess_cpp_command(path, opts),
+        Some(_) | None => {
+            // a script without an interpreter fails here rather than in the first round
+            let cmd = get_command(path, &opts.backend, &opts.flags)?;
+            let interpreter = (cmd.get_program() != path.as_os_str()).then(|| PathBuf::from(cmd.get_program()));
+            Ok(PreparedProgram { binary: path.to_path_buf(), interpreter, compiled: None, warnings: String::new() })
+        },
+    }
+}
+
+/// `preprocess` with the options of the source among the programmes
+pub fn build<P>(path: P, args: &Programs) -> Result<PreparedProgram, Box<dyn Error>>
 where P: AsRef<Path>
 {
-    let bin = is_binary(path.as_ref()).unwrap_or_else(|e| panic!("Cannot open {}, error: {}", path.as_ref().to_string_lossy(), e));
-    match path.as_ref().extension().and_then(OsStr::to_str).and_then(|s| s.split('.').last()) {
-        Some("cpp") | Some("cxx") if !bin => preprocess_cpp_command(path, args),
-        Some(_) | None => Ok(PathBuf::from(path.as_ref())),
+    let path = path.as_ref();
+    preprocess(path, &args.preprocess_options(args.role_of(path), path))
+}
+
+/// Preprocesses the sources in their roles at once, at most `--jobs` of them, giving the results in the order
+/// of the sources.
+pub fn preprocess_commands(sources: &[(Role, PathBuf)], args: &Programs, toolchain: Option<&Toolchain>) -> Vec<Result<PreparedProgram, Box<dyn Error>>> {
+    let jobs = args.jobs.or_else(|| thread::available_parallelism().ok()).map_or(1, |n| n.get());
+    let next = AtomicUsize::new(0);
+    let muted = output::is_muted();
+    let mut results: Vec<_> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs.min(sources.len())).map(|_| scope.spawn(|| {
+            let _mute = muted.then(output::mute);
+            let mut done = vec![];
+            while let Some((role, source)) = sources.get(next.fetch_add(1, Ordering::Relaxed)) {
+                // the errors aren't Send, only what they say crosses the threads, unless the output shows more of them
+                let opts = PreprocessOptions { toolchain: toolchain.cloned(), ..args.preprocess_options(*role, source) };
+                let result = preprocess(source, &opts).map_err(|e| -> Box<dyn Error + Send + Sync> {
+                    let e = match e.downcast::<CompileError>() {
+                        Ok(e) => return e,
+                        Err(e) => e,
+                    };
+                    match e.downcast::<UnsupportedFlags>() {
+                        Ok(e) => e,
+                        Err(e) => e.to_string().into(),
+                    }
+                });
+                done.push(((*role, source), result));
+            }
+            done
+        })).collect();
+        workers.into_iter().flat_map(|w| w.join().expect("a compiling thread panicked")).collect()
+    });
+    results.sort_by_key(|((role, source), _)| sources.iter().position(|(r, s)| r == role && s == *source));
+    results.into_iter()
+        .map(|(_, result)| result.map_err(|e| e as Box<dyn Error>))
+        .collect()
+}
+
+/// directory where compiled sources are cached across sessions and projects, `--cache-dir` or else
+/// in the user's cache directory
+pub fn cache_dir(relocated: Option<&Path>) -> PathBuf {
+    if let Some(dir) = relocated {
+        return dir.to_path_buf();
+    }
+    let cache = env::var_os("XDG_CACHE_HOME").map(PathBuf::from)
+        .or_else(|| env::var_os("LOCALAPPDATA").map(PathBuf::from))
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")));
+    match cache {
+        Some(cache) => cache.join("compdiff"),
+        None => env::temp_dir().join("compdiff-build"),
+    }
+}
+
+/// the file marking a directory compdiff created, which `compdiff clean` may remove
+pub const OWNED_MARKER: &str = ".compdiff";
+
+/// creates the directory with its parents, marked as compdiff's when it didn't exist yet
+pub fn create_owned_dir(dir: &Path) -> io::Result<()> {
+    if dir.is_dir() {
+        return Ok(());
+    }
+    fs::create_dir_all(dir)?;
+    fs::write(dir.join(OWNED_MARKER), "created by compdiff, `compdiff clean` may remove this directory\n")
+}
+
+pub fn is_owned_dir(dir: &Path) -> bool {
+    dir.join(OWNED_MARKER).is_file()
+}
+
+/// directory of the binaries built by a session with --no-cache, removed at its end
+pub fn session_build_dir() -> PathBuf {
+    env::temp_dir().join(format!("compdiff-session-{}", process::id()))
+}
+
+pub fn remove_session_build_dir() -> io::Result<()> {
+    match fs::remove_dir_all(session_build_dir()) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        removed => removed,
     }
 }
 
+/// where the sources are compiled: --build-dir, else the session's own directory with --no-cache, else the cache
+pub fn builds_into(args: &Programs) -> PathBuf {
+    match (&args.build_dir, args.no_cache) {
+        (Some(dir), _) => dir.clone(),
+        (None, true) => session_build_dir(),
+        (None, false) => cache_dir(args.cache_dir.as_deref()),
+    }
+}
+
+/// held by the process evicting from the cache
+const CACHE_LOCK: &str = ".lock";
+/// a lock older than this was left by a process which died while evicting
+const CACHE_LOCK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Removes the least recently used binaries of the cache, with their records, until it holds at most
+/// `max_bytes`. Returns how many were removed, none when another process is evicting already.
+pub fn evict_cache(dir: &Path, max_bytes: u64) -> io::Result<usize> {
+    let lock = dir.join(CACHE_LOCK);
+    let abandoned = fs::metadata(&lock).and_then(|m| m.modified()).is_ok_and(|t| t.elapsed().unwrap_or_default() > CACHE_LOCK_TIMEOUT);
+    if abandoned {
+        let _ = fs::remove_file(&lock);
+    }
+    match fs::OpenOptions::new().write(true).create_new(true).open(&lock) {
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => return Ok(0),
+        opened => opened?,
+    };
+
+    let evicted = (|| {
+        // (last used, size of the binary and its records, the binary)
+        let mut builds = vec![];
+        let mut total = 0;
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let size = entry.metadata()?.len();
+            total += size;
+            let path = entry.path();
+            if path.extension().is_some_and(|e| e == "o") {
+                let records = [SOURCE_RECORD, WARNINGS_RECORD].iter()
+                    .filter_map(|r| fs::metadata(path.with_extension(r)).ok())
+                    .map(|m| m.len())
+                    .sum::<u64>();
+                builds.push((entry.metadata()?.modified()?, size + records, path));
+            }
+        }
+        builds.sort();
+        let mut evicted = 0;
+        for (_, size, binary) in builds {
+            if total <= max_bytes {
+                break;
+            }
+            for record in [SOURCE_RECORD, WARNINGS_RECORD] {
+                let _ = fs::remove_file(binary.with_extension(record));
+            }
+            fs::remove_file(&binary)?;
+            total = total.saturating_sub(size);
+            evicted += 1;
+        }
+        Ok(evicted)
+    })();
+    let _ = fs::remove_file(&lock);
+    evicted
+}
+
+/// the extension of the file next to every cached binary, holding its sources and the hashes of their contents
+const SOURCE_RECORD: &str = "src";
+
+/// the extension of the file next to a cached binary built with warnings, holding them
+const WARNINGS_RECORD: &str = "warnings";
+
+/// the cached binaries whose source was changed or removed since, with their records
+pub fn stale_builds(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut stale = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == SOURCE_RECORD || e == WARNINGS_RECORD) || path.ends_with(CACHE_LOCK) || path.ends_with(OWNED_MARKER) {
+            continue;
+        }
+        let record = path.with_extension(SOURCE_RECORD);
+        // a source and the hash of its contents on every two lines
+        let current = fs::read_to_string(&record).ok().and_then(|text| {
+            let lines = text.lines().collect::<Vec<_>>();
+            lines.chunks(2).all(|pair| match pair {
+                [source, hash] => fs::read(source).is_ok_and(|contents| format!("{:016x}", hash_str(&String::from_utf8_lossy(&contents))) == hash.trim()),
+                _ => false,
+            }).then_some(())
+        });
+        if current.is_none() {
+            let warnings = path.with_extension(WARNINGS_RECORD);
+            stale.push(path);
+            stale.extend([record, warnings].into_iter().filter(|r| r.exists()));
+        }
+    }
+    Ok(stale)
+}
+
+/// The c++ compiler of the session, as reported with the results.
+#[derive(Clone, Debug, Serialize)]
+pub struct Toolchain {
+    pub compiler: PathBuf,
+    /// the first line of its `--version`
+    pub version: String,
+}
+
+/// The c++ compiler given by --cpp-compiler, or else by the CXX variable (unless --ignore-env-toolchain), or else
+/// g++ or clang when installed.
+pub fn cpp_compiler(chosen: Option<&Path>, backend: &Backend) -> Result<PathBuf, Box<dyn Error>> {
+    match chosen {
+        Some(name) => backend.which(name)
+            .ok_or_else(|| into_err(format!("couldn't find the c++ compiler {}", name.display()))),
+        None => ["g++", "clang"]
+            .iter()
+            .find_map(|x| backend.which(x))
+            .ok_or_else(|| static_err("couldn't find a c++ compiler! (support is present for g++ or clang only, choose another one with --cpp-compiler)")),
+    }
+}
+
+/// A source the compiler refused, with everything it said.
+#[derive(Debug)]
+pub struct CompileError {
+    pub path: PathBuf,
+    /// the command line which was run
+    pub command: String,
+    pub stderr: String,
+    /// the --grader linked with the source
+    pub grader: Option<PathBuf>,
+    /// the linked sources the compiler reports errors in, none when only the link failed
+    pub erring: Vec<PathBuf>,
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "couldn't compile {}, command used: {}", self.path.display(), self.command)
+    }
+}
+
+impl Error for CompileError {}
+
+impl Toolchain {
+    pub fn detect(args: &Programs) -> Result<Self, Box<dyn Error>> {
+        let compiler = cpp_compiler(args.chosen_cpp_compiler().as_deref(), &args.backend)?;
+        let version = tool_version(&compiler, &args.backend);
+        Ok(Toolchain { compiler, version })
+    }
+}
+
+/// the first line of `<tool> --version`, read from its stderr when its stdout is empty as some tools print it there
+pub fn tool_version(tool: &Path, backend: &Backend) -> String {
+    let mut version = Command::new(tool);
+    version.arg("--version");
+    let first_line = |text: &[u8]| String::from_utf8_lossy(text).lines().map(str::trim).find(|line| !line.is_empty()).map(str::to_string);
+    backend.command(version, &[], Limits::default()).output().ok()
+        .and_then(|out| first_line(&out.stdout).or_else(|| first_line(&out.stderr)))
+        .unwrap_or_default()
+}
+
+/// The compiler can't build even an empty programme with the flags, typically for a `-std=` it's too old for.
+#[derive(Debug)]
+pub struct UnsupportedFlags {
+    pub compiler: PathBuf,
+    /// the flags which fail on their own, empty when only their combination does
+    pub rejected: Vec<String>,
+    /// a `-std=` the compiler supports, when it rejects the one asked for
+    pub suggestion: Option<String>,
+    pub stderr: String,
+}
+
+impl fmt::Display for UnsupportedFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.rejected.as_slice() {
+            [] => write!(f, "{} can't build a programme with these flags together", self.compiler.display())?,
+            rejected => write!(f, "{} rejects {}", self.compiler.display(), rejected.join(" "))?,
+        }
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, ", try {}", suggestion)?;
+        }
+        write!(f, " (the flags are changed with --flags cpp=...)")
+    }
+}
+
+impl Error for UnsupportedFlags {}
+
+/// whether the compiler builds an empty programme with the flags, with what it said when it doesn't
+fn compiles_with(compiler: &Path, flags: &[String], backend: &Backend) -> Result<(), String> {
+    static PROBES: AtomicUsize = AtomicUsize::new(0);
+    let base = env::temp_dir().join(format!("compdiff-probe-{}-{}", process::id(), PROBES.fetch_add(1, Ordering::Relaxed)));
+    let (source, binary) = (base.with_extension("cpp"), base.with_extension("o"));
+    fs::write(&source, "int main() { return 0; }\n").map_err(|e| e.to_string())?;
+    let mut probe = Command::new(compiler);
+    probe.arg(&source).args(flags).arg("-o").arg(&binary);
+    let output = backend.command(probe, &[&env::temp_dir()], Limits::default()).output();
+    let _ = fs::remove_file(&source);
+    let _ = fs::remove_file(&binary);
+    match output {
+        Ok(output) if output.status.success() => Ok(()),
+        Ok(output) => Err(String::from_utf8_lossy(&output.stderr).into_owned()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Builds an empty programme with the flags before the sources, once per compiler and flags, so that a flag
+/// the compiler doesn't know is reported as such rather than as an error of every source.
+fn probe_compiler(compiler: &Path, flags: &[String], backend: &Backend) -> Result<(), UnsupportedFlags> {
+    // the lock is held while probing, so that the sources compiled in parallel wait for the one probe
+    type Probes = BTreeMap<(PathBuf, Vec<String>), Result<(), String>>;
+    static PROBED: Mutex<Probes> = Mutex::new(BTreeMap::new());
+    let mut probed = PROBED.lock().unwrap_or_else(PoisonError::into_inner);
+    let probe = probed.entry((compiler.to_path_buf(), flags.to_vec()))
+        .or_insert_with(|| {
+            debug!("probing {} with the flags {}", compiler.display(), flags.join(" "));
+            compiles_with(compiler, flags, backend)
+        });
+    let Err(stderr) = probe.clone() else { return Ok(()) };
+
+    let rejected = flags.iter().filter(|flag| compiles_with(compiler, std::slice::from_ref(flag), backend).is_err()).cloned().collect::<Vec<_>>();
+    // the newest standard of the same dialect the compiler knows
+    let suggestion = rejected.iter().find_map(|flag| flag.strip_prefix("-std=")).and_then(|standard| {
+        let dialect = if standard.starts_with("gnu") { "gnu++" } else { "c++" };
+        ["23", "20", "17", "14", "11"].iter()
+            .map(|year| format!("-std={}{}", dialect, year))
+            .find(|flag| compiles_with(compiler, std::slice::from_ref(flag), backend).is_ok())
+    });
+    Err(UnsupportedFlags { compiler: compiler.to_path_buf(), rejected, suggestion, stderr })
+}
+
+/// how the programme of the role is run, as printed by `--dry-run`
+pub fn command_line(path: &Path, role: Role, args: &Programs) -> Result<String, Box<dyn Error>> {
+    Ok(format!("{:?}", args.backend_of(role).command(get_command(path, &args.backend, &args.language_flags())?, &[], Limits::default())))
+}
+
 fn get_bin_command<P>(path: P) -> Command
 where P: AsRef<Path>
 {
     Command::new(path.as_ref())
 }
 
-fn preprocess_cpp_command<P>(path: P, args: &Cli) -> Result<PathBuf, Box<dyn Error>>
-where P: AsRef<Path>
-{
-    println!("Compiling {}", path.as_ref().to_str().unwrap());
+/// the sources the compiler reports errors in, or errors in the headers they include
+fn erring_sources(sources: &[PathBuf], stderr: &str) -> Vec<PathBuf> {
+    sources.iter()
+        .filter(|source| {
+            let prefix = format!("{}:", source.display());
+            stderr.lines().any(|line| match line.strip_prefix("In file included from ") {
+                Some(line) => line.starts_with(&prefix),
+                None => line.starts_with(&prefix) && line.contains("error"),
+            })
+        })
+        .cloned()
+        .collect()
+}
 
-    let compiler = ["g++", "clang"]
-        .iter()
-        .map(which::which)
-        .find_map(|x| x.ok())
-        .ok_or_else(|| static_err("couldn't find a c++ compiler! (support is present for g++ or clang only)"))?;
-
-    let output_name = format!("./{}_{}.o", 
-        path.as_ref().file_name().and_then(OsStr::to_str).expect("program has no filename!"), 
-        compiler.file_name().unwrap().to_str().unwrap()
-        );
-
-    let command = format!("{} {} -o {}", 
-        path.as_ref().to_str().unwrap(),
-        args.cpp_compiler_flags,
-        output_name
-        );
+/// the contents of every file under the include directories, so that a changed header is built again
+fn include_dir_contents(dirs: &[PathBuf]) -> io::Result<String> {
+    let mut files = vec![];
+    let mut pending = dirs.to_vec();
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() { pending.push(path) } else { files.push(path) }
+        }
+    }
+    files.sort();
+    let mut contents = String::new();
+    for file in files {
+        contents += &format!("{}\0{}\0", file.display(), String::from_utf8_lossy(&fs::read(&file)?));
+    }
+    Ok(contents)
+}
+
+fn preprocess_cpp_command(path: &Path, opts: &PreprocessOptions) -> Result<PreparedProgram, Box<dyn Error>> {
+    let sources = if opts.linked_sources.is_empty() { vec![path.to_path_buf()] } else { opts.linked_sources.clone() };
+    let extension = sources[0].extension().and_then(OsStr::to_str).unwrap_or_default();
+    let template = opts.compile_cmd.iter().rev().find(|c| c.extension == extension);
+    let compiler = match template {
+        Some(template) => opts.backend.which(&template.argv[0])
+            .ok_or_else(|| into_err(format!("couldn't find {}, the compiler of --compile-cmd {}", template.argv[0], template)))?,
+        None => cpp_compiler(opts.cpp_compiler.as_deref(), &opts.backend)?,
+    };
+
+    // every distinct source contents, flag set, compiler and role gets its own binary, so that sources sharing
+    // a filename (or a source used with different flags, or as the programme and as a reference) never
+    // overwrite each other, and an unchanged source isn't compiled again
+    let graded = opts.grader.is_some() && sources.last() == opts.grader.as_ref();
+    let sources = sources.iter().map(fs::canonicalize).collect::<io::Result<Vec<_>>>()?;
+    let cpp_flags = opts.flags.get("cpp").map_or("", String::as_str);
+    let flags = if opts.extra_flags.is_empty() { cpp_flags.to_string() } else { format!("{} {}", cpp_flags, opts.extra_flags) };
+    let contents = sources.iter()
+        .map(|source| fs::read(source).map(|c| String::from_utf8_lossy(&c).into_owned()))
+        .collect::<io::Result<Vec<_>>>()?;
+    // single arguments, whatever spaces the directories and the values of the macros hold
+    let include_dirs = &opts.include_dirs;
+    let includes = include_dirs.iter().map(|dir| format!("-I{}", dir.display()))
+        .chain(opts.defines.iter().map(|define| format!("-D{}", define)))
+        .collect::<Vec<_>>();
+    create_owned_dir(&opts.build_dir)?;
+    // the binaries are run by their path, which mustn't be looked up in PATH
+    let dir = fs::canonicalize(&opts.build_dir)?;
+    // a binary is only reused when built by the same version of the compiler
+    let version = match &opts.toolchain {
+        Some(toolchain) if toolchain.compiler == compiler => toolchain.version.clone(),
+        _ => tool_version(&compiler, &opts.backend),
+    };
+    let role = opts.role.map(|role| format!("{}_", role)).unwrap_or_default();
+    let output_name = dir.join(format!("{}_{}{}_{:016x}.o", 
+        path.file_name().and_then(OsStr::to_str).expect("program has no filename!"), 
+        role,
+        compiler.file_name().unwrap().to_str().unwrap(),
+        hash_str(&format!("{}\0{}\0{}\0{}\0{}\0{}\0{}", contents.join("\0"), flags, version, 
+            template.map(|t| t.to_string()).unwrap_or_default(), includes.join("\0"), include_dir_contents(include_dirs)?, role))
+        ));
+    let warnings_record = output_name.with_extension(WARNINGS_RECORD);
+    let split = split_flags(&flags).map_err(into_err)?;
+    let flags = split.iter().chain(&includes).cloned().collect::<Vec<_>>();
+    let compiled = |reused| Some(Compiled { compiler: compiler.clone(), flags: flags.clone(), reused });
+    // with --warnings-as-errors a binary built with warnings is built again, to fail
+    if opts.reuse && output_name.is_file() && !(opts.warnings_as_errors && warnings_record.is_file()) {
+        info!("cache hit: {} is built as {}", path.display(), output_name.display());
+        // the time of the last use, which decides what gets evicted first
+        if let Err(e) = fs::File::options().append(true).open(&output_name).and_then(|f| f.set_modified(SystemTime::now())) {
+            debug!("couldn't mark {} as used: {}", output_name.display(), e);
+        }
+        let warnings = fs::read_to_string(&warnings_record).unwrap_or_default();
+        return Ok(PreparedProgram { binary: output_name, interpreter: None, compiled: compiled(true), warnings });
+    }
+    info!("cache miss: {} isn't built yet", path.display());
+    say!("Compiling {}", path.to_str().unwrap());
+
+    // built under another name first, so that another session never runs a half-written binary, and
+    // every thread building the same binary gets a name of its own
+    static PARTIALS: AtomicUsize = AtomicUsize::new(0);
+    let partial = output_name.with_extension(format!("{}.{}.partial", process::id(), PARTIALS.fetch_add(1, Ordering::Relaxed)));
+
+    let srcs = sources.iter().map(|s| s.to_str().unwrap()).collect::<Vec<_>>();
+    let out = partial.to_str().unwrap();
+    // a --compile-cmd may not take the flags the way the compiler alone does
+    if template.is_none() {
+        probe_compiler(&compiler, &split, &opts.backend)?;
+    }
+    let argv: Vec<String> = match template {
+        // every placeholder stays a single argument, whatever the paths contain, {src} is one for every source
+        Some(template) => template.argv[1..].iter()
+            .flat_map(|arg| match arg.as_str() {
+                "{flags}" => flags.clone(),
+                "{src}" => srcs.iter().map(|s| s.to_string()).collect(),
+                arg => vec![arg.replace("{out}", out)],
+            })
+            .collect(),
+        None => srcs.iter().map(|s| s.to_string())
+            .chain(flags.iter().cloned())
+            .chain(["-o".to_string(), out.to_string()])
+            .collect(),
+    };
+    let command = argv.join(" ");
     
-    let process = Command::new(compiler.to_str().unwrap())
-        .args(command.split(" "))
-        .current_dir(current_dir().expect("process does not have cwd defined!"))
+    info!("compiling {} into {}: {} {}", path.display(), output_name.display(), compiler.display(), command);
+    output::log(format_args!("$ {} {}", compiler.display(), command));
+    let mut compile = Command::new(compiler.to_str().unwrap());
+    compile.args(&argv).current_dir(current_dir().expect("process does not have cwd defined!"));
+    let process = opts.backend.command(compile, &[&dir], Limits::default())
         .stdout(Stdio::piped())
-        //.stderr(Stdio::piped())
+        .stderr(Stdio::piped())
         .spawn()?;
 
     let output = process
@@ -82,225 +618,1304 @@ where P: AsRef<Path>
         .wait()?
         .ok_or_else(|| static_err("couldn't start program"))?;
 
-    let gen_errors = String::from_utf8(output.stderr).expect("error parsing string");
-    if !gen_errors.is_empty() || !output.status.success()  {
-        return Err(into_err(format!("couldn't compile file \"{}\", errors generated by compiler (\"{}\") should have appeared before. Command used: {} {}", 
-            path.as_ref().to_str().unwrap(), 
-            compiler.to_str().unwrap(),
-            compiler.file_name().unwrap().to_str().unwrap(),
-            command)));
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    if !output.status.success() || (opts.warnings_as_errors && !stderr.trim().is_empty()) {
+        let _ = fs::remove_file(&partial);
+        return Err(Box::new(CompileError {
+            path: path.to_path_buf(),
+            command: format!("{} {}", compiler.display(), command),
+            grader: sources.last().filter(|_| graded).cloned(),
+            erring: erring_sources(&sources, &stderr),
+            stderr,
+        }));
     }
 
-    Ok(PathBuf::from(output_name))
+    fs::rename(&partial, &output_name)?;
+    let record = sources.iter().zip(&contents).map(|(source, contents)| format!("{}\n{:016x}\n", source.display(), hash_str(contents))).collect::<String>();
+    fs::write(output_name.with_extension(SOURCE_RECORD), record)?;
+    if stderr.trim().is_empty() {
+        let _ = fs::remove_file(&warnings_record);
+    } else {
+        fs::write(&warnings_record, &stderr)?;
+    }
+    if let Some(max_bytes) = opts.max_cache_size {
+        match evict_cache(&dir, max_bytes) {
+            Ok(0) => {},
+            Ok(n) => info!("evicted {} binaries from the cache {}", n, dir.display()),
+            Err(e) => warn!("couldn't evict from the cache {}: {}", dir.display(), e),
+        }
+    }
+    Ok(PreparedProgram { binary: output_name, interpreter: None, compiled: compiled(false), warnings: stderr })
 }
 
-fn get_python_command<P>(path: P) -> Result<Command, Box<dyn Error>>
+fn get_python_command<P>(path: P, backend: &Backend, flags: &str) -> Result<Command, Box<dyn Error>>
 where P: AsRef<Path>
 {
     let pyint = ["python", "python3", "python2"]
         .iter()
-        .map(which::which)
-        .find_map(|x| x.ok()) 
+        .find_map(|x| backend.which(x))
         .ok_or_else(|| static_err("cannot find a python intepreter!"))?;
 
     let mut cmd = Command::new(pyint);
     cmd.current_dir(current_dir()?);
+    cmd.args(split_flags(flags).map_err(into_err)?);
     cmd.arg(path.as_ref().as_os_str());
     Ok(cmd)
 }
 
 
 
-// pub type Failure<'a> = (&'a Path, ExitStatus, String);
-pub enum Failure<'a> {
-    Prog(&'a Path, String, String),
-    TimeLimit(&'a Path),
+/// the limit an execution was killed for exceeding
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Limit {
+    Time,
+    /// what was left of the round
+    RoundTimeout,
+    /// the session was cancelled while it ran
+    Cancelled,
 }
-pub type Success<'a> = (&'a Path, String);
-pub type Execution<'a> = Result<Success<'a>, Failure<'a>>;
 
-pub fn generate_input(args: &Cli) -> Execution {
-    execute_prog(args.generator.as_path())
+/// Everything known of an execution of a programme, whether it succeeded or not.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExecutionResult {
+    pub path: PathBuf,
+    pub stdout: Captured,
+    pub stderr: String,
+    /// how it exited, such as `exit status: 1`, None when it was killed or didn't run
+    pub status: Option<String>,
+    pub exit_code: Option<i64>,
+    /// the signal which killed the process
+    pub signal: Option<i32>,
+    /// wall time from spawning to exiting (or being killed)
+    pub duration: Duration,
+    /// peak memory in kilobytes, only measured with --mem-compare
+    pub peak_memory: Option<u64>,
+    /// the limit it was killed for exceeding
+    pub limit_hit: Option<Limit>,
 }
 
-fn get_execution_limits(args: &Cli) -> (Option<Duration>, Option<usize>) {
-    let tlimit = args.time_limit.map(Duration::from_secs_f64);
-    let mlimit = args.memory_limit.map(|x| x*1000); // convert from kilobytes to bytes
-    (tlimit, mlimit)
+impl ExecutionResult {
+    /// the output of a programme which didn't have to run, such as the input file or a cached output
+    pub fn new(path: PathBuf, stdout: String) -> Self {
+        ExecutionResult { path, stdout: stdout.into(), ..Default::default() }
+    }
+
+    /// the execution which ended with `out`, or which was killed for exceeding the limit when it's None
+    pub fn of_output(path: &Path, out: Option<Output>, duration: Duration, limit: Limit) -> Self {
+        let text = |bytes: &[u8]| String::from_utf8_lossy(bytes).into_owned();
+        let mut result = ExecutionResult { path: path.to_path_buf(), duration, ..Default::default() };
+        match out {
+            Some(out) => {
+                result.stdout = text(&out.stdout).into();
+                result.stderr = text(&out.stderr);
+                result.status = Some(out.status.to_string());
+                result.exit_code = out.status.code();
+                result.signal = exit_signal(&out.status);
+            },
+            None => result.limit_hit = Some(limit),
+        }
+        result
+    }
+
+    /// whether the process exited cleanly without writing any errors
+    pub fn succeeded(&self) -> bool {
+        self.limit_hit.is_none() && self.exit_code == Some(0) && self.stderr.is_empty()
+    }
+
+    /// how it went, as the programme of the role
+    pub fn stats(&self, role: Role) -> ExecutionStats {
+        ExecutionStats {
+            path: self.path.clone(),
+            role,
+            seconds: self.duration.as_secs_f64(),
+            exit_code: self.exit_code,
+            signal: self.signal,
+            killed: self.limit_hit.is_some(),
+            memory_kb: self.peak_memory,
+        }
+    }
 }
 
-pub fn execute_prog<'a>(path: &'a Path) -> Execution<'a>
-{
-    let gen = get_command(path)
-        .expect("cannot open program")
-        .output()
-        .expect("cannot start program");
-
-    let gen_errors = String::from_utf8(gen.stderr).expect("error parsing string");
-    if !gen_errors.is_empty() || !gen.status.success()  {
-        Err(Failure::Prog(path, gen.status.to_string(), gen_errors))
+/// An execution which failed, its verdict is derived from how it went.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Failure {
+    /// it exited unsuccessfully, or wrote errors
+    Prog(Box<ExecutionResult>),
+    TimeLimit(Box<ExecutionResult>),
+    /// the process was killed because the whole round ran out of time, or the session was cancelled
+    RoundTimeout(Box<ExecutionResult>),
+    /// the process couldn't be started, or its output couldn't be read
+    Spawn(Box<RunError>),
+}
+
+impl Failure {
+    pub fn path(&self) -> &Path {
+        match self {
+            Failure::Prog(x) | Failure::TimeLimit(x) | Failure::RoundTimeout(x) => &x.path,
+            Failure::Spawn(e) => &e.path,
+        }
+    }
+
+    /// the execution, None when the process couldn't be started
+    pub fn execution(&self) -> Option<&ExecutionResult> {
+        match self {
+            Failure::Prog(x) | Failure::TimeLimit(x) | Failure::RoundTimeout(x) => Some(x),
+            Failure::Spawn(_) => None,
+        }
+    }
+}
+
+/// What a programme is in the session.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Role {
+    Generator,
+    Program,
+    Reference,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Role::Generator => "generator",
+            Role::Program => "program",
+            Role::Reference => "reference",
+        })
+    }
+}
+
+/// An error and the chain of its sources, kept as their messages so that a failure can be cloned and saved.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cause {
+    pub message: String,
+    pub source: Option<Box<Cause>>,
+}
+
+impl Cause {
+    pub fn new(message: impl Into<String>) -> Self {
+        Cause { message: message.into(), source: None }
+    }
+
+    /// the error with its sources, their messages lowercased like the rest of the output
+    pub fn of(error: &(dyn Error + 'static)) -> Self {
+        Cause { message: lowercased(&error.to_string()), source: error.source().map(|source| Box::new(Cause::of(source))) }
+    }
+
+    pub fn caused_by(mut self, source: Cause) -> Self {
+        self.source = Some(Box::new(source));
+        self
+    }
+
+    /// the messages of the error and of its sources, the outermost first
+    pub fn chain(&self) -> Vec<&str> {
+        let mut chain = vec![self.message.as_str()];
+        let mut cause = self;
+        while let Some(source) = &cause.source {
+            chain.push(&source.message);
+            cause = source;
+        }
+        chain
+    }
+}
+
+/// "Permission denied" as "permission denied", leaving words such as "IO" alone
+fn lowercased(message: &str) -> String {
+    let mut chars = message.chars();
+    match (chars.next(), chars.next()) {
+        (Some(first), Some(second)) if first.is_uppercase() && !second.is_uppercase() => first.to_lowercase().chain(message.chars().skip(1)).collect(),
+        _ => message.to_string(),
+    }
+}
+
+impl fmt::Display for Cause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for Cause {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_deref().map(|source| source as &(dyn Error + 'static))
+    }
+}
+
+/// Why a programme couldn't be run: what was being done to it, in which round, and the error which caused it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RunError {
+    pub role: Role,
+    pub path: PathBuf,
+    pub round: u64,
+    /// what couldn't be done to the programme, as "run" or "read the output of"
+    pub action: String,
+    pub cause: Cause,
+}
+
+impl RunError {
+    pub fn new(ctx: &RoundCtx, role: Role, path: &Path, action: &str, cause: Cause) -> Self {
+        RunError { role, path: path.to_path_buf(), round: ctx.round, action: action.to_string(), cause }
+    }
+
+    /// "failed to run reference refs/brute.py in round 12: permission denied (os error 13)", with the
+    /// programme shown by its source and only the innermost cause unless `full` asks for all of them
+    pub fn describe(&self, args: &Cli, full: bool) -> String {
+        self.message(args.source_of(&self.path), full)
+    }
+
+    fn message(&self, shown: &Path, full: bool) -> String {
+        let chain = self.cause.chain();
+        let causes = if full { chain.join(": ") } else { chain[chain.len() - 1].to_string() };
+        format!("failed to {} {} {} in round {}: {}", self.action, self.role, shown.display(), self.round, causes)
+    }
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message(&self.path, true))
+    }
+}
+
+impl Error for RunError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.cause)
+    }
+}
+
+pub type Execution = Result<ExecutionResult, Failure>;
+/// additional information attached to a failure report
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub title: String,
+    pub body: String,
+}
+
+impl Diagnostic {
+    pub fn new(title: &str, body: impl Into<String>) -> Self {
+        Diagnostic { title: title.to_string(), body: body.into() }
+    }
+}
+
+/// the part of a round which is being executed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Stage {
+    Generator,
+    Program,
+    References,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Stage::Generator => "generator",
+            Stage::Program => "program",
+            Stage::References => "references",
+        })
+    }
+}
+
+/// State shared by all the executions of a single round, which may run on threads of their own.
+pub struct RoundCtx<'a> {
+    pub args: &'a Cli,
+    pub round: u64,
+    /// the seed handed to the generator in this round
+    pub seed: u64,
+    deadline: Option<Instant>,
+    retries: AtomicU32,
+    executions: Mutex<Vec<ExecutionStats>>,
+    /// the outputs and commands of the executions, only kept for `--keep-artifacts`
+    artifacts: Mutex<Vec<ExecutionArtifact>>,
+    cancellation: CancellationToken,
+}
+
+impl<'a> RoundCtx<'a> {
+    pub fn new(args: &'a Cli, round: u64) -> Self {
+        let deadline = args.round_timeout.map(|t| Instant::now() + Duration::from_secs_f64(t));
+        let seed = round_seed(args.seed.unwrap_or_default(), round);
+        RoundCtx { args, round, seed, deadline, retries: AtomicU32::new(0), executions: Mutex::default(), artifacts: Mutex::default(), cancellation: CancellationToken::new() }
+    }
+
+    /// the programmes of the round are killed once the token is cancelled
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = token;
+        self
+    }
+
+    pub fn cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    /// how many executions had to be repeated in this round
+    pub fn retries(&self) -> u32 {
+        self.retries.load(Ordering::Relaxed)
+    }
+
+    /// whether the failure is likely caused by the infrastructure rather than the programme,
+    /// wrong answers and time limits are never transient
+    fn is_transient(&self, out: &Execution) -> bool {
+        match out {
+            Err(Failure::Spawn(_)) => true,
+            Err(Failure::Prog(x)) => self.args.retry_pattern.as_ref().is_some_and(|p| x.stderr.contains(p.as_str())),
+            _ => false,
+        }
+    }
+
+    /// whether the failed execution is run again, counting the retry
+    fn retrying(&self, out: &Execution, attempt: u32) -> bool {
+        if !self.is_transient(out) || self.cancelled() {
+            return false;
+        }
+        self.retries.fetch_add(1, Ordering::Relaxed);
+        info!("retrying a failed execution (attempt {})", attempt);
+        true
+    }
+
+    /// time left until the round timeout
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline.map(|d| d.saturating_duration_since(Instant::now()))
+    }
+
+    /// every execution waited for in this round so far, retries included
+    pub fn executions(&self) -> Vec<ExecutionStats> {
+        self.executions.lock().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// the artifacts of the executions of this round so far, empty without `--keep-artifacts`
+    pub fn take_artifacts(&self) -> Vec<ExecutionArtifact> {
+        std::mem::take(&mut *self.artifacts.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+/// How a single execution went: timing and exit status.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExecutionStats {
+    pub path: PathBuf,
+    /// what the programme is in the session, the same source may run as the programme and as a reference
+    pub role: Role,
+    /// wall time from spawning to exiting (or being killed)
+    pub seconds: f64,
+    pub exit_code: Option<i64>,
+    pub signal: Option<i32>,
+    /// killed for exceeding the time limit or the round timeout
+    pub killed: bool,
+    /// peak memory in kilobytes, only measured with --mem-compare
+    pub memory_kb: Option<u64>,
+}
+
+/// Everything about an execution for `--keep-artifacts`, including what's discarded for successful ones.
+#[derive(Clone, Debug)]
+pub struct ExecutionArtifact {
+    pub stats: ExecutionStats,
+    /// the command line, with the environment set for it
+    pub command: String,
+    pub stdout: Captured,
+    pub stderr: String,
+}
+
+/// Stops a session, from another thread as well: no round starts once it's cancelled, and the programmes
+/// still running are killed. Its clones are cancelled with it.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+static INTERRUPTS: OnceLock<CancellationToken> = OnceLock::new();
+
+/// makes Ctrl+C cancel the returned token, which stops the session, instead of killing compdiff
+pub fn catch_interrupts() -> CancellationToken {
+    INTERRUPTS.get_or_init(|| {
+        let token = CancellationToken::new();
+        let cancel = token.clone();
+        if let Err(e) = ctrlc::set_handler(move || cancel.cancel()) {
+            say!("couldn't catch Ctrl+C: {}", e);
+        }
+        token
+    }).clone()
+}
+
+/// whether Ctrl+C was pressed since `catch_interrupts`
+pub fn interrupted() -> bool {
+    INTERRUPTS.get().is_some_and(CancellationToken::is_cancelled)
+}
+
+/// environment variable holding the seed when the generator gets it via `--gen-seed-via env`
+pub const SEED_ENV: &str = "COMPDIFF_SEED";
+
+fn splitmix64(x: u64) -> u64 {
+    let x = x.wrapping_add(0x9e3779b97f4a7c15);
+    let x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    let x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+/// the random numbers of `--explore` and `--gen-spec`, the same ones for the same seed
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 = splitmix64(self.0);
+        self.0
+    }
+
+    /// a number below the bound, which is above 0
+    pub(crate) fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+
+    /// a number from lo to hi, both included, lo being at most hi
+    pub(crate) fn between(&mut self, lo: i64, hi: i64) -> i64 {
+        let span = (hi as i128 - lo as i128 + 1) as u128;
+        (lo as i128 + ((self.next() as u128 * span) >> 64) as i128) as i64
+    }
+}
+
+/// a session seed for when none was given
+pub fn random_seed() -> u64 {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64;
+    splitmix64(nanos ^ u64::from(process::id()))
+}
+
+/// the seed of the given round, fully determined by the session seed
+pub fn round_seed(session: u64, round: u64) -> u64 {
+    splitmix64(session ^ splitmix64(round))
+}
+
+/// the `--gen-args` split into the arguments of the generator
+fn gen_args(args: &Cli) -> Result<Vec<String>, String> {
+    args.gen_args.as_deref().map_or(Ok(vec![]), split_flags)
+}
+
+/// the word as the shell reads it, quoted when it has to be
+fn shell_word(word: &str) -> Cow<'_, str> {
+    if !word.is_empty() && word.chars().all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c)) {
+        Cow::Borrowed(word)
     } else {
-        Ok((path, String::from_utf8(gen.stdout).expect("cannot parse string")))
+        Cow::Owned(format!("'{}'", word.replace('\'', r"'\''")))
     }
 }
 
-pub fn start_prog_input<P>(path: P, input: &str) -> Child
-where P: AsRef<Path>
-{
-    let mut gen = get_command(path)
-        .expect("cannot open program")
-        .stdin(Stdio::piped())
-        .stderr(Stdio::piped())
-        .stdout(Stdio::piped())
-        .spawn()
-        .expect("cannot start program");
+/// a shell command reproducing the input of the round with the given seed
+pub fn generator_invocation(args: &Cli, round: u64, seed: u64) -> String {
+    let gen = match (&args.input_file, &args.gen_spec, args.generator_of(round, seed)) {
+        (Some(file), ..) => return format!("cat {}", file.display()),
+        (None, Some(spec), _) => return format!("# {} '{}' with the seed {}", GEN_SPEC, spec.source(), seed),
+        (None, None, Some(gen)) => args.source_of(&gen.path).display(),
+        (None, None, None) => return String::new(),
+    };
+    let gen = match gen_args(args) {
+        Ok(words) if !words.is_empty() => format!("{} {}", gen, words.iter().map(|w| shell_word(w)).collect::<Vec<_>>().join(" ")),
+        _ => gen.to_string(),
+    };
+    match args.gen_seed_via {
+        SeedVia::Argv => format!("{} {}", gen, seed),
+        SeedVia::Stdin => format!("echo {} | {}", seed, gen),
+        SeedVia::Env => format!("{}={} {}", SEED_ENV, seed, gen),
+    }
+}
 
-    let mut stdin = gen.stdin.take().expect("failed to open stdin");
-    stdin.write_all(input.as_bytes()).expect("failed to write input!");
-    gen
+/// the input of the round when it isn't generated, failing the round like a generator when it can't be read
+fn given_input(ctx: &RoundCtx) -> Option<Execution> {
+    let file = ctx.args.input_file.as_ref()?;
+    Some(fs::read_to_string(file)
+        .map(|input| ExecutionResult::new(file.clone(), input))
+        .map_err(|e| run_failure(ctx, Role::Generator, file, "read", Cause::of(&e))))
 }
 
-pub fn start_prog_input_limits<P>(path: P, input: &str, args: &Cli) -> Option<process_control::Output>
-where P: AsRef<Path>
-{
-    let (tlimit, mlimit) = get_execution_limits(args);
+/// the input the generator wrote, read from its file when it was written to one
+pub(crate) fn generated_input(generated: ExecutionResult, ctx: &RoundCtx) -> Result<String, Failure> {
+    let path = generated.path;
+    generated.stdout.into_string().map_err(|e| run_failure(ctx, Role::Generator, &path, "read the output of", Cause::of(&e)))
+}
+
+/// the input the `--gen-spec` writes from the seed of the round
+fn spec_input(spec: &GenSpec, ctx: &RoundCtx) -> Execution {
+    let path = Path::new(GEN_SPEC);
+    let started = Instant::now();
+    // a spec repeating for long is stopped like a generator, once the round is cancelled or over
+    let stop = || ctx.cancelled() || ctx.remaining() == Some(Duration::ZERO);
+    let Some(input) = spec.generate_until(ctx.seed, &stop) else {
+        let limit = if ctx.cancelled() { Limit::Cancelled } else { Limit::RoundTimeout };
+        return output_to_execution(ExecutionResult::of_output(path, None, started.elapsed(), limit));
+    };
+    let input = input.map_err(|e| run_failure(ctx, Role::Generator, path, "evaluate", Cause::of(&e)))?;
+    Ok(ExecutionResult { duration: started.elapsed(), ..ExecutionResult::new(path.to_path_buf(), input) })
+}
+
+fn run_failure(ctx: &RoundCtx, role: Role, path: &Path, action: &str, cause: Cause) -> Failure {
+    Failure::Spawn(Box::new(RunError::new(ctx, role, path, action, cause)))
+}
+
+pub fn generate_input<B: ExecutionBackend + ?Sized>(ctx: &RoundCtx, backend: &B) -> Execution {
+    if let Some(input) = given_input(ctx) {
+        return input;
+    }
+    if let Some(spec) = &ctx.args.gen_spec {
+        return spec_input(spec, ctx);
+    }
+
+    let path = &ctx.args.generator_of(ctx.round, ctx.seed).expect("either a generator, a spec or an input file is required").path;
+    let seed = ctx.seed.to_string();
+    let line = format!("{}\n", seed);
+    let args = gen_args(ctx.args).map_err(|e| run_failure(ctx, Role::Generator, path, "pass the arguments to", Cause::new(e)))?;
+    let invocation = Invocation { args, ..Invocation::new(path, Role::Generator) };
+    let invocation = match ctx.args.gen_seed_via {
+        SeedVia::Argv => Invocation { args: [invocation.args, vec![seed]].concat(), ..invocation },
+        SeedVia::Stdin => invocation.with_input(&line),
+        SeedVia::Env => Invocation { env: vec![(SEED_ENV.to_string(), seed)], ..invocation },
+    };
+    execute(backend, &invocation, ctx)
+}
+
+pub(crate) fn get_execution_limits(args: &Cli) -> Limits {
+    let time = args.time_limit.map(Duration::from_secs_f64);
+    let memory = args.memory_limit.map(|x| x*1000); // convert from kilobytes to bytes
+    Limits { time, memory }
+}
+
+/// the size in bytes past which an output is written to a temporary file
+fn spill_threshold(args: &Cli) -> usize {
+    args.spill_threshold * 1000
+}
+
+/// pause before the n-th retry of a transiently failing execution
+const RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Repeats a failed execution while its failure looks transient (the programme couldn't be
+/// spawned, or its stderr matches `--retry-pattern`), at most `--retries` times.
+fn retry(mut out: Execution, ctx: &RoundCtx, mut again: impl FnMut() -> Execution) -> Execution {
+    for attempt in 1..=ctx.args.retries {
+        if !ctx.retrying(&out, attempt) { break; }
+        thread::sleep(RETRY_BACKOFF * attempt);
+        out = again();
+    }
+    out
+}
 
-    let mut gen = get_command(&path)
-        .expect("cannot open program")
-        .stdin(Stdio::piped())
+/// runs the programme with the backend, again while it fails transiently
+pub fn execute<B: ExecutionBackend + ?Sized>(backend: &B, invocation: &Invocation, ctx: &RoundCtx) -> Execution {
+    retry(backend.execute(invocation, ctx), ctx, || backend.execute(invocation, ctx))
+}
+
+/// Starts the programme with its input on its stdin, returns it with its command line. The (time, memory)
+/// limits only bound a container, `wait_prog` enforces them here.
+pub fn start_prog(invocation: &Invocation, args: &Programs) -> Result<(Child, String), Box<dyn Error>> {
+    let mut cmd = prog_command(invocation, args)?;
+    let command = format!("{:?}{}", cmd, if invocation.input.is_some() { " < input" } else { "" });
+    output::log(format_args!("$ {}", command));
+    let mut gen = cmd
+        .stdin(if invocation.input.is_some() { Stdio::piped() } else { Stdio::null() })
         .stderr(Stdio::piped())
         .stdout(Stdio::piped())
-        .spawn()
-        .expect(format!("coudln't start program {}", path.as_ref().to_str().unwrap()).as_str());
+        .spawn()?;
 
-    let mut stdin = gen.stdin.take().expect("failed to open stdin");
-    stdin.write_all(input.as_bytes()).expect("failed to write input!");
-    
-    let mut gen = gen
-        .controlled_with_output();
+    // the input is written on a thread of its own while the output is read, so that neither of the pipes fills up
+    if let (Some(mut stdin), Some(input)) = (gen.stdin.take(), invocation.input) {
+        let (input, path) = (input.to_string(), invocation.path.to_path_buf());
+        thread::spawn(move || match stdin.write_all(input.as_bytes()) {
+            // the programme exited without reading all of its input
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {},
+            Err(e) => warn!("couldn't write the input of {}: {}", path.display(), e),
+            Ok(()) => {},
+        });
+    }
+    Ok((gen, command))
+}
+
+/// The programme run by its backend, which bounds it when it's a container or another host, with the
+/// arguments and the environment of the invocation.
+fn prog_command(invocation: &Invocation, args: &Programs) -> Result<Command, Box<dyn Error>> {
+    let mut cmd = get_command(invocation.path, &args.backend, &args.language_flags())?;
+    cmd.args(&invocation.args).envs(invocation.env.iter().map(|(name, value)| (name, value)));
+    Ok(args.backend_of(invocation.role).command(cmd, &[], invocation.limits))
+}
 
+/// How long the programme may run: the time limit, or what's left of the round when that's less, and
+/// whether it's the round timeout which bounds it.
+fn time_bound(path: &Path, tlimit: Option<Duration>, ctx: &RoundCtx) -> (Option<Duration>, bool) {
+    let round_left = ctx.remaining();
+    let round_bound = match (tlimit, round_left) {
+        (_, None) => false,
+        (None, Some(_)) => true,
+        (Some(t), Some(r)) => r < t,
+    };
+    let tlimit = if round_bound { round_left } else { tlimit };
     if let Some(t) = tlimit {
-        gen = gen.time_limit(t);
+        debug!("{} may run for {:.3}s, bound by the {}", path.display(), t.as_secs_f64(), if round_bound { "round timeout" } else { "time limit" });
     }
-    #[cfg(process_control_memory_limit)]
-    if let Some(m) = mlimit {
-        gen = gen.memory_limit(m);
-    }
-    #[cfg(not(process_control_memory_limit))]
+    (tlimit, round_bound)
+}
+
+/// how long a programme is waited for before checking again whether the session was cancelled
+pub(crate) const CANCEL_POLL: Duration = Duration::from_millis(50);
+
+/// reads the pipe to its end on a thread, so that the programme never blocks on it while it's waited for
+pub(crate) fn drain<R: Read + Send + 'static>(pipe: Option<R>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut bytes = vec![];
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut bytes);
+        }
+        bytes
+    })
+}
+
+/// Waits for a programme started at `started`, killing it once it exceeds its limits, the round runs out of
+/// time or the session is cancelled.
+pub fn wait_prog(invocation: &Invocation, mut child: Child, command: &str, started: Instant, ctx: &RoundCtx) -> Execution
+{
+    let (path, limits) = (invocation.path, invocation.limits);
+    let (tlimit, round_bound) = time_bound(path, limits.time, ctx);
+    let deadline = tlimit.map(|t| Instant::now() + t);
+
+    // a container or a host is given its memory limit, docker or ssh itself isn't
+    let backend = ctx.args.programs.backend_of(invocation.role);
+    let mlimit = limits.memory.filter(|_| backend.is_local());
+    #[cfg(not(any(target_os = "android", all(target_os = "linux", any(target_env = "gnu", target_env = "musl")), windows)))]
     if mlimit.is_some() {
         panic!("Memory limit cannot be set on this platofrm [NOT SUPPORTED]")
     }
-        
-    gen
-        .terminate_for_timeout()
-        .wait()
-        .expect("couldn't wait for the programme!")
-}
 
-pub fn output_to_execution(out: Output, path: & Path) -> Execution
-{
-    let gen_errors = String::from_utf8(out.stderr).expect("error parsing string");
-    if !gen_errors.is_empty() || !out.status.success() {
-        Err(Failure::Prog(path, out.status.to_string(), gen_errors))
+    let threshold = spill_threshold(ctx.args);
+    let stdout = child.stdout.take().map(|pipe| thread::spawn(move || capture(pipe, threshold)));
+    let stderr = drain(child.stderr.take());
+    // --mem-compare reaps the programme itself to learn its peak memory, which it only can of a local process
+    let (status, memory_kb) = if ctx.args.mem_compare && backend.is_local() {
+        match bench::wait_measured(&mut child, started, deadline, ctx) {
+            Ok(waited) => waited.map_or((None, None), |(status, measured)| (Some(status.into()), measured.memory_kb)),
+            Err(e) => {
+                let _ = child.kill();
+                return Err(run_failure(ctx, invocation.role, path, "wait for", Cause::of(&e)));
+            },
+        }
     } else {
-        Ok((path, String::from_utf8(out.stdout).expect("cannot parse string")))
+        let status = loop {
+            let left = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+            if ctx.cancelled() || left == Some(Duration::ZERO) {
+                let _ = child.kill();
+                let _ = child.wait();
+                break None;
+            }
+            let mut gen = child
+                .controlled()
+                .time_limit(left.map_or(CANCEL_POLL, |left| left.min(CANCEL_POLL)));
+            #[cfg(any(target_os = "android", all(target_os = "linux", any(target_env = "gnu", target_env = "musl")), windows))]
+            if let Some(m) = mlimit {
+                gen = gen.memory_limit(m);
+            }
+            match gen.wait() {
+                Ok(Some(status)) => break Some(status),
+                Ok(None) => {},
+                Err(e) => {
+                    let _ = child.kill();
+                    return Err(run_failure(ctx, invocation.role, path, "wait for", Cause::of(&e)));
+                },
+            }
+        };
+        (status, None)
+    };
+    // the pipes of a killed programme may be held open by what it started, its output is dropped anyway
+    let out = status.map(|status| {
+        let stdout = stdout.and_then(|stdout| stdout.join().ok()).unwrap_or_default();
+        (Output { status, stdout: vec![], stderr: stderr.join().unwrap_or_default() }, stdout)
+    });
+    finish_execution(invocation, command, started, out, memory_kb, round_bound, ctx)
+}
+
+/// Records the execution of a programme in the round and tells how it went. It's None when the programme was
+/// killed, its stdout is captured apart from the rest of its output otherwise.
+fn finish_execution(invocation: &Invocation, command: &str, started: Instant, out: Option<(Output, Captured)>, memory_kb: Option<u64>, round_bound: bool, ctx: &RoundCtx) -> Execution {
+    let (path, role) = (invocation.path, invocation.role);
+    let lost = out.as_ref().is_some_and(|(o, _)| ctx.args.programs.backend_of(role).lost_connection(&o.status));
+    let killed_for = if ctx.cancelled() { Limit::Cancelled } else if round_bound { Limit::RoundTimeout } else { Limit::Time };
+    let (out, stdout) = out.unzip();
+    let mut result = ExecutionResult::of_output(path, out, started.elapsed(), killed_for);
+    if let Some(stdout) = stdout {
+        result.stdout = stdout;
+    }
+    result.peak_memory = memory_kb;
+    let stats = result.stats(role);
+    if ctx.args.keep_artifacts.is_some() {
+        ctx.artifacts.lock().unwrap_or_else(PoisonError::into_inner).push(ExecutionArtifact {
+            stats: stats.clone(),
+            command: command.to_string(),
+            stdout: result.stdout.clone(),
+            stderr: result.stderr.clone(),
+        });
+    }
+    ctx.executions.lock().unwrap_or_else(PoisonError::into_inner).push(stats);
+    if result.limit_hit.is_some() {
+        info!("{} was killed after {:.3}s", path.display(), result.duration.as_secs_f64());
+    }
+    if lost {
+        let cause = Cause::new("lost the connection to its host").caused_by(Cause::new(result.stderr.trim()));
+        return Err(run_failure(ctx, role, path, "run", cause));
     }
+    output_to_execution(result)
 }
 
-pub fn execute_prog_input_limits<'a>(path: &'a Path, input: &str, args: &Cli) -> Execution<'a>
-{
-    let out = start_prog_input_limits(path, input, args);    
-    match out {
-        None => Err(Failure::TimeLimit(path)),
-        Some(out) => {
-            let gen_errors = String::from_utf8(out.stderr).expect("error parsing string");
-            if !gen_errors.is_empty() || !out.status.success() {
-                Err(Failure::Prog(path, out.status.to_string(), gen_errors))
-            } else {
-                Ok((path, String::from_utf8(out.stdout).expect("cannot parse string")))
-            }
-        }
+#[cfg(unix)]
+fn exit_signal(status: &process_control::ExitStatus) -> Option<i32> {
+    status.signal()
+}
+
+#[cfg(not(unix))]
+fn exit_signal(_status: &process_control::ExitStatus) -> Option<i32> {
+    None
+}
+
+/// the execution when it succeeded, or the failure it is
+pub fn output_to_execution(result: ExecutionResult) -> Execution {
+    match result.limit_hit {
+        Some(Limit::Time) => Err(Failure::TimeLimit(Box::new(result))),
+        Some(Limit::RoundTimeout | Limit::Cancelled) => Err(Failure::RoundTimeout(Box::new(result))),
+        None if !result.succeeded() => Err(Failure::Prog(Box::new(result))),
+        None => Ok(result),
     }
 }
 
-pub fn execute_prog_input<'a>(path: &'a Path, input: &str) -> Execution<'a>
-{
-    let gen = start_prog_input(path, input);
-    let out = gen.wait_with_output().expect("failed to read stdout and stderr");
-    output_to_execution(out, path)
+/// runs the references, taking the outputs the session remembers or the --ref-cache has for the input instead of running them again
+fn execute_refs<B: ExecutionBackend + ?Sized>(input: &str, ctx: &RoundCtx, backend: &B, timed: Option<TimedReference>) -> Vec<Execution> {
+    if let Some(remembered) = remembered_refs(input, ctx) {
+        return remembered;
+    }
+    let cached = cached_refs(input, ctx, timed.as_ref());
+    let missing = missing_refs(input, &cached, timed.as_ref(), ctx);
+    let executed = backend.execute_all(&missing, ctx).into_iter().zip(&missing)
+        .map(|(out, invocation)| retry(out, ctx, || backend.execute(invocation, ctx)))
+        .collect();
+    let executed = with_timed(executed, &cached, timed);
+    merge_refs(input, cached, executed, ctx)
 }
 
-pub fn execute_progs_input<'a, I>(paths: I, input: &str) -> Vec<Execution<'a>>
-where I: Iterator<Item = &'a Path>, 
-{
-    paths
-        .map(|path| (path, start_prog_input(path, input)))
-        .map(|(path, child)| (path, child.wait_with_output().expect("failed to read stdout and stderr")))
-        .map(|(path, child)| output_to_execution(child, path))
+/// the outputs of the references on an input an earlier round of the session ran them on
+fn remembered_refs(input: &str, ctx: &RoundCtx) -> Option<Vec<Execution>> {
+    let args = ctx.args;
+    let outputs = args.ref_memo.get(input)?;
+    if args.verbose > 0 {
+        say!("  the outputs of the references come from an earlier round with the same input");
+    }
+    Some(args.programs.reference.iter().zip(outputs).map(|(reference, stdout)| Ok(ExecutionResult { path: reference.clone(), stdout, ..Default::default() })).collect())
+}
+
+/// what the --ref-cache has of the output of every reference on the input, but for the one which ran first
+fn cached_refs(input: &str, ctx: &RoundCtx, timed: Option<&TimedReference>) -> Vec<Option<String>> {
+    let references = &ctx.args.programs.reference;
+    match &ctx.args.opened_ref_cache {
+        Some(cache) => references.iter().enumerate().map(|(i, r)| cache.get(r, input).filter(|_| timed.is_none_or(|(t, _)| *t != i))).collect(),
+        None => vec![None; references.len()],
+    }
+}
+
+/// the references which run on the input: those without a cached output, but for the one which ran first
+fn missing_refs<'a>(input: &'a str, cached: &[Option<String>], timed: Option<&TimedReference>, ctx: &RoundCtx<'a>) -> Vec<Invocation<'a>> {
+    ctx.args.programs.reference.iter().zip(cached).enumerate()
+        .filter(|(i, (_, c))| c.is_none() && timed.is_none_or(|(t, _)| t != i))
+        .map(|(_, (r, _))| Invocation::new(r, Role::Reference).with_input(input))
         .collect()
 }
 
-pub enum Round<'a>{
-    GeneratorFail(Failure<'a>),
-    ReferenceFails(String, Vec<Failure<'a>>),
-    ProgramFail(String, Failure<'a>),
-    Success(String, Success<'a>, Vec<Success<'a>>),
+/// the executions of the references without a cached output, the one which ran first put back among them
+fn with_timed(mut executed: Vec<Execution>, cached: &[Option<String>], timed: Option<TimedReference>) -> Vec<Execution> {
+    if let Some((i, execution)) = timed {
+        executed.insert(cached[..i].iter().filter(|c| c.is_none()).count(), execution);
+    }
+    executed
 }
 
-pub fn run_round(args: &Cli) -> Round {
-    if args.verbose { println!("round start"); }
-    let inp = generate_input(args);
-    if let Err(x) = inp { return Round::GeneratorFail(x); }
-    let inp = unsafe{ inp.unwrap_unchecked() };
-    if args.verbose { println!("finished generating input"); }
+/// The reference `--time-limit-rel` is relative to, as it ran before the programme, and its index among
+/// the references.
+type TimedReference = (usize, Execution);
 
-    let prg = if args.time_limit.is_none() { 
-        execute_prog_input(args.program.as_path(), inp.1.as_str())
-    } else {
-        execute_prog_input_limits(args.program.as_path(), inp.1.as_str(), args)
+/// Runs the reference of `--time-limit-rel` on the input. It runs in every round, as neither the session
+/// nor the --ref-cache remembers how long it took.
+fn time_reference<B: ExecutionBackend + ?Sized>(input: &str, ctx: &RoundCtx, backend: &B) -> Option<TimedReference> {
+    let i = ctx.args.relative_reference()?;
+    Some((i, execute(backend, &Invocation::new(&ctx.args.programs.reference[i], Role::Reference).with_input(input), ctx)))
+}
+
+/// The limits of the programme in the round, its time limit relative to how long the reference took with
+/// `--time-limit-rel`, or the floor when the reference failed.
+fn program_limits(args: &Cli, timed: Option<&TimedReference>) -> Limits {
+    let mut limits = get_execution_limits(args);
+    if let (Some(relative), Some((_, reference))) = (&args.time_limit_rel, timed) {
+        let floor = Duration::from_secs_f64(args.time_limit_floor);
+        limits.time = Some(reference.as_ref().map_or(floor, |r| relative.limit(r.duration, floor)));
+    }
+    limits
+}
+
+/// how the time limit the programme exceeded came from the time of the reference
+fn relative_limit(args: &Cli, timed: Option<&TimedReference>) -> Option<Diagnostic> {
+    let (Some(relative), Some((_, Ok(reference)))) = (&args.time_limit_rel, timed) else { return None };
+    let floor = Duration::from_secs_f64(args.time_limit_floor);
+    let limit = relative.limit(reference.duration, floor);
+    let floored = if limit == floor { format!(", raised to the floor of {:.3}s", floor.as_secs_f64()) } else { String::new() };
+    Some(Diagnostic::new("relative time limit", format!("{} took {:.3}s on this input, which made the time limit of the programme {}x that: {:.3}s{}",
+        args.source_of(&reference.path).display(), reference.duration.as_secs_f64(), relative.factor, reference.duration.mul_f64(relative.factor).as_secs_f64(), floored)))
+}
+
+/// the outputs of the references, the cached ones and those `executed`, which are kept in the cache and
+/// remembered for the rest of the session when all of them succeeded
+fn merge_refs(input: &str, cached: Vec<Option<String>>, executed: Vec<Execution>, ctx: &RoundCtx) -> Vec<Execution> {
+    let args = ctx.args;
+    let refs = match &args.opened_ref_cache {
+        Some(cache) => cache_refs(input, cache, cached, executed, ctx),
+        None => executed,
+    };
+    if args.ref_memo.is_enabled() {
+        if let Some(outputs) = refs.iter().map(|r| r.as_ref().ok().map(|x| x.stdout.clone())).collect() {
+            args.ref_memo.put(input, outputs);
+        }
+    }
+    refs
+}
+
+/// the cached outputs of the references and those `executed`, which are kept in the cache
+fn cache_refs(input: &str, cache: &RefCache, cached: Vec<Option<String>>, executed: Vec<Execution>, ctx: &RoundCtx) -> Vec<Execution> {
+    let args = ctx.args;
+    let references = &args.programs.reference;
+    let mut executed = executed.into_iter();
+    references.iter().zip(cached)
+        .map(|(reference, cached)| match cached {
+            Some(output) => {
+                if args.verbose > 0 {
+                    say!("  the output of {} comes from the reference cache", args.source_of(reference).display());
+                }
+                Ok(ExecutionResult::new(reference.clone(), output))
+            },
+            None => {
+                let out = executed.next().expect("every reference missing from the cache is run");
+                // an output written to a file is too long to be worth keeping
+                if let Some(output) = out.as_ref().ok().and_then(|x| x.stdout.text()) {
+                    if let Err(e) = cache.put(reference, input, output) {
+                        warn!("couldn't keep the output of {} in the reference cache: {}", reference.display(), e);
+                    }
+                }
+                out
+            },
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Round{
+    GeneratorFail(Failure),
+    /// how every reference went, in the order of `--reference`, some of them failed
+    ReferenceFails { input: String, refs: Vec<Execution> },
+    ProgramFail { input: String, fail: Failure, diagnostics: Vec<Diagnostic> },
+    /// the execution of the programme and those of the references, in the order of `--reference`
+    Success { input: String, prog: ExecutionResult, refs: Vec<ExecutionResult> },
+    /// the round timeout was exceeded in the given stage, the input is known if the generator finished
+    Timeout { stage: Stage, input: Option<String> },
+}
+
+impl Round {
+    /// the input of the round, unless it wasn't generated
+    pub fn input(&self) -> Option<&str> {
+        match self {
+            Round::GeneratorFail(_) => None,
+            Round::ReferenceFails { input, .. } | Round::ProgramFail { input, .. } | Round::Success { input, .. } => Some(input),
+            Round::Timeout { input, .. } => input.as_deref(),
+        }
+    }
+}
+
+/// signals which indicate that the programme crashed rather than exited
+#[cfg(unix)]
+pub fn is_crash_signal(signal: i32) -> bool {
+    matches!(signal, libc::SIGSEGV | libc::SIGABRT | libc::SIGFPE | libc::SIGBUS | libc::SIGILL)
+}
+
+#[cfg(not(unix))]
+pub fn is_crash_signal(_signal: i32) -> bool {
+    false
+}
+
+/// how much longer than the time limit a diagnostic re-run (gdb, sanitizers) is allowed to take
+const RERUN_TIME_FACTOR: f64 = 10.0;
+/// diagnostic re-run limit when no time limit was given
+const RERUN_DEFAULT_LIMIT: Duration = Duration::from_secs(60);
+
+fn rerun_time_limit(args: &Cli) -> Duration {
+    args.time_limit
+        .map(|t| Duration::from_secs_f64(t * RERUN_TIME_FACTOR))
+        .unwrap_or(RERUN_DEFAULT_LIMIT)
+}
+
+pub fn hash_str(s: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    s.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Re-runs a crashed programme on the same input under gdb and returns the stack trace.
+pub fn capture_backtrace(path: &Path, input: &str, args: &Cli) -> Result<String, Box<dyn Error>> {
+    let gdb = which::which("gdb")
+        .map_err(|_| static_err("gdb is not on PATH, skipping the backtrace"))?;
+    if !is_binary(path)? {
+        return Err(static_err("backtraces are only supported for compiled programmes"));
+    }
+    if !args.programs.backend_of(Role::Program).is_local() {
+        return Err(static_err("backtraces are only captured of programmes which run on this host"));
+    }
+
+    // every crash gets an input of its own, since sessions on other threads may crash at the same time
+    static BACKTRACES: AtomicUsize = AtomicUsize::new(0);
+    let input_file = env::temp_dir().join(format!("compdiff-backtrace-{}-{}.in", process::id(), BACKTRACES.fetch_add(1, Ordering::Relaxed)));
+    fs::write(&input_file, input)?;
+
+    let limit = rerun_time_limit(args);
+
+    let mut cmd = Command::new(gdb);
+    cmd.args(["--batch", "-q", "-nx"])
+        .arg("-ex").arg(format!("run < \"{}\"", input_file.display()))
+        .arg("-ex").arg("bt")
+        .arg("--args").arg(path);
+    output::log(format_args!("$ {:?}", cmd));
+    let out = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?
+        .controlled_with_output()
+        .time_limit(limit)
+        .terminate_for_timeout()
+        .wait();
+    let _ = fs::remove_file(&input_file);
+
+    let out = out?.ok_or_else(|| into_err(format!("gdb did not finish within {:.1}s, skipping the backtrace", limit.as_secs_f64())))?;
+    let out = String::from_utf8_lossy(&out.stdout);
+    // drop the programme's own output which gdb echoes before the crash
+    let trace = match out.find("Program received signal") {
+        Some(i) => &out[i..],
+        None => &out,
+    };
+    Ok(trace.trim_end().to_string())
+}
+
+pub const SANITIZER_FLAGS: &str = "-fsanitize=address,undefined -g";
+
+/// Re-executes crashing inputs against a sanitizer build of the programme.
+pub struct CrashRerun {
+    spec: String,
+    source: PathBuf,
+    // None until the first crash, then the outcome of resolving/compiling the sanitizer build
+    binary: Option<Result<PathBuf, String>>,
+    reports: HashMap<u64, Diagnostic>,
+}
+
+impl CrashRerun {
+    /// `spec` is either a path to a sanitizer binary or `auto`, `source` is the programme's source
+    pub fn new(spec: &str, source: &Path) -> Self {
+        CrashRerun { spec: spec.to_string(), source: source.to_path_buf(), binary: None, reports: HashMap::new() }
+    }
+
+    fn resolve(&self, args: &Cli) -> Result<PathBuf, Box<dyn Error>> {
+        if self.spec != "auto" {
+            return build(&self.spec, &args.programs).map(|prepared| prepared.binary);
+        }
+        let ext = self.source.extension().and_then(OsStr::to_str);
+        if !matches!(ext, Some("cpp") | Some("cxx")) || is_binary(&self.source)? {
+            return Err(static_err("--crash-rerun auto requires a C++ source programme"));
+        }
+        let opts = PreprocessOptions { extra_flags: SANITIZER_FLAGS.to_string(), ..args.programs.preprocess_options(Role::Program, &self.source) };
+        preprocess(&self.source, &opts).map(|prepared| prepared.binary)
+    }
+
+    pub fn report(&mut self, input: &str, args: &Cli) -> Diagnostic {
+        let key = hash_str(input);
+        if let Some(report) = self.reports.get(&key) {
+            return report.clone();
+        }
+
+        if self.binary.is_none() {
+            self.binary = Some(self.resolve(args).map_err(|e| e.to_string()));
+        }
+        let report = match self.binary.as_ref().unwrap() {
+            Err(e) => Diagnostic::new("sanitizer re-run unavailable", e.clone()),
+            Ok(binary) => {
+                let limit = rerun_time_limit(args);
+                // the sanitizer build is only on this machine, it isn't copied to the host of --remote
+                let local = Programs { program_backend: None, ..args.programs.clone() };
+                let out = start_prog(&Invocation::new(binary, Role::Program).with_input(input), &local)
+                    .and_then(|(child, _)| Ok(child
+                        .controlled_with_output()
+                        .time_limit(limit)
+                        .terminate_for_timeout()
+                        .wait()?));
+                match out {
+                    Err(e) => Diagnostic::new("sanitizer re-run unavailable", e.to_string()),
+                    Ok(None) => Diagnostic::new("sanitizer re-run unavailable", 
+                        format!("the sanitizer build did not finish within {:.1}s", limit.as_secs_f64())),
+                    Ok(Some(out)) if out.stderr.is_empty() && out.status.success() => Diagnostic::new("sanitizer report", 
+                        format!("the sanitizer build ({}) did not reproduce the crash", binary.display())),
+                    Ok(Some(out)) => Diagnostic::new("sanitizer report", 
+                        String::from_utf8_lossy(&out.stderr).trim_end()),
+                }
+            }
+        };
+        self.reports.insert(key, report.clone());
+        report
+    }
+}
+
+/// Runs a round with the backend: the generator, then the programme and then the references on its input.
+pub fn run_round<B: ExecutionBackend + ?Sized>(ctx: &RoundCtx, backend: &B) -> Round {
+    trace!("generating the input");
+    let inp = match generate_input(ctx, backend).and_then(|generated| generated_input(generated, ctx)) {
+        Ok(inp) => inp,
+        Err(Failure::RoundTimeout(_)) => return Round::Timeout { stage: Stage::Generator, input: None },
+        Err(x) => return Round::GeneratorFail(x),
+    };
+    trace!("finished generating the input");
+    run_round_with_input(ctx, inp, backend)
+}
+
+/// Like `run_round`, on an input which wasn't generated by the round: one replayed, or made by the caller.
+pub fn run_round_with_input<B: ExecutionBackend + ?Sized>(ctx: &RoundCtx, inp: String, backend: &B) -> Round {
+    let args = ctx.args;
+    let timed = time_reference(&inp, ctx, backend);
+    if let Some((_, Err(Failure::RoundTimeout(_)))) = timed {
+        return Round::Timeout { stage: Stage::References, input: Some(inp) };
+    }
+    let program = Invocation { limits: program_limits(args, timed.as_ref()), ..Invocation::new(&args.programs.program, Role::Program).with_input(&inp) };
+    let prg = execute(backend, &program, ctx);
+    trace!("finished executing the program");
+    let prq = match prg {
+        Ok(prq) => prq,
+        Err(x) => return program_failure(inp, x, args, timed.as_ref(), true),
     };
-    if args.verbose { println!("finished executing program"); }
-    if let Err(x) = prg { return Round::ProgramFail(inp.1, x); }
-    let prq = unsafe{ prg.unwrap_unchecked() };
 
-    let refs = args.reference.iter()
-        .map(PathBuf::as_path);
-    let refs = execute_progs_input(refs, inp.1.as_str());
-    if args.verbose { println!("finished executing references"); }
+    let refs = execute_refs(&inp, ctx, backend, timed);
+    trace!("finished executing the references");
+    judge_refs(inp, prq, refs)
+}
+
+/// The round in which the programme failed, with a backtrace when it crashed and one was asked for, and
+/// where its time limit came from when it was relative to a reference. gdb is only run when the thread
+/// may block on it, which that of an async round may not.
+fn program_failure(inp: String, x: Failure, args: &Cli, timed: Option<&TimedReference>, may_block: bool) -> Round {
+    if let Failure::RoundTimeout(_) = x { return Round::Timeout { stage: Stage::Program, input: Some(inp) }; }
+    let mut diagnostics = vec![];
+    if let Failure::TimeLimit(_) = x {
+        diagnostics.extend(relative_limit(args, timed));
+    }
+    if let Failure::Prog(prog) = &x {
+        if args.backtrace_on_crash && prog.signal.is_some_and(is_crash_signal) {
+            diagnostics.push(if may_block {
+                info!("capturing a backtrace of {}", prog.path.display());
+                match capture_backtrace(&prog.path, inp.as_str(), args) {
+                    Ok(trace) => Diagnostic::new("backtrace", trace),
+                    Err(e) => Diagnostic::new("backtrace unavailable", e.to_string()),
+                }
+            } else {
+                Diagnostic::new("backtrace unavailable", "gdb isn't run by async rounds, replay the input to get a backtrace")
+            });
+        }
+    }
+    Round::ProgramFail { input: inp, fail: x, diagnostics }
+}
+
+/// the round in which the programme ran, once the references ran as well
+fn judge_refs(inp: String, prq: ExecutionResult, refs: Vec<Execution>) -> Round {
+    if refs.iter().any(|x| matches!(x, Err(Failure::RoundTimeout(_)))) {
+        return Round::Timeout { stage: Stage::References, input: Some(inp) };
+    }
 
     if refs.iter().any(|x| x.is_err()) { 
-        let r = refs.into_iter().filter_map(|x| x.err()).collect();
-        Round::ReferenceFails(inp.1, r)
+        Round::ReferenceFails { input: inp, refs }
     } else { 
-        let r = refs.into_iter().map(|x| unsafe{ x.unwrap_unchecked() }).collect();
-        Round::Success(inp.1, prq, r)
+        let refs = refs.into_iter().map(|x| unsafe{ x.unwrap_unchecked() }).collect();
+        Round::Success { input: inp, prog: prq, refs }
     }
 }
 
-pub enum Mismatch<'a>{
+/// the references which failed, by their position among the references: "reference 2 of 3 (refs/slow.py)
+/// failed, the others succeeded"
+pub fn failed_references(args: &Cli, refs: &[Execution]) -> String {
+    let failed: Vec<_> = refs.iter().enumerate()
+        .filter_map(|(i, r)| r.as_ref().err().map(|f| format!("reference {} of {} ({})", i + 1, refs.len(), args.source_of(f.path()).display())))
+        .collect();
+    let others = match refs.len() - failed.len() {
+        0 => "",
+        1 => ", the other succeeded",
+        _ => ", the others succeeded",
+    };
+    format!("{} failed{}", failed.join(", "), others)
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Mismatch{
     AllMatch,
-    RefMismatch(Vec<Success<'a>>),
-    ProgMismatch(Success<'a>, Vec<Success<'a>>),
+    RefMismatch(Vec<ExecutionResult>),
+    ProgMismatch { prog: ExecutionResult, refs: Vec<ExecutionResult> },
 }
 
-pub fn test_mismatch<'a>(prog: Success<'a>, refs: Vec<Success<'a>>) -> Mismatch<'a> {
-    if refs.iter().all(|x| x.1 == prog.1) { return Mismatch::AllMatch; }
+/// The outcome of a round in short, as used in reports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Verdict {
+    #[serde(rename = "OK")]
+    Ok,
+    #[serde(rename = "WA")]
+    WrongAnswer,
+    #[serde(rename = "RE")]
+    RuntimeError,
+    #[serde(rename = "TLE")]
+    TimeLimit,
+    #[serde(rename = "ROUND_TIMEOUT")]
+    RoundTimeout,
+    #[serde(rename = "SPAWN_FAIL")]
+    SpawnFail,
+    #[serde(rename = "GEN_FAIL")]
+    GeneratorFail,
+    #[serde(rename = "REF_FAIL")]
+    ReferenceFail,
+    #[serde(rename = "REF_MISMATCH")]
+    ReferenceMismatch,
+}
 
-    // just a random string which will should never be the output
-    // indeed it is impossible that the \0 is at the beginning
-    let bad = String::from("\0\0\0\t@"); 
-    let bad = refs
-        .iter()
-        .map(|x| &x.1)
-        .filter(|x| x != &&prog.1)
-        .reduce(|a, i| if a == i { a } else { &bad } )
-        .unwrap()
-        == &bad;
-        
-    if bad { Mismatch::RefMismatch(refs) }
-    else { 
-        let refs = refs.into_iter().filter(|x| x.1 != prog.1).collect();
-        Mismatch::ProgMismatch(prog, refs) 
+impl Verdict {
+    pub fn of_failure(fail: &Failure) -> Self {
+        match fail {
+            Failure::Prog(..) => Verdict::RuntimeError,
+            Failure::TimeLimit(_) => Verdict::TimeLimit,
+            Failure::RoundTimeout(_) => Verdict::RoundTimeout,
+            Failure::Spawn(_) => Verdict::SpawnFail,
+        }
+    }
+
+    /// the verdict of a round, a successful one is only judged once its outputs are compared
+    pub fn of_round(round: &Round) -> Self {
+        match round {
+            Round::GeneratorFail(_) => Verdict::GeneratorFail,
+            Round::ReferenceFails { .. } => Verdict::ReferenceFail,
+            Round::ProgramFail { fail, .. } => Verdict::of_failure(fail),
+            Round::Timeout { .. } => Verdict::RoundTimeout,
+            Round::Success { .. } => Verdict::Ok,
+        }
+    }
+
+    pub fn of_mismatch(mismatch: &Mismatch) -> Self {
+        match mismatch {
+            Mismatch::AllMatch => Verdict::Ok,
+            Mismatch::ProgMismatch { .. } => Verdict::WrongAnswer,
+            Mismatch::RefMismatch(_) => Verdict::ReferenceMismatch,
+        }
+    }
+
+    /// the short code used in reports, e.g. WA
+    pub fn code(&self) -> &'static str {
+        match self {
+            Verdict::Ok => "OK",
+            Verdict::WrongAnswer => "WA",
+            Verdict::RuntimeError => "RE",
+            Verdict::TimeLimit => "TLE",
+            Verdict::RoundTimeout => "ROUND_TIMEOUT",
+            Verdict::SpawnFail => "SPAWN_FAIL",
+            Verdict::GeneratorFail => "GEN_FAIL",
+            Verdict::ReferenceFail => "REF_FAIL",
+            Verdict::ReferenceMismatch => "REF_MISMATCH",
+        }
+    }
+
+    /// human readable description
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Verdict::Ok => "ok",
+            Verdict::WrongAnswer => "wrong answer",
+            Verdict::RuntimeError => "runtime error",
+            Verdict::TimeLimit => "time limit exceeded",
+            Verdict::RoundTimeout => "round timeout",
+            Verdict::SpawnFail => "couldn't start",
+            Verdict::GeneratorFail => "generator failure",
+            Verdict::ReferenceFail => "reference failure",
+            Verdict::ReferenceMismatch => "reference mismatch",
+        }
+    }
+}
+
+/// A coarse classification of a failing round, rounds sharing it likely fail because of the same bug.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct Signature {
+    pub verdict: Verdict,
+    /// the first line in which the programme output differs from a reference, for wrong answers
+    pub line: Option<usize>,
+    /// the signal which killed the programme
+    pub signal: Option<i32>,
+}
+
+impl Signature {
+    /// like `Verdict::of_round`, a successful round is only classified once it's `judged`
+    pub fn of_round(round: &Round) -> Self {
+        let (line, signal) = match round {
+            Round::ProgramFail { fail: Failure::Prog(x), .. } => (None, x.signal),
+            Round::Success { prog, refs, .. } =>
+                (refs.iter().find_map(|r| differing_line(&r.stdout, &prog.stdout)), None),
+            _ => (None, None),
+        };
+        Signature { verdict: Verdict::of_round(round), line, signal }
+    }
+
+    /// the signature with the final verdict of the round
+    pub fn judged(self, verdict: Verdict) -> Self {
+        let line = self.line.filter(|_| verdict == Verdict::WrongAnswer);
+        Signature { verdict, line, ..self }
     }
 }
 
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.line, self.signal) {
+            (Some(line), _) => write!(f, "{} at line {}", self.verdict.code(), line),
+            (_, Some(signal)) => f.write_str(&signal_name(signal)),
+            _ => f.write_str(self.verdict.code()),
+        }
+    }
+}
+
+/// e.g. SIGSEGV
+#[cfg(unix)]
+pub fn signal_name(signal: i32) -> String {
+    match signal {
+        libc::SIGILL => "SIGILL".to_string(),
+        libc::SIGABRT => "SIGABRT".to_string(),
+        libc::SIGBUS => "SIGBUS".to_string(),
+        libc::SIGFPE => "SIGFPE".to_string(),
+        libc::SIGKILL => "SIGKILL".to_string(),
+        libc::SIGSEGV => "SIGSEGV".to_string(),
+        libc::SIGPIPE => "SIGPIPE".to_string(),
+        libc::SIGTERM => "SIGTERM".to_string(),
+        n => format!("signal {}", n),
+    }
+}
+
+#[cfg(not(unix))]
+pub fn signal_name(signal: i32) -> String {
+    format!("signal {}", signal)
+}
+
+/// the 1-based number of the first line in which the texts differ, with both versions of it
+pub fn first_difference<'a>(expected: &'a str, got: &'a str) -> Option<(usize, &'a str, &'a str)> {
+    let (mut expected, mut got) = (expected.lines(), got.lines());
+    for line in 1.. {
+        match (expected.next(), got.next()) {
+            (None, None) => return None,
+            (e, g) if e != g => return Some((line, e.unwrap_or("<end of output>"), g.unwrap_or("<end of output>"))),
+            _ => {},
+        }
+    }
+    unreachable!()
+}
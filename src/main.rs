@@ -1,9 +1,9 @@
 
-use clap::Parser;
-use compdiff::{cli::Cli, cli::handle_cli};
+use compdiff::{cli::handle_app, config::parse_args, output::init_logger};
 
 fn main() {
-    let args = Cli::parse();
+    let app = parse_args();
+    init_logger(app.verbosity());
 
-    handle_cli(args);
+    std::process::exit(handle_app(app));
 }
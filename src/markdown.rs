@@ -0,0 +1,102 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    };
+use similar::TextDiff;
+
+use crate::environment::Environment;
+use crate::options::Cli;
+use crate::report::{RoundRecord, Summary};
+use crate::{generator_invocation, Verdict};
+
+/// A Markdown report of the failing rounds. Every failing round is appended as soon as it's known,
+/// so an interrupted session still leaves the rounds so far; the summary table is put on top at the end.
+pub struct MarkdownReport {
+    path: PathBuf,
+    sections: String,
+}
+
+/// a code block whose fence is longer than any run of backticks in the text
+fn code_block(lang: &str, text: &str) -> String {
+    let longest = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest.max(2) + 1);
+    let newline = if text.ends_with('\n') || text.is_empty() { "" } else { "\n" };
+    format!("{}{}\n{}{}{}\n\n", fence, lang, text, newline, fence)
+}
+
+fn truncated_note(truncated: bool) -> &'static str {
+    if truncated { " (truncated)" } else { "" }
+}
+
+impl MarkdownReport {
+    pub fn new(path: &Path) -> io::Result<Self> {
+        fs::write(path, "# compdiff report\n\n_the session is still running_\n\n")?;
+        Ok(MarkdownReport { path: path.to_path_buf(), sections: String::new() })
+    }
+
+    /// appends the section of a failing round to the file
+    pub fn add(&mut self, args: &Cli, record: &RoundRecord) -> io::Result<()> {
+        let mut md = format!("## Round {}: {} ({})\n\n", record.round, record.verdict.code(), record.verdict.describe());
+        if let Some(file) = &args.input_file {
+            md += &format!("The input was read from `{}`.\n\n", file.display());
+        } else {
+            md += &format!("Seed `{}`, reproduce the input with:\n\n{}", record.seed, code_block("sh", &generator_invocation(args, record.round, record.seed)));
+        }
+        if let Some(path) = &record.input_path {
+            md += &format!("The input is saved in `{}`.\n\n", path.display());
+        }
+        if let Some(input) = &record.input {
+            md += &format!("Input{}:\n\n{}", truncated_note(input.truncated), code_block("text", &input.text));
+        }
+        if let (Some(input), Some(minimized)) = (&record.input, &record.minimized_input) {
+            md += &format!("Minimized input, {} bytes instead of {}{}:\n\n{}", minimized.bytes, input.bytes, truncated_note(minimized.truncated), code_block("text", &minimized.text));
+        }
+
+        for fail in &record.failures {
+            md += &format!("`{}` failed: {}", fail.path.display(), fail.verdict.describe());
+            if let Some(status) = &fail.status {
+                md += &format!(", {}", status);
+            }
+            md += ".\n\n";
+            if let Some(stderr) = fail.stderr.as_ref().filter(|e| !e.text.is_empty()) {
+                md += &format!("Error output{}:\n\n{}", truncated_note(stderr.truncated), code_block("text", &stderr.text));
+            }
+        }
+
+        match (&record.program, record.verdict) {
+            (Some(prog), Verdict::WrongAnswer) => for r in record.references.iter().filter(|r| r.output.text != prog.output.text) {
+                let diff = TextDiff::from_lines(r.output.text.as_str(), prog.output.text.as_str())
+                    .unified_diff()
+                    .header(&r.path.display().to_string(), &prog.path.display().to_string())
+                    .to_string();
+                md += &format!("Diff of the program (`{}`) output against the reference (`{}`){}:\n\n{}",
+                    prog.path.display(), r.path.display(), truncated_note(prog.output.truncated || r.output.truncated), code_block("diff", &diff));
+            },
+            _ => for out in record.program.iter().chain(record.references.iter()) {
+                md += &format!("Output of `{}`{}:\n\n{}", out.path.display(), truncated_note(out.output.truncated), code_block("text", &out.output.text));
+            },
+        }
+
+        let mut file = fs::OpenOptions::new().append(true).open(&self.path)?;
+        file.write_all(md.as_bytes())?;
+        self.sections += &md;
+        Ok(())
+    }
+
+    /// rewrites the file with the summary table and the environment of the session on top
+    pub fn finish(self, summary: &Summary, environment: Option<&Environment>) -> io::Result<()> {
+        let mut md = String::from("# compdiff report\n\n| verdict | rounds |\n|---|---|\n");
+        for (verdict, count) in &summary.verdicts {
+            md += &format!("| {} | {} |\n", verdict.describe(), count);
+        }
+        md += &format!("| **total** | **{}** |\n\n{} passed, {} failed.\n\n", summary.rounds, summary.passed, summary.failed);
+        if let Some(environment) = environment {
+            md += "## Environment\n\n";
+            md += &environment.lines().iter().map(|line| format!("- {}\n", line)).collect::<String>();
+            md += &format!("\n<details><summary>configuration</summary>\n\n{}</details>\n\n", code_block("text", &environment.config));
+        }
+        md += &self.sections;
+        fs::write(&self.path, md)
+    }
+}
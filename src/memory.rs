@@ -0,0 +1,42 @@
+use std::path::{Path, PathBuf};
+
+use crate::bench::{Sample, Stats};
+use crate::perf::{ComparedRound, WorstRounds};
+use crate::session::RoundMeta;
+use crate::Verdict;
+
+/// a round in which the peak memory of the programme, in kilobytes, was measured against that of the leanest reference
+pub type MemoryRound = ComparedRound<u64>;
+
+/// The peak memory of the programme against that of the references over the session of `--mem-compare`,
+/// and of every one of them on its own.
+pub struct MemoryComparison {
+    /// the programme and then the references, with the memory they used in every round they were measured in
+    binaries: Vec<(PathBuf, Vec<Sample>)>,
+    pub rounds: WorstRounds<u64>,
+}
+
+impl MemoryComparison {
+    pub fn new(factor: f64, top: usize, program: &Path, references: &[PathBuf]) -> Self {
+        let binaries = std::iter::once(program).chain(references.iter().map(PathBuf::as_path)).map(|path| (path.to_path_buf(), vec![])).collect();
+        MemoryComparison { binaries, rounds: WorstRounds::new(factor, top) }
+    }
+
+    /// the programme against the leanest reference of the round
+    pub fn measured(round: u64, seed: u64, verdict: Verdict, meta: &RoundMeta, input: &str) -> Option<MemoryRound> {
+        ComparedRound::of(round, seed, verdict, meta.program_memory_kb, &meta.references_memory_kb, input)
+    }
+
+    /// keeps what every binary used in the round
+    pub fn record(&mut self, seed: u64, meta: &RoundMeta) {
+        let used = std::iter::once(&meta.program_memory_kb).chain(&meta.references_memory_kb);
+        for ((_, samples), kb) in self.binaries.iter_mut().zip(used) {
+            samples.extend(kb.map(|kb| Sample { seed, value: kb as f64 }));
+        }
+    }
+
+    /// the median and the max of what every binary used, in kilobytes, none for one never measured
+    pub fn binaries(&self) -> impl Iterator<Item = (&Path, Option<Stats>)> {
+        self.binaries.iter().map(|(path, samples)| (path.as_path(), Stats::of(samples)))
+    }
+}
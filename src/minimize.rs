@@ -0,0 +1,144 @@
+use crate::compare::compare_outputs;
+use crate::options::Cli;
+use crate::runner::{Invocation, Processes};
+use crate::{execute, run_round_with_input, Role, Round, RoundCtx, Signature, Verdict};
+
+/// how many times the programmes may be run on candidates before the smallest input so far is kept
+pub const MAX_RUNS: usize = 500;
+
+/// The smallest input found which fails the same way as the input of a failing round.
+#[derive(Clone, Debug)]
+pub struct Minimized {
+    pub input: String,
+    /// how many candidates were run
+    pub runs: usize,
+}
+
+/// Shrinks the input of the failing round: first it removes lines, fewer and fewer at a time, then it
+/// shrinks every number towards 0 and removes the words of the lines which have several. A candidate is
+/// kept when the `--minimize-validator` accepts it and the round fails on it with the same signature, but
+/// for the line where the outputs differ, which moves as the input shrinks.
+pub fn minimize(args: &Cli, round: u64, input: &str, signature: Signature) -> Minimized {
+    let mut minimizer = Minimizer { args, round, signature: Signature { line: None, ..signature }, runs: 0 };
+    let input = minimizer.without_lines(input);
+    let input = minimizer.smaller_words(&input);
+    Minimized { input, runs: minimizer.runs }
+}
+
+/// The signature of the round run on the candidate, judged as a round of the session is, or none when the
+/// `--minimize-validator` rejects the candidate.
+pub(crate) fn candidate_signature(args: &Cli, round: u64, candidate: &str) -> Option<Signature> {
+    let ctx = RoundCtx::new(args, round);
+    if let Some(validator) = &args.minimize_validator {
+        if execute(&Processes, &Invocation::new(validator, Role::Generator).with_input(candidate), &ctx).is_err() {
+            return None;
+        }
+    }
+    let outcome = run_round_with_input(&ctx, candidate.to_string(), &Processes);
+    let verdict = match &outcome {
+        Round::Success { prog, refs, .. } if !refs.is_empty() => Verdict::of_mismatch(&compare_outputs(prog, refs, &*args.comparator())),
+        outcome => Verdict::of_round(outcome),
+    };
+    Some(Signature::of_round(&outcome).judged(verdict))
+}
+
+struct Minimizer<'a> {
+    args: &'a Cli,
+    round: u64,
+    signature: Signature,
+    runs: usize,
+}
+
+impl Minimizer<'_> {
+    /// whether the validator accepts the candidate and the round fails on it as it did
+    fn fails(&mut self, candidate: &str) -> bool {
+        if self.runs >= MAX_RUNS {
+            return false;
+        }
+        self.runs += 1;
+        candidate_signature(self.args, self.round, candidate).is_some_and(|signature| Signature { line: None, ..signature } == self.signature)
+    }
+
+    fn without_lines(&mut self, input: &str) -> String {
+        let mut lines: Vec<&str> = input.split_inclusive('\n').collect();
+        let mut chunk = lines.len() / 2;
+        while chunk > 0 {
+            let mut i = 0;
+            while i < lines.len() {
+                let end = (i + chunk).min(lines.len());
+                let candidate: String = lines[..i].iter().chain(&lines[end..]).copied().collect();
+                if self.fails(&candidate) {
+                    lines.drain(i..end);
+                } else {
+                    i = end;
+                }
+            }
+            chunk /= 2;
+        }
+        lines.concat()
+    }
+
+    fn smaller_words(&mut self, input: &str) -> String {
+        let mut pieces = pieces(input);
+        let mut i = 0;
+        while i < pieces.len() {
+            if pieces[i].starts_with(char::is_whitespace) {
+                i += 1;
+                continue;
+            }
+            if let Ok(n) = pieces[i].parse::<i64>() {
+                self.smaller_number(&mut pieces, i, n);
+            }
+            // a word is only removed along with the whitespace after or before it on its own line
+            let removed = match (pieces.get(i + 1), i.checked_sub(1).map(|before| &pieces[before])) {
+                (Some(after), _) if !after.contains('\n') => Some(i..i + 2),
+                (_, Some(before)) if !before.contains('\n') => Some(i - 1..i + 1),
+                _ => None,
+            };
+            match removed {
+                Some(range) if self.fails(&joined_without(&pieces, range.clone())) => {
+                    i = range.start;
+                    pieces.drain(range);
+                },
+                _ => i += 1,
+            }
+        }
+        pieces.concat()
+    }
+
+    /// 0 or 1 at once when the round fails the same way with them, or else the number halved for as long as it does
+    fn smaller_number(&mut self, pieces: &mut [String], i: usize, mut n: i64) {
+        if [0, 1].into_iter().any(|small| small < n.abs() && self.fails_with(pieces, i, &small.to_string())) {
+            return;
+        }
+        while n.abs() > 1 && self.fails_with(pieces, i, &(n / 2).to_string()) {
+            n /= 2;
+        }
+    }
+
+    /// whether the round fails the same way with the word replaced, which it's left replaced with when it does
+    fn fails_with(&mut self, pieces: &mut [String], i: usize, word: &str) -> bool {
+        let original = std::mem::replace(&mut pieces[i], word.to_string());
+        let fails = self.fails(&pieces.concat());
+        if !fails {
+            pieces[i] = original;
+        }
+        fails
+    }
+}
+
+/// the words of the text and the whitespace between them, one after the other
+fn pieces(text: &str) -> Vec<String> {
+    let mut pieces: Vec<String> = vec![];
+    for c in text.chars() {
+        match pieces.last_mut() {
+            Some(last) if last.starts_with(char::is_whitespace) == c.is_whitespace() => last.push(c),
+            _ => pieces.push(c.to_string()),
+        }
+    }
+    pieces
+}
+
+fn joined_without(pieces: &[String], range: std::ops::Range<usize>) -> String {
+    pieces[..range.start].iter().chain(&pieces[range.end..]).map(String::as_str).collect()
+}
@@ -0,0 +1,182 @@
+use std::{
+    future::Future,
+    io,
+    num::NonZeroUsize,
+    process::Stdio,
+    time::Instant,
+    };
+use futures::stream::{self, StreamExt};
+use process_control::Output;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+
+use crate::backend::ulimited;
+use crate::capture::{captured_text, Captured, Spilling, CHUNK_BYTES};
+use crate::options::SeedVia;
+use crate::output;
+use crate::runner::Invocation;
+use crate::{cached_refs, finish_execution, gen_args, generated_input, given_input, judge_refs, merge_refs, missing_refs, program_failure, program_limits, prog_command, remembered_refs, run_failure, spec_input, spill_threshold, time_bound, with_timed, TimedReference};
+use crate::{Cause, Execution, Failure, Role, Round, RoundCtx, Stage, CANCEL_POLL, RETRY_BACKOFF, SEED_ENV};
+
+// The execution layer of `run_round` on tokio, for programmes which run many rounds at once without a thread
+// for each of them. The futures hold the `RoundCtx`, so they run on a single task (`join` them, or use a
+// `LocalSet`), and the runtime needs its timers for the time limits.
+
+/// like `crate::retry`, waiting without blocking the thread
+async fn retry<F: Future<Output = Execution>>(mut out: Execution, ctx: &RoundCtx<'_>, mut again: impl FnMut() -> F) -> Execution {
+    for attempt in 1..=ctx.args.retries {
+        if !ctx.retrying(&out, attempt) { break; }
+        tokio::time::sleep(RETRY_BACKOFF * attempt).await;
+        out = again().await;
+    }
+    out
+}
+
+/// like `crate::capture::capture`, reading the pipe without blocking the thread
+async fn capture<R: AsyncRead + Unpin>(mut pipe: R, threshold: usize) -> Captured {
+    let mut head = vec![];
+    let _ = (&mut pipe).take(threshold as u64 + 1).read_to_end(&mut head).await;
+    if head.len() <= threshold {
+        return captured_text(&head);
+    }
+    // the temporary file is written to as the output comes, which only blocks for as long as a local disk takes
+    let mut spilling = Spilling::start(head);
+    let mut chunk = vec![0; CHUNK_BYTES];
+    while let Ok(n @ 1..) = pipe.read(&mut chunk).await {
+        spilling.append(&chunk[..n]);
+    }
+    spilling.finish()
+}
+
+/// like `Processes::execute`, killing the programme once it exceeds the time limit or the round runs out of time
+async fn execute_once(invocation: &Invocation<'_>, ctx: &RoundCtx<'_>) -> Execution {
+    let (path, role, input, limits) = (invocation.path, invocation.role, invocation.input, invocation.limits);
+    let started = Instant::now();
+    let (tlimit, round_bound) = time_bound(path, limits.time, ctx);
+    let mut cmd = prog_command(invocation, &ctx.args.programs).map_err(|e| run_failure(ctx, role, path, "run", Cause::of(&*e)))?;
+    // a container or a host is given its memory limit by the backend, here it's bounded before it starts
+    if let (Some(bytes), true) = (limits.memory, ctx.args.programs.backend_of(role).is_local()) {
+        cmd = ulimited(&cmd, bytes);
+    }
+    let command = format!("{:?}{}", cmd, if input.is_some() { " < input" } else { "" });
+    output::log(format_args!("$ {}", command));
+
+    let mut cmd = tokio::process::Command::from(cmd);
+    cmd.stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    let mut child = cmd.spawn().map_err(|e| run_failure(ctx, role, path, "run", Cause::of(&e)))?;
+    let (stdin, stdout) = (child.stdin.take(), child.stdout.take());
+    let threshold = spill_threshold(ctx.args);
+    let write = async move {
+        let (Some(mut stdin), Some(input)) = (stdin, input) else { return Ok(()) };
+        match stdin.write_all(input.as_bytes()).await {
+            // the programme exited without reading all of its input
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            x => x,
+        }
+    };
+    let read = async move {
+        match stdout {
+            Some(stdout) => capture(stdout, threshold).await,
+            None => Captured::default(),
+        }
+    };
+    // the input is written while the output is read, so that neither of the pipes fills up
+    let run = async move {
+        let (written, stdout, out) = tokio::join!(write, read, child.wait_with_output());
+        written.and(out).map(|out| (Output::from(out), stdout))
+    };
+    // a programme which runs out of time, or whose session is cancelled, is dropped with its future, and killed
+    let bounded = async move {
+        match tlimit {
+            Some(t) => tokio::time::timeout(t, run).await.ok(),
+            None => Some(run.await),
+        }
+    };
+    let cancelled = async {
+        while !ctx.cancelled() {
+            tokio::time::sleep(CANCEL_POLL).await;
+        }
+    };
+    let out = tokio::select! {
+        out = bounded => out,
+        () = cancelled => None,
+    };
+    let out = out.transpose().map_err(|e| run_failure(ctx, role, path, "run", Cause::of(&e)))?;
+    finish_execution(invocation, &command, started, out, None, round_bound, ctx)
+}
+
+/// like `crate::execute`
+pub async fn execute(invocation: &Invocation<'_>, ctx: &RoundCtx<'_>) -> Execution {
+    retry(execute_once(invocation, ctx).await, ctx, || execute_once(invocation, ctx)).await
+}
+
+pub async fn generate_input(ctx: &RoundCtx<'_>) -> Execution {
+    if let Some(input) = given_input(ctx) {
+        return input;
+    }
+    if let Some(spec) = &ctx.args.gen_spec {
+        return spec_input(spec, ctx);
+    }
+
+    let path = &ctx.args.generator_of(ctx.round, ctx.seed).expect("either a generator, a spec or an input file is required").path;
+    let seed = ctx.seed.to_string();
+    let line = format!("{}\n", seed);
+    let args = gen_args(ctx.args).map_err(|e| run_failure(ctx, Role::Generator, path, "pass the arguments to", Cause::new(e)))?;
+    let invocation = Invocation { args, ..Invocation::new(path, Role::Generator) };
+    let invocation = match ctx.args.gen_seed_via {
+        SeedVia::Argv => Invocation { args: [invocation.args, vec![seed]].concat(), ..invocation },
+        SeedVia::Stdin => invocation.with_input(&line),
+        SeedVia::Env => Invocation { env: vec![(SEED_ENV.to_string(), seed)], ..invocation },
+    };
+    execute(&invocation, ctx).await
+}
+
+/// runs the references which the session doesn't remember nor the --ref-cache has the output of, at most
+/// --ref-jobs of them at a time
+async fn execute_refs(input: &str, ctx: &RoundCtx<'_>, timed: Option<TimedReference>) -> Vec<Execution> {
+    if let Some(remembered) = remembered_refs(input, ctx) {
+        return remembered;
+    }
+    let cached = cached_refs(input, ctx, timed.as_ref());
+    let missing = missing_refs(input, &cached, timed.as_ref(), ctx);
+    let jobs = ctx.args.ref_jobs.map_or(missing.len(), NonZeroUsize::get).max(1);
+    let executed = stream::iter(missing.iter().map(|invocation| execute(invocation, ctx))).buffered(jobs).collect().await;
+    let executed = with_timed(executed, &cached, timed);
+    merge_refs(input, cached, executed, ctx)
+}
+
+/// like `crate::time_reference`
+async fn time_reference(input: &str, ctx: &RoundCtx<'_>) -> Option<TimedReference> {
+    let i = ctx.args.relative_reference()?;
+    Some((i, execute(&Invocation::new(&ctx.args.programs.reference[i], Role::Reference).with_input(input), ctx).await))
+}
+
+/// like `crate::run_round`
+pub async fn run_round(ctx: &RoundCtx<'_>) -> Round {
+    let inp = match generate_input(ctx).await.and_then(|generated| generated_input(generated, ctx)) {
+        Ok(inp) => inp,
+        Err(Failure::RoundTimeout(_)) => return Round::Timeout { stage: Stage::Generator, input: None },
+        Err(x) => return Round::GeneratorFail(x),
+    };
+    run_round_with_input(ctx, inp).await
+}
+
+/// like `crate::run_round_with_input`
+pub async fn run_round_with_input(ctx: &RoundCtx<'_>, inp: String) -> Round {
+    let args = ctx.args;
+    let timed = time_reference(&inp, ctx).await;
+    if let Some((_, Err(Failure::RoundTimeout(_)))) = timed {
+        return Round::Timeout { stage: Stage::References, input: Some(inp) };
+    }
+    let program = Invocation { limits: program_limits(args, timed.as_ref()), ..Invocation::new(&args.programs.program, Role::Program).with_input(&inp) };
+    let prg = execute(&program, ctx).await;
+    let prq = match prg {
+        Ok(prq) => prq,
+        Err(x) => return program_failure(inp, x, args, timed.as_ref(), false),
+    };
+
+    let refs = execute_refs(&inp, ctx, timed).await;
+    judge_refs(inp, prq, refs)
+}
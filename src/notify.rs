@@ -0,0 +1,112 @@
+use std::{
+    env,
+    fs,
+    io::Write,
+    process::{Command, Stdio},
+    thread::{self, JoinHandle},
+    time::Duration,
+    };
+use std::error::Error;
+#[cfg(feature = "cli")]
+use clap::ValueEnum;
+use serde::Serialize;
+use string_error::{into_err, static_err};
+
+use crate::output::{Mark, Style};
+use crate::report::{Blob, Summary};
+use crate::{say, Verdict};
+
+/// inputs longer than this (bytes) are truncated in the notifications
+const MAX_INPUT: usize = 1024;
+/// how long a notification may take before it's given up
+const TIMEOUT_SECS: u32 = 10;
+
+/// when `--notify-url` is sent a notification
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum NotifyOn {
+    /// the first failing round
+    FirstFailure,
+    /// every failing round
+    EveryFailure,
+    /// the end of the session, with the counts of the verdicts
+    Completion,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Payload<'a> {
+    Failure { verdict: Verdict, round: u64, seed: u64, input: Option<Blob>, host: &'a str, elapsed_seconds: f64 },
+    Completion { summary: &'a Summary, host: &'a str, elapsed_seconds: f64 },
+}
+
+fn host_name() -> String {
+    env::var("HOSTNAME").ok()
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// POSTs the JSON with curl, which takes care of https
+fn post(url: &str, body: &str) -> Result<(), Box<dyn Error>> {
+    let curl = which::which("curl").map_err(|_| static_err("couldn't find curl, which sends the notifications"))?;
+    let mut child = Command::new(curl)
+        .args(["--silent", "--show-error", "--fail", "--max-time", &TIMEOUT_SECS.to_string()])
+        .args(["--header", "Content-Type: application/json", "--data-binary", "@-", url])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    child.stdin.take().expect("piped stdin").write_all(body.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(into_err(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+    Ok(())
+}
+
+/// Sends the notifications of `--notify-url` in the background, a failure to send one is only reported.
+pub struct Notifier {
+    url: String,
+    on: Vec<NotifyOn>,
+    host: String,
+    style: Style,
+    failures: usize,
+    pending: Vec<JoinHandle<()>>,
+}
+
+impl Notifier {
+    pub fn new(url: &str, on: &[NotifyOn], style: Style) -> Self {
+        Notifier { url: url.to_string(), on: on.to_vec(), host: host_name(), style, failures: 0, pending: vec![] }
+    }
+
+    fn send(&mut self, payload: &Payload) {
+        let body = serde_json::to_string(payload).expect("notifications are serializable");
+        let (url, style) = (self.url.clone(), self.style);
+        self.pending.push(thread::spawn(move || if let Err(e) = post(&url, &body) {
+            say!("  {} warning : couldn't send the notification to {}: {}", style.mark(Mark::Warn), url, e);
+        }));
+    }
+
+    pub fn failure(&mut self, round: u64, seed: u64, verdict: Verdict, input: Option<&str>, elapsed: Duration) {
+        self.failures += 1;
+        let wanted = self.on.contains(&NotifyOn::EveryFailure) || (self.failures == 1 && self.on.contains(&NotifyOn::FirstFailure));
+        if wanted {
+            let host = self.host.clone();
+            let input = input.map(|i| Blob::new(i, MAX_INPUT));
+            self.send(&Payload::Failure { verdict, round, seed, input, host: &host, elapsed_seconds: elapsed.as_secs_f64() });
+        }
+    }
+
+    /// sends the completion notification when asked for, then waits until every notification is sent
+    pub fn finish(mut self, summary: &Summary, elapsed: Duration) {
+        if self.on.contains(&NotifyOn::Completion) {
+            let host = self.host.clone();
+            self.send(&Payload::Completion { summary, host: &host, elapsed_seconds: elapsed.as_secs_f64() });
+        }
+        for pending in self.pending {
+            let _ = pending.join();
+        }
+    }
+}
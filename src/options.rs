@@ -0,0 +1,1284 @@
+use std::{path::{Path, PathBuf}, num::NonZeroUsize, env, fs, fmt, error::Error, time::Duration, collections::{BTreeMap, HashMap}};
+#[cfg(feature = "cli")]
+use clap::{ArgGroup, Args, Parser, ValueEnum};
+#[cfg(feature = "cli")]
+use clap::builder::{OsStringValueParser, TypedValueParser};
+use string_error::into_err;
+
+use crate::backend::Backend;
+use crate::compare::{self, Comparator};
+use crate::environment::Environment;
+use crate::genspec::GenSpec;
+use crate::notify::NotifyOn;
+use crate::output::{ColorChoice, PagerChoice, Style};
+use crate::refcache::{RefCache, RefMemo};
+use crate::{builds_into, preprocess_commands, Prepared, PreprocessOptions, Role, Toolchain};
+
+// The options of a session, which the library runs with whether or not they come from the command line: with
+// the `cli` feature they're parsed by clap, without it `Cli::default()` has the defaults of the command line.
+
+/// what a mismatch report shows of the outputs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum Show {
+    /// the lines in which the program output differs from each reference
+    DiffOnly,
+    /// the program output and every reference output in full
+    Full,
+    /// the differing lines of every reference output and the program output next to each other
+    SideBySide,
+}
+
+/// the format of the console output
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum Format {
+    Human,
+    /// the human readable output and GitHub Actions annotations of the failures and the summary
+    Github,
+}
+
+/// how the generator receives the seed of the round
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum SeedVia {
+    /// as its last command line argument
+    Argv,
+    /// on the standard input
+    Stdin,
+    /// in the COMPDIFF_SEED environment variable
+    Env,
+}
+
+/// which of the generators writes the input of a round
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum GenPick {
+    /// one after the other, each for as many rounds in a row as its weight
+    RoundRobin,
+    /// at random from the seed of the round, each as likely as its weight
+    Weighted,
+}
+
+/// what the session does with a round whose generator failed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum OnGenFail {
+    /// skip the round: it's reported, but it neither stops the session nor counts towards --max-failures
+    Skip,
+    /// stop the session
+    Stop,
+}
+
+/// A `--generator`, written `gen.py` or `gen.py:3` to give it a weight of 3.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Generator {
+    pub path: PathBuf,
+    /// how many rounds it writes for every round written by a generator of weight 1
+    pub weight: u32,
+}
+
+impl Generator {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Generator { path: path.into(), weight: 1 }
+    }
+}
+
+impl std::str::FromStr for Generator {
+    type Err = Box<dyn Error + Send + Sync>;
+
+    /// the weight follows the last `:`, a path ending with something else than a number has none
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.rsplit_once(':') {
+            Some((path, weight)) if !weight.is_empty() && weight.bytes().all(|b| b.is_ascii_digit()) => match weight.parse() {
+                Ok(weight @ 1..) => Ok(Generator { path: path.into(), weight }),
+                _ => Err(format!("the weight of {} must be a positive number, not {}", path, weight).into()),
+            },
+            _ => Ok(Generator::new(s)),
+        }
+    }
+}
+
+/// A `--time-limit-rel`, written `2.5x:brute.cpp`: the programme may take 2.5 times as long as the reference
+/// brute.cpp took on the same input.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RelativeTimeLimit {
+    pub factor: f64,
+    pub reference: PathBuf,
+}
+
+impl RelativeTimeLimit {
+    /// the time limit of the programme when the reference took `reference`, never under the floor
+    pub fn limit(&self, reference: Duration, floor: Duration) -> Duration {
+        reference.mul_f64(self.factor).max(floor)
+    }
+}
+
+impl std::str::FromStr for RelativeTimeLimit {
+    type Err = Box<dyn Error + Send + Sync>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let malformed = || format!("`{}` isn't a relative time limit, expected a factor and a reference such as 2.5x:brute.cpp", s);
+        let (factor, reference) = s.split_once(':').ok_or_else(malformed)?;
+        match factor.strip_suffix('x').unwrap_or(factor).parse::<f64>() {
+            Ok(factor) if factor.is_finite() && factor > 0.0 && !reference.is_empty() => Ok(RelativeTimeLimit { factor, reference: reference.into() }),
+            _ => Err(malformed().into()),
+        }
+    }
+}
+
+/// how the output of the programme is compared with those of the references
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum Compare {
+    /// byte for byte
+    Exact,
+    /// ignoring the whitespace at the ends of the lines and the blank lines at the end
+    Trimmed,
+    /// word by word, however they're separated by whitespace
+    Tokens,
+    /// word by word, numbers within --float-tolerance of the reference's
+    Float,
+}
+
+/// a set of flags the c++ sources are built with, added to --cpp-compiler-flags
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum BuildProfile {
+    /// --debug-flags, for debuggability
+    Debug,
+    /// --release-flags, for realistic timing
+    Release,
+    /// --cpp-compiler-flags alone
+    Custom,
+}
+
+/// a sanitizer the c++ programme under test is built with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum Sanitizer {
+    Address,
+    Undefined,
+    Leak,
+    Thread,
+    Memory,
+}
+
+impl Sanitizer {
+    /// the name of -fsanitize=
+    pub fn name(self) -> &'static str {
+        match self {
+            Sanitizer::Address => "address",
+            Sanitizer::Undefined => "undefined",
+            Sanitizer::Leak => "leak",
+            Sanitizer::Thread => "thread",
+            Sanitizer::Memory => "memory",
+        }
+    }
+}
+
+/// the programmes under test and how they are built, shared by the subcommands
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "cli", derive(Args))]
+pub struct Programs {
+    /// the programme to be examined, c++ sources linked together can be given as `sol.cpp,geometry.cpp`
+    /// or as the directory holding them
+    // the default only stands in until the positional form of a session is resolved, see `Cli::take_positional`
+    #[cfg_attr(feature = "cli", arg(short, long, value_name = "FILE", default_value = "", hide_default_value = true, required = true,
+        value_parser = TypedValueParser::map(OsStringValueParser::new(), PathBuf::from)))]
+    pub program: PathBuf,
+
+    /// the sources linked into the programme when there are several, see `Programs::expand_program`
+    #[cfg_attr(feature = "cli", arg(skip))]
+    pub program_sources: Vec<PathBuf>,
+
+    /// the reference programme/programmes, patterns such as 'refs/*.cpp' are expanded
+    #[cfg_attr(feature = "cli", arg(short, long, alias = "ref", action = clap::ArgAction::Append))]
+    pub reference: Vec<PathBuf>,
+
+    /// leave out the references matching this pattern
+    #[cfg_attr(feature = "cli", arg(long, value_name = "PATTERN"))]
+    pub reference_exclude: Vec<String>,
+
+    /// the c++ compiler, a name looked up in PATH or a path, by default the CXX variable or else g++ or clang
+    #[cfg_attr(feature = "cli", arg(long, value_name = "NAME|PATH"))]
+    pub cpp_compiler: Option<PathBuf>,
+
+    /// take neither the c++ compiler from the CXX variable nor flags from CXXFLAGS
+    #[cfg_attr(feature = "cli", arg(long, default_value = "false"))]
+    pub ignore_env_toolchain: bool,
+
+    /// compile and run the programme, the references and the generator in containers of this docker image,
+    /// with the working directory mounted read-only
+    #[cfg_attr(feature = "cli", arg(long, value_name = "IMAGE"))]
+    pub docker: Option<String>,
+
+    /// run the programme under test on this host over ssh, as user@host, the references and the generator
+    /// still run here
+    #[cfg_attr(feature = "cli", arg(long, value_name = "USER@HOST", conflicts_with = "docker"))]
+    pub remote: Option<String>,
+
+    /// the directory of --remote the programme is copied into
+    #[cfg_attr(feature = "cli", arg(long, value_name = "DIR", default_value = "/tmp/compdiff", requires = "remote"))]
+    pub remote_dir: String,
+
+    /// where the sources are compiled and run, see `Backend::open`
+    #[cfg_attr(feature = "cli", arg(skip))]
+    pub backend: Backend,
+
+    /// where the programme runs when it's elsewhere than the rest, see `Backend::open_remote`
+    #[cfg_attr(feature = "cli", arg(skip))]
+    pub program_backend: Option<Backend>,
+
+    /// options for c++ compiler (-std=c++20 by default), deprecated: the same as `--flags cpp=...`, which wins
+    /// when both are given
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub cpp_compiler_flags: Option<String>,
+
+    /// the c++ standard, e.g. c++17, gnu++20 or 17, in place of the one of the default flags, a -std= of the
+    /// c++ flags given explicitly wins
+    #[cfg_attr(feature = "cli", arg(long, value_name = "STANDARD", value_parser = parse_standard))]
+    pub std: Option<String>,
+
+    /// the flags of the sources of a language named by its extension, e.g. `cpp=-O2 -std=c++20` passed to the
+    /// c++ compiler or `py=-O` passed to python; quotes keep spaces within a flag
+    #[cfg_attr(feature = "cli", arg(long, value_name = "EXT=FLAGS", value_parser = parse_language_flags))]
+    pub flags: Vec<LanguageFlags>,
+
+    /// compile the sources with this extension using the command, e.g. `cpp=ccache g++ {flags} {src} -o {out} -Ilib`,
+    /// where {src}, {out} and {flags} are replaced by the source, the binary and the flags of c++
+    #[cfg_attr(feature = "cli", arg(long, value_name = "EXT=COMMAND", value_parser = parse_compile_cmd))]
+    pub compile_cmd: Vec<CompileCmd>,
+
+    /// compile the sources afresh into a directory of the session, removed at its end, instead of reusing
+    /// the binaries cached from earlier sessions
+    #[cfg_attr(feature = "cli", arg(long, default_value = "false"))]
+    pub no_cache: bool,
+
+    /// compile into this directory, which is kept, instead of the cache
+    #[cfg_attr(feature = "cli", arg(long, value_name = "DIR"))]
+    pub build_dir: Option<PathBuf>,
+
+    /// the cache of the binaries shared by the sessions of every project, by default compdiff in the user's
+    /// cache directory
+    #[cfg_attr(feature = "cli", arg(long, value_name = "DIR"))]
+    pub cache_dir: Option<PathBuf>,
+
+    /// the size (MB) beyond which the least recently used binaries are removed from the cache
+    #[cfg_attr(feature = "cli", arg(long, value_name = "MB", default_value = "500"))]
+    pub max_cache_size: u64,
+
+    /// a directory of headers of the c++ sources, passed as -I
+    #[cfg_attr(feature = "cli", arg(long, value_name = "DIR"))]
+    pub include_dir: Vec<PathBuf>,
+
+    /// a grader source, as in IOI-style tasks, linked with the c++ programme and references which implement
+    /// the functions it calls, it reads the input
+    #[cfg_attr(feature = "cli", arg(long, value_name = "FILE"))]
+    pub grader: Option<PathBuf>,
+
+    /// a directory of the headers of the grader, passed as -I
+    #[cfg_attr(feature = "cli", arg(long, value_name = "DIR", requires = "grader"))]
+    pub grader_include: Vec<PathBuf>,
+
+    /// define the macro, as NAME or NAME=VALUE, when compiling the c++ programme under test
+    #[cfg_attr(feature = "cli", arg(long, value_name = "NAME[=VALUE]"))]
+    pub define: Vec<String>,
+
+    /// define the macro, as NAME or NAME=VALUE, when compiling the c++ references
+    #[cfg_attr(feature = "cli", arg(long, value_name = "NAME[=VALUE]"))]
+    pub define_refs: Vec<String>,
+
+    /// show what the compiler warns about the sources which compile
+    #[cfg_attr(feature = "cli", arg(long, default_value = "false"))]
+    pub show_compile_warnings: bool,
+
+    /// a source the compiler warns about fails to compile
+    #[cfg_attr(feature = "cli", arg(long, default_value = "false"))]
+    pub warnings_as_errors: bool,
+
+    /// how many sources are compiled at once, by default the number of CPUs
+    #[cfg_attr(feature = "cli", arg(short = 'j', long))]
+    pub jobs: Option<NonZeroUsize>,
+
+    /// the flags of the c++ sources, unless --program-profile or --ref-profile choose others
+    #[cfg_attr(feature = "cli", arg(long, value_name = "PROFILE", default_value = "custom"))]
+    pub build_profile: BuildProfile,
+
+    /// the flags of the programme under test, by default --build-profile
+    #[cfg_attr(feature = "cli", arg(long, value_name = "PROFILE"))]
+    pub program_profile: Option<BuildProfile>,
+
+    /// the flags of the references, by default --build-profile
+    #[cfg_attr(feature = "cli", arg(long, value_name = "PROFILE"))]
+    pub ref_profile: Option<BuildProfile>,
+
+    /// flags of the debug profile
+    #[cfg_attr(feature = "cli", arg(long, value_name = "FLAGS", default_value = "-O0 -g"))]
+    pub debug_flags: String,
+
+    /// flags of the release profile
+    #[cfg_attr(feature = "cli", arg(long, value_name = "FLAGS", default_value = "-O2"))]
+    pub release_flags: String,
+
+    /// build the c++ programme under test with these sanitizers, the references are built as usual;
+    /// the programme inherits ASAN_OPTIONS, UBSAN_OPTIONS, ... from the environment
+    #[cfg_attr(feature = "cli", arg(long, value_name = "SANITIZER", value_delimiter = ','))]
+    pub sanitize: Vec<Sanitizer>,
+}
+
+impl Programs {
+    /// the flags of every language, `--cpp-compiler-flags` being those of c++ unless `--flags cpp=...` is given
+    pub fn language_flags(&self) -> BTreeMap<String, String> {
+        let cpp = match (self.explicit_cpp_flags(), &self.std) {
+            (Some(flags), _) if self.std_overridden() => flags.to_string(),
+            (Some(flags), Some(std)) => format!("{} -std={}", flags, std).trim_start().to_string(),
+            (Some(flags), None) => flags.to_string(),
+            (None, std) => DEFAULT_CPP_FLAGS.split(' ')
+                .map(|flag| match std {
+                    Some(std) if flag.starts_with("-std=") => format!("-std={}", std),
+                    _ => flag.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(" "),
+        };
+        let mut flags = BTreeMap::from([("cpp".to_string(), cpp)]);
+        for language in self.flags.iter().filter(|l| l.language != "cpp") {
+            flags.insert(language.language.clone(), language.flags.clone());
+        }
+        flags
+    }
+
+    /// the c++ flags given by `--flags cpp=...`, or else by --cpp-compiler-flags
+    fn explicit_cpp_flags(&self) -> Option<&str> {
+        self.flags.iter().rev().find(|l| l.language == "cpp").map(|l| l.flags.as_str())
+            .or(self.cpp_compiler_flags.as_deref())
+    }
+
+    /// whether --std is given along with explicit c++ flags which have a -std= of their own, those win
+    pub fn std_overridden(&self) -> bool {
+        self.std.is_some() && self.explicit_cpp_flags()
+            .is_some_and(|flags| split_flags(flags).is_ok_and(|flags| flags.iter().any(|f| f.starts_with("-std="))))
+    }
+
+    /// the flags of the language, by its extension
+    pub fn flags_of(&self, language: &str) -> String {
+        self.language_flags().remove(language).unwrap_or_default()
+    }
+
+    /// Splits a programme given as several sources, the first of them naming the programme, or finds the
+    /// sources in the directory given as the programme.
+    pub fn expand_program(&mut self) -> Result<(), Box<dyn Error>> {
+        let program = self.program.to_string_lossy().into_owned();
+        if program.contains(',') {
+            self.program_sources = program.split(',').filter(|s| !s.is_empty()).map(PathBuf::from).collect();
+            self.program = self.program_sources.first().cloned()
+                .ok_or_else(|| into_err(format!("no source in --program {}", program)))?;
+        } else if self.program.is_dir() {
+            let mut sources = fs::read_dir(&self.program)?
+                .map(|entry| entry.map(|e| e.path()))
+                .collect::<Result<Vec<_>, _>>()?;
+            sources.retain(|s| s.extension().and_then(|e| e.to_str()).is_some_and(|e| COMPILED_EXTENSIONS.contains(&e)));
+            if sources.is_empty() {
+                return Err(into_err(format!("no c++ source in the programme's directory {}", program)));
+            }
+            sources.sort();
+            self.program_sources = sources;
+        }
+        Ok(())
+    }
+
+    /// the sources compiled into the binary of the source in the role, the grader last
+    pub fn linked_sources(&self, role: Role, source: &Path) -> Vec<PathBuf> {
+        let mut sources = if role == Role::Program && !self.program_sources.is_empty() {
+            self.program_sources.clone()
+        } else {
+            vec![source.to_path_buf()]
+        };
+        if let Some(grader) = self.grader.as_ref().filter(|_| self.is_graded(role, source)) {
+            sources.push(grader.clone());
+        }
+        sources
+    }
+
+    /// whether the source, or one of the sources linked into its binary, is compiled before it runs
+    pub fn is_compiled(&self, role: Role, source: &Path) -> bool {
+        self.linked_sources(role, source).iter()
+            .any(|s| s.extension().and_then(|e| e.to_str()).is_some_and(|e| COMPILED_EXTENSIONS.contains(&e)))
+    }
+
+    /// whether the source is linked with the --grader, as the c++ programme and references are
+    fn is_graded(&self, role: Role, source: &Path) -> bool {
+        let cpp = source.extension().and_then(|e| e.to_str()).is_some_and(|e| COMPILED_EXTENSIONS.contains(&e));
+        match role {
+            Role::Program => cpp || !self.program_sources.is_empty(),
+            Role::Reference => cpp,
+            Role::Generator => false,
+        }
+    }
+
+    /// the generators, the programme and the references with their roles, a source passed twice in a role only once
+    pub fn distinct_sources(&self, generators: &[Generator]) -> Vec<(Role, PathBuf)> {
+        let generators = generators.iter().map(|g| (Role::Generator, &g.path));
+        let references = self.reference.iter().map(|r| (Role::Reference, r));
+        let mut sources: Vec<(Role, PathBuf)> = vec![];
+        for (role, source) in generators.chain([(Role::Program, &self.program)]).chain(references) {
+            if !sources.iter().any(|(r, s)| *r == role && s == source) {
+                sources.push((role, source.clone()));
+            }
+        }
+        sources
+    }
+
+    /// the role of a source given on its own, the programme's when it's a reference as well
+    pub fn role_of(&self, source: &Path) -> Role {
+        if source == self.program {
+            Role::Program
+        } else if self.reference.iter().any(|r| r == source) {
+            Role::Reference
+        } else {
+            Role::Generator
+        }
+    }
+
+    /// the macros defined when compiling the sources of the role
+    pub fn defines_of(&self, role: Role) -> &[String] {
+        match role {
+            Role::Program => &self.define,
+            Role::Reference => &self.define_refs,
+            Role::Generator => &[],
+        }
+    }
+
+    /// the build profile of the sources of the role
+    pub fn profile_of(&self, role: Role) -> BuildProfile {
+        let profile = match role {
+            Role::Program => self.program_profile,
+            Role::Reference => self.ref_profile,
+            Role::Generator => None,
+        };
+        profile.unwrap_or(self.build_profile)
+    }
+
+    /// where the (built) programmes of the role run
+    pub fn backend_of(&self, role: Role) -> &Backend {
+        match &self.program_backend {
+            Some(backend) if role == Role::Program => backend,
+            _ => &self.backend,
+        }
+    }
+
+    /// the flags added to --cpp-compiler-flags when compiling the sources of the role: those of its profile,
+    /// the sanitizers and CXXFLAGS
+    pub fn extra_flags(&self, role: Role) -> String {
+        let mut flags = match self.profile_of(role) {
+            BuildProfile::Debug => vec![self.debug_flags.clone()],
+            BuildProfile::Release => vec![self.release_flags.clone()],
+            BuildProfile::Custom => vec![],
+        };
+        if !self.sanitize.is_empty() && role == Role::Program {
+            let sanitizers = self.sanitize.iter()
+                .map(|s| s.name())
+                .collect::<Vec<_>>();
+            flags.push(format!("-fsanitize={} -g -fno-omit-frame-pointer", sanitizers.join(",")));
+        }
+        flags.extend(self.env_cxx_flags());
+        flags.retain(|f| !f.trim().is_empty());
+        flags.join(" ")
+    }
+
+    /// the c++ compiler given by --cpp-compiler, or else by the CXX variable
+    pub fn chosen_cpp_compiler(&self) -> Option<PathBuf> {
+        self.cpp_compiler.clone().or_else(|| self.env_cxx())
+    }
+
+    /// how the source is preprocessed in the role
+    pub fn preprocess_options(&self, role: Role, source: &Path) -> PreprocessOptions {
+        let cache = self.build_dir.is_none() && !self.no_cache;
+        PreprocessOptions {
+            cpp_compiler: self.chosen_cpp_compiler(),
+            toolchain: None,
+            role: Some(role),
+            flags: self.language_flags(),
+            extra_flags: self.extra_flags(role),
+            defines: self.defines_of(role).to_vec(),
+            include_dirs: self.include_dir.iter().chain(&self.grader_include).cloned().collect(),
+            linked_sources: self.linked_sources(role, source),
+            grader: self.grader.clone().filter(|_| self.is_graded(role, source)),
+            compile_cmd: self.compile_cmd.clone(),
+            build_dir: builds_into(self),
+            reuse: !self.no_cache,
+            max_cache_size: cache.then_some(self.max_cache_size * 1024 * 1024),
+            warnings_as_errors: self.warnings_as_errors,
+            backend: self.backend.clone(),
+        }
+    }
+
+    /// the c++ compiler of the CXX variable, unless --ignore-env-toolchain
+    pub fn env_cxx(&self) -> Option<PathBuf> {
+        env::var_os("CXX").filter(|cxx| !cxx.is_empty() && !self.ignore_env_toolchain).map(PathBuf::from)
+    }
+
+    /// the flags of the CXXFLAGS variable, appended to all the others unless --ignore-env-toolchain
+    pub fn env_cxx_flags(&self) -> Option<String> {
+        env::var("CXXFLAGS").ok().map(|flags| flags.trim().to_string()).filter(|flags| !flags.is_empty() && !self.ignore_env_toolchain)
+    }
+
+    /// Replaces the patterns among the references with the files they match, sorted, so that the shell
+    /// doesn't have to expand them. Then leaves out those matching `--reference-exclude`.
+    pub fn expand_references(&mut self) -> Result<(), Box<dyn Error>> {
+        let exclude = self.reference_exclude.iter()
+            .map(|p| glob::Pattern::new(p).map_err(|e| into_err(format!("invalid pattern `{}` of --reference-exclude: {}", p, e))))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut references = vec![];
+        for reference in &self.reference {
+            let pattern = reference.to_string_lossy();
+            if !pattern.contains(['*', '?', '[']) {
+                references.push(reference.clone());
+                continue;
+            }
+            let mut matches = glob::glob(&pattern)
+                .map_err(|e| into_err(format!("invalid pattern `{}` of --reference: {}", pattern, e)))?
+                .collect::<Result<Vec<_>, _>>()?;
+            if matches.is_empty() {
+                return Err(into_err(format!("no reference matches the pattern `{}`", pattern)));
+            }
+            matches.sort();
+            references.extend(matches);
+        }
+        references.retain(|r| !exclude.iter().any(|p| p.matches_path(r)));
+        self.reference = references;
+        Ok(())
+    }
+}
+
+/// the options of a session
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "cli", derive(Parser))]
+#[cfg_attr(feature = "cli", command(author, version, about))]
+#[cfg_attr(feature = "cli", command(mut_arg("program", |a| a.required(false).required_unless_present("program_file"))))]
+#[cfg_attr(feature = "cli", command(group(ArgGroup::new("searches").args(["minimize", "explore"]).multiple(true))))]
+pub struct Cli {
+    /// the generator, when the files are given by position: `compdiff gen.py sol.cpp brute.py`
+    /// is the same as `compdiff -g gen.py -p sol.cpp -r brute.py`
+    #[cfg_attr(feature = "cli", arg(value_name = "GENERATOR", conflicts_with_all = ["generator", "input_file", "gen_spec"], requires = "program_file"))]
+    pub generator_file: Option<PathBuf>,
+
+    /// the programme, when the files are given by position
+    #[cfg_attr(feature = "cli", arg(value_name = "PROGRAM", conflicts_with = "program"))]
+    pub program_file: Option<PathBuf>,
+
+    /// the references, when the files are given by position
+    #[cfg_attr(feature = "cli", arg(value_name = "REFERENCE", conflicts_with = "reference"))]
+    pub reference_files: Vec<PathBuf>,
+
+    /// read the options which aren't given on the command line from this file (compdiff.toml by default)
+    #[cfg_attr(feature = "cli", arg(long, value_name = "FILE"))]
+    pub config: Option<PathBuf>,
+
+    /// take the options of the `[profile.NAME]` section of the configuration over its top-level ones
+    #[cfg_attr(feature = "cli", arg(long, value_name = "NAME"))]
+    pub profile: Option<String>,
+
+    /// the test-case generator programme, repeated for several generators which take turns writing the
+    /// inputs, as `gen.py:3` it writes 3 times as many as a generator without a weight
+    #[cfg_attr(feature = "cli", arg(short, long, value_name = "FILE[:WEIGHT]", required_unless_present_any = ["input_file", "generator_file", "gen_spec"]))]
+    pub generator: Vec<Generator>,
+
+    /// how the generator of a round is picked among several
+    #[cfg_attr(feature = "cli", arg(long, value_enum, default_value = "round-robin"))]
+    pub gen_pick: GenPick,
+
+    /// whether a generator which fails skips its round or stops the session
+    #[cfg_attr(feature = "cli", arg(long, value_enum, default_value = "skip"))]
+    pub on_gen_fail: OnGenFail,
+
+    /// write the input of every round from its seed with this spec instead of a generator programme, such as
+    /// `n = int(1, 1e5); print(n); print(ints(n, 1, 1e9))`, see `genspec` for the whole language
+    #[cfg_attr(feature = "cli", arg(long, value_name = "SPEC", conflicts_with_all = ["generator", "input_file"]))]
+    pub gen_spec: Option<GenSpec>,
+
+    /// run a single round on the input read from this file instead of generating one
+    #[cfg_attr(feature = "cli", arg(short, long, value_name = "FILE", conflicts_with = "generator"))]
+    pub input_file: Option<PathBuf>,
+
+    #[cfg_attr(feature = "cli", command(flatten))]
+    pub programs: Programs,
+
+    /// the session seed from which the seed of every round is derived (random by default)
+    #[cfg_attr(feature = "cli", arg(short = 's', long))]
+    pub seed: Option<u64>,
+
+    /// how the generator receives the seed of the round
+    #[cfg_attr(feature = "cli", arg(long, value_enum, default_value = "argv"))]
+    pub gen_seed_via: SeedVia,
+
+    /// arguments of every generator, split as a shell would, e.g. `--n-max 100 --edges "very sparse"`,
+    /// followed by the seed with `--gen-seed-via argv`
+    #[cfg_attr(feature = "cli", arg(long, value_name = "ARGS", value_parser = parse_gen_args, allow_hyphen_values = true))]
+    pub gen_args: Option<String>,
+
+    /// for how many rounds should the programme be ran, 0 runs until the programme fails
+    #[cfg_attr(feature = "cli", arg(short = 'c', long))]
+    pub rounds: Option<u64>,
+
+    /// stop as soon as the programme fails, without --rounds run until then
+    #[cfg_attr(feature = "cli", arg(long, default_value = "false"))]
+    pub until_failure: bool,
+
+    /// print how many rounds passed every N rounds, 0 disables it
+    #[cfg_attr(feature = "cli", arg(long, value_name = "N", default_value = "1000"))]
+    pub heartbeat: u64,
+
+    /// show at most this many lines of every input, output and error, the first and the last half of them
+    #[cfg_attr(feature = "cli", arg(long, value_name = "N", default_value = "100"))]
+    pub max_lines: usize,
+
+    /// show at most this many bytes of every input, output and error
+    #[cfg_attr(feature = "cli", arg(long, value_name = "BYTES", default_value = "16384"))]
+    pub max_bytes: usize,
+
+    /// show inputs, outputs and errors in full
+    #[cfg_attr(feature = "cli", arg(long, default_value = "false"))]
+    pub full_output: bool,
+
+    /// how the outputs are compared
+    #[cfg_attr(feature = "cli", arg(long, value_enum, default_value = "exact"))]
+    pub compare: Compare,
+
+    /// with --compare float, how far a number may be from the reference's, absolutely or relatively
+    #[cfg_attr(feature = "cli", arg(long, value_name = "EPSILON", default_value = "1e-6"))]
+    pub float_tolerance: f64,
+
+    /// what a mismatch report shows of the outputs
+    #[cfg_attr(feature = "cli", arg(long, value_enum, default_value = "diff-only"))]
+    pub show: Show,
+
+    /// cut the lines of the reports at this many columns, 0 doesn't,
+    /// the width of the terminal by default and 100 when the output isn't one
+    #[cfg_attr(feature = "cli", arg(long, value_name = "COLUMNS"))]
+    pub width: Option<usize>,
+
+    /// the format of the console output, `github` when the GITHUB_ACTIONS variable is set
+    #[cfg_attr(feature = "cli", arg(long, value_enum))]
+    pub format: Option<Format>,
+
+    /// show the report of a failing round through $PAGER (`less -R` by default),
+    /// `auto` does when stdout is a terminal and the report doesn't fit on the screen
+    #[cfg_attr(feature = "cli", arg(long, value_enum, default_value = "auto"))]
+    pub pager: PagerChoice,
+
+    /// write everything in full, with timestamps and the spawned commands, into this file
+    #[cfg_attr(feature = "cli", arg(long, value_name = "FILE"))]
+    pub log_file: Option<PathBuf>,
+
+    /// time limit for the programme excluding references, such as 250ms, 1.5s or 2m (bare numbers are seconds)
+    #[cfg_attr(feature = "cli", arg(short = 't', long, value_name = "DURATION", value_parser = parse_seconds))]
+    pub time_limit: Option<f64>,
+
+    /// time limit for the programme relative to a reference, which runs before it on the same input:
+    /// `2.5x:brute.cpp` lets it take 2.5 times as long as brute.cpp did
+    #[cfg_attr(feature = "cli", arg(long, value_name = "FACTORx:REFERENCE", conflicts_with = "time_limit"))]
+    pub time_limit_rel: Option<RelativeTimeLimit>,
+
+    /// the least time limit --time-limit-rel gives the programme, however quick the reference was
+    #[cfg_attr(feature = "cli", arg(long, value_name = "DURATION", value_parser = parse_seconds, default_value = "100ms", requires = "time_limit_rel"))]
+    pub time_limit_floor: f64,
+
+    /// time limit for a whole round: generator, programme and references together, such as 30s or 2m
+    #[cfg_attr(feature = "cli", arg(long, value_name = "DURATION", value_parser = parse_seconds))]
+    pub round_timeout: Option<f64>,
+
+    /// how many references run at the same time, all of them by default
+    #[cfg_attr(feature = "cli", arg(long, value_name = "N"))]
+    pub ref_jobs: Option<NonZeroUsize>,
+
+    /// memory limit (kB) for the programme excluding references
+    #[cfg_attr(feature = "cli", arg(short = 'm', long))]
+    pub memory_limit: Option<usize>,
+
+    /// the size (kB) past which an output is written to a temporary file instead of being kept in memory,
+    /// and only its beginning is shown
+    #[cfg_attr(feature = "cli", arg(long, value_name = "KB", default_value = "16384"))]
+    pub spill_threshold: usize,
+
+    /// print additional information, -vv adds debug logs of every step and -vvv traces (RUST_LOG overrides these)
+    #[cfg_attr(feature = "cli", arg(short = 'v', long, action = clap::ArgAction::Count))]
+    pub verbose: u8,
+
+    /// print only the failing rounds, the summary and the final counts
+    #[cfg_attr(feature = "cli", arg(short = 'q', long, default_value = "false", conflicts_with = "verbose"))]
+    pub quiet: bool,
+
+    /// replace the emoji with plain markers such as [OK] and [FAIL], implied by TERM=dumb
+    #[cfg_attr(feature = "cli", arg(long, default_value = "false"))]
+    pub ascii: bool,
+
+    /// colour the markers, `auto` respects NO_COLOR
+    #[cfg_attr(feature = "cli", arg(long, value_enum, default_value = "auto"))]
+    pub color: ColorChoice,
+
+    /// when the programme crashes (SIGSEGV, SIGABRT, ...) re-run it under gdb and print the backtrace
+    #[cfg_attr(feature = "cli", arg(long, default_value = "false"))]
+    pub backtrace_on_crash: bool,
+
+    /// when the programme crashes re-run the input against this sanitizer build,
+    /// `auto` compiles the c++ programme with -fsanitize=address,undefined
+    #[cfg_attr(feature = "cli", arg(long, value_name = "FILE|auto"))]
+    pub crash_rerun: Option<String>,
+
+    /// how many times to retry an execution which failed to start (or matched --retry-pattern)
+    #[cfg_attr(feature = "cli", arg(long, default_value = "0"))]
+    pub retries: u32,
+
+    /// also retry executions whose error output contains this text
+    #[cfg_attr(feature = "cli", arg(long, value_name = "TEXT"))]
+    pub retry_pattern: Option<String>,
+
+    /// stop at the first failing round
+    #[cfg_attr(feature = "cli", arg(long, default_value = "false"))]
+    pub fail_fast: bool,
+
+    /// stop once this many rounds have failed
+    #[cfg_attr(feature = "cli", arg(long, value_name = "N"))]
+    pub max_failures: Option<u64>,
+
+    /// list every failing round in the final summary, instead of identical failures only once
+    #[cfg_attr(feature = "cli", arg(long, default_value = "false"))]
+    pub no_dedup: bool,
+
+    /// save the input and outputs of every failing round into this directory
+    #[cfg_attr(feature = "cli", arg(long, value_name = "DIR"))]
+    pub save_failures: Option<PathBuf>,
+
+    /// keep the input, the outputs, the commands and the times of every round in `DIR/round_0007/`
+    #[cfg_attr(feature = "cli", arg(long, value_name = "DIR"))]
+    pub keep_artifacts: Option<PathBuf>,
+
+    /// keep the directories of only the last N rounds of --keep-artifacts
+    #[cfg_attr(feature = "cli", arg(long, value_name = "N", requires = "keep_artifacts"))]
+    pub keep_last: Option<usize>,
+
+    /// write the input of every round, whatever its verdict, into `DIR/<seed>.in` once for every distinct
+    /// input, listed in `DIR/index.tsv` with its verdict and times, to be run again with `compdiff replay`
+    #[cfg_attr(feature = "cli", arg(long, value_name = "DIR"))]
+    pub corpus_dir: Option<PathBuf>,
+
+    /// the size in MB --corpus-dir is kept under by removing its oldest passing inputs, the failing ones are
+    /// always kept
+    #[cfg_attr(feature = "cli", arg(long, value_name = "MB", requires = "corpus_dir"))]
+    pub corpus_size: Option<u64>,
+
+    /// list the rounds in which the programme ran the longest, passing ones included, in the summary
+    #[cfg_attr(feature = "cli", arg(long, value_name = "N"))]
+    pub top: Option<usize>,
+
+    /// save the inputs of the --top (5 by default) slowest rounds into this directory
+    #[cfg_attr(feature = "cli", arg(long, value_name = "DIR"))]
+    pub save_slowest: Option<PathBuf>,
+
+    /// time the programme against the fastest reference in every round, flag the rounds in which it took
+    /// more than --perf-factor times as long and list the --top (5 by default) slowest in the summary
+    #[cfg_attr(feature = "cli", arg(long, default_value = "false"))]
+    pub perf_compare: bool,
+
+    /// how many times the time of the fastest reference the programme may take before --perf-compare flags the round
+    #[cfg_attr(feature = "cli", arg(long, value_name = "FACTOR", default_value = "3", requires = "perf_compare"))]
+    pub perf_factor: f64,
+
+    /// save the inputs of the rounds --perf-compare flagged and lists in the summary into this directory
+    #[cfg_attr(feature = "cli", arg(long, value_name = "DIR", requires = "perf_compare"))]
+    pub save_perf: Option<PathBuf>,
+
+    /// measure the peak memory of the programme and the references in every round, flag the rounds in which
+    /// the programme used more than --mem-factor times as much as the leanest reference, and show the median
+    /// and max of each in the summary, along with the --top (5 by default) rounds it used the most in (unix)
+    #[cfg_attr(feature = "cli", arg(long, default_value = "false", conflicts_with = "memory_limit"))]
+    pub mem_compare: bool,
+
+    /// how many times the memory of the leanest reference the programme may use before --mem-compare flags the round
+    #[cfg_attr(feature = "cli", arg(long, value_name = "FACTOR", default_value = "3", requires = "mem_compare"))]
+    pub mem_factor: f64,
+
+    /// save the inputs of the rounds --mem-compare flagged and lists in the summary into this directory
+    #[cfg_attr(feature = "cli", arg(long, value_name = "DIR", requires = "mem_compare"))]
+    pub save_mem: Option<PathBuf>,
+
+    /// write a JSON report of every round and a summary to this file, `-` for stdout
+    #[cfg_attr(feature = "cli", arg(long, value_name = "FILE|-"))]
+    pub report_json: Option<PathBuf>,
+
+    /// outputs, inputs and errors longer than this (bytes) are truncated in the JSON report
+    #[cfg_attr(feature = "cli", arg(long, value_name = "BYTES", default_value = "65536"))]
+    pub report_max_output: usize,
+
+    /// write the failing rounds into this Markdown file as they happen, with a summary table on top
+    #[cfg_attr(feature = "cli", arg(long, value_name = "FILE"))]
+    pub report_md: Option<PathBuf>,
+
+    /// write a self-contained HTML report of every round into this file
+    #[cfg_attr(feature = "cli", arg(long, value_name = "FILE"))]
+    pub report_html: Option<PathBuf>,
+
+    /// write a row per round (verdict, times, sizes) into this CSV file as the rounds end
+    #[cfg_attr(feature = "cli", arg(long, value_name = "FILE"))]
+    pub report_csv: Option<PathBuf>,
+
+    /// append the session and its rounds to this SQLite database, created on first use, see `compdiff stats`
+    #[cfg_attr(feature = "cli", arg(long, value_name = "FILE"))]
+    pub db: Option<PathBuf>,
+
+    /// POST a JSON notification to this URL, on the first failing round by default
+    #[cfg_attr(feature = "cli", arg(long, value_name = "URL"))]
+    pub notify_url: Option<String>,
+
+    /// when to notify `--notify-url`, several values can be given separated by commas
+    #[cfg_attr(feature = "cli", arg(long, value_enum, value_delimiter = ',', default_value = "first-failure", requires = "notify_url"))]
+    pub notify_on: Vec<NotifyOn>,
+
+    /// stream one JSON event per line (round starts, verdicts, the summary) to this file, `-` for stdout,
+    /// in which case the human readable output goes to stderr
+    #[cfg_attr(feature = "cli", arg(long, value_name = "FILE|-"))]
+    pub events_ndjson: Option<PathBuf>,
+
+    /// don't show the progress bar, print the output of every round instead
+    #[cfg_attr(feature = "cli", arg(long, default_value = "false"))]
+    pub no_progress: bool,
+
+    /// never pause after a failing round to ask what to do next, which only happens when stdin is a terminal
+    #[cfg_attr(feature = "cli", arg(long, default_value = "false"))]
+    pub no_interactive: bool,
+
+    /// keep the outputs of the references in this directory, and take them from it instead of running the
+    /// references again on an input they already ran on
+    #[cfg_attr(feature = "cli", arg(long, value_name = "DIR"))]
+    pub ref_cache: Option<PathBuf>,
+
+    /// the size in MB --ref-cache is kept under at the start of a session, by removing the least recently
+    /// used outputs
+    #[cfg_attr(feature = "cli", arg(long, value_name = "MB", default_value = "500", requires = "ref_cache"))]
+    pub ref_cache_size: u64,
+
+    /// how many inputs the outputs of the references are remembered for during the session, so that they
+    /// don't run again on an input which comes again, 0 to always run them
+    #[cfg_attr(feature = "cli", arg(long, value_name = "N", default_value = "1000"))]
+    pub ref_memo_size: usize,
+
+    /// record the progress of the session into this file after every round, and when it exists
+    /// go on from where it stopped with the same seeds
+    #[cfg_attr(feature = "cli", arg(long, value_name = "FILE", conflicts_with_all = ["input_file", "watch"]))]
+    pub session: Option<PathBuf>,
+
+    /// after the session re-run it whenever the programme, generator or references change, until Ctrl+C
+    #[cfg_attr(feature = "cli", arg(long, default_value = "false"))]
+    pub watch: bool,
+
+    /// build the sources, print the plan of the session and run the generator once, without running
+    /// the programme or the references
+    #[cfg_attr(feature = "cli", arg(long, default_value = "false", conflicts_with = "watch"))]
+    pub dry_run: bool,
+
+    /// only time the programme and the references on the inputs of the rounds, without comparing their
+    /// outputs, and print the mean, median, p90, p99 and max of their wall and cpu times and peak memory
+    #[cfg_attr(feature = "cli", arg(long, default_value = "false", conflicts_with_all = ["watch", "dry_run", "session"]))]
+    pub bench: bool,
+
+    /// with --bench, how many times every programme is timed on each input
+    #[cfg_attr(feature = "cli", arg(long, value_name = "N", default_value = "1", requires = "bench"))]
+    pub runs_per_input: u32,
+
+    /// with --bench, how many times every programme runs on each input before it's timed
+    #[cfg_attr(feature = "cli", arg(long, value_name = "N", default_value = "0", requires = "bench"))]
+    pub warmup: u32,
+
+    /// with --bench, run the programmes on this cpu only, for steadier times (linux)
+    #[cfg_attr(feature = "cli", arg(long, value_name = "CPU", requires = "bench"))]
+    pub pin_cpu: Option<usize>,
+
+    /// shrink the input of every failing round into a smaller one which fails the same way, removing its
+    /// lines and then shrinking its numbers and removing its words
+    #[cfg_attr(feature = "cli", arg(long, default_value = "false"))]
+    pub minimize: bool,
+
+    /// after every failing round run this many mutants of its input, with a number perturbed, a line
+    /// duplicated or deleted, or the words of a line shuffled, and save those which fail too
+    #[cfg_attr(feature = "cli", arg(long, value_name = "N"))]
+    pub explore: Option<usize>,
+
+    /// while minimizing or exploring, only run the inputs this programme accepts, exiting successfully
+    /// when it's given them on stdin, so that no input breaks the constraints of the problem
+    #[cfg_attr(feature = "cli", arg(long, value_name = "VALIDATOR", requires = "searches"))]
+    pub minimize_validator: Option<PathBuf>,
+
+    /// maps the preprocessed (compiled) programmes back to their sources
+    #[cfg_attr(feature = "cli", arg(skip))]
+    pub sources: HashMap<PathBuf, PathBuf>,
+
+    /// how the human readable output looks, set from --ascii and --color
+    #[cfg_attr(feature = "cli", arg(skip))]
+    pub style: Style,
+
+    /// the c++ compiler which built the sources, when any were
+    #[cfg_attr(feature = "cli", arg(skip))]
+    pub toolchain: Option<Toolchain>,
+
+    /// what the session runs on and with, gathered once its sources are prepared
+    #[cfg_attr(feature = "cli", arg(skip))]
+    pub environment: Option<Environment>,
+
+    /// the opened --ref-cache
+    #[cfg_attr(feature = "cli", arg(skip))]
+    pub opened_ref_cache: Option<RefCache>,
+
+    /// the outputs of the references on the inputs of the session, forgotten when the sources are prepared
+    #[cfg_attr(feature = "cli", arg(skip))]
+    pub ref_memo: RefMemo,
+}
+
+/// the flags of the c++ sources when neither `--flags cpp=...` nor --cpp-compiler-flags are given
+pub const DEFAULT_CPP_FLAGS: &str = "-std=c++20";
+
+/// the extensions of the sources which are compiled before they're run
+const COMPILED_EXTENSIONS: &[&str] = &["cpp", "cxx"];
+
+/// A `--compile-cmd` template: the compiler and its arguments, each of them a single argument of the command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompileCmd {
+    pub extension: String,
+    pub argv: Vec<String>,
+}
+
+impl fmt::Display for CompileCmd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.extension, self.argv.join(" "))
+    }
+}
+
+/// parses `EXT=COMMAND`, split with quotes like the flags, the command has to use both {src} and {out}
+pub fn parse_compile_cmd(text: &str) -> Result<CompileCmd, String> {
+    let (extension, command) = text.split_once('=')
+        .ok_or_else(|| format!("`{}` should be EXT=COMMAND, such as cpp=g++ {{flags}} {{src}} -o {{out}}", text))?;
+    let extension = extension.trim().trim_start_matches('.');
+    if !COMPILED_EXTENSIONS.contains(&extension) {
+        return Err(format!("`{}` sources aren't compiled, only {} are", extension, COMPILED_EXTENSIONS.join(" and ")));
+    }
+    let argv = split_flags(command)?;
+    if argv.is_empty() {
+        return Err(format!("the compile command of `{}` is empty", extension));
+    }
+    for placeholder in ["{src}", "{out}"] {
+        if !argv[1..].iter().any(|arg| arg.contains(placeholder)) {
+            return Err(format!("the compile command `{}` doesn't use {}", command.trim(), placeholder));
+        }
+    }
+    // it stands for every source, each of them an argument
+    if argv.iter().any(|arg| arg.contains("{src}") && arg != "{src}") {
+        return Err(format!("{{src}} has to be an argument of its own in the compile command `{}`", command.trim()));
+    }
+    Ok(CompileCmd { extension: extension.to_string(), argv })
+}
+
+/// parses a --std, where a bare year is a standard of c++
+pub fn parse_standard(text: &str) -> Result<String, String> {
+    let text = text.trim().trim_start_matches("-std=");
+    if text.is_empty() || text.contains(char::is_whitespace) {
+        return Err(format!("`{}` isn't a c++ standard, such as c++17", text));
+    }
+    Ok(if text.chars().all(|c| c.is_ascii_digit()) { format!("c++{}", text) } else { text.to_string() })
+}
+
+/// parses `--gen-args`, which have to split into arguments
+pub fn parse_gen_args(text: &str) -> Result<String, String> {
+    split_flags(text)?;
+    Ok(text.trim().to_string())
+}
+
+/// A `--flags` entry: the flags of the sources of a language, named by its extension.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LanguageFlags {
+    pub language: String,
+    pub flags: String,
+}
+
+/// parses `EXT=FLAGS`, the flags have to split into arguments
+pub fn parse_language_flags(text: &str) -> Result<LanguageFlags, String> {
+    let (language, flags) = text.split_once('=')
+        .ok_or_else(|| format!("`{}` should be EXT=FLAGS, such as `cpp=-O2 -std=c++20`", text))?;
+    let language = language.trim().trim_start_matches('.');
+    if language.is_empty() || !language.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("`{}` isn't the extension of a language", language));
+    }
+    split_flags(flags)?;
+    Ok(LanguageFlags { language: language.to_string(), flags: flags.trim().to_string() })
+}
+
+/// Splits flags into arguments as a shell would: quotes keep the spaces of an argument and a backslash
+/// escapes the next character, outside of single quotes.
+pub fn split_flags(text: &str) -> Result<Vec<String>, String> {
+    let mut args = vec![];
+    let (mut arg, mut started, mut quote) = (String::new(), false, None);
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (None, '\'' | '"') => (quote, started) = (Some(c), true),
+            (None | Some('"'), '\\') => {
+                arg.extend(chars.next());
+                started = true;
+            },
+            (None, c) if c.is_whitespace() => if started {
+                args.push(std::mem::take(&mut arg));
+                started = false;
+            },
+            (_, c) => {
+                arg.push(c);
+                started = true;
+            },
+        }
+    }
+    if let Some(q) = quote {
+        return Err(format!("the quote {} isn't closed in `{}`", q, text));
+    }
+    if started {
+        args.push(arg);
+    }
+    Ok(args)
+}
+
+/// Parses a duration into seconds: a number of seconds, or a number followed by `ms`, `s`, `m` or `h`.
+pub fn parse_seconds(text: &str) -> Result<f64, String> {
+    let number_end = text.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(text.len());
+    let (number, unit) = text.split_at(number_end);
+    let factor = match unit {
+        "" | "s" => Some(1.0),
+        "ms" => Some(0.001),
+        "m" => Some(60.0),
+        "h" => Some(3600.0),
+        _ => None,
+    };
+    match (number.parse::<f64>(), factor) {
+        (Ok(n), Some(factor)) if n.is_finite() => Ok(n * factor),
+        _ => Err(format!("`{}` isn't a duration, expected a number of seconds or a number followed by ms, s, m or h, such as 250ms, 1.5s or 3m", text)),
+    }
+}
+
+impl Cli {
+    /// the built-in comparator chosen by --compare
+    pub fn comparator(&self) -> Box<dyn Comparator> {
+        match self.compare {
+            Compare::Exact => Box::new(compare::Exact),
+            Compare::Trimmed => Box::new(compare::Trimmed),
+            Compare::Tokens => Box::new(compare::Tokens),
+            Compare::Float => Box::new(compare::FloatTolerance(self.float_tolerance)),
+        }
+    }
+
+    /// moves the files given by position into the options they stand for
+    pub fn take_positional(&mut self) {
+        if let Some(generator) = self.generator_file.take() {
+            self.generator = vec![Generator::new(generator)];
+        }
+        if let Some(program) = self.program_file.take() {
+            self.programs.program = program;
+        }
+        self.programs.reference.append(&mut self.reference_files);
+    }
+
+    /// The generator which writes the input of the round with the seed, none without generators. The rounds
+    /// are dealt out by weight, in turn or from the seed.
+    pub fn generator_of(&self, round: u64, seed: u64) -> Option<&Generator> {
+        let total: u64 = self.generator.iter().map(|g| u64::from(g.weight)).sum();
+        let mut slot = match self.gen_pick {
+            GenPick::RoundRobin => round,
+            GenPick::Weighted => seed,
+        } % total.max(1);
+        self.generator.iter().find(|g| {
+            let found = slot < u64::from(g.weight);
+            slot = slot.saturating_sub(u64::from(g.weight));
+            found
+        })
+    }
+
+    /// the path the user supplied for a preprocessed programme
+    pub fn source_of<'a>(&'a self, path: &'a Path) -> &'a Path {
+        self.sources.get(path).map(PathBuf::as_path).unwrap_or(path)
+    }
+
+    /// which of the references the --time-limit-rel is relative to
+    pub fn relative_reference(&self) -> Option<usize> {
+        let reference = &self.time_limit_rel.as_ref()?.reference;
+        self.programs.reference.iter().position(|r| self.source_of(r) == reference)
+    }
+
+    /// the generator, the programme and the references with their roles, a source passed twice in a role only once
+    pub fn distinct_sources(&self) -> Vec<(Role, PathBuf)> {
+        self.programs.distinct_sources(&self.generator)
+    }
+
+    /// Finds the c++ compiler into `toolchain` when one of the sources is compiled. Without one every c++
+    /// source fails to build, and says why.
+    pub fn detect_toolchain(&mut self) {
+        if self.distinct_sources().iter().any(|(role, source)| self.programs.is_compiled(*role, source)) {
+            self.toolchain = Toolchain::detect(&self.programs).ok();
+        }
+    }
+
+    /// Preprocesses every source in each of its roles, giving the result of each, and only once all of them are
+    /// ready runs the built programmes in place of their sources. A source given in two roles gets a binary
+    /// for each, built with the options of the role.
+    pub fn prepare(&mut self) -> Vec<Prepared> {
+        let sources = self.distinct_sources();
+        let results: Vec<_> = sources.iter().map(|(_, source)| source.clone()).zip(preprocess_commands(&sources, &self.programs, self.toolchain.as_ref())).collect();
+        if results.iter().all(|(_, result)| result.is_ok()) {
+            let built: HashMap<_, _> = sources.iter().zip(&results)
+                .filter_map(|(source, (_, result))| result.as_ref().ok().map(|built| (source.clone(), built.binary.clone())))
+                .collect();
+            let binary = |role, source: &PathBuf| built[&(role, source.clone())].clone();
+            self.programs.program = binary(Role::Program, &self.programs.program);
+            self.generator = self.generator.iter().map(|g| Generator { path: binary(Role::Generator, &g.path), ..g.clone() }).collect();
+            self.programs.reference = self.programs.reference.iter().map(|r| binary(Role::Reference, r)).collect();
+            self.sources = built.into_iter().map(|((_, source), binary)| (binary, source)).collect();
+            // the configuration it records isn't to hold the environment of an earlier preparation
+            self.environment = None;
+            self.environment = Some(Environment::gather(self, &results));
+        }
+        self.ref_memo = RefMemo::new(self.ref_memo_size);
+        results
+    }
+}
+
+impl Default for Programs {
+    fn default() -> Self {
+        Programs {
+            program: PathBuf::new(),
+            program_sources: vec![],
+            reference: vec![],
+            reference_exclude: vec![],
+            cpp_compiler: None,
+            ignore_env_toolchain: false,
+            docker: None,
+            remote: None,
+            remote_dir: "/tmp/compdiff".to_string(),
+            backend: Backend::Local,
+            program_backend: None,
+            cpp_compiler_flags: None,
+            std: None,
+            flags: vec![],
+            compile_cmd: vec![],
+            no_cache: false,
+            build_dir: None,
+            cache_dir: None,
+            max_cache_size: 500,
+            include_dir: vec![],
+            grader: None,
+            grader_include: vec![],
+            define: vec![],
+            define_refs: vec![],
+            show_compile_warnings: false,
+            warnings_as_errors: false,
+            jobs: None,
+            build_profile: BuildProfile::Custom,
+            program_profile: None,
+            ref_profile: None,
+            debug_flags: "-O0 -g".to_string(),
+            release_flags: "-O2".to_string(),
+            sanitize: vec![],
+        }
+    }
+}
+
+/// the defaults of the command line, with neither a generator nor a programme
+impl Default for Cli {
+    fn default() -> Self {
+        Cli {
+            generator_file: None,
+            program_file: None,
+            reference_files: vec![],
+            config: None,
+            profile: None,
+            generator: vec![],
+            gen_pick: GenPick::RoundRobin,
+            on_gen_fail: OnGenFail::Skip,
+            gen_spec: None,
+            input_file: None,
+            programs: Programs::default(),
+            seed: None,
+            gen_seed_via: SeedVia::Argv,
+            gen_args: None,
+            rounds: None,
+            until_failure: false,
+            heartbeat: 1000,
+            max_lines: 100,
+            max_bytes: 16384,
+            full_output: false,
+            compare: Compare::Exact,
+            float_tolerance: 1e-6,
+            show: Show::DiffOnly,
+            width: None,
+            format: None,
+            pager: PagerChoice::Auto,
+            log_file: None,
+            time_limit: None,
+            time_limit_rel: None,
+            time_limit_floor: 0.1,
+            round_timeout: None,
+            ref_jobs: None,
+            memory_limit: None,
+            spill_threshold: 16384,
+            verbose: 0,
+            quiet: false,
+            ascii: false,
+            color: ColorChoice::Auto,
+            backtrace_on_crash: false,
+            crash_rerun: None,
+            retries: 0,
+            retry_pattern: None,
+            fail_fast: false,
+            max_failures: None,
+            no_dedup: false,
+            save_failures: None,
+            keep_artifacts: None,
+            keep_last: None,
+            corpus_dir: None,
+            corpus_size: None,
+            top: None,
+            save_slowest: None,
+            perf_compare: false,
+            perf_factor: 3.0,
+            save_perf: None,
+            mem_compare: false,
+            mem_factor: 3.0,
+            save_mem: None,
+            report_json: None,
+            report_max_output: 65536,
+            report_md: None,
+            report_html: None,
+            report_csv: None,
+            db: None,
+            notify_url: None,
+            notify_on: vec![NotifyOn::FirstFailure],
+            events_ndjson: None,
+            no_progress: false,
+            no_interactive: false,
+            ref_cache: None,
+            ref_cache_size: 500,
+            ref_memo_size: 1000,
+            session: None,
+            watch: false,
+            dry_run: false,
+            bench: false,
+            runs_per_input: 1,
+            warmup: 0,
+            pin_cpu: None,
+            minimize: false,
+            explore: None,
+            minimize_validator: None,
+            sources: HashMap::new(),
+            style: Style::default(),
+            toolchain: None,
+            environment: None,
+            opened_ref_cache: None,
+            ref_memo: RefMemo::default(),
+        }
+    }
+}
@@ -0,0 +1,535 @@
+use std::{
+    borrow::Cow,
+    cell::Cell,
+    env,
+    fs::File,
+    io::{self, IsTerminal, Write as _},
+    fmt,
+    fmt::Write as _,
+    path::Path,
+    process::{Command, Stdio},
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    };
+use indicatif::{ProgressBar, ProgressStyle};
+#[cfg(feature = "cli")]
+use clap::ValueEnum;
+use log::LevelFilter;
+
+use crate::session::Progress;
+
+fn paint(text: &str, color: u8) -> String {
+    format!("\x1b[{}m{}\x1b[0m", color, text)
+}
+
+/// when to colour the output
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum ColorChoice {
+    /// when stdout is a terminal and neither NO_COLOR nor TERM=dumb is set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// when to page the reports of failing rounds
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum PagerChoice {
+    /// when stdout is a terminal and the report doesn't fit on the screen
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// the markers in front of the lines of the human readable output
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mark {
+    Ok,
+    Fail,
+    Warn,
+    /// a single programme failed
+    Bad,
+    Saved,
+    Retry,
+    Timeout,
+}
+
+impl Mark {
+    const ALL: [Mark; 7] = [Mark::Ok, Mark::Fail, Mark::Warn, Mark::Bad, Mark::Saved, Mark::Retry, Mark::Timeout];
+}
+
+/// How the human readable output looks: emoji or plain ASCII markers, with or without colour.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Style {
+    pub ascii: bool,
+    pub color: bool,
+}
+
+impl Style {
+    /// `--ascii` is implied by a dumb terminal, colour is off for NO_COLOR or a dumb terminal
+    pub fn detect(ascii: bool, color: ColorChoice) -> Self {
+        let dumb = env::var("TERM").is_ok_and(|t| t == "dumb");
+        let color = match color {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => !dumb && env::var_os("NO_COLOR").is_none() && io::stdout().is_terminal(),
+        };
+        Style { ascii: ascii || dumb, color }
+    }
+
+    pub fn mark(&self, mark: Mark) -> String {
+        let text = match (mark, self.ascii) {
+            (Mark::Ok, false) => "✔",
+            (Mark::Fail, false) => "❌",
+            (Mark::Warn, false) => "🚧",
+            (Mark::Bad, false) => "👎",
+            (Mark::Saved, false) => "💾",
+            (Mark::Retry, false) => "🔁",
+            (Mark::Timeout, false) => "⌛",
+            (Mark::Ok, true) => "[OK]",
+            (Mark::Fail | Mark::Bad, true) => "[FAIL]",
+            (Mark::Warn, true) => "[WARN]",
+            (Mark::Saved, true) => "[SAVED]",
+            (Mark::Retry, true) => "[RETRY]",
+            (Mark::Timeout, true) => "[TIMEOUT]",
+        };
+        // green, red and yellow
+        let color = match mark {
+            Mark::Ok => Some(32),
+            Mark::Fail | Mark::Bad => Some(31),
+            Mark::Warn | Mark::Timeout => Some(33),
+            Mark::Saved | Mark::Retry => None,
+        };
+        match color {
+            Some(c) if self.color => paint(text, c),
+            _ => text.to_string(),
+        }
+    }
+
+    /// the marker of a cut line
+    pub fn ellipsis(&self) -> &'static str {
+        if self.ascii { "..." } else { "…" }
+    }
+
+    /// colours the added, removed and position lines of a unified diff
+    pub fn diff_line<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        // the file headers are left alone
+        let color = match line.as_bytes() {
+            _ if !self.color => None,
+            [b'+', b'+', b'+', ..] | [b'-', b'-', b'-', ..] => None,
+            [b'+', ..] => Some(32),
+            [b'-', ..] => Some(31),
+            [b'@', b'@', ..] => Some(36),
+            _ => None,
+        };
+        match color {
+            Some(c) => Cow::Owned(paint(line, c)),
+            None => Cow::Borrowed(line),
+        }
+    }
+}
+
+/// Logs the diagnostics of the `log` facade to stderr, with timestamps and module targets: warnings by default,
+/// then info, debug and trace for every --verbose. RUST_LOG overrides the level.
+pub fn init_logger(verbosity: u8) {
+    let level = match verbosity {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    env_logger::Builder::new()
+        .filter_level(level)
+        .parse_default_env()
+        .format_timestamp_millis()
+        .init();
+}
+
+/// the session log of `--log-file`
+struct Log {
+    file: File,
+    start: Instant,
+}
+
+struct State {
+    to_stderr: bool,
+    log: Option<Log>,
+    /// only the output of failing rounds is printed
+    quiet: bool,
+    progress: Option<ProgressBar>,
+    /// output of the current round, held back until it's known whether the round failed
+    round: Option<String>,
+    pager: PagerChoice,
+    /// the report of the current round, held back to be paged
+    page: Option<String>,
+}
+
+static STATE: Mutex<State> = Mutex::new(State {
+    to_stderr: false, log: None, quiet: false, progress: None, round: None, pager: PagerChoice::Auto, page: None,
+});
+
+fn state() -> std::sync::MutexGuard<'static, State> {
+    STATE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// sends the human readable output to stderr, keeping stdout for machine readable streams
+pub fn human_to_stderr(on: bool) {
+    state().to_stderr = on;
+}
+
+/// from now on the output of a round is only printed when the round fails
+pub fn set_quiet(on: bool) {
+    state().quiet = on;
+}
+
+thread_local! {
+    /// nothing this thread says is printed, it works for a `session::Session`
+    static MUTED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Nothing the current thread says is printed until it's dropped, the work of a `session::Session` is done
+/// under it. The other threads, the command line of the same programme among them, go on saying everything.
+pub struct Mute {
+    was: bool,
+}
+
+pub fn mute() -> Mute {
+    Mute { was: MUTED.replace(true) }
+}
+
+impl Drop for Mute {
+    fn drop(&mut self) {
+        MUTED.set(self.was);
+    }
+}
+
+/// whether the current thread is muted, the threads it starts for its work are to be muted as well
+pub fn is_muted() -> bool {
+    MUTED.get()
+}
+
+pub fn set_pager(pager: PagerChoice) {
+    state().pager = pager;
+}
+
+/// Starts a log of everything said (in full, without colours or emoji) and of every spawned command.
+/// Only the first call opens a log, so that it covers all the sessions of `--watch`.
+pub fn open_log(path: &Path) -> io::Result<()> {
+    let mut state = state();
+    if state.log.is_some() {
+        return Ok(());
+    }
+    let mut file = File::create(path)?;
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    writeln!(file, "compdiff log started at {} (unix time), lines are stamped with seconds since then", secs)?;
+    state.log = Some(Log { file, start: Instant::now() });
+    Ok(())
+}
+
+/// the text with emoji replaced by the ASCII markers and colours removed
+fn plain(text: &str) -> String {
+    let (fancy, ascii) = (Style { ascii: false, color: false }, Style { ascii: true, color: false });
+    let mut text = Mark::ALL.iter().fold(text.to_string(), |t, m| t.replace(&fancy.mark(*m), &ascii.mark(*m)));
+    while let Some(start) = text.find("\x1b[") {
+        let end = text[start..].find('m').map(|e| start + e + 1).unwrap_or(text.len());
+        text.replace_range(start..end, "");
+    }
+    text
+}
+
+fn write_log(state: &mut State, text: &str) {
+    if let Some(log) = &mut state.log {
+        let time = log.start.elapsed().as_secs_f64();
+        for line in plain(text).split('\n') {
+            let _ = writeln!(log.file, "[{:10.3}] {}", time, line);
+        }
+    }
+}
+
+/// writes only into the log file, and the debug log of spawned commands
+pub fn log(args: fmt::Arguments) {
+    log::debug!(target: "compdiff::spawn", "{}", args);
+    write_log(&mut state(), &args.to_string());
+}
+
+pub fn say(args: fmt::Arguments) {
+    let text = args.to_string();
+    say_with_log(&text, &text);
+}
+
+/// says `text` but logs `full`, for texts which get truncated on the console
+pub fn say_with_log(text: &str, full: &str) {
+    if is_muted() {
+        return;
+    }
+    let mut state = state();
+    write_log(&mut state, full);
+    let State { page, round, .. } = &mut *state;
+    if let Some(held) = page.as_mut().or(round.as_mut()) {
+        let _ = writeln!(held, "{}", text);
+        return;
+    }
+    print_line(&state, text);
+}
+
+fn print_line(state: &State, line: &str) {
+    let print = || if state.to_stderr { eprintln!("{}", line) } else { println!("{}", line) };
+    match &state.progress {
+        Some(bar) => bar.suspend(print),
+        None => print(),
+    }
+}
+
+/// whether the text goes through the pager rather than straight to the terminal
+fn should_page(state: &State, text: &str) -> bool {
+    // the pager writes to stdout, which may be taken by the machine readable output
+    if state.to_stderr {
+        return false;
+    }
+    match state.pager {
+        PagerChoice::Never => false,
+        PagerChoice::Always => true,
+        PagerChoice::Auto => io::stdout().is_terminal() && terminal_size::terminal_size()
+            .is_some_and(|(_, terminal_size::Height(rows))| text.lines().count() >= rows as usize),
+    }
+}
+
+/// Pipes the text through $PAGER (`less -R` by default) and waits until it's closed, like git does.
+/// False when there's no pager to run.
+fn page(state: &State, text: &str) -> bool {
+    let pager = env::var("PAGER").unwrap_or_else(|_| "less -R".to_string());
+    if pager.trim().is_empty() || pager.trim() == "cat" {
+        return false;
+    }
+    let run = || {
+        let Ok(mut child) = Command::new("sh").args(["-c", &pager]).stdin(Stdio::piped()).spawn() else {
+            return false;
+        };
+        // the pager may be quit before it has read everything
+        let _ = child.stdin.take().expect("piped stdin").write_all(text.as_bytes());
+        let _ = child.wait();
+        true
+    };
+    match &state.progress {
+        Some(bar) => bar.suspend(run),
+        None => run(),
+    }
+}
+
+/// prints the report of a failing round, through the pager when it's long
+fn print_report(state: &State, text: &str) {
+    if !(should_page(state, text) && page(state, &format!("{}\n", text))) {
+        print_line(state, text);
+    }
+}
+
+/// starts holding back the report of the round, so that a long one can be paged
+pub fn begin_page() {
+    state().page = Some(String::new());
+}
+
+/// prints the held back report, paged when the round failed and it's long (or with `--pager always`)
+pub fn end_page(failed: bool) {
+    let mut state = state();
+    let Some(report) = state.page.take() else {
+        return;
+    };
+    let report = report.trim_end();
+    if report.is_empty() {
+        return;
+    }
+    if let Some(round) = &mut state.round {
+        // the output of the round is printed all at once at its end anyway
+        let _ = writeln!(round, "{}", report);
+    } else if failed {
+        print_report(&state, report);
+    } else {
+        print_line(&state, report);
+    }
+}
+
+/// Prints the question on stderr, above the progress bar, and reads the answer from stdin.
+/// None when stdin is closed.
+pub fn ask(question: &str) -> Option<String> {
+    let mut state = state();
+    write_log(&mut state, question);
+    let read = || {
+        eprint!("{} ", question);
+        let mut answer = String::new();
+        match io::stdin().read_line(&mut answer) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(answer.trim().to_string()),
+        }
+    };
+    let answer = match &state.progress {
+        Some(bar) => bar.suspend(read),
+        None => read(),
+    };
+    write_log(&mut state, &format!("> {}", answer.as_deref().unwrap_or("")));
+    answer
+}
+
+/// prints the line on stdout even when the human readable output goes elsewhere or is held back
+pub fn raw(line: &str) {
+    let mut state = state();
+    write_log(&mut state, line);
+    match &state.progress {
+        Some(bar) => bar.suspend(|| println!("{}", line)),
+        None => println!("{}", line),
+    }
+}
+
+/// `println!` for the human readable output, which goes wherever `human_to_stderr` says
+#[macro_export]
+macro_rules! say {
+    ($($arg:tt)*) => { $crate::output::say(format_args!($($arg)*)) };
+}
+
+/// Shows a progress bar of the rounds on stderr, which implies `set_quiet`.
+/// Without a number of rounds only the count and the elapsed time are shown.
+pub fn start_progress(rounds: Option<u64>, style: Style) {
+    let (bar, template) = match rounds {
+        Some(n) => (ProgressBar::new(n), "{bar:40} {pos}/{len} rounds, {msg} [{elapsed_precise}, eta {eta}]"),
+        None => (ProgressBar::no_length(), "{spinner} {pos} rounds, {msg} [{elapsed_precise}]"),
+    };
+    let mut bar_style = ProgressStyle::with_template(template)
+        .expect("invalid progress bar template");
+    if style.ascii {
+        bar_style = bar_style.progress_chars("#>-");
+    }
+    bar.set_style(bar_style);
+    bar.enable_steady_tick(Duration::from_millis(500));
+    state().progress = Some(bar);
+}
+
+pub fn has_progress() -> bool {
+    state().progress.is_some()
+}
+
+/// starts holding back the output, when quiet or there is a progress bar
+pub fn begin_round() {
+    let mut state = state();
+    if state.quiet || state.progress.is_some() {
+        state.round = Some(String::new());
+    }
+}
+
+/// prints the held back output of a failed round
+pub fn end_round(failed: bool) {
+    let mut state = state();
+    if let Some(round) = state.round.take() {
+        if failed {
+            print_report(&state, round.trim_end());
+        }
+    }
+}
+
+/// moves the progress bar to where the rounds got, it's the progress callback of the command line
+pub fn show_progress(progress: Progress) {
+    if let Some(bar) = &state().progress {
+        bar.set_message(format!("{} passed, {} failed", progress.passed, progress.failed));
+        bar.set_position(progress.completed);
+    }
+}
+
+/// leaves the final state of the progress bar on the screen
+pub fn finish_progress() {
+    let mut state = state();
+    if let Some(bar) = state.progress.take() {
+        bar.finish();
+    }
+}
+
+/// formats the number with thousands separators, e.g. 10,000
+pub fn thousands(n: u64) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// e.g. 512 B, 3.4 kB or 1.2 GB, counting 1024 bytes to the kB
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["kB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = None;
+    while size >= 1024.0 && unit.is_none_or(|u| u + 1 < UNITS.len()) {
+        size /= 1024.0;
+        unit = Some(unit.map_or(0, |u| u + 1));
+    }
+    match unit {
+        None => format!("{} B", bytes),
+        Some(u) => format!("{:.1} {}", size, UNITS[u]),
+    }
+}
+
+/// e.g. 1h 4m, 4m 10s or 12s
+pub fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    match (secs / 3600, secs / 60 % 60, secs % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m {}s", m, s),
+        (h, m, _) => format!("{}h {}m", h, m),
+    }
+}
+
+/// the width of the reports when the output isn't a terminal, so that files and CI logs look the same everywhere
+pub const DEFAULT_WIDTH: usize = 100;
+
+/// the width of the terminal the human readable output goes to, detected anew for every session
+pub fn detect_width() -> usize {
+    let size = if state().to_stderr {
+        io::stderr().is_terminal().then(|| terminal_size::terminal_size_of(io::stderr())).flatten()
+    } else {
+        io::stdout().is_terminal().then(|| terminal_size::terminal_size_of(io::stdout())).flatten()
+    };
+    size.map(|(terminal_size::Width(w), _)| w as usize).filter(|w| *w > 0).unwrap_or(DEFAULT_WIDTH)
+}
+
+/// cuts the line to `width` characters, ending with the ellipsis, a width of 0 doesn't cut
+pub fn fit_line<'a>(line: &'a str, width: usize, ellipsis: &str) -> Cow<'a, str> {
+    if width == 0 || line.chars().count() <= width {
+        return Cow::Borrowed(line);
+    }
+    let keep = width.saturating_sub(ellipsis.chars().count());
+    Cow::Owned(line.chars().take(keep).chain(ellipsis.chars()).collect())
+}
+
+/// cuts every line of the text to `width` characters
+pub fn fit<'a>(text: &'a str, width: usize, ellipsis: &str) -> Cow<'a, str> {
+    if width == 0 || text.lines().all(|l| l.chars().count() <= width) {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(text.lines().map(|l| fit_line(l, width, ellipsis)).collect::<Vec<_>>().join("\n"))
+}
+
+/// Keeps the first and the last lines of a long text, replacing the middle with a marker,
+/// then does the same by bytes for texts with very long lines.
+pub fn truncate_middle<'a>(text: &'a str, max_lines: usize, max_bytes: usize, note: &str) -> Cow<'a, str> {
+    let lines: Vec<&str> = text.lines().collect();
+    let text: Cow<str> = if lines.len() > max_lines {
+        let head = max_lines.div_ceil(2);
+        let tail = max_lines / 2;
+        Cow::Owned(format!("{}\n... {} lines omitted{} ...\n{}",
+            lines[..head].join("\n"), thousands((lines.len() - head - tail) as u64), note, lines[lines.len() - tail..].join("\n")))
+    } else {
+        Cow::Borrowed(text)
+    };
+    if text.len() <= max_bytes {
+        return text;
+    }
+    let mut head = max_bytes.div_ceil(2);
+    while !text.is_char_boundary(head) { head -= 1; }
+    let mut tail = text.len() - max_bytes / 2;
+    while !text.is_char_boundary(tail) { tail += 1; }
+    Cow::Owned(format!("{}\n... {} bytes omitted{} ...\n{}",
+        &text[..head], thousands((tail - head) as u64), note, &text[tail..]))
+}
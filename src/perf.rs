@@ -0,0 +1,148 @@
+use std::{
+    fmt::Write as _,
+    fs,
+    io,
+    path::Path,
+    time::Duration,
+    };
+
+use crate::session::RoundMeta;
+use crate::{create_owned_dir, output, Verdict};
+
+/// What the programme is compared to the references by: the time of `--perf-compare`, or the peak memory in
+/// kilobytes of `--mem-compare`.
+pub trait Measure: Copy + Ord {
+    /// how many times the other this is
+    fn ratio(self, other: Self) -> f64;
+
+    /// such as 0.250s or 64.0 MB
+    fn show(self) -> String;
+
+    /// too small to be compared to
+    fn is_zero(self) -> bool;
+}
+
+impl Measure for Duration {
+    fn ratio(self, other: Self) -> f64 {
+        self.as_secs_f64() / other.as_secs_f64()
+    }
+
+    fn show(self) -> String {
+        format!("{:.3}s", self.as_secs_f64())
+    }
+
+    fn is_zero(self) -> bool {
+        Duration::is_zero(&self)
+    }
+}
+
+/// in kilobytes
+impl Measure for u64 {
+    fn ratio(self, other: Self) -> f64 {
+        self as f64 / other as f64
+    }
+
+    fn show(self) -> String {
+        output::human_size(self * 1024)
+    }
+
+    fn is_zero(self) -> bool {
+        self == 0
+    }
+}
+
+/// a round in which the programme was measured against the best of the references
+#[derive(Clone, Debug, PartialEq)]
+pub struct ComparedRound<M> {
+    pub round: u64,
+    pub seed: u64,
+    pub verdict: Verdict,
+    pub program: M,
+    /// of the best reference
+    pub reference: M,
+    pub input: String,
+}
+
+impl<M: Measure> ComparedRound<M> {
+    /// The programme against the best reference of the round, none when either wasn't measured or the
+    /// reference measured nothing.
+    pub fn of(round: u64, seed: u64, verdict: Verdict, program: Option<M>, references: &[Option<M>], input: &str) -> Option<Self> {
+        let reference = references.iter().flatten().min().copied().filter(|m| !m.is_zero())?;
+        Some(ComparedRound { round, seed, verdict, program: program?, reference, input: input.to_string() })
+    }
+
+    /// how many times the measure of the best reference the programme took
+    pub fn ratio(&self) -> f64 {
+        self.program.ratio(self.reference)
+    }
+}
+
+/// The programme against the references over a session. Only the inputs of the `top` rounds in which the
+/// programme compared the worst are kept, the others are just counted.
+pub struct WorstRounds<M> {
+    factor: f64,
+    top: usize,
+    /// the worst compared first
+    worst: Vec<ComparedRound<M>>,
+    pub compared: u64,
+    /// in which the programme took more than the factor
+    pub flagged: u64,
+}
+
+impl<M: Measure> WorstRounds<M> {
+    pub fn new(factor: f64, top: usize) -> Self {
+        WorstRounds { factor, top, worst: Vec::with_capacity(top + 1), compared: 0, flagged: 0 }
+    }
+
+    /// whether the programme took more than the factor in the round
+    pub fn is_flagged(&self, compared: &ComparedRound<M>) -> bool {
+        compared.ratio() > self.factor
+    }
+
+    /// counts the round, returning whether it's flagged
+    pub fn add(&mut self, compared: ComparedRound<M>) -> bool {
+        let flagged = self.is_flagged(&compared);
+        self.compared += 1;
+        self.flagged += u64::from(flagged);
+        if self.worst.len() < self.top || self.worst.last().is_some_and(|best| best.ratio() < compared.ratio()) {
+            let at = self.worst.partition_point(|worse| worse.ratio() >= compared.ratio());
+            self.worst.insert(at, compared);
+            self.worst.truncate(self.top);
+        }
+        flagged
+    }
+
+    pub fn factor(&self) -> f64 {
+        self.factor
+    }
+
+    /// the worst compared first
+    pub fn worst(&self) -> &[ComparedRound<M>] {
+        &self.worst
+    }
+}
+
+/// a round in which the programme was timed against the fastest of the references
+pub type PerfRound = ComparedRound<Duration>;
+
+/// The times of the programme against those of the references over the session of `--perf-compare`.
+pub type PerfComparison = WorstRounds<Duration>;
+
+impl PerfComparison {
+    /// the programme against the fastest reference of the round
+    pub fn timed(round: u64, seed: u64, verdict: Verdict, meta: &RoundMeta, input: &str) -> Option<PerfRound> {
+        ComparedRound::of(round, seed, verdict, meta.program, &meta.references, input)
+    }
+}
+
+/// writes the inputs of the flagged rounds as `round_N.in` into the directory, with their measures in the file
+pub fn save<M: Measure>(dir: &Path, flagged: &[&ComparedRound<M>], file: &str) -> io::Result<()> {
+    create_owned_dir(dir)?;
+    let mut measures = String::new();
+    for compared in flagged {
+        fs::write(dir.join(format!("round_{}.in", compared.round)), &compared.input)?;
+        let _ = writeln!(measures, "round {}: {} against {}, {:.1}x, verdict {}, seed {}",
+            compared.round, compared.program.show(), compared.reference.show(), compared.ratio(), compared.verdict.code(), compared.seed);
+    }
+    fs::write(dir.join(file), measures)
+}
@@ -0,0 +1,153 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    io,
+    path::{Path, PathBuf},
+    process,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+    };
+use log::{debug, warn};
+
+use crate::capture::Captured;
+use crate::options::Programs;
+use crate::{command_line, create_owned_dir, hash_str, Role};
+
+/// The outputs of the references on the inputs they already ran on, for `--ref-cache`. An output is kept in
+/// a file named by the hashes of the reference and of the input, after a line with the hash of the output
+/// which tells a damaged file from a good one.
+#[derive(Clone, Debug)]
+pub struct RefCache {
+    dir: PathBuf,
+    /// the hash of every reference, of its binary or script and how it's run
+    references: HashMap<PathBuf, u64>,
+}
+
+impl RefCache {
+    /// opens the cache for the (built) references, first evicting the least recently used outputs beyond `max_bytes`
+    pub fn open(dir: &Path, max_bytes: u64, programs: &Programs) -> io::Result<Self> {
+        create_owned_dir(dir)?;
+        let mut references = HashMap::new();
+        for reference in &programs.reference {
+            let command = command_line(reference, Role::Reference, programs).map_err(|e| io::Error::other(e.to_string()))?;
+            let contents = String::from_utf8_lossy(&fs::read(reference)?).into_owned();
+            references.insert(reference.clone(), hash_str(&format!("{}\0{}", command, contents)));
+        }
+        match evict(dir, max_bytes) {
+            Ok(0) => {},
+            Ok(n) => debug!("evicted {} outputs from the reference cache {}", n, dir.display()),
+            Err(e) => warn!("couldn't evict from the reference cache {}: {}", dir.display(), e),
+        }
+        Ok(RefCache { dir: dir.to_path_buf(), references })
+    }
+
+    fn entry(&self, reference: &Path, input: &str) -> Option<PathBuf> {
+        let reference = self.references.get(reference)?;
+        Some(self.dir.join(format!("{:016x}_{:016x}.out", reference, hash_str(input))))
+    }
+
+    /// the output of the reference on the input, None when it isn't cached or its file is damaged
+    pub fn get(&self, reference: &Path, input: &str) -> Option<String> {
+        let entry = self.entry(reference, input)?;
+        let text = fs::read_to_string(&entry).ok()?;
+        let checked = text.split_once('\n')
+            .filter(|(hash, output)| *hash == format!("{:016x}", hash_str(output)))
+            .map(|(_, output)| output.to_string());
+        if checked.is_none() {
+            warn!("the reference cache entry {} is damaged, running the reference instead", entry.display());
+            let _ = fs::remove_file(&entry);
+            return None;
+        }
+        // the time of the last use, which decides what gets evicted first
+        if let Err(e) = fs::File::options().append(true).open(&entry).and_then(|f| f.set_modified(SystemTime::now())) {
+            debug!("couldn't mark {} as used: {}", entry.display(), e);
+        }
+        checked
+    }
+
+    /// keeps the output, written under another name first so that another session never reads half of it
+    pub fn put(&self, reference: &Path, input: &str, output: &str) -> io::Result<()> {
+        let Some(entry) = self.entry(reference, input) else { return Ok(()) };
+        let partial = entry.with_extension(format!("{}.partial", process::id()));
+        fs::write(&partial, format!("{:016x}\n{}", hash_str(output), output))?;
+        fs::rename(&partial, &entry)
+    }
+}
+
+/// removes the least recently used outputs until the cache holds at most `max_bytes`, returns how many were removed
+fn evict(dir: &Path, max_bytes: u64) -> io::Result<usize> {
+    let mut entries = vec![];
+    let mut total = 0;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.extension().is_some_and(|e| e == "out") {
+            let meta = entry.metadata()?;
+            total += meta.len();
+            entries.push((meta.modified()?, meta.len(), path));
+        }
+    }
+    entries.sort();
+    let mut evicted = 0;
+    for (_, size, path) in entries {
+        if total <= max_bytes {
+            break;
+        }
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {},
+        }
+        total -= size;
+        evicted += 1;
+    }
+    Ok(evicted)
+}
+
+/// The outputs of the references on the inputs of a session, kept in memory so that they don't run again
+/// on an input which comes again. It remembers the last `capacity` inputs, and is shared by its clones.
+#[derive(Clone, Debug, Default)]
+pub struct RefMemo {
+    capacity: usize,
+    remembered: Arc<Mutex<Remembered>>,
+}
+
+#[derive(Debug, Default)]
+struct Remembered {
+    /// by the hash of the input
+    outputs: HashMap<u64, Vec<Captured>>,
+    /// the hashes of the inputs, from the oldest
+    order: VecDeque<u64>,
+}
+
+impl RefMemo {
+    pub fn new(capacity: usize) -> Self {
+        RefMemo { capacity, remembered: Arc::default() }
+    }
+
+    /// whether it remembers anything at all
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// the output of every reference on the input, in their order
+    pub fn get(&self, input: &str) -> Option<Vec<Captured>> {
+        self.remembered.lock().unwrap().outputs.get(&hash_str(input)).cloned()
+    }
+
+    /// remembers the outputs of the references on the input, forgetting the oldest input beyond the capacity
+    pub fn put(&self, input: &str, outputs: Vec<Captured>) {
+        if !self.is_enabled() {
+            return;
+        }
+        let mut remembered = self.remembered.lock().unwrap();
+        let key = hash_str(input);
+        if remembered.outputs.insert(key, outputs).is_none() {
+            remembered.order.push_back(key);
+        }
+        while remembered.order.len() > self.capacity {
+            if let Some(oldest) = remembered.order.pop_front() {
+                remembered.outputs.remove(&oldest);
+            }
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    collections::{BTreeMap, HashMap},
+    };
+use serde::{Deserialize, Serialize};
+
+use crate::capture::Captured;
+use crate::environment::Environment;
+use crate::options::Cli;
+use crate::session::RoundResult;
+use crate::{generator_invocation, hash_str, Round, Failure, Diagnostic, ExecutionResult, ExecutionStats, Mismatch, Verdict, Signature, Toolchain};
+
+/// A piece of text which may have been cut short to keep the report small.
+#[derive(Clone, Debug, Serialize)]
+pub struct Blob {
+    pub text: String,
+    /// the text was cut at `--report-max-output` bytes, or at the beginning of an output written to a file
+    pub truncated: bool,
+    /// length of the whole text in bytes
+    pub bytes: usize,
+}
+
+impl Blob {
+    pub fn new(text: &str, max: usize) -> Self {
+        let mut end = text.len().min(max);
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        Blob { text: text[..end].to_string(), truncated: end < text.len(), bytes: text.len() }
+    }
+
+    /// the blob of an output, cut short at its beginning as well when it was written to a file
+    pub fn of_output(out: &Captured, max: usize) -> Self {
+        let blob = Blob::new(out.preview(), max);
+        Blob { truncated: blob.truncated || out.is_spilled(), bytes: out.len() as usize, ..blob }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct OutputRecord {
+    pub path: PathBuf,
+    /// the position of a reference among the references, from 1
+    pub reference: Option<usize>,
+    pub output: Blob,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FailureRecord {
+    pub path: PathBuf,
+    /// the position of a reference among the references, from 1
+    pub reference: Option<usize>,
+    pub verdict: Verdict,
+    pub status: Option<String>,
+    pub stderr: Option<Blob>,
+    pub signal: Option<i32>,
+}
+
+/// Everything the report says about a single round.
+#[derive(Clone, Debug, Serialize)]
+pub struct RoundRecord {
+    pub round: u64,
+    pub seed: u64,
+    pub verdict: Verdict,
+    pub signature: Signature,
+    pub seconds: f64,
+    /// when the round started, in seconds since the unix epoch
+    pub started_at: f64,
+    /// the source of the generator which wrote the input
+    pub generator: Option<PathBuf>,
+    /// the shell command which writes the input again
+    pub reproduce: String,
+    pub generator_seconds: Option<f64>,
+    pub program_seconds: Option<f64>,
+    /// in the order of the references, absent for those which didn't run
+    pub reference_seconds: Vec<Option<f64>>,
+    pub retries: u32,
+    pub input: Option<Blob>,
+    /// where the input was saved by `--save-failures`
+    pub input_path: Option<PathBuf>,
+    /// the smaller input which fails the same way, found by `--minimize`
+    pub minimized_input: Option<Blob>,
+    pub program: Option<OutputRecord>,
+    pub references: Vec<OutputRecord>,
+    pub failures: Vec<FailureRecord>,
+    pub diagnostics: Vec<Diagnostic>,
+    pub executions: Vec<ExecutionStats>,
+}
+
+impl RoundRecord {
+    pub fn new(args: &Cli, result: &RoundResult) -> Self {
+        let outs = &result.outcome;
+        let max = args.report_max_output;
+        let output = |x: &ExecutionResult| OutputRecord {
+            path: args.source_of(&x.path).to_path_buf(),
+            reference: None,
+            output: Blob::of_output(&x.stdout, max),
+        };
+        let reference = |i: usize, x: &ExecutionResult| OutputRecord { reference: Some(i + 1), ..output(x) };
+        let failure = |fail: &Failure| failure_record(args, fail);
+
+        let mut record = RoundRecord {
+            round: result.round,
+            seed: result.seed,
+            verdict: Verdict::of_round(outs),
+            signature: Signature::of_round(outs),
+            seconds: result.duration.as_secs_f64(),
+            started_at: result.meta.started_secs(),
+            generator: result.meta.generator_source.clone(),
+            reproduce: generator_invocation(args, result.round, result.seed),
+            generator_seconds: result.meta.generator.map(|d| d.as_secs_f64()),
+            program_seconds: result.meta.program.map(|d| d.as_secs_f64()),
+            reference_seconds: result.meta.references.iter().map(|d| d.map(|d| d.as_secs_f64())).collect(),
+            retries: result.retries,
+            input: None,
+            input_path: None,
+            minimized_input: None,
+            program: None,
+            references: vec![],
+            failures: vec![],
+            diagnostics: vec![],
+            executions: result.executions.iter()
+                .map(|e| ExecutionStats { path: args.source_of(&e.path).to_path_buf(), ..e.clone() })
+                .collect(),
+        };
+        match outs {
+            Round::GeneratorFail(fail) => record.failures.push(failure(fail)),
+            // the references which succeeded are kept along with those which failed
+            Round::ReferenceFails { refs, .. } => for (i, r) in refs.iter().enumerate() {
+                match r {
+                    Ok(x) => record.references.push(reference(i, x)),
+                    Err(fail) => record.failures.push(FailureRecord { reference: Some(i + 1), ..failure(fail) }),
+                }
+            },
+            Round::ProgramFail { fail, diagnostics, .. } => {
+                record.failures.push(failure(fail));
+                record.diagnostics = diagnostics.clone();
+            },
+            Round::Success { prog, refs, .. } => {
+                record.program = Some(output(prog));
+                record.references = refs.iter().enumerate().map(|(i, x)| reference(i, x)).collect();
+            },
+            Round::Timeout { .. } => {},
+        }
+        record.input = outs.input().map(|inp| Blob::new(inp, max));
+        record
+    }
+}
+
+fn failure_record(args: &Cli, fail: &Failure) -> FailureRecord {
+    let max = args.report_max_output;
+    let (status, stderr, signal) = match fail {
+        Failure::Prog(x) => (x.status.clone(), Some(Blob::new(&x.stderr, max)), x.signal),
+        Failure::Spawn(e) => (None, Some(Blob::new(&e.describe(args, true), max)), None),
+        Failure::TimeLimit(_) | Failure::RoundTimeout(_) => (None, None, None),
+    };
+    FailureRecord { path: args.source_of(fail.path()).to_path_buf(), reference: None, verdict: Verdict::of_failure(fail), status, stderr, signal }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Summary {
+    pub rounds: usize,
+    pub passed: usize,
+    pub failed: usize,
+    /// how many rounds ended with each verdict
+    pub verdicts: BTreeMap<Verdict, usize>,
+    pub exit_code: i32,
+    /// the session was cancelled before all of its rounds ran
+    #[serde(default)]
+    pub cancelled: bool,
+}
+
+impl Summary {
+    pub fn add(&mut self, verdict: Verdict) {
+        self.rounds += 1;
+        if verdict == Verdict::Ok { self.passed += 1; } else { self.failed += 1; }
+        *self.verdicts.entry(verdict).or_insert(0) += 1;
+    }
+}
+
+/// what tells identical failures apart: the verdict, the input and the output of the programme (the beginning
+/// of it when it was written to a file), whitespace at the ends of the lines aside
+fn fingerprint(inp: &str, mismatch: &Mismatch) -> u64 {
+    let normalize = |text: &str| text.lines().map(str::trim_end).collect::<Vec<_>>().join("\n").trim_end().to_string();
+    let prog = match mismatch {
+        Mismatch::ProgMismatch { prog, .. } => prog.stdout.preview(),
+        _ => "",
+    };
+    hash_str(&format!("{}\0{}\0{}", Verdict::of_mismatch(mismatch).code(), normalize(inp), normalize(prog)))
+}
+
+/// a round whose programme and references ran, but whose outputs didn't all match
+#[derive(Clone, Debug)]
+pub struct MismatchedRound {
+    pub round: u64,
+    pub seed: u64,
+    pub input: String,
+    pub mismatch: Mismatch,
+}
+
+/// a round which didn't pass
+#[derive(Clone, Debug)]
+pub struct FailedRound {
+    pub signature: Signature,
+    pub round: u64,
+    pub seed: u64,
+    /// None when the generator didn't produce one
+    pub input: Option<String>,
+}
+
+/// identical mismatches, the first of them and how many there were
+#[derive(Clone, Debug)]
+pub struct DistinctFailure {
+    pub first: MismatchedRound,
+    pub occurrences: u64,
+}
+
+/// the failing rounds with the same signature, the first of them and how many there were
+#[derive(Clone, Debug)]
+pub struct FailureMode {
+    pub first: FailedRound,
+    pub rounds: usize,
+}
+
+/// The mismatches of a session, with the identical ones only once when `dedup`.
+pub fn distinct_failures(fails: Vec<MismatchedRound>, dedup: bool) -> Vec<DistinctFailure> {
+    let mut distinct: Vec<DistinctFailure> = vec![];
+    let mut seen: HashMap<u64, usize> = HashMap::new();
+    for fail in fails {
+        let key = dedup.then(|| fingerprint(&fail.input, &fail.mismatch));
+        match key.and_then(|k| seen.get(&k)) {
+            Some(&i) => distinct[i].occurrences += 1,
+            None => {
+                if let Some(key) = key {
+                    seen.insert(key, distinct.len());
+                }
+                distinct.push(DistinctFailure { first: fail, occurrences: 1 });
+            },
+        }
+    }
+    distinct
+}
+
+/// Groups the failing rounds by their signature, the most frequent first.
+pub fn failure_modes(failures: Vec<FailedRound>) -> Vec<FailureMode> {
+    let mut groups: Vec<FailureMode> = vec![];
+    for failure in failures {
+        match groups.iter_mut().find(|g| g.first.signature == failure.signature) {
+            Some(group) => group.rounds += 1,
+            None => groups.push(FailureMode { first: failure, rounds: 1 }),
+        }
+    }
+    groups.sort_by_key(|g| std::cmp::Reverse(g.rounds));
+    groups
+}
+
+/// The document written by `--report-json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct Report {
+    /// the session seed, absent when the input was read from a file
+    pub seed: Option<u64>,
+    /// the c++ compiler which built the sources, when any were
+    pub toolchain: Option<Toolchain>,
+    /// what the session ran on and with
+    pub environment: Option<Environment>,
+    pub rounds: Vec<RoundRecord>,
+    pub summary: Summary,
+}
+
+impl Report {
+    pub fn new(args: &Cli, rounds: Vec<RoundRecord>, summary: Summary) -> Self {
+        let seed = if args.input_file.is_some() { None } else { args.seed };
+        Report { seed, toolchain: args.toolchain.clone(), environment: args.environment.clone(), rounds, summary }
+    }
+
+    /// writes the report to the file, or to stdout when the path is `-`
+    pub fn write(&self, dest: &Path) -> io::Result<()> {
+        let mut out: Box<dyn Write> = if dest == Path::new("-") {
+            Box::new(io::stdout().lock())
+        } else {
+            Box::new(fs::File::create(dest)?)
+        };
+        serde_json::to_writer_pretty(&mut out, self)?;
+        writeln!(out)?;
+        out.flush()
+    }
+}
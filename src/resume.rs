@@ -0,0 +1,112 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io,
+    path::Path,
+    };
+use std::error::Error;
+use serde::{Deserialize, Serialize};
+use string_error::into_err;
+
+use crate::options::Cli;
+use crate::report::Summary;
+use crate::Verdict;
+
+/// a failing round of the part of the session before it was resumed
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PastFailure {
+    pub round: u64,
+    pub seed: u64,
+    pub verdict: Verdict,
+}
+
+/// The progress of a `--session`, rewritten after every round so that an interrupted session can go on
+/// from the next round with the same seeds.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SessionFile {
+    /// the options which decide the inputs and the verdicts, they can't change when resuming
+    pub config: BTreeMap<String, String>,
+    pub seed: u64,
+    /// how many rounds are done, the session goes on from this one
+    pub rounds_done: u64,
+    pub summary: Summary,
+    pub failed_rounds: u64,
+    pub exit_code: i32,
+    pub failures: Vec<PastFailure>,
+}
+
+/// the options recorded in the session file, `args` are those of the session with the sources already built
+fn config_of(args: &Cli) -> BTreeMap<String, String> {
+    let optional = |value: Option<String>| value.unwrap_or_else(|| "none".to_string());
+    let sanitizers = args.programs.sanitize.iter().map(|s| format!("{:?}", s).to_lowercase()).collect::<Vec<_>>();
+    let generators = args.generator.iter()
+        .map(|g| match g.weight {
+            1 => args.source_of(&g.path).display().to_string(),
+            weight => format!("{}:{}", args.source_of(&g.path).display(), weight),
+        })
+        .collect::<Vec<_>>();
+    let references = args.programs.reference.iter().map(|r| args.source_of(r).display().to_string()).collect::<Vec<_>>();
+    BTreeMap::from([
+        ("program".to_string(), args.source_of(&args.programs.program).display().to_string()),
+        ("reference".to_string(), references.join(", ")),
+        ("generator".to_string(), optional(Some(generators.join(", ")).filter(|g| !g.is_empty()))),
+        ("gen-pick".to_string(), format!("{:?}", args.gen_pick).to_lowercase()),
+        ("gen-spec".to_string(), optional(args.gen_spec.as_ref().map(|spec| spec.source().to_string()))),
+        ("grader".to_string(), optional(args.programs.grader.as_ref().map(|g| g.display().to_string()))),
+        ("gen-args".to_string(), optional(args.gen_args.clone())),
+        ("gen-seed-via".to_string(), format!("{:?}", args.gen_seed_via).to_lowercase()),
+        ("cpp-compiler-flags".to_string(), args.programs.flags_of("cpp")),
+        ("define".to_string(), optional(Some(args.programs.define.join(" ")).filter(|d| !d.is_empty()))),
+        ("define-refs".to_string(), optional(Some(args.programs.define_refs.join(" ")).filter(|d| !d.is_empty()))),
+        ("sanitize".to_string(), optional(Some(sanitizers.join(",")).filter(|s| !s.is_empty()))),
+        ("time-limit".to_string(), optional(args.time_limit.map(|t| format!("{}s", t)))),
+        ("time-limit-rel".to_string(), optional(args.time_limit_rel.as_ref().map(|r| format!("{}x:{} at least {}s", r.factor, r.reference.display(), args.time_limit_floor)))),
+        ("round-timeout".to_string(), optional(args.round_timeout.map(|t| format!("{}s", t)))),
+        ("memory-limit".to_string(), optional(args.memory_limit.map(|m| format!("{}kB", m)))),
+    ])
+}
+
+impl SessionFile {
+    pub fn new(args: &Cli, seed: u64) -> Self {
+        SessionFile {
+            config: config_of(args),
+            seed,
+            rounds_done: 0,
+            summary: Summary::default(),
+            failed_rounds: 0,
+            exit_code: 0,
+            failures: vec![],
+        }
+    }
+
+    /// Reads the session to resume, None when the file doesn't exist yet.
+    /// Refuses a session started with other options or another seed.
+    pub fn resume(path: &Path, args: &Cli) -> Result<Option<Self>, Box<dyn Error>> {
+        let text = match fs::read_to_string(path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            text => text?,
+        };
+        let session: SessionFile = serde_json::from_str(&text)
+            .map_err(|e| into_err(format!("it isn't a session file: {}", e)))?;
+        let config = config_of(args);
+        let changed = session.config.iter()
+            .filter(|(key, value)| config.get(*key) != Some(value))
+            .map(|(key, value)| format!("--{} was {} and is now {}", key, value, config.get(key).map_or("missing", String::as_str)))
+            .collect::<Vec<_>>();
+        if !changed.is_empty() {
+            return Err(into_err(format!("it was started with other options: {}", changed.join(", "))));
+        }
+        if let Some(seed) = args.seed.filter(|s| *s != session.seed) {
+            return Err(into_err(format!("it was started with the seed {}, not {}", session.seed, seed)));
+        }
+        Ok(Some(session))
+    }
+
+    /// writes a new file and renames it over the old one, so that a crash never leaves half of a file
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut temporary = path.as_os_str().to_os_string();
+        temporary.push(".tmp");
+        fs::write(&temporary, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&temporary, path)
+    }
+}
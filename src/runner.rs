@@ -0,0 +1,97 @@
+use std::{
+    num::NonZeroUsize,
+    path::Path,
+    sync::{Mutex, PoisonError},
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+    };
+
+use crate::output;
+use crate::{run_failure, start_prog, wait_prog, Cause, Execution, Role, RoundCtx};
+
+/// A programme to run once, as a round asks its `ExecutionBackend` for it.
+#[derive(Clone, Debug)]
+pub struct Invocation<'a> {
+    pub path: &'a Path,
+    /// what the programme is in the session, which decides where it runs
+    pub role: Role,
+    /// added to the command line of the programme
+    pub args: Vec<String>,
+    /// set in its environment
+    pub env: Vec<(String, String)>,
+    /// written to its stdin, which is empty otherwise
+    pub input: Option<&'a str>,
+    /// the round timeout bounds it as well
+    pub limits: Limits,
+}
+
+/// What a programme may use before it's killed, nothing by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    pub time: Option<Duration>,
+    /// in bytes
+    pub memory: Option<usize>,
+}
+
+impl<'a> Invocation<'a> {
+    pub fn new(path: &'a Path, role: Role) -> Self {
+        Invocation { path, role, args: vec![], env: vec![], input: None, limits: Limits::default() }
+    }
+
+    pub fn with_input(mut self, input: &'a str) -> Self {
+        self.input = Some(input);
+        self
+    }
+}
+
+/// How the programmes of a round are run, `Processes` unless another backend is given to `run_round`, such
+/// as a fake one which scripts the outcomes of a test. Like the comparators, backends can be shared by threads.
+pub trait ExecutionBackend: Send + Sync {
+    /// Runs the programme once, bounded by its limits and by what's left of the round. The caller retries the
+    /// failures which look transient.
+    fn execute(&self, invocation: &Invocation, ctx: &RoundCtx) -> Execution;
+
+    /// runs the programmes all at once when the backend can, one after the other otherwise
+    fn execute_all(&self, invocations: &[Invocation], ctx: &RoundCtx) -> Vec<Execution> {
+        invocations.iter().map(|invocation| self.execute(invocation, ctx)).collect()
+    }
+}
+
+/// Runs the programmes as processes of this machine, which are docker or ssh with `--docker` or `--remote`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Processes;
+
+impl ExecutionBackend for Processes {
+    fn execute(&self, invocation: &Invocation, ctx: &RoundCtx) -> Execution {
+        let started = Instant::now();
+        let (child, command) = start_prog(invocation, &ctx.args.programs).map_err(|e| run_failure(ctx, invocation.role, invocation.path, "run", Cause::of(&*e)))?;
+        wait_prog(invocation, child, &command, started, ctx)
+    }
+
+    fn execute_all(&self, invocations: &[Invocation], ctx: &RoundCtx) -> Vec<Execution> {
+        // every programme is started and waited for on a thread, at most --ref-jobs of them at a time, so
+        // that each is timed on its own; they're all bounded by the round timeout
+        let jobs = ctx.args.ref_jobs.map_or(invocations.len(), NonZeroUsize::get).min(invocations.len());
+        let next = AtomicUsize::new(0);
+        let outs = Mutex::new((0..invocations.len()).map(|_| None).collect::<Vec<_>>());
+        let muted = output::is_muted();
+        thread::scope(|scope| {
+            for _ in 0..jobs {
+                scope.spawn(|| {
+                    // the programmes of a session run from another programme say nothing either
+                    let _mute = muted.then(output::mute);
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(invocation) = invocations.get(i) else { break };
+                        let out = self.execute(invocation, ctx);
+                        outs.lock().unwrap_or_else(PoisonError::into_inner)[i] = Some(out);
+                    }
+                });
+            }
+        });
+        outs.into_inner().unwrap_or_else(PoisonError::into_inner).into_iter()
+            .map(|out| out.expect("every programme is run"))
+            .collect()
+    }
+}
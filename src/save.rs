@@ -0,0 +1,106 @@
+use std::{
+    borrow::Cow,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+    };
+
+use crate::capture::Captured;
+use crate::environment::Environment;
+use crate::session::RoundMeta;
+use crate::create_owned_dir;
+
+/// Writes the inputs and outputs of failing rounds into a per-session directory.
+pub struct FailureSaver {
+    dir: PathBuf,
+}
+
+/// what gets saved about a failing round
+pub struct SavedRound<'a> {
+    pub round: u64,
+    pub verdict: &'a str,
+    pub seed: u64,
+    pub duration: Duration,
+    pub meta: &'a RoundMeta,
+    pub input: Option<&'a str>,
+    /// the shell command which writes the input again, see `generator_invocation`
+    pub reproduce: &'a str,
+    /// (file suffix, contents), e.g. ("program.out", ...)
+    pub files: Vec<(String, Cow<'a, Captured>)>,
+}
+
+impl FailureSaver {
+    /// Creates a fresh timestamped subdirectory of `base`, so that re-runs never overwrite each other. Its
+    /// meta.txt starts with the environment of the session, followed by a line for every saved round.
+    pub fn new(base: &Path, environment: Option<&Environment>) -> io::Result<Self> {
+        let secs = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let mut dir = base.join(format!("session_{}", secs));
+        let mut n = 1;
+        while dir.exists() {
+            dir = base.join(format!("session_{}_{}", secs, n));
+            n += 1;
+        }
+        create_owned_dir(base)?;
+        create_owned_dir(&dir)?;
+        if let Some(environment) = environment {
+            let mut meta = environment.lines().join("\n");
+            meta += &format!("\nconfiguration: {}\n\n", environment.config);
+            fs::write(dir.join("meta.txt"), meta)?;
+        }
+        Ok(FailureSaver { dir })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// saves the round and returns the paths of the written files
+    pub fn save(&self, round: &SavedRound) -> io::Result<Vec<PathBuf>> {
+        let mut paths = vec![];
+        let mut write = |suffix: &str, save: &dyn Fn(&Path) -> io::Result<()>| -> io::Result<()> {
+            let path = self.dir.join(format!("round_{}.{}", round.round, suffix));
+            save(&path)?;
+            paths.push(path);
+            Ok(())
+        };
+
+        if let Some(input) = round.input {
+            write("in", &|path| fs::write(path, input))?;
+        }
+        for (suffix, contents) in &round.files {
+            write(suffix, &|path| contents.save(path))?;
+        }
+
+        let mut meta = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join("meta.txt"))?;
+        let seconds = |d: Option<Duration>| d.map_or("-".to_string(), |d| format!("{:.3}s", d.as_secs_f64()));
+        let references = round.meta.references.iter().map(|&d| seconds(d)).collect::<Vec<_>>().join(" ");
+        let generator = round.meta.generator_source.as_ref().map_or(String::new(), |g| format!(" by {}", g.display()));
+        writeln!(meta, "round {}: verdict {}, seed {}{}, started at {:.3}, took {:.3}s (generator {}, program {}, references {})",
+            round.round, round.verdict, round.seed, generator, round.meta.started_secs(), round.duration.as_secs_f64(),
+            seconds(round.meta.generator), seconds(round.meta.program), references)?;
+        if !round.reproduce.is_empty() {
+            writeln!(meta, "  reproduce the input with: {}", round.reproduce)?;
+        }
+        Ok(paths)
+    }
+}
+
+/// file suffixes `<name>.out` for the given outputs, made unique when names repeat
+pub fn output_suffixes<'a, I>(names: I) -> Vec<String>
+where I: Iterator<Item = &'a Path>
+{
+    let names: Vec<String> = names
+        .map(|p| p.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default())
+        .collect();
+    names.iter().enumerate().map(|(i, name)| {
+        if names.iter().filter(|n| *n == name).count() > 1 {
+            format!("{}_{}.out", name, i)
+        } else {
+            format!("{}.out", name)
+        }
+    }).collect()
+}
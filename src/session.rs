@@ -0,0 +1,752 @@
+use std::{
+    error::Error,
+    fmt,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    };
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use string_error::{into_err, static_err};
+
+use crate::genspec::GenSpec;
+use crate::options::{Cli, Generator, OnGenFail};
+use crate::compare::{compare_outputs, Comparator, Exact};
+use crate::output;
+use crate::report::{FailedRound, MismatchedRound, Summary};
+use crate::resume::SessionFile;
+use crate::runner::{ExecutionBackend, Processes};
+use crate::{random_seed, round_seed, run_round, CancellationToken, ExecutionArtifact, ExecutionStats, Role, Round, RoundCtx, Verdict};
+use crate::{is_crash_signal, CrashRerun, Failure, Mismatch, Signature};
+
+/// A stress session run from another programme, or from the command line with `of_cli`. Built with the builder
+/// it never prints anything, the results of its rounds are returned instead:
+/// `Session::builder().generator("gen.py").program("sol.cpp").reference("brute.py").rounds(100).build()?.rounds()`
+pub struct Session {
+    args: Cli,
+    comparator: Box<dyn Comparator>,
+    backend: Box<dyn ExecutionBackend>,
+    observers: Vec<Box<dyn Observer>>,
+    cancellation: CancellationToken,
+    progress: Option<Box<dyn Fn(Progress) + Send + Sync>>,
+    /// nothing its builds and rounds do is printed, unless it's the session of the command line
+    muted: bool,
+}
+
+/// The options of a `Session`, all of them checked and the sources built by `build`.
+#[derive(Default)]
+pub struct SessionBuilder {
+    generators: Vec<Generator>,
+    gen_spec: Option<String>,
+    input_file: Option<PathBuf>,
+    program: Option<PathBuf>,
+    references: Vec<PathBuf>,
+    rounds: Option<u64>,
+    seed: Option<u64>,
+    time_limit: Option<Duration>,
+    memory_limit: Option<usize>,
+    round_timeout: Option<Duration>,
+    spill_threshold: Option<usize>,
+    comparator: Option<Box<dyn Comparator>>,
+    backend: Option<Box<dyn ExecutionBackend>>,
+    observers: Vec<Box<dyn Observer>>,
+    cancellation: Option<CancellationToken>,
+    progress: Option<Box<dyn Fn(Progress) + Send + Sync>>,
+    build_dir: Option<PathBuf>,
+}
+
+/// Watches the rounds of a session as they run, see `SessionBuilder::observer`. It's only shown the results,
+/// it can't change them.
+pub trait Observer: Send + Sync {
+    /// the round is about to run, its generator is given the seed
+    fn on_round_start(&self, _round: u64, _seed: u64) {}
+
+    /// a programme of the round ended, once the round is over, in the order they ended
+    fn on_execution_finished(&self, _round: u64, _execution: &ExecutionStats) {}
+
+    fn on_verdict(&self, _result: &RoundResult) {}
+
+    /// the rounds were dropped, whether they all ran, they were stopped before or the session was cancelled
+    fn on_session_end(&self, _summary: &Summary) {}
+}
+
+impl fmt::Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Session").field("args", &self.args).finish_non_exhaustive()
+    }
+}
+
+impl fmt::Debug for SessionBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionBuilder").field("program", &self.program).field("rounds", &self.rounds).finish_non_exhaustive()
+    }
+}
+
+/// what came out of a round of a `Session`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RoundResult {
+    pub round: u64,
+    /// the seed the generator was given
+    pub seed: u64,
+    /// a round whose programme ran is only Ok when its output matches the references
+    pub verdict: Verdict,
+    pub duration: Duration,
+    pub outcome: Round,
+    /// how many executions had to be repeated
+    pub retries: u32,
+    pub executions: Vec<ExecutionStats>,
+    pub meta: RoundMeta,
+    /// the outputs of the executions, only kept for `--keep-artifacts`
+    #[serde(skip)]
+    pub artifacts: Vec<ExecutionArtifact>,
+}
+
+/// When a round started and how long each of its programmes ran, the last time when it was retried. A
+/// programme which didn't run, such as a reference whose output was remembered, has no duration.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RoundMeta {
+    pub started_at: SystemTime,
+    /// the source of the generator which wrote the input, of the one it was picked among several
+    pub generator_source: Option<PathBuf>,
+    pub generator: Option<Duration>,
+    pub program: Option<Duration>,
+    /// in the order of the references
+    pub references: Vec<Option<Duration>>,
+    /// the peak memory of the programme in kilobytes, only measured with --mem-compare
+    #[serde(default)]
+    pub program_memory_kb: Option<u64>,
+    /// in the order of the references
+    #[serde(default)]
+    pub references_memory_kb: Vec<Option<u64>>,
+}
+
+impl RoundMeta {
+    fn of(ctx: &RoundCtx, started_at: SystemTime, executions: &[ExecutionStats]) -> Self {
+        let args = ctx.args;
+        // the same source may have run as the programme and as a reference, which only their roles tell apart
+        let last_of = |role: Role, path: &Path| executions.iter().rev().find(|e| e.role == role && e.path == path);
+        let last = |role, path| last_of(role, path).map(|e| Duration::from_secs_f64(e.seconds));
+        let memory = |role, path| last_of(role, path).and_then(|e| e.memory_kb);
+        let generator = args.generator_of(ctx.round, ctx.seed);
+        RoundMeta {
+            started_at,
+            generator_source: generator.map(|g| args.source_of(&g.path).to_path_buf()),
+            generator: generator.and_then(|g| last(Role::Generator, &g.path)),
+            program: last(Role::Program, &args.programs.program),
+            references: args.programs.reference.iter().map(|r| last(Role::Reference, r)).collect(),
+            program_memory_kb: memory(Role::Program, &args.programs.program),
+            references_memory_kb: args.programs.reference.iter().map(|r| memory(Role::Reference, r)).collect(),
+        }
+    }
+
+    /// in seconds since the unix epoch
+    pub fn started_secs(&self) -> f64 {
+        self.started_at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64()
+    }
+}
+
+/// how long the programmes of the rounds of a session ran altogether
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StageTimes {
+    pub generator: Duration,
+    pub program: Duration,
+    pub references: Duration,
+}
+
+impl StageTimes {
+    pub fn add(&mut self, meta: &RoundMeta) {
+        self.generator += meta.generator.unwrap_or_default();
+        self.program += meta.program.unwrap_or_default();
+        self.references += meta.references.iter().flatten().sum::<Duration>();
+    }
+}
+
+impl RoundResult {
+    /// Runs the round with the backend, judging its outputs with the comparator, until the token is cancelled.
+    /// The same round gets the same input in every session with the same seed.
+    pub fn of<C: Comparator + ?Sized>(args: &Cli, backend: &dyn ExecutionBackend, comparator: &C, round: u64, cancellation: &CancellationToken) -> Self {
+        let ctx = RoundCtx::new(args, round).with_cancellation(cancellation.clone());
+        let (started, started_at) = (Instant::now(), SystemTime::now());
+        let outcome = run_round(&ctx, backend);
+        let duration = started.elapsed();
+        let verdict = match &outcome {
+            Round::Success { prog, refs, .. } if !refs.is_empty() => Verdict::of_mismatch(&compare_outputs(prog, refs, comparator)),
+            outcome => Verdict::of_round(outcome),
+        };
+        let executions = ctx.executions();
+        let meta = RoundMeta::of(&ctx, started_at, &executions);
+        RoundResult { round, seed: ctx.seed, verdict, duration, outcome, retries: ctx.retries(), executions, meta, artifacts: ctx.take_artifacts() }
+    }
+}
+
+/// How far the rounds got, given to the progress callback after every round, see `Rounds::on_progress`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Progress {
+    /// the rounds before the upcoming one, including those a resumed session ran before
+    pub completed: u64,
+    /// None when the rounds go on for ever
+    pub total: Option<u64>,
+    /// the verdicts of the rounds run, and of those of the summary the rounds resumed
+    pub passed: u64,
+    pub failed: u64,
+    /// since the rounds were created
+    pub elapsed: Duration,
+}
+
+/// The rounds of a session, each one run only when it's asked for, so that it can be stopped after any of
+/// them and resumed from `upcoming` later.
+pub struct Rounds<'a> {
+    args: &'a Cli,
+    comparator: &'a dyn Comparator,
+    backend: &'a dyn ExecutionBackend,
+    next: u64,
+    /// the round after the last one, None to go on for ever
+    end: Option<u64>,
+    observers: &'a [Box<dyn Observer>],
+    cancellation: CancellationToken,
+    /// the verdicts of the rounds run so far
+    summary: Summary,
+    started: Instant,
+    on_progress: Option<&'a (dyn Fn(Progress) + Sync)>,
+    /// nothing the rounds do is printed, they're those of a `Session`
+    muted: bool,
+}
+
+impl<'a> Rounds<'a> {
+    pub fn new(args: &'a Cli, comparator: &'a dyn Comparator, first: u64, end: Option<u64>) -> Self {
+        Rounds {
+            args, comparator, backend: &Processes, next: first, end, observers: &[], cancellation: CancellationToken::new(),
+            summary: Summary::default(), started: Instant::now(), on_progress: None, muted: false,
+        }
+    }
+
+    /// the rounds go on from the summary of a session stopped before the first of them
+    pub fn resumed(mut self, summary: Summary) -> Self {
+        self.summary = summary;
+        self
+    }
+
+    /// the callback is given the progress after every round, from the thread which runs them
+    pub fn on_progress(mut self, callback: &'a (dyn Fn(Progress) + Sync)) -> Self {
+        self.on_progress = Some(callback);
+        self
+    }
+
+    pub fn progress(&self) -> Progress {
+        Progress {
+            completed: self.next,
+            total: self.end,
+            passed: self.summary.passed as u64,
+            failed: self.summary.failed as u64,
+            elapsed: self.started.elapsed(),
+        }
+    }
+
+    /// the programmes are run by the backend rather than as processes
+    pub fn executed_by(mut self, backend: &'a dyn ExecutionBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// no round starts once the token is cancelled, and the one which runs is stopped and dropped
+    pub fn cancelled_by(mut self, token: CancellationToken) -> Self {
+        self.cancellation = token;
+        self
+    }
+
+    /// whether the rounds stopped because they were cancelled, the summary says so as well
+    pub fn cancelled(&self) -> bool {
+        self.summary.cancelled
+    }
+
+    /// the verdicts of the rounds run so far
+    pub fn summary(&self) -> &Summary {
+        &self.summary
+    }
+
+    /// the observers are shown every round
+    pub fn observed(mut self, observers: &'a [Box<dyn Observer>]) -> Self {
+        self.observers = observers;
+        self
+    }
+
+    /// nothing the rounds do is printed, while the rest of the programme goes on printing
+    pub fn muted(mut self) -> Self {
+        self.muted = true;
+        self
+    }
+
+    /// the round which comes next, None when they're all done
+    pub fn upcoming(&self) -> Option<u64> {
+        self.end.is_none_or(|end| self.next < end).then_some(self.next)
+    }
+}
+
+impl Iterator for Rounds<'_> {
+    type Item = RoundResult;
+
+    fn next(&mut self) -> Option<RoundResult> {
+        let round = self.upcoming()?;
+        if self.cancellation.is_cancelled() {
+            self.summary.cancelled = true;
+            return None;
+        }
+        let _mute = self.muted.then(output::mute);
+        let seed = round_seed(self.args.seed.unwrap_or_default(), round);
+        self.observers.iter().for_each(|o| o.on_round_start(round, seed));
+        let result = RoundResult::of(self.args, self.backend, self.comparator, round, &self.cancellation);
+        // the programmes of the round were killed, so it says nothing, and it's run again when resumed
+        if self.cancellation.is_cancelled() {
+            self.summary.cancelled = true;
+            return None;
+        }
+        self.next += 1;
+        for execution in &result.executions {
+            self.observers.iter().for_each(|o| o.on_execution_finished(round, execution));
+        }
+        self.summary.add(result.verdict);
+        self.observers.iter().for_each(|o| o.on_verdict(&result));
+        if let Some(callback) = self.on_progress {
+            callback(self.progress());
+        }
+        Some(result)
+    }
+}
+
+impl Drop for Rounds<'_> {
+    fn drop(&mut self) {
+        self.observers.iter().for_each(|o| o.on_session_end(&self.summary));
+    }
+}
+
+/// the sources which couldn't be preprocessed, and why
+#[derive(Debug)]
+pub struct PrepareError {
+    pub failed: Vec<(PathBuf, Box<dyn Error>)>,
+}
+
+impl fmt::Display for PrepareError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failed = self.failed.iter().map(|(source, e)| format!("{}: {}", source.display(), e)).collect::<Vec<_>>();
+        write!(f, "couldn't prepare {}", failed.join("; "))
+    }
+}
+
+impl Error for PrepareError {}
+
+impl SessionBuilder {
+    /// the test-case generator, given the seed of every round like with `--gen-seed-via argv`, several of them
+    /// take turns like with `--gen-pick round-robin`
+    pub fn generator(self, path: impl Into<PathBuf>) -> Self {
+        self.weighted_generator(path, 1)
+    }
+
+    /// a generator which writes as many rounds in a row as its weight
+    pub fn weighted_generator(mut self, path: impl Into<PathBuf>, weight: u32) -> Self {
+        self.generators.push(Generator { path: path.into(), weight });
+        self
+    }
+
+    /// the input of every round written from its seed by this spec, instead of a generator, see `genspec`
+    pub fn gen_spec(mut self, spec: impl Into<String>) -> Self {
+        self.gen_spec = Some(spec.into());
+        self
+    }
+
+    /// a single round on the input of this file, instead of a generator
+    pub fn input_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.input_file = Some(path.into());
+        self
+    }
+
+    /// the programme under test, its sources may be listed like with `--program`
+    pub fn program(mut self, path: impl Into<PathBuf>) -> Self {
+        self.program = Some(path.into());
+        self
+    }
+
+    /// a reference, or a pattern of references such as 'refs/*.cpp'
+    pub fn reference(mut self, path: impl Into<PathBuf>) -> Self {
+        self.references.push(path.into());
+        self
+    }
+
+    /// how many rounds `Session::rounds` yields, 1 by default
+    pub fn rounds(mut self, rounds: u64) -> Self {
+        self.rounds = Some(rounds);
+        self
+    }
+
+    /// the session seed, random by default
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn time_limit(mut self, limit: Duration) -> Self {
+        self.time_limit = Some(limit);
+        self
+    }
+
+    /// the memory limit of the programme, in kB
+    pub fn memory_limit(mut self, kilobytes: usize) -> Self {
+        self.memory_limit = Some(kilobytes);
+        self
+    }
+
+    pub fn round_timeout(mut self, timeout: Duration) -> Self {
+        self.round_timeout = Some(timeout);
+        self
+    }
+
+    /// the size in kB past which an output is written to a temporary file, like `--spill-threshold`
+    pub fn spill_threshold(mut self, kilobytes: usize) -> Self {
+        self.spill_threshold = Some(kilobytes);
+        self
+    }
+
+    /// how the outputs are judged, `Exact` by default
+    pub fn compare(mut self, comparator: impl Comparator + 'static) -> Self {
+        self.comparator = Some(Box::new(comparator));
+        self
+    }
+
+    /// runs the programmes with the backend, such as a fake one in tests, as processes by default
+    pub fn backend(mut self, backend: impl ExecutionBackend + 'static) -> Self {
+        self.backend = Some(Box::new(backend));
+        self
+    }
+
+    /// the token which cancels the session, a new one by default, see `Session::cancellation`
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// calls the callback after every round with how far the rounds got
+    pub fn on_progress(mut self, callback: impl Fn(Progress) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Box::new(callback));
+        self
+    }
+
+    /// where the sources are compiled, the cache every session shares by default
+    pub fn build_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.build_dir = Some(dir.into());
+        self
+    }
+
+    /// shows the rounds to the observer as they run, after the observers added before it
+    pub fn observer(mut self, observer: impl Observer + 'static) -> Self {
+        self.observers.push(Box::new(observer));
+        self
+    }
+
+    /// Checks the options and builds every source, so that a session which can't run fails here rather than
+    /// in its first round. Like the rounds of the session it prints nothing, what the rest of the programme
+    /// says is still printed.
+    pub fn build(self) -> Result<Session, Box<dyn Error>> {
+        let _mute = output::mute();
+        let program = self.program.ok_or_else(|| static_err("a session needs a programme"))?;
+        let gen_spec = self.gen_spec.map(|spec| spec.parse::<GenSpec>().map_err(|e| into_err(format!("the spec is malformed {}", e)))).transpose()?;
+        match (self.generators.is_empty(), &self.input_file, &gen_spec) {
+            (false, Some(_), _) | (false, _, Some(_)) | (_, Some(_), Some(_)) =>
+                return Err(static_err("a session has only generators, a spec or an input file")),
+            (true, None, None) => return Err(static_err("a session needs a generator, a spec or an input file")),
+            _ => {},
+        }
+        if self.generators.iter().any(|g| g.weight == 0) {
+            return Err(static_err("the weights of the generators must be positive"));
+        }
+        if self.rounds == Some(0) {
+            return Err(static_err("a session runs at least one round"));
+        }
+        if [self.time_limit, self.round_timeout].contains(&Some(Duration::ZERO)) || self.memory_limit == Some(0) {
+            return Err(static_err("the limits must be positive"));
+        }
+
+        let mut args = Cli { generator: self.generators, gen_spec, input_file: self.input_file, ..Cli::default() };
+        args.programs.program = program;
+        args.programs.reference = self.references;
+        args.programs.build_dir = self.build_dir;
+        args.rounds = Some(if args.input_file.is_some() { 1 } else { self.rounds.unwrap_or(1) });
+        args.seed = Some(self.seed.unwrap_or_else(random_seed));
+        args.time_limit = self.time_limit.map(|limit| limit.as_secs_f64());
+        args.memory_limit = self.memory_limit;
+        args.round_timeout = self.round_timeout.map(|timeout| timeout.as_secs_f64());
+        args.spill_threshold = self.spill_threshold.unwrap_or(args.spill_threshold);
+        args.programs.expand_references()?;
+        args.programs.expand_program()?;
+        args.detect_toolchain();
+
+        let failed: Vec<_> = args.prepare().into_iter()
+            .filter_map(|(source, result)| result.err().map(|e| (source, e)))
+            .collect();
+        if !failed.is_empty() {
+            return Err(Box::new(PrepareError { failed }));
+        }
+        let comparator = self.comparator.unwrap_or_else(|| Box::new(Exact));
+        let backend = self.backend.unwrap_or_else(|| Box::new(Processes));
+        let cancellation = self.cancellation.unwrap_or_default();
+        Ok(Session { args, comparator, backend, observers: self.observers, cancellation, progress: self.progress, muted: true })
+    }
+}
+
+impl Session {
+    pub fn builder() -> SessionBuilder {
+        SessionBuilder::default()
+    }
+
+    /// The session of a command line whose sources are prepared, its options were checked by clap rather than
+    /// by the builder. Its rounds print what they do, and they're compared like `--compare` says.
+    pub fn of_cli(args: Cli, cancellation: CancellationToken) -> Session {
+        Session { comparator: args.comparator(), args, backend: Box::new(Processes), observers: vec![], cancellation, progress: None, muted: false }
+    }
+
+    /// the options of the session, as a command line would give them
+    pub fn args(&self) -> &Cli {
+        &self.args
+    }
+
+    /// the session seed, from which the seed of every round is derived
+    pub fn seed(&self) -> u64 {
+        self.args.seed.unwrap_or_default()
+    }
+
+    /// Cancels the session when it's cancelled, from any thread: the rounds stop, the programmes which run
+    /// are killed and `Rounds::cancelled` tells so.
+    pub fn cancellation(&self) -> CancellationToken {
+        self.cancellation.clone()
+    }
+
+    /// runs the given round alone, without the observers
+    pub fn round(&self, round: u64) -> RoundResult {
+        let _mute = self.muted.then(output::mute);
+        RoundResult::of(&self.args, &*self.backend, &*self.comparator, round, &self.cancellation)
+    }
+
+    /// every round of the session in turn
+    pub fn rounds(&self) -> Rounds<'_> {
+        self.rounds_from(0)
+    }
+
+    /// the rounds from `first` on, to resume a session stopped before it
+    pub fn rounds_from(&self, first: u64) -> Rounds<'_> {
+        let rounds = Rounds::new(&self.args, &*self.comparator, first, planned_rounds(&self.args)).executed_by(&*self.backend).cancelled_by(self.cancellation.clone()).observed(&self.observers);
+        let rounds = if self.muted { rounds.muted() } else { rounds };
+        match &self.progress {
+            Some(callback) => rounds.on_progress(&**callback),
+            None => rounds,
+        }
+    }
+
+    /// Runs the rounds like `run_session` does, going on from the session file when it's resumed, with the
+    /// comparator, backend and observers of the session.
+    pub fn run(&self, resumed: Option<&SessionFile>, view: &mut dyn SessionView) -> SessionReport {
+        let first = resumed.map_or(0, |r| r.rounds_done);
+        run_rounds(&self.args, self.rounds_from(first), resumed, view)
+    }
+}
+
+/// every round passed
+pub const EXIT_OK: i32 = 0;
+/// the programme gave a wrong answer, crashed or exceeded a limit
+pub const EXIT_FAILURE: i32 = 1;
+/// the generator or the references failed, so the programme couldn't be judged
+pub const EXIT_INFRA: i32 = 2;
+
+/// failures of the programme take precedence over infrastructure problems
+fn worse_exit_code(a: i32, b: i32) -> i32 {
+    if a == EXIT_FAILURE || b == EXIT_FAILURE { EXIT_FAILURE } else { a.max(b) }
+}
+
+/// how many rounds the session runs, None until the programme fails
+pub fn planned_rounds(args: &Cli) -> Option<u64> {
+    let until_failure = args.until_failure || args.rounds == Some(0);
+    match (args.input_file.is_some(), args.rounds) {
+        // a fixed input file always yields the same round
+        (true, _) => Some(1),
+        (false, Some(0)) => None,
+        (false, None) if until_failure => None,
+        (false, rounds) => Some(rounds.unwrap_or(1)),
+    }
+}
+
+/// Why `run_session` stopped before all of its rounds ran.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stop {
+    /// the cancellation token was cancelled, the round which ran then says nothing
+    Interrupted,
+    /// the host of `--remote` couldn't be reached
+    LostRemote,
+    /// `--fail-fast`, at the first round which didn't pass
+    FailFast,
+    /// `--until-failure` found a round where the programme fails
+    FoundFailure,
+    /// the view didn't want to go on
+    Asked,
+    /// `--max-failures` rounds didn't pass
+    MaxFailures,
+    /// a generator failed, with `--on-gen-fail stop`
+    GeneratorFailed,
+}
+
+/// What a session run with `run_session` came to, nothing of it is printed.
+#[derive(Clone, Debug, Default)]
+pub struct SessionReport {
+    /// the verdicts of the rounds, those of a resumed session included, and what the session exits with
+    pub summary: Summary,
+    /// the session goes on from this round when resumed
+    pub rounds_done: u64,
+    /// the rounds which didn't pass, those of a resumed session included, but for the skipped ones
+    pub failed_rounds: u64,
+    /// the rounds of this session skipped as their generator failed, with `--on-gen-fail skip`
+    pub skipped_rounds: u64,
+    /// the rounds whose outputs didn't all match
+    pub mismatches: Vec<MismatchedRound>,
+    /// every round which didn't pass
+    pub failures: Vec<FailedRound>,
+    /// None when all of the planned rounds ran
+    pub stopped: Option<Stop>,
+    pub elapsed: Duration,
+    /// of the rounds of this session, not of those before it was resumed
+    pub times: StageTimes,
+}
+
+/// A round of `run_session` as its view is shown it, once it's judged.
+#[derive(Debug)]
+pub struct RoundEnd {
+    pub result: RoundResult,
+    /// how the outputs compare, None unless the programme and the references ran, or without references
+    pub mismatch: Option<Mismatch>,
+    /// the signature with the verdict of the round
+    pub signature: Signature,
+    /// what the session would exit with for this round alone
+    pub exit_code: i32,
+    /// the programme couldn't run because the host of `--remote` couldn't be reached
+    pub lost_remote: bool,
+    pub progress: Progress,
+}
+
+/// How `run_session` is shown, the command line prints the rounds as they run, `()` shows nothing.
+pub trait SessionView {
+    /// the round is about to run
+    fn round_start(&mut self, _round: u64) {}
+
+    /// The round ran and was judged, the report counts it. The view may take the artifacts of the result.
+    fn round_end(&mut self, _end: &mut RoundEnd, _report: &SessionReport) {}
+
+    /// asked after every round which none of the options stops the session at, false stops it
+    fn go_on(&mut self, _end: &RoundEnd, _report: &SessionReport) -> bool {
+        true
+    }
+}
+
+impl SessionView for () {}
+
+/// what the round makes the session exit with: the programme failed, or it couldn't be judged
+fn exit_code_of(outcome: &Round, mismatch: Option<&Mismatch>, lost_remote: bool) -> i32 {
+    match (outcome, mismatch) {
+        (Round::ProgramFail { .. }, _) if !lost_remote => EXIT_FAILURE,
+        (Round::Success { .. }, None | Some(Mismatch::AllMatch)) => EXIT_OK,
+        (Round::Success { .. }, Some(Mismatch::ProgMismatch { .. })) => EXIT_FAILURE,
+        _ => EXIT_INFRA,
+    }
+}
+
+/// Runs the rounds of a session whose sources are prepared, going on from the session file when it's resumed,
+/// until they're all done, an option stops them or the token is cancelled. The view is shown every round,
+/// the rest is in the report.
+pub fn run_session(args: &Cli, resumed: Option<&SessionFile>, cancellation: &CancellationToken, view: &mut dyn SessionView) -> SessionReport {
+    let comparator = args.comparator();
+    let rounds = Rounds::new(args, &*comparator, resumed.map_or(0, |r| r.rounds_done), planned_rounds(args)).cancelled_by(cancellation.clone());
+    run_rounds(args, rounds, resumed, view)
+}
+
+/// the rounds of `run_session` and `Session::run`, those of a resumed session starting after its last one
+fn run_rounds(args: &Cli, mut upcoming: Rounds, resumed: Option<&SessionFile>, view: &mut dyn SessionView) -> SessionReport {
+    let mut report = SessionReport {
+        summary: resumed.map(|r| r.summary.clone()).unwrap_or_default(),
+        rounds_done: resumed.map_or(0, |r| r.rounds_done),
+        failed_rounds: resumed.map_or(0, |r| r.failed_rounds),
+        ..SessionReport::default()
+    };
+    report.summary.exit_code = resumed.map_or(EXIT_OK, |r| r.exit_code);
+    let until_failure = args.until_failure || args.rounds == Some(0);
+    let mut crash_rerun = args.crash_rerun.as_ref().map(|spec| CrashRerun::new(spec, &args.programs.program));
+    let started = Instant::now();
+    let comparator = upcoming.comparator;
+    upcoming = upcoming.resumed(report.summary.clone());
+    while let Some(round) = upcoming.upcoming() {
+        if upcoming.cancellation.is_cancelled() {
+            report.stopped = Some(Stop::Interrupted);
+            break;
+        }
+        view.round_start(round);
+        let Some(mut result) = upcoming.next() else {
+            report.stopped = Some(Stop::Interrupted);
+            break;
+        };
+
+        if let (Some(rerun), Round::ProgramFail { input, fail: Failure::Prog(x), diagnostics }) = (crash_rerun.as_mut(), &mut result.outcome) {
+            if x.signal.is_some_and(is_crash_signal) {
+                info!("re-running the input of round {} against the sanitizer build", round);
+                diagnostics.push(rerun.report(input, args));
+            }
+        }
+        let lost_remote = args.programs.program_backend.is_some() && matches!(result.outcome, Round::ProgramFail { fail: Failure::Spawn(_), .. });
+        let mismatch = match &result.outcome {
+            Round::Success { prog, refs, .. } if !refs.is_empty() => {
+                debug!("comparing the outputs of round {}", round);
+                Some(compare_outputs(prog, refs, comparator))
+            },
+            _ => None,
+        };
+        let exit_code = exit_code_of(&result.outcome, mismatch.as_ref(), lost_remote);
+        let generator_failed = matches!(result.outcome, Round::GeneratorFail(_));
+        let skipped = generator_failed && args.on_gen_fail == OnGenFail::Skip;
+        let signature = Signature::of_round(&result.outcome).judged(result.verdict);
+        info!("round {} ended with {} after {:.3}s", round, result.verdict.code(), result.duration.as_secs_f64());
+
+        // the summary of the rounds doesn't know what the session exits with
+        let exit_code_before = report.summary.exit_code;
+        report.summary = upcoming.summary().clone();
+        report.summary.exit_code = worse_exit_code(exit_code_before, exit_code);
+        report.rounds_done = round + 1;
+        report.elapsed = started.elapsed();
+        report.times.add(&result.meta);
+        if skipped {
+            report.skipped_rounds += 1;
+        } else if exit_code != EXIT_OK {
+            report.failed_rounds += 1;
+        }
+        if exit_code != EXIT_OK {
+            report.failures.push(FailedRound { signature, round, seed: result.seed, input: result.outcome.input().map(str::to_string) });
+        }
+        if let (Some(mismatch @ (Mismatch::ProgMismatch { .. } | Mismatch::RefMismatch(_))), Some(input)) = (&mismatch, result.outcome.input()) {
+            report.mismatches.push(MismatchedRound { round, seed: result.seed, input: input.to_string(), mismatch: mismatch.clone() });
+        }
+
+        let mut end = RoundEnd { result, mismatch, signature, exit_code, lost_remote, progress: upcoming.progress() };
+        view.round_end(&mut end, &report);
+        report.stopped = if lost_remote {
+            Some(Stop::LostRemote)
+        } else if generator_failed && !skipped {
+            Some(Stop::GeneratorFailed)
+        } else if args.fail_fast && exit_code != EXIT_OK && !skipped {
+            Some(Stop::FailFast)
+        } else if until_failure && exit_code == EXIT_FAILURE {
+            Some(Stop::FoundFailure)
+        } else if !view.go_on(&end, &report) {
+            Some(Stop::Asked)
+        } else if args.max_failures.is_some_and(|max| report.failed_rounds >= max) {
+            Some(Stop::MaxFailures)
+        } else {
+            None
+        };
+        if report.stopped.is_some() {
+            break;
+        }
+    }
+    report.summary.cancelled = upcoming.cancelled() || report.stopped == Some(Stop::Interrupted);
+    report.elapsed = started.elapsed();
+    report
+}
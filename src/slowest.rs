@@ -0,0 +1,60 @@
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    fmt::Write as _,
+    fs,
+    io,
+    path::Path,
+    time::Duration,
+    };
+
+use crate::{create_owned_dir, Verdict};
+
+/// a round in which the programme ran for long
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SlowRound {
+    pub time: Duration,
+    pub round: u64,
+    pub seed: u64,
+    pub verdict: Verdict,
+    pub input: String,
+}
+
+/// The `--top` rounds in which the programme ran the longest, whatever their verdict.
+/// Only those inputs are kept: the heap holds the fastest of them on top, to be replaced by a slower round.
+pub struct SlowestRounds {
+    top: usize,
+    heap: BinaryHeap<Reverse<SlowRound>>,
+}
+
+impl SlowestRounds {
+    pub fn new(top: usize) -> Self {
+        SlowestRounds { top, heap: BinaryHeap::with_capacity(top + 1) }
+    }
+
+    pub fn add(&mut self, slow: SlowRound) {
+        if self.heap.len() == self.top && self.heap.peek().is_some_and(|Reverse(fastest)| fastest.time >= slow.time) {
+            return;
+        }
+        self.heap.push(Reverse(slow));
+        if self.heap.len() > self.top {
+            self.heap.pop();
+        }
+    }
+
+    /// the slowest round first
+    pub fn into_sorted(self) -> Vec<SlowRound> {
+        self.heap.into_sorted_vec().into_iter().map(|Reverse(slow)| slow).collect()
+    }
+}
+
+/// writes the inputs as `round_N.in` into the directory, with their times in `slowest.txt`
+pub fn save(dir: &Path, slowest: &[SlowRound]) -> io::Result<()> {
+    create_owned_dir(dir)?;
+    let mut times = String::new();
+    for slow in slowest {
+        fs::write(dir.join(format!("round_{}.in", slow.round)), &slow.input)?;
+        let _ = writeln!(times, "round {}: {:.3}s, verdict {}, seed {}", slow.round, slow.time.as_secs_f64(), slow.verdict.code(), slow.seed);
+    }
+    fs::write(dir.join("slowest.txt"), times)
+}
@@ -0,0 +1,46 @@
+use std::{
+    fs,
+    thread,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+    };
+
+use crate::cli::{Cli, handle_cli};
+use crate::{say, catch_interrupts, interrupted};
+
+/// how often the sources are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+fn modification_times(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    files.iter().map(|f| fs::metadata(f).and_then(|m| m.modified()).ok()).collect()
+}
+
+/// Runs the session, then runs it again (recompiling) every time one of the sources changes,
+/// until Ctrl+C. Returns the exit code of the last session.
+pub fn watch(args: Cli) -> i32 {
+    catch_interrupts();
+    let mut files: Vec<PathBuf> = args.generator.iter().map(|g| &g.path)
+        .chain(args.input_file.iter())
+        .chain(std::iter::once(&args.programs.program))
+        .chain(args.programs.program_sources.iter())
+        .chain(args.programs.grader.iter())
+        .chain(args.programs.reference.iter())
+        .cloned()
+        .collect();
+    files.sort();
+    files.dedup();
+
+    loop {
+        let exit_code = handle_cli(args.clone());
+        if interrupted() { return exit_code; }
+
+        say!("== watching {} for changes, press Ctrl+C to stop", 
+            files.iter().map(|f| f.display().to_string()).collect::<Vec<_>>().join(", "));
+        let times = modification_times(&files);
+        while modification_times(&files) == times {
+            thread::sleep(POLL_INTERVAL);
+            if interrupted() { return exit_code; }
+        }
+        say!("\n==================== sources changed, re-running ====================\n");
+    }
+}
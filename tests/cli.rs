@@ -0,0 +1,241 @@
+use std::path::Path;
+
+use clap::{Parser, ValueEnum};
+use compdiff::cli::{App, Cli, Command, Generator, RelativeTimeLimit, Sanitizer, split_flags};
+use compdiff::Role;
+
+fn parse(argv: &[&str]) -> App {
+    App::try_parse_from(std::iter::once("compdiff").chain(argv.iter().copied()))
+        .unwrap_or_else(|e| panic!("{:?} didn't parse: {}", argv, e))
+}
+
+#[test]
+fn command_lines_without_a_subcommand_run_a_session() {
+    let app = parse(&["-g", "gen.py", "-p", "sol.cpp", "-r", "brute.py", "-r", "other.py", "-c", "100", "-t", "1.5"]);
+    assert!(app.command.is_none());
+    let args = app.run.expect("session options");
+    assert_eq!(args.generator, [Generator::new("gen.py")]);
+    assert_eq!(args.programs.program, Path::new("sol.cpp"));
+    assert_eq!(args.programs.reference.len(), 2);
+    assert_eq!(args.rounds, Some(100));
+    assert_eq!(args.time_limit, Some(1.5));
+
+    let args = parse(&["--input-file", "round_7.in", "--program", "sol.cpp", "--ref", "brute.py", "--fail-fast"]).run.unwrap();
+    assert!(args.fail_fast);
+    assert_eq!(args.input_file.as_deref(), Some(Path::new("round_7.in")));
+}
+
+#[test]
+fn session_options_are_still_validated() {
+    assert!(App::try_parse_from(["compdiff", "-g", "gen.py"]).is_err());
+    assert!(App::try_parse_from(["compdiff", "-g", "gen.py", "-i", "in.txt", "-p", "sol.cpp"]).is_err());
+    assert!(App::try_parse_from(["compdiff", "-i", "in.txt", "-p", "sol.cpp", "--session", "night.json"]).is_err());
+    assert!(App::try_parse_from(["compdiff", "-g", "gen.py", "-p", "sol.cpp", "--session", "night.json", "--watch"]).is_err());
+}
+
+#[test]
+fn completions_subcommand() {
+    let app = parse(&["completions", "bash"]);
+    assert!(matches!(app.command, Some(Command::Completions { .. })));
+    assert!(app.run.is_none());
+    assert!(App::try_parse_from(["compdiff", "completions", "tcsh"]).is_err());
+}
+
+#[test]
+fn run_compile_replay_and_clean_subcommands() {
+    let app = parse(&["run", "-g", "gen.py", "-p", "sol.cpp", "-c", "3"]);
+    let Some(Command::Run(args)) = app.command else { panic!("expected run") };
+    assert_eq!(args.rounds, Some(3));
+
+    let app = parse(&["compile", "-p", "sol.cpp", "-r", "brute.cpp", "--cpp-compiler-flags=-O2"]);
+    let Some(Command::Compile(args)) = app.command else { panic!("expected compile") };
+    assert_eq!(args.programs.reference, [Path::new("brute.cpp")]);
+    assert_eq!(args.programs.cpp_compiler_flags.as_deref(), Some("-O2"));
+    assert!(App::try_parse_from(["compdiff", "compile", "-p", "sol.cpp", "-c", "3"]).is_err());
+    let app = parse(&["compile", "-p", "sol.cpp", "-j", "4"]);
+    let Some(Command::Compile(args)) = app.command else { panic!("expected compile") };
+    assert_eq!(args.programs.jobs.map(|j| j.get()), Some(4));
+    assert!(App::try_parse_from(["compdiff", "compile", "-p", "sol.cpp", "-j", "0"]).is_err());
+
+    // the generator isn't needed to replay an input
+    let app = parse(&["replay", "round_7.in", "-p", "sol.cpp", "-r", "brute.py"]);
+    let Some(Command::Replay(args)) = app.command else { panic!("expected replay") };
+    assert_eq!(args.input, Path::new("round_7.in"));
+    assert!(args.session.generator.is_empty());
+    assert!(App::try_parse_from(["compdiff", "replay", "-p", "sol.cpp"]).is_err());
+
+    assert!(matches!(parse(&["clean"]).command, Some(Command::Clean { stale: false, cache: false, cache_dir: None, artifacts: false, .. })));
+    assert!(matches!(parse(&["clean", "--stale"]).command, Some(Command::Clean { stale: true, .. })));
+    let Some(Command::Clean { cache: true, cache_dir: Some(dir), .. }) = parse(&["clean", "--cache", "--cache-dir", "cache"]).command
+        else { panic!("expected clean --cache") };
+    assert_eq!(dir, Path::new("cache"));
+    assert!(App::try_parse_from(["compdiff", "clean", "--cache", "--stale"]).is_err());
+    let Some(Command::Clean { artifacts: true, config: Some(config), .. }) = parse(&["clean", "--artifacts", "--config", "ci.toml"]).command
+        else { panic!("expected clean --artifacts") };
+    assert_eq!(config, Path::new("ci.toml"));
+}
+
+#[test]
+fn verbose_can_be_repeated() {
+    assert_eq!(parse(&["-g", "gen.py", "-p", "sol.cpp"]).verbosity(), 0);
+    assert_eq!(parse(&["-g", "gen.py", "-p", "sol.cpp", "-vv"]).verbosity(), 2);
+    assert_eq!(parse(&["replay", "round_7.in", "-p", "sol.cpp", "-v", "--verbose"]).verbosity(), 2);
+    assert_eq!(parse(&["clean"]).verbosity(), 0);
+}
+
+#[test]
+fn time_limits_take_durations() {
+    let limits = |t: &str| parse(&["-g", "gen.py", "-p", "sol.cpp", "-t", t, "--round-timeout", t]).run.map(|a| (a.time_limit, a.round_timeout));
+    assert_eq!(limits("250ms"), Some((Some(0.25), Some(0.25))));
+    assert_eq!(limits("1.5s"), Some((Some(1.5), Some(1.5))));
+    assert_eq!(limits("3m"), Some((Some(180.0), Some(180.0))));
+    assert_eq!(limits("2"), Some((Some(2.0), Some(2.0))));
+    for nonsense in ["2sm", "s", "1.5.2s", "fast"] {
+        let err = App::try_parse_from(["compdiff", "-g", "gen.py", "-p", "sol.cpp", "-t", nonsense]).unwrap_err().to_string();
+        assert!(err.contains("followed by ms, s, m or h"), "{}", err);
+    }
+}
+
+#[test]
+fn files_can_be_given_by_position() {
+    let mut args = parse(&["gen.py", "sol.cpp", "brute.py", "other.py", "-t", "1"]).run.expect("a session");
+    args.take_positional();
+    assert_eq!(args.generator, [Generator::new("gen.py")]);
+    assert_eq!(args.programs.program, Path::new("sol.cpp"));
+    assert_eq!(args.programs.reference, [Path::new("brute.py"), Path::new("other.py")]);
+    assert!(args.reference_files.is_empty());
+
+    assert!(App::try_parse_from(["compdiff", "gen.py"]).is_err());
+    assert!(App::try_parse_from(["compdiff", "gen.py", "sol.cpp", "-p", "other.cpp"]).is_err());
+    assert!(App::try_parse_from(["compdiff", "gen.py", "sol.cpp", "-g", "other.py"]).is_err());
+    assert!(App::try_parse_from(["compdiff", "gen.py", "sol.cpp", "brute.py", "-r", "other.py"]).is_err());
+}
+
+#[test]
+fn compile_commands_are_checked_when_parsed() {
+    let args = parse(&["gen.py", "sol.cpp", "--compile-cmd", "cpp=ccache g++ {flags} {src} -o {out} -Ilib"]).run.expect("a session");
+    let cmd = &args.programs.compile_cmd[0];
+    assert_eq!(cmd.extension, "cpp");
+    assert_eq!(cmd.argv, ["ccache", "g++", "{flags}", "{src}", "-o", "{out}", "-Ilib"]);
+    let args = parse(&["gen.py", "sol.cpp", "--compile-cmd", "cpp=g++ {src} -o {out} -I\"my lib\""]).run.expect("a session");
+    assert_eq!(args.programs.compile_cmd[0].argv, ["g++", "{src}", "-o", "{out}", "-Imy lib"]);
+
+    for wrong in ["g++ {src} -o {out}", "rs=rustc {src} -o {out}", "cpp=g++ {src}", "cpp={src} {out}", "cpp=", "cpp=g++ src={src} -o {out}", "cpp=g++ \"{src} -o {out}"] {
+        assert!(App::try_parse_from(["compdiff", "gen.py", "sol.cpp", "--compile-cmd", wrong]).is_err(), "{}", wrong);
+    }
+}
+
+#[test]
+fn only_the_program_is_sanitized() {
+    let args = parse(&["-g", "gen.py", "-p", "sol.cpp", "-r", "brute.cpp", "--sanitize", "address,undefined"]).run.expect("a session");
+    assert_eq!(args.programs.extra_flags(Role::Program), "-fsanitize=address,undefined -g -fno-omit-frame-pointer");
+    assert_eq!(args.programs.extra_flags(Role::Reference), "");
+    assert!(App::try_parse_from(["compdiff", "gen.py", "sol.cpp", "--sanitize", "address,valgrind"]).is_err());
+}
+
+#[test]
+fn build_profiles_choose_the_flags_by_role() {
+    let args = parse(&["-g", "gen.cpp", "-p", "sol.cpp", "-r", "brute.cpp", "--build-profile", "release", "--program-profile", "debug"])
+        .run.expect("a session");
+    assert_eq!(args.programs.extra_flags(Role::Program), "-O0 -g");
+    assert_eq!(args.programs.extra_flags(Role::Reference), "-O2");
+    assert_eq!(args.programs.extra_flags(Role::Generator), "-O2");
+
+    let args = parse(&["-g", "gen.py", "-p", "sol.cpp", "-r", "brute.cpp", "--ref-profile", "release", "--release-flags=-O3"])
+        .run.expect("a session");
+    assert_eq!(args.programs.extra_flags(Role::Program), "");
+    assert_eq!(args.programs.extra_flags(Role::Reference), "-O3");
+}
+
+#[test]
+fn flags_are_given_per_language() {
+    let args = parse(&["gen.py", "sol.cpp", "--flags", "cpp=-O2 -std=c++17", "--flags", "py=-O"]).run.expect("a session");
+    assert_eq!(args.programs.flags_of("cpp"), "-O2 -std=c++17");
+    assert_eq!(args.programs.flags_of("py"), "-O");
+    assert_eq!(args.programs.flags_of("rs"), "");
+
+    // the deprecated option is the c++ entry
+    let args = parse(&["gen.py", "sol.cpp", "--cpp-compiler-flags=-O3"]).run.expect("a session");
+    assert_eq!(args.programs.flags_of("cpp"), "-O3");
+
+    // --std swaps the standard of the default flags, or adds one to explicit flags without it
+    let std = |argv: &[&str]| parse(&[&["gen.py", "sol.cpp"], argv].concat()).run.expect("a session").programs;
+    assert_eq!(std(&[]).flags_of("cpp"), "-std=c++20");
+    assert_eq!(std(&["--std", "c++17"]).flags_of("cpp"), "-std=c++17");
+    assert_eq!(std(&["--std", "14", "--flags", "cpp=-O2"]).flags_of("cpp"), "-O2 -std=c++14");
+    let overridden = std(&["--std", "c++17", "--flags", "cpp=-O2 -std=gnu++20"]);
+    assert!(overridden.std_overridden());
+    assert_eq!(overridden.flags_of("cpp"), "-O2 -std=gnu++20");
+    // whatever the environment holds
+    let ignored = std(&["--ignore-env-toolchain"]);
+    assert_eq!((ignored.env_cxx(), ignored.env_cxx_flags()), (None, None));
+
+    assert_eq!(split_flags(r#"-O2 "-DNAME=a b" '-DQ="x"' a\ b"#).unwrap(), ["-O2", "-DNAME=a b", r#"-DQ="x""#, "a b"]);
+    for wrong in ["-O2", "=-O2", "cpp=-DX='a"] {
+        assert!(App::try_parse_from(["compdiff", "gen.py", "sol.cpp", "--flags", wrong]).is_err(), "{}", wrong);
+    }
+}
+
+#[test]
+fn macros_are_defined_by_role() {
+    let args = parse(&["-g", "gen.py", "-p", "sol.cpp", "-r", "brute.cpp", "--define", "LOCAL", "--define", "VALUE=3", "--define-refs", "BRUTE"])
+        .run.expect("a session");
+    assert_eq!(args.programs.defines_of(Role::Program), ["LOCAL", "VALUE=3"]);
+    assert_eq!(args.programs.defines_of(Role::Reference), ["BRUTE"]);
+    assert!(args.programs.defines_of(Role::Generator).is_empty());
+}
+
+#[test]
+fn dry_runs_do_not_watch() {
+    assert!(parse(&["gen.py", "sol.cpp", "--dry-run"]).run.expect("a session").dry_run);
+    assert!(App::try_parse_from(["compdiff", "gen.py", "sol.cpp", "--dry-run", "--watch"]).is_err());
+}
+
+#[test]
+fn options_without_the_command_line_have_its_defaults() {
+    let parsed = parse(&["-g", "gen.py", "-p", "sol.cpp"]).run.expect("a session");
+    let mut built = Cli { generator: vec![Generator::new("gen.py")], ..Cli::default() };
+    built.programs.program = "sol.cpp".into();
+    assert_eq!(format!("{:?}", built), format!("{:?}", parsed));
+    assert!(Sanitizer::value_variants().iter().all(|s| s.to_possible_value().is_some_and(|v| v.get_name() == s.name())));
+}
+
+#[test]
+fn the_validator_checks_the_inputs_of_minimizing_or_exploring() {
+    for search in [&["--minimize"][..], &["--explore", "20"], &["--minimize", "--explore", "20"]] {
+        let args = parse(&[&["gen.py", "sol.cpp", "--minimize-validator", "valid.py"][..], search].concat()).run.expect("a session");
+        assert_eq!(args.minimize_validator.as_deref(), Some(Path::new("valid.py")));
+    }
+    assert!(App::try_parse_from(["compdiff", "gen.py", "sol.cpp", "--minimize-validator", "valid.py"]).is_err());
+}
+
+#[test]
+fn a_spec_replaces_the_generator() {
+    let args = parse(&["-p", "sol.cpp", "--gen-spec", "n = int(1, 10); print(n)"]).run.expect("a session");
+    assert_eq!(args.gen_spec.expect("a spec").source(), "n = int(1, 10); print(n)");
+    assert!(App::try_parse_from(["compdiff", "-g", "gen.py", "-p", "sol.cpp", "--gen-spec", "print(1)"]).is_err());
+    let malformed = App::try_parse_from(["compdiff", "-p", "sol.cpp", "--gen-spec", "print(ints(3, 1))"]).unwrap_err().to_string();
+    assert!(malformed.contains("at column 7: `ints` takes 3 arguments (n, lo, hi), not 2"), "{}", malformed);
+}
+
+#[test]
+fn generators_repeat_with_weights() {
+    let args = parse(&["-g", "small.py:3", "-g", "big.py", "-p", "sol.cpp", "--gen-pick", "weighted"]).run.expect("a session");
+    assert_eq!(args.generator, [Generator { path: "small.py".into(), weight: 3 }, Generator::new("big.py")]);
+    // the seed modulo the weights, 4 in all, picks the generator
+    let picked: Vec<_> = [3, 0, 7, 2].into_iter().enumerate().map(|(round, seed)| args.generator_of(round as u64, seed).unwrap().path.to_str().unwrap()).collect();
+    assert_eq!(picked, ["big.py", "small.py", "big.py", "small.py"]);
+    assert!(App::try_parse_from(["compdiff", "-g", "gen.py:0", "-p", "sol.cpp"]).is_err());
+}
+
+#[test]
+fn time_limits_can_be_relative_to_a_reference() {
+    let args = parse(&["gen.py", "sol.cpp", "brute.cpp", "--time-limit-rel", "2.5x:brute.cpp"]).run.expect("a session");
+    assert_eq!(args.time_limit_rel, Some(RelativeTimeLimit { factor: 2.5, reference: "brute.cpp".into() }));
+    assert_eq!(args.time_limit_floor, 0.1);
+    let relative = parse(&["gen.py", "sol.cpp", "--time-limit-rel", "3:brute.cpp", "--time-limit-floor", "1s"]).run.expect("a session");
+    assert_eq!((relative.time_limit_rel.map(|r| r.factor), relative.time_limit_floor), (Some(3.0), 1.0));
+    for malformed in [&["--time-limit-rel", "brute.cpp"][..], &["--time-limit-rel", "0x:brute.cpp"], &["--time-limit-rel", "2x:brute.cpp", "-t", "1s"]] {
+        assert!(App::try_parse_from([&["compdiff", "gen.py", "sol.cpp"][..], malformed].concat()).is_err(), "{:?}", malformed);
+    }
+}
@@ -0,0 +1,2195 @@
+use std::{fs, io::Write, path::{Path, PathBuf}, process::{Command, Stdio}, sync::{Arc, Mutex}, thread, time::{Duration, SystemTime}};
+
+use clap::Parser;
+use compdiff::backend::Backend;
+use compdiff::fixtures::{self, Fixture};
+use compdiff::runner::Limits;
+use compdiff::refcache::{RefCache, RefMemo};
+use compdiff::report::Summary;
+use compdiff::session::{Observer, PrepareError, RoundResult, Session};
+use compdiff::{build, cli::Cli, create_owned_dir, evict_cache, is_owned_dir, preprocess, CompileError, PreprocessOptions, UnsupportedFlags, ExecutionStats, Role, Round, RoundCtx, Verdict};
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("compdiff_test_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// the command line itself, for what it prints, without a terminal
+fn compdiff(args: &[&str]) -> Command {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_compdiff"));
+    cmd.args(args).stdin(Stdio::null()).env_remove("NO_COLOR").env_remove("RUST_LOG");
+    cmd
+}
+
+#[test]
+fn same_named_sources_do_not_collide() {
+    let dir = scratch_dir("same_named_sources");
+    let (a, b) = (dir.join("a"), dir.join("b"));
+    fs::create_dir_all(&a).unwrap();
+    fs::create_dir_all(&b).unwrap();
+    fs::write(a.join("sol.cpp"), "#include <cstdio>\nint main(){ puts(\"a\"); }\n").unwrap();
+    fs::write(b.join("sol.cpp"), "#include <cstdio>\nint main(){ puts(\"b\"); }\n").unwrap();
+
+    let args = Cli::try_parse_from(["compdiff", "-g", "gen.py", "-p", "sol.cpp"]).unwrap();
+    let bin_a = build(a.join("sol.cpp"), &args.programs).unwrap().binary;
+    let bin_b = build(b.join("sol.cpp"), &args.programs).unwrap().binary;
+    assert_ne!(bin_a, bin_b);
+
+    let run = |bin: &PathBuf| String::from_utf8(Command::new(bin).output().unwrap().stdout).unwrap();
+    assert_eq!(run(&bin_a), "a\n");
+    assert_eq!(run(&bin_b), "b\n");
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn a_source_in_two_roles_is_built_for_each() {
+    let dir = scratch_dir("two_roles");
+    let sol = dir.join("sol.cpp");
+    fs::write(&sol, "#include <cstdio>\nint main(){\n#ifdef LOCAL\n puts(\"1\");\n#else\n puts(\"2\");\n#endif\n}\n").unwrap();
+    let input = dir.join("round.in");
+    fs::write(&input, "0\n").unwrap();
+    let (sol, input) = (sol.to_str().unwrap(), input.to_str().unwrap());
+
+    // the define only applies to the programme, not to the same source as the reference
+    let mut args = Cli::try_parse_from(["compdiff", "-i", input, "-p", sol, "-r", sol, "--define", "LOCAL"]).unwrap();
+    assert!(args.prepare().iter().all(|(_, built)| built.is_ok()));
+    assert_ne!(args.programs.program, args.programs.reference[0]);
+    let round = compdiff::run_round_with_input(&RoundCtx::new(&args, 0), "0\n".to_string(), &compdiff::runner::Processes);
+    let Round::Success { prog, refs, .. } = round else { panic!("both of them run") };
+    assert_eq!(prog.stdout, "1\n");
+    assert_eq!(refs[0].stdout, "2\n");
+
+    let out = compdiff(&["-i", input, "-p", sol, "-r", sol, "--define", "LOCAL"]).output().unwrap();
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(compdiff(&["-i", input, "-p", sol, "-r", sol]).status().unwrap().code(), Some(0));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn reference_patterns_are_expanded_before_preprocessing() {
+    let dir = scratch_dir("reference_patterns");
+    for name in ["b.py", "a.py", "broken.py", "notes.txt"] {
+        fs::write(dir.join(name), "print(1)\n").unwrap();
+    }
+    let pattern = dir.join("*.py");
+    let mut args = Cli::try_parse_from(["compdiff", "-g", "gen.py", "-p", "sol.cpp", "-r", "brute.cpp", "-r",
+        pattern.to_str().unwrap(), "--reference-exclude", "*broken*"]).unwrap();
+    args.programs.expand_references().unwrap();
+    assert_eq!(args.programs.reference, [PathBuf::from("brute.cpp"), dir.join("a.py"), dir.join("b.py")]);
+
+    let mut args = Cli::try_parse_from(["compdiff", "-g", "gen.py", "-p", "sol.cpp", "-r", dir.join("*.cpp").to_str().unwrap()]).unwrap();
+    assert!(args.programs.expand_references().is_err());
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn files_which_cannot_run_fail_before_the_rounds() {
+    let dir = scratch_dir("cannot_run");
+    fs::write(dir.join("brute.py"), "print(1)\n").unwrap();
+    fs::write(dir.join("brute.sh"), "echo 1\n").unwrap();
+
+    let args = Cli::try_parse_from(["compdiff", "-g", "gen.py", "-p", "sol.cpp"]).unwrap();
+    assert!(build(dir.join("brute.py"), &args.programs).unwrap().interpreter.is_some());
+    assert!(build(dir.join("brute.sh"), &args.programs).is_err());
+    assert!(build(dir.join("missing.py"), &args.programs).unwrap_err().to_string().contains("couldn't open"));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn sessions_are_built_and_run_without_the_command_line() {
+    let dir = scratch_dir("session_builder");
+    let (gen, sol, wrong) = (fixtures::seeded_generator(100), fixtures::multiplying_program(2), fixtures::script("wrong.py", "print(int(input()) + 2)\n"));
+
+    let session = |reference: &Path| Session::builder().generator(&gen).program(&sol)
+        .reference(reference).rounds(3).seed(7).time_limit(Duration::from_secs(5)).build();
+    let rounds: Vec<_> = session(sol.path()).unwrap().rounds().collect();
+    assert_eq!(rounds.iter().map(|r| (r.round, r.verdict)).collect::<Vec<_>>(), [(0, Verdict::Ok), (1, Verdict::Ok), (2, Verdict::Ok)]);
+    assert!(session(wrong.path()).unwrap().rounds().all(|r| r.verdict == Verdict::WrongAnswer || r.outcome.input() == Some("0\n")));
+
+    // every round knows when it started and how long each of its programmes ran
+    let meta = session(wrong.path()).unwrap().round(1).meta;
+    assert!(meta.generator.is_some() && meta.program.is_some() && matches!(meta.references[..], [Some(_)]));
+    assert!(meta.started_at <= SystemTime::now());
+
+    // stopping after a round and resuming from the next gives the same rounds
+    let resumed = session(sol.path()).unwrap();
+    let mut rest = resumed.rounds_from(1);
+    assert_eq!(rest.next().map(|r| (r.round, r.seed)), Some((1, rounds[1].seed)));
+    assert_eq!((rest.upcoming(), rest.count()), (Some(2), 1));
+
+    // observers see every round, and the end of the session when the rounds are dropped
+    struct Seen(Arc<Mutex<Vec<String>>>);
+    impl Observer for Seen {
+        fn on_round_start(&self, round: u64, _: u64) { self.0.lock().unwrap().push(format!("start {}", round)); }
+        fn on_execution_finished(&self, _: u64, _: &ExecutionStats) { self.0.lock().unwrap().push("execution".to_string()); }
+        fn on_verdict(&self, result: &RoundResult) { self.0.lock().unwrap().push(format!("{:?}", result.verdict)); }
+        fn on_session_end(&self, summary: &Summary) { self.0.lock().unwrap().push(format!("end {}", summary.rounds)); }
+    }
+    let seen = Arc::default();
+    let observed = Session::builder().generator(&gen).program(&sol).reference(&sol)
+        .rounds(3).observer(Seen(Arc::clone(&seen))).build().unwrap();
+    assert_eq!(observed.rounds().take(1).count(), 1);
+    assert_eq!(*seen.lock().unwrap(), ["start 0", "execution", "execution", "execution", "Ok", "end 1"]);
+
+    // the results outlive the session, and go through JSON unchanged
+    let json = serde_json::to_string(&rounds).unwrap();
+    assert_eq!(serde_json::to_string(&serde_json::from_str::<Vec<RoundResult>>(&json).unwrap()).unwrap(), json);
+
+    let e = session(&dir.join("missing.py")).unwrap_err();
+    assert!(e.downcast_ref::<PrepareError>().is_some_and(|e| e.failed.len() == 1 && e.failed[0].0 == dir.join("missing.py")));
+    assert!(Session::builder().program(&sol).build().is_err());
+    assert!(Session::builder().generator(&gen).program(&sol).rounds(0).build().is_err());
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn only_the_work_of_a_session_is_muted() {
+    use compdiff::output::is_muted;
+
+    struct Muted(Arc<Mutex<Vec<bool>>>);
+    impl Observer for Muted {
+        fn on_round_start(&self, _: u64, _: u64) { self.0.lock().unwrap().push(is_muted()); }
+    }
+    let (gen, sol) = (fixtures::seeded_generator(100), fixtures::multiplying_program(2));
+    let seen = Arc::default();
+    let session = Session::builder().generator(&gen).program(&sol).reference(&sol).rounds(2)
+        .observer(Muted(Arc::clone(&seen))).build().unwrap();
+    // what the programme says once the session is built is still printed, on this thread and the others
+    assert!(!is_muted());
+    assert!(!thread::spawn(is_muted).join().unwrap());
+    assert_eq!(session.rounds().count(), 2);
+    assert_eq!(*seen.lock().unwrap(), [true, true]);
+    assert!(!is_muted());
+
+    // the session of the command line prints its rounds
+    let mut args = Cli::try_parse_from(["compdiff", "-g", gen.arg(), "-p", sol.arg(), "-r", sol.arg(), "-c", "2"]).unwrap();
+    assert!(args.prepare().iter().all(|(_, built)| built.is_ok()));
+    let cli = Session::of_cli(args, Default::default());
+    struct Shown(Vec<bool>);
+    impl compdiff::session::SessionView for Shown {
+        fn round_start(&mut self, _: u64) { self.0.push(is_muted()); }
+    }
+    let mut shown = Shown(vec![]);
+    assert_eq!(cli.run(None, &mut shown).summary.passed, 2);
+    assert_eq!(shown.0, [false, false]);
+}
+
+#[test]
+fn outputs_are_compared_as_chosen() {
+    use compdiff::compare::{compare_outputs, Comparator, Comparison, Exact, FloatTolerance, Tokens, Trimmed};
+    use compdiff::{ExecutionResult, Mismatch};
+
+    let differ = |c: &dyn Comparator, program: &str, reference: &str| match c.compare(program.as_bytes(), reference.as_bytes()) {
+        Comparison::Match => None,
+        Comparison::Differ { line, .. } => Some(line),
+    };
+    assert_eq!(differ(&Exact, "1\n2 \n", "1\n2\n"), Some(Some(2)));
+    assert_eq!(differ(&Exact, "1\n2", "1\n2\n"), Some(None));
+    assert_eq!(differ(&Trimmed, "1\n2 \n\n", "1\n2"), None);
+    assert_eq!(differ(&Trimmed, "1 2\n", "1  2\n"), Some(Some(1)));
+    assert_eq!(differ(&Tokens, "1  2\n3", "1\n2 3\n"), None);
+    assert_eq!(differ(&Tokens, "1 2\n3\n4", "1 2\n3\n"), Some(Some(3)));
+    assert_eq!(differ(&FloatTolerance(1e-6), "0.3333333 1e9", "0.33333333 1000000000.5"), None);
+    assert_eq!(differ(&FloatTolerance(1e-6), "x 0.34", "x 0.33"), Some(Some(1)));
+
+    // the programme is judged against every reference, which must agree among themselves where it differs
+    let output = |name: &str, stdout: &str| ExecutionResult::new(PathBuf::from(name), stdout.to_string());
+    let (program, two, three) = (output("sol", "1 2\n"), output("a", "1  2\n"), output("b", "1 3\n"));
+    assert!(matches!(compare_outputs(&program, std::slice::from_ref(&two), &Tokens), Mismatch::AllMatch));
+    assert!(matches!(compare_outputs(&program, &[two.clone(), three.clone()], &Exact), Mismatch::RefMismatch(refs) if refs.len() == 2));
+    assert!(matches!(compare_outputs(&program, &[two, three.clone()], &Tokens), Mismatch::ProgMismatch { refs, .. } if refs[0].path == three.path));
+
+    // a session judges its rounds with the comparator it's given
+    struct Parity;
+    impl Comparator for Parity {
+        fn compare(&self, program: &[u8], reference: &[u8]) -> Comparison {
+            let parity = |out: &[u8]| String::from_utf8_lossy(out).trim().parse::<i64>().map(|n| n % 2);
+            if parity(program).is_ok() && parity(program) == parity(reference) { Comparison::Match }
+            else { Comparison::Differ { line: Some(1), explanation: "the parities differ".to_string() } }
+        }
+    }
+    let (gen, sol, other) = (fixtures::seeded_generator(100), fixtures::multiplying_program(2), fixtures::multiplying_program(4));
+    let session = Session::builder().generator(&gen).program(&sol).reference(&other).rounds(3).compare(Parity).build().unwrap();
+    assert!(session.rounds().all(|r| r.verdict == Verdict::Ok));
+}
+
+#[test]
+fn fake_backends_script_the_outcomes_of_the_rounds() {
+    use compdiff::runner::{ExecutionBackend, Invocation};
+    use compdiff::{output_to_execution, Execution, ExecutionResult, Limit};
+
+    // the generator prints the seed, the programmes are told apart by their names
+    struct Scripted;
+    impl ExecutionBackend for Scripted {
+        fn execute(&self, invocation: &Invocation, _: &RoundCtx) -> Execution {
+            let path = invocation.path.to_path_buf();
+            match path.file_name().and_then(|name| name.to_str()) {
+                Some("gen.py") => Ok(ExecutionResult::new(path, format!("{}\n", invocation.args[0]))),
+                Some("echo.py") => Ok(ExecutionResult::new(path, invocation.input.unwrap_or_default().to_string())),
+                Some("crash.py") => output_to_execution(ExecutionResult { signal: Some(11), status: Some("signal: 11 (SIGSEGV)".to_string()), ..ExecutionResult::new(path, String::new()) }),
+                _ => output_to_execution(ExecutionResult { limit_hit: Some(Limit::Time), ..ExecutionResult::new(path, String::new()) }),
+            }
+        }
+    }
+    let dir = scratch_dir("fake_backend");
+    for file in ["gen.py", "echo.py", "crash.py", "slow.py"] {
+        fs::write(dir.join(file), "").unwrap();
+    }
+    let verdicts = |program: &str| Session::builder().generator(dir.join("gen.py")).program(dir.join(program)).reference(dir.join("echo.py"))
+        .rounds(2).seed(3).backend(Scripted).build().unwrap().rounds().map(|r| r.verdict).collect::<Vec<_>>();
+    assert_eq!(verdicts("echo.py"), [Verdict::Ok, Verdict::Ok]);
+    assert_eq!(verdicts("crash.py"), [Verdict::RuntimeError, Verdict::RuntimeError]);
+    assert_eq!(verdicts("slow.py"), [Verdict::TimeLimit, Verdict::TimeLimit]);
+
+    let mut args = Cli::try_parse_from(["compdiff", "-g", dir.join("gen.py").to_str().unwrap(), "-p", dir.join("echo.py").to_str().unwrap(), "-s", "3"]).unwrap();
+    assert!(args.prepare().iter().all(|(_, built)| built.is_ok()));
+    let ctx = RoundCtx::new(&args, 0);
+    assert!(matches!(compdiff::run_round(&ctx, &Scripted), Round::Success { input, refs, .. } if input == format!("{}\n", ctx.seed) && refs.is_empty()));
+    // an input of the caller's own skips the generator
+    let ctx = RoundCtx::new(&args, 0);
+    assert!(matches!(compdiff::run_round_with_input(&ctx, "42\n".to_string(), &Scripted), Round::Success { prog, .. } if prog.stdout == "42\n"));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn executions_keep_everything_about_how_they_went() {
+    use compdiff::runner::{ExecutionBackend, Invocation, Processes};
+    use compdiff::{output_to_execution, ExecutionResult, Failure, Limit};
+
+    let ok = fixtures::echo_program();
+    let fails = fixtures::script("fails.py", "import sys\nprint('half')\nprint('broken', file=sys.stderr)\nsys.exit(3)\n");
+    let (quiet, slow, killed) = (fixtures::failing_program(2, ""), fixtures::slow_program(Duration::from_secs(5)), fixtures::crashing_program(9));
+    let args = Cli::try_parse_from(["compdiff", "-i", ok.arg(), "-p", ok.arg()]).unwrap();
+    let ctx = RoundCtx::new(&args, 0);
+    let run = |program: &Fixture| Processes.execute(&Invocation { limits: Limits { time: Some(Duration::from_millis(500)), memory: None }, ..Invocation::new(program.path(), Role::Program).with_input("42\n") }, &ctx);
+
+    let ok = run(&ok).unwrap();
+    assert_eq!((ok.path.as_path(), ok.stdout.preview(), ok.stderr.as_str(), ok.exit_code, ok.signal, ok.limit_hit), (args.programs.program.as_path(), "42\n", "", Some(0), None, None));
+    assert!(ok.succeeded() && ok.status.is_some() && ok.duration > Duration::ZERO);
+
+    // the output of a failing programme is kept along with its errors
+    let Err(Failure::Prog(fails)) = run(&fails) else { panic!("fails.py should fail") };
+    assert_eq!((fails.stdout.preview(), fails.stderr.as_str(), fails.exit_code), ("half\n", "broken\n", Some(3)));
+    assert!(fails.status.as_deref().is_some_and(|status| status.contains('3')));
+    let Err(Failure::Prog(quiet)) = run(&quiet) else { panic!("quiet.py should fail") };
+    assert_eq!((quiet.stderr.as_str(), quiet.exit_code), ("", Some(2)));
+
+    let Err(Failure::TimeLimit(slow)) = run(&slow) else { panic!("slow.py should exceed the time limit") };
+    assert_eq!((slow.limit_hit, slow.status.clone(), slow.exit_code), (Some(Limit::Time), None, None));
+    assert!(slow.duration >= Duration::from_millis(500) && slow.duration < Duration::from_secs(5));
+    assert!(slow.stats(Role::Program).killed);
+
+    let Err(Failure::Prog(killed)) = run(&killed) else { panic!("killed.py should fail") };
+    assert_eq!((killed.signal, killed.exit_code), (Some(9), None));
+
+    // every execution of the round is recorded with how it went
+    let stats = ctx.executions();
+    assert_eq!(stats.len(), 5);
+    assert_eq!(stats.iter().map(|s| (s.exit_code, s.killed)).collect::<Vec<_>>(), [(Some(0), false), (Some(3), false), (Some(2), false), (None, true), (None, false)]);
+
+    // the verdict is derived from the result alone, errors fail a programme which exits cleanly
+    let clean = ExecutionResult { exit_code: Some(0), ..ExecutionResult::new(ok.path.clone(), "1\n".to_string()) };
+    assert!(output_to_execution(clean.clone()).is_ok());
+    assert!(matches!(output_to_execution(ExecutionResult { stderr: "warning\n".to_string(), ..clean.clone() }), Err(Failure::Prog(_))));
+    assert!(matches!(output_to_execution(ExecutionResult { limit_hit: Some(Limit::RoundTimeout), ..clean.clone() }), Err(Failure::RoundTimeout(_))));
+    assert!(matches!(output_to_execution(ExecutionResult { exit_code: None, ..clean.clone() }), Err(Failure::Prog(_))));
+    assert_eq!(Failure::Prog(Box::new(clean.clone())).path(), ok.path);
+
+    let json = serde_json::to_string(&fails).unwrap();
+    assert_eq!(serde_json::from_str::<ExecutionResult>(&json).unwrap(), *fails);
+}
+
+#[test]
+fn long_outputs_are_written_to_files_and_compared_from_them() {
+    use compdiff::capture::capture;
+    use compdiff::compare::{compare_captured, Comparison, Exact, Tokens};
+
+    let short = capture(&b"1\n2\n"[..], 16);
+    assert_eq!((short.is_spilled(), short.preview()), (false, "1\n2\n"));
+
+    // past the threshold all of the output is in the file, and its beginning in memory
+    let long = "7\n".repeat(100_000);
+    let spilled = capture(long.as_bytes(), 16);
+    assert!(spilled.is_spilled());
+    assert_eq!(spilled.len(), long.len() as u64);
+    assert!(long.starts_with(spilled.preview()) && spilled.preview().len() < long.len());
+    assert_eq!(spilled.clone().into_string().unwrap(), long);
+
+    // the files are compared to their last lines
+    let differs = capture(format!("{}8\n", "7\n".repeat(99_999)).as_bytes(), 16);
+    assert_eq!(compare_captured(&Exact, &spilled, &capture(long.as_bytes(), 16)), Comparison::Match);
+    assert!(matches!(compare_captured(&Exact, &differs, &spilled), Comparison::Differ { line: Some(100_000), .. }));
+    assert!(matches!(compare_captured(&Tokens, &differs, &long.clone().into()), Comparison::Differ { line: Some(100_000), .. }));
+
+    // the rounds of a session write the long outputs to files as well
+    let dir = scratch_dir("spill");
+    fs::write(dir.join("gen.py"), "print(1)\n").unwrap();
+    fs::write(dir.join("sol.py"), "print('7\\n' * 100000, end='')\n").unwrap();
+    fs::write(dir.join("same.py"), "print('7\\n' * 100000, end='')\n").unwrap();
+    fs::write(dir.join("other.py"), "print('7\\n' * 99999 + '8')\n").unwrap();
+    let verdict = |reference: &str| Session::builder().generator(dir.join("gen.py")).program(dir.join("sol.py")).reference(dir.join(reference))
+        .spill_threshold(1).build().unwrap().round(0);
+    assert_eq!(verdict("same.py").verdict, Verdict::Ok);
+    let mismatched = verdict("other.py");
+    assert_eq!((mismatched.verdict, compdiff::Signature::of_round(&mismatched.outcome).line), (Verdict::WrongAnswer, Some(100_000)));
+    assert!(matches!(mismatched.outcome, Round::Success { prog, .. } if prog.stdout.is_spilled()));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn references_run_side_by_side_each_timed_on_its_own() {
+    use compdiff::runner::{ExecutionBackend, Invocation, Processes};
+
+    let (slow, fast) = (fixtures::slow_program(Duration::from_secs(1)), fixtures::echo_program());
+    // writes all of its output before it reads its input, which fills both pipes unless they're read and written at once
+    let chatty = fixtures::script("chatty.py", "import sys\nsys.stdout.write('x' * 1000000)\nsys.stdout.flush()\nsys.stdin.read()\n");
+    let (slow, fast, chatty) = (slow.path(), fast.path(), chatty.path());
+    let input = "1\n".repeat(500_000);
+    let invocations = [Invocation::new(slow, Role::Program).with_input("1\n"), Invocation::new(fast, Role::Program).with_input("2\n"), Invocation::new(chatty, Role::Program).with_input(&input)];
+
+    let run = |jobs: &str| {
+        let args = Cli::try_parse_from(["compdiff", "-i", "in.txt", "-p", "sol.py", "--ref-jobs", jobs]).unwrap();
+        let ctx = RoundCtx::new(&args, 0);
+        let started = std::time::Instant::now();
+        let outs = Processes.execute_all(&invocations, &ctx);
+        (outs.into_iter().map(|out| out.unwrap()).collect::<Vec<_>>(), started.elapsed())
+    };
+    let (outs, elapsed) = run("3");
+    assert_eq!(outs.iter().map(|x| x.path.as_path()).collect::<Vec<_>>(), [slow, fast, chatty]);
+    assert_eq!((outs[1].stdout.preview(), outs[2].stdout.len()), ("2\n", 1_000_000));
+    assert!(outs[1].duration < Duration::from_millis(900) && elapsed < Duration::from_millis(1900));
+    // one at a time, the fast reference waits for the slow one but isn't timed for it
+    let (outs, elapsed) = run("1");
+    assert!(outs[0].duration >= Duration::from_secs(1) && outs[1].duration < Duration::from_millis(900) && elapsed >= Duration::from_secs(1));
+}
+
+#[test]
+fn cancelled_sessions_stop_and_kill_what_runs() {
+    use compdiff::CancellationToken;
+
+    let (gen, sol, slow) = (fixtures::seeded_generator(100), fixtures::multiplying_program(2), fixtures::slow_program(Duration::from_secs(10)));
+
+    // the programme which runs is killed, and its round is dropped to run again when the session resumes
+    let slow = Session::builder().generator(&gen).program(&slow).reference(&sol).rounds(100).build().unwrap();
+    let token = slow.cancellation();
+    let canceller = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(300));
+        token.cancel();
+    });
+    let started = SystemTime::now();
+    let mut rounds = slow.rounds();
+    assert!(rounds.next().is_none());
+    assert!(started.elapsed().unwrap() < Duration::from_secs(5));
+    assert!(rounds.cancelled() && rounds.summary().cancelled && rounds.summary().rounds == 0);
+    assert_eq!(rounds.upcoming(), Some(0));
+    canceller.join().unwrap();
+
+    // the rounds which ran before are kept, and no other one starts
+    let token = CancellationToken::new();
+    let fast = Session::builder().generator(&gen).program(&sol).reference(&sol).rounds(100).cancellation(token.clone()).build().unwrap();
+    let mut rounds = fast.rounds();
+    let ran: Vec<_> = rounds.by_ref().take(2).collect();
+    token.cancel();
+    assert!(rounds.next().is_none());
+    assert_eq!((ran.len(), rounds.summary().rounds, rounds.cancelled()), (2, 2, true));
+}
+
+#[test]
+fn progress_is_reported_after_every_round() {
+    use compdiff::session::Progress;
+
+    let (gen, sol, wrong) = (fixtures::seeded_generator(2), fixtures::multiplying_program(2), fixtures::script("wrong.py", "print(int(input()) + 1)\n"));
+
+    let seen: Arc<Mutex<Vec<Progress>>> = Arc::default();
+    let recorded = Arc::clone(&seen);
+    let session = Session::builder().generator(&gen).program(&sol).reference(&wrong)
+        .rounds(4).on_progress(move |progress| recorded.lock().unwrap().push(progress)).build().unwrap();
+    let verdicts: Vec<_> = session.rounds().map(|r| r.verdict).collect();
+    let seen = seen.lock().unwrap();
+    assert_eq!(seen.iter().map(|p| (p.completed, p.total)).collect::<Vec<_>>(), [(1, Some(4)), (2, Some(4)), (3, Some(4)), (4, Some(4))]);
+    let last = seen.last().unwrap();
+    assert_eq!((last.passed + last.failed, last.passed), (4, verdicts.iter().filter(|v| **v == Verdict::Ok).count() as u64));
+    assert!(seen.windows(2).all(|w| w[0].elapsed <= w[1].elapsed));
+
+    // resumed rounds go on from where the session stopped
+    let rounds = session.rounds_from(3);
+    assert_eq!(rounds.progress().completed, 3);
+}
+
+#[test]
+fn sessions_report_what_their_rounds_came_to() {
+    use compdiff::cli::{EXIT_FAILURE, EXIT_OK};
+    use compdiff::session::{run_session, SessionReport, Stop};
+    use compdiff::{CancellationToken, Mismatch};
+
+    let (gen, sol) = (fixtures::seeded_generator(10), fixtures::multiplying_program(2));
+    let wrong = fixtures::script("wrong.py", "print(int(input()) * 2 + 1)\n");
+    // SIGABRT
+    let (crash, slow) = (fixtures::crashing_program(6), fixtures::slow_program(Duration::from_secs(5)));
+    let run = |program: &Fixture, options: &[&str], token: &CancellationToken| -> SessionReport {
+        let mut args = Cli::try_parse_from(["compdiff", "-g", gen.arg(), "-p", program.arg(), "-r", sol.arg(), "-s", "7", "-c", "4"].iter().chain(options)).unwrap();
+        assert!(args.prepare().iter().all(|(_, built)| built.is_ok()));
+        run_session(&args, None, token, &mut ())
+    };
+    let token = CancellationToken::new();
+
+    let report = run(&sol, &[], &token);
+    assert_eq!((report.summary.passed, report.summary.exit_code, report.rounds_done, report.stopped), (4, EXIT_OK, 4, None));
+    assert!(report.mismatches.is_empty() && report.failures.is_empty());
+
+    let report = run(&wrong, &[], &token);
+    assert_eq!((report.summary.failed, report.summary.exit_code, report.failed_rounds), (4, EXIT_FAILURE, 4));
+    assert!(report.mismatches.iter().all(|m| matches!(m.mismatch, Mismatch::ProgMismatch { .. })));
+    assert!(report.failures.iter().all(|f| f.signature.verdict == Verdict::WrongAnswer && f.signature.line == Some(1)));
+    assert_eq!(report.failures.iter().map(|f| f.round).collect::<Vec<_>>(), [0, 1, 2, 3]);
+    // a round which passes after a failing one doesn't make the session pass
+    let first = compdiff::round_seed(7, 0) % 10;
+    assert_ne!(compdiff::round_seed(7, 3) % 10, first);
+    let report = run(&fixtures::script("first.py", &format!("n = int(input())\nprint(n * 2 + (n == {}))\n", first)), &[], &token);
+    assert_eq!((report.failures[0].round, report.summary.exit_code), (0, EXIT_FAILURE));
+    let report = run(&wrong, &["--fail-fast"], &token);
+    assert_eq!((report.summary.rounds, report.stopped), (1, Some(Stop::FailFast)));
+
+    let report = run(&crash, &["--max-failures", "2"], &token);
+    assert_eq!((report.summary.rounds, report.stopped, report.summary.exit_code), (2, Some(Stop::MaxFailures), EXIT_FAILURE));
+    let crash = &report.failures[0];
+    assert_eq!((crash.signature.verdict, crash.signature.signal), (Verdict::RuntimeError, Some(6)));
+    assert!(crash.input.is_some() && report.mismatches.is_empty());
+
+    let report = run(&slow, &["-t", "0.5", "--max-failures", "1"], &token);
+    assert_eq!(report.summary.verdicts.get(&Verdict::TimeLimit), Some(&1));
+    assert!(report.elapsed < Duration::from_secs(5));
+
+    token.cancel();
+    let report = run(&sol, &[], &token);
+    assert_eq!((report.summary.rounds, report.stopped, report.summary.cancelled), (0, Some(Stop::Interrupted), true));
+}
+
+#[test]
+fn sessions_run_side_by_side_on_threads() {
+    use compdiff::session::SessionReport;
+
+    fn shareable<T: Send + Sync>() {}
+    shareable::<Session>();
+    shareable::<Cli>();
+    shareable::<RoundResult>();
+    shareable::<SessionReport>();
+
+    let dir = scratch_dir("threads");
+    fs::write(dir.join("gen.py"), "import sys\nprint(int(sys.argv[1]) % 100 + 1)\n").unwrap();
+    fs::write(dir.join("double.py"), "print(int(input()) * 2)\n").unwrap();
+    // the same programme with another factor, each built into a directory of its own
+    let source = |factor| format!("#include <cstdio>\nint main(){{ int n; scanf(\"%d\", &n); printf(\"%d\\n\", {} * n); }}\n", factor);
+    fs::write(dir.join("double.cpp"), source(2)).unwrap();
+    fs::write(dir.join("triple.cpp"), source(3)).unwrap();
+
+    let run = |program: &str, build: &str| {
+        let (dir, program, reference, build) = (dir.clone(), dir.join(program), dir.join("double.py"), dir.join(build));
+        thread::spawn(move || {
+            let session = Session::builder().generator(dir.join("gen.py")).program(program).reference(reference)
+                .rounds(5).seed(3).build_dir(&build).build().unwrap();
+            let verdicts: Vec<_> = session.rounds().map(|r| r.verdict).collect();
+            (verdicts, fs::read_dir(&build).unwrap().count())
+        })
+    };
+    let (right, wrong) = (run("double.cpp", "build_a"), run("triple.cpp", "build_b"));
+    let (right, wrong) = (right.join().unwrap(), wrong.join().unwrap());
+    assert_eq!(right.0, [Verdict::Ok; 5]);
+    assert_eq!(wrong.0, [Verdict::WrongAnswer; 5]);
+    assert!(right.1 > 0 && wrong.1 > 0);
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn rounds_run_on_tokio_like_they_run_blocking() {
+    use compdiff::nonblocking;
+
+    let (gen, sol, slow) = (fixtures::seeded_generator(100), fixtures::multiplying_program(2), fixtures::slow_program(Duration::from_secs(5)));
+    let parse = |program: &Fixture| {
+        let mut args = Cli::try_parse_from(["compdiff", "-g", gen.arg(), "-p", program.arg(), "-r", sol.arg(), "-s", "7", "-t", "1"]).unwrap();
+        assert!(args.prepare().iter().all(|(_, built)| built.is_ok()));
+        args
+    };
+
+    let args = parse(&sol);
+    let ctx = RoundCtx::new(&args, 3);
+    let (blocking, nonblocking) = (compdiff::run_round(&ctx, &compdiff::runner::Processes), nonblocking::run_round(&ctx).await);
+    assert!(matches!((&blocking, &nonblocking), (Round::Success { input: a, .. }, Round::Success { input: b, .. }) if a == b));
+
+    let args = parse(&slow);
+    let started = std::time::Instant::now();
+    assert!(matches!(nonblocking::run_round(&RoundCtx::new(&args, 0)).await, Round::ProgramFail { fail: compdiff::Failure::TimeLimit(_), .. }));
+    assert!(started.elapsed() < Duration::from_secs(4));
+
+    // gdb would block the runtime's thread
+    let crash = fixtures::crashing_program(6);
+    let mut args = parse(&crash);
+    args.backtrace_on_crash = true;
+    let Round::ProgramFail { diagnostics, .. } = nonblocking::run_round(&RoundCtx::new(&args, 0)).await else { panic!("the programme didn't fail") };
+    assert!(diagnostics.iter().any(|d| d.title == "backtrace unavailable" && d.body.contains("async")), "{:?}", diagnostics);
+}
+
+#[test]
+fn compile_errors_carry_the_compiler_output() {
+    let dir = scratch_dir("compile_errors");
+    fs::write(dir.join("bad.cpp"), "int main(){ return undeclared; }\n").unwrap();
+
+    let args = Cli::try_parse_from(["compdiff", "-g", "gen.py", "-p", "bad.cpp"]).unwrap();
+    let e = build(dir.join("bad.cpp"), &args.programs).unwrap_err();
+    let e = e.downcast_ref::<CompileError>().expect("a compile error");
+    assert_eq!(e.path, dir.join("bad.cpp"));
+    assert!(e.command.contains("bad.cpp"));
+    assert!(e.stderr.contains("undeclared"));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn flags_the_compiler_rejects_are_named() {
+    let dir = scratch_dir("unsupported_flags");
+    fs::write(dir.join("sol.cpp"), "int main(){}\n").unwrap();
+
+    let build_dir = dir.join("build");
+    let args = Cli::try_parse_from(["compdiff", "-g", "gen.py", "-p", "sol.cpp", "--build-dir", build_dir.to_str().unwrap(),
+        "--flags", "cpp=-O2 -std=c++77"]).unwrap();
+    let e = build(dir.join("sol.cpp"), &args.programs).unwrap_err();
+    let e = e.downcast_ref::<UnsupportedFlags>().expect("unsupported flags");
+    assert_eq!(e.rejected, ["-std=c++77"]);
+    assert!(e.suggestion.as_ref().is_some_and(|s| s.starts_with("-std=c++")));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn sources_are_compiled_into_the_build_dir() {
+    let dir = scratch_dir("build_dir");
+    fs::write(dir.join("sol.cpp"), "int main(){}\n").unwrap();
+
+    let build_dir = dir.join("build");
+    let args = Cli::try_parse_from(["compdiff", "-g", "gen.py", "-p", "sol.cpp", "--build-dir", build_dir.to_str().unwrap()]).unwrap();
+    let binary = build(dir.join("sol.cpp"), &args.programs).unwrap().binary;
+    assert!(binary.starts_with(fs::canonicalize(&build_dir).unwrap()));
+    assert!(binary.is_file());
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn compile_builds_every_source_once_per_role() {
+    let dir = scratch_dir("compile_once");
+    for name in ["a.cpp", "b.cpp"] {
+        fs::write(dir.join(name), "int main(){}\n").unwrap();
+    }
+    let (a, b) = (dir.join("a.cpp"), dir.join("b.cpp"));
+    let (a, b) = (a.to_str().unwrap(), b.to_str().unwrap());
+    let out = compdiff(&["compile", "-p", a, "-r", a, "-r", b, "-r", a]).output().unwrap();
+    assert_eq!(out.status.code(), Some(0));
+    let built: Vec<_> = String::from_utf8(out.stdout).unwrap().lines().filter(|l| l.contains(" -> ")).map(str::to_string).collect();
+    assert_eq!(built.len(), 3, "{:?}", built);
+    assert_eq!(built.iter().filter(|l| l.contains("a.cpp_reference_")).count(), 1);
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn sources_are_preprocessed_without_the_command_line() {
+    let dir = scratch_dir("preprocess_options");
+    fs::write(dir.join("sol.cpp"), "#include <cstdio>\nint main(){ printf(\"%d\\n\", VALUE); }\n").unwrap();
+    fs::write(dir.join("brute.py"), "print(1)\n").unwrap();
+
+    let opts = PreprocessOptions {
+        flags: [("cpp".to_string(), "-O2".to_string())].into(),
+        defines: vec!["VALUE=7".to_string()],
+        build_dir: dir.join("build"),
+        reuse: true,
+        ..PreprocessOptions::default()
+    };
+    let built = preprocess(&dir.join("sol.cpp"), &opts).unwrap();
+    let compiled = built.compiled.expect("a compiled source");
+    assert!(!compiled.reused && compiled.flags.contains(&"-DVALUE=7".to_string()));
+    assert_eq!(String::from_utf8(Command::new(&built.binary).output().unwrap().stdout).unwrap(), "7\n");
+    assert!(preprocess(&dir.join("sol.cpp"), &opts).unwrap().compiled.is_some_and(|c| c.reused));
+
+    let script = preprocess(&dir.join("brute.py"), &opts).unwrap();
+    assert!(script.compiled.is_none() && script.interpreter.is_some() && script.binary == dir.join("brute.py"));
+
+    // the version of the toolchain the session detected goes into the binary's name in place of asking again
+    let detected = compdiff::Toolchain::detect(&compdiff::cli::Programs::default()).unwrap();
+    let built_by = |version: &str| {
+        let toolchain = compdiff::Toolchain { version: version.to_string(), ..detected.clone() };
+        preprocess(&dir.join("sol.cpp"), &PreprocessOptions { toolchain: Some(toolchain), ..opts.clone() }).unwrap().binary
+    };
+    assert_eq!(built_by(&detected.version), built.binary);
+    assert_ne!(built_by("another version"), built.binary);
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn programs_made_of_several_sources_are_linked() {
+    let dir = scratch_dir("linked_sources");
+    let (include, solution) = (dir.join("include"), dir.join("solution"));
+    fs::create_dir_all(&include).unwrap();
+    fs::create_dir_all(&solution).unwrap();
+    fs::write(include.join("geometry.h"), "int twice(int x);\n").unwrap();
+    fs::write(solution.join("sol.cpp"), "#include <cstdio>\n#include \"geometry.h\"\nint main(){ printf(\"%d\\n\", twice(21)); }\n").unwrap();
+    fs::write(solution.join("geometry.cpp"), "#include \"geometry.h\"\nint twice(int x) { return 2 * x; }\n").unwrap();
+
+    let run = |bin: &PathBuf| String::from_utf8(Command::new(bin).output().unwrap().stdout).unwrap();
+    let listed = format!("{},{}", solution.join("sol.cpp").display(), solution.join("geometry.cpp").display());
+    for program in [listed.as_str(), solution.to_str().unwrap()] {
+        let mut args = Cli::try_parse_from(["compdiff", "-g", "gen.py", "-p", program, "--include-dir", include.to_str().unwrap()]).unwrap();
+        args.programs.expand_program().unwrap();
+        assert_eq!(args.programs.program_sources.len(), 2);
+        let binary = build(&args.programs.program, &args.programs).unwrap().binary;
+        assert_eq!(run(&binary), "42\n");
+    }
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn programs_are_linked_with_the_grader() {
+    let dir = scratch_dir("grader");
+    let include = dir.join("include");
+    fs::create_dir_all(&include).unwrap();
+    fs::write(include.join("task.h"), "int solve(int n);\n").unwrap();
+    fs::write(dir.join("grader.cpp"), "#include <cstdio>\n#include \"task.h\"\nint main(){ int n; scanf(\"%d\", &n); printf(\"%d\\n\", solve(n)); }\n").unwrap();
+    fs::write(dir.join("sol.cpp"), "#include \"task.h\"\nint solve(int n) { return n + 1; }\n").unwrap();
+    fs::write(dir.join("bad.cpp"), "#include \"task.h\"\nint solve(int n) { return m; }\n").unwrap();
+
+    let grader = dir.join("grader.cpp");
+    let compile = |program: &str| {
+        let args = Cli::try_parse_from(["compdiff", "-g", "gen.py", "-p", program, "-r", "brute.py",
+            "--grader", grader.to_str().unwrap(), "--grader-include", include.to_str().unwrap()]).unwrap();
+        assert_eq!(args.programs.linked_sources(Role::Reference, Path::new("brute.py")), [Path::new("brute.py")]);
+        build(program, &args.programs)
+    };
+    let binary = compile(dir.join("sol.cpp").to_str().unwrap()).unwrap().binary;
+    let mut child = Command::new(binary).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    child.stdin.take().unwrap().write_all(b"41\n").unwrap();
+    assert_eq!(child.wait_with_output().unwrap().stdout, b"42\n");
+
+    let e = compile(dir.join("bad.cpp").to_str().unwrap()).unwrap_err();
+    let e = e.downcast_ref::<CompileError>().expect("a compile error");
+    assert_eq!(e.grader, Some(fs::canonicalize(&grader).unwrap()));
+    assert_eq!(e.erring, [fs::canonicalize(dir.join("bad.cpp")).unwrap()]);
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn compile_warnings_are_kept_or_fail_the_build() {
+    let dir = scratch_dir("compile_warnings");
+    fs::write(dir.join("warn.cpp"), "int main(){ int unused; }\n").unwrap();
+    let build_dir = dir.join("build");
+
+    let argv = ["compdiff", "-g", "gen.py", "-p", "warn.cpp", "--flags", "cpp=-Wall", "--build-dir", build_dir.to_str().unwrap()];
+    let args = Cli::try_parse_from(argv).unwrap();
+    let built = build(dir.join("warn.cpp"), &args.programs).unwrap();
+    assert!(built.warnings.contains("unused"));
+    // the warnings are kept with the cached binary
+    assert_eq!(build(dir.join("warn.cpp"), &args.programs).unwrap().warnings, built.warnings);
+
+    let args = Cli::try_parse_from(argv.into_iter().chain(["--warnings-as-errors"])).unwrap();
+    let e = build(dir.join("warn.cpp"), &args.programs).unwrap_err();
+    assert!(e.downcast_ref::<CompileError>().expect("a compile error").stderr.contains("unused"));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn the_least_recently_used_binaries_are_evicted() {
+    let dir = scratch_dir("evict_cache");
+    let now = SystemTime::now();
+    for (name, age) in [("old", 30), ("recent", 10), ("newest", 0)] {
+        fs::write(dir.join(format!("{}.o", name)), [0; 100]).unwrap();
+        fs::write(dir.join(format!("{}.src", name)), "sol.cpp\n0\n").unwrap();
+        let binary = fs::File::options().append(true).open(dir.join(format!("{}.o", name))).unwrap();
+        binary.set_modified(now - Duration::from_secs(age)).unwrap();
+    }
+
+    assert_eq!(evict_cache(&dir, 1000).unwrap(), 0);
+    assert_eq!(evict_cache(&dir, 150).unwrap(), 2);
+    assert!(dir.join("newest.o").is_file());
+    assert!(!dir.join("old.o").exists() && !dir.join("old.src").exists());
+    assert!(!dir.join("recent.o").exists());
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn only_the_directories_compdiff_created_are_its_own() {
+    let dir = scratch_dir("owned_dirs");
+    create_owned_dir(&dir).unwrap();
+    assert!(!is_owned_dir(&dir), "the directory existed before");
+
+    let created = dir.join("failures");
+    create_owned_dir(&created).unwrap();
+    assert!(is_owned_dir(&created));
+    create_owned_dir(&created).unwrap();
+    assert!(is_owned_dir(&created));
+}
+
+#[test]
+fn reference_outputs_are_cached_by_input() {
+    let dir = scratch_dir("ref_cache");
+    let reference = dir.join("brute.py");
+    fs::write(&reference, "print(input())\n").unwrap();
+    let args = Cli::try_parse_from(["compdiff", "-g", "gen.py", "-p", "sol.py", "-r", reference.to_str().unwrap()]).unwrap();
+
+    let cache = RefCache::open(&dir.join("cache"), u64::MAX, &args.programs).unwrap();
+    assert_eq!(cache.get(&reference, "1\n"), None);
+    cache.put(&reference, "1\n", "1\n").unwrap();
+    assert_eq!(cache.get(&reference, "1\n").as_deref(), Some("1\n"));
+    assert_eq!(cache.get(&reference, "2\n"), None);
+
+    // a changed reference doesn't get the outputs of the old one
+    fs::write(&reference, "print(int(input()) * 2)\n").unwrap();
+    let changed = RefCache::open(&dir.join("cache"), u64::MAX, &args.programs).unwrap();
+    assert_eq!(changed.get(&reference, "1\n"), None);
+
+    // a damaged entry is a miss
+    changed.put(&reference, "1\n", "2\n").unwrap();
+    for entry in fs::read_dir(dir.join("cache")).unwrap() {
+        let entry = entry.unwrap().path();
+        if entry.extension().is_some_and(|e| e == "out") {
+            fs::write(&entry, fs::read_to_string(&entry).unwrap() + "garbage").unwrap();
+        }
+    }
+    assert_eq!(changed.get(&reference, "1\n"), None);
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn reference_outputs_are_remembered_for_the_last_inputs() {
+    let memo = RefMemo::new(2);
+    memo.put("1\n", vec!["1\n".to_string().into()]);
+    memo.put("2\n", vec!["2\n".to_string().into()]);
+    assert_eq!(memo.get("1\n"), Some(vec!["1\n".to_string().into()]));
+    memo.put("3\n", vec!["3\n".to_string().into()]);
+    assert_eq!(memo.get("1\n"), None);
+    assert_eq!(memo.get("3\n"), Some(vec!["3\n".to_string().into()]));
+
+    let forgetful = RefMemo::new(0);
+    forgetful.put("1\n", vec!["1\n".to_string().into()]);
+    assert_eq!(forgetful.get("1\n"), None);
+}
+
+#[test]
+fn docker_runs_the_commands_in_containers_with_their_files_mounted() {
+    let dir = scratch_dir("docker");
+    let (build, binary) = (dir.join("build"), dir.join("build").join("sol.o"));
+    fs::create_dir_all(&build).unwrap();
+    fs::write(&binary, "").unwrap();
+
+    let docker = Backend::Docker { docker: PathBuf::from("docker"), image: "gcc:13".to_string(), user: None };
+    let mut cmd = Command::new(&binary);
+    cmd.current_dir(&dir).env("COMPDIFF_SEED", "7");
+    let wrapped = docker.command(cmd, &[], Limits { time: Some(Duration::from_secs(2)), memory: Some(64_000_000) });
+    let argv = wrapped.get_args().map(|a| a.to_string_lossy().into_owned()).collect::<Vec<_>>();
+    let mounted = |dir: &Path| format!("{0}:{0}:ro", dir.display());
+    assert_eq!(argv[..3], ["run", "--rm", "-i"]);
+    // the binary is within the working directory, which is mounted already
+    assert!(argv.contains(&mounted(&dir)) && !argv.contains(&mounted(&build)));
+    assert!(argv.contains(&"COMPDIFF_SEED=7".to_string()) && argv.contains(&"--memory=64000000".to_string()));
+    assert!(argv.ends_with(&["gcc:13", "timeout", "--signal=KILL", "2.000", binary.to_str().unwrap()].map(String::from)));
+
+    // here nothing changes, and in a container programmes are found when they run
+    assert_eq!(Backend::Local.command(Command::new("g++"), &[], Limits::default()).get_program(), "g++");
+    assert_eq!(docker.which("g++"), Some(PathBuf::from("g++")));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn remote_runs_the_copied_programme_over_ssh() {
+    let binary = PathBuf::from("/build/sol.o");
+    let remote = Backend::Remote {
+        ssh: PathBuf::from("ssh"),
+        host: "me@box".to_string(),
+        dir: "/tmp/compdiff".to_string(),
+        files: vec![(binary.clone(), "/tmp/compdiff/sol.o".to_string())],
+    };
+    let mut cmd = Command::new(&binary);
+    cmd.arg("it's").env("COMPDIFF_SEED", "7");
+    let wrapped = remote.command(cmd, &[], Limits { time: Some(Duration::from_secs(2)), memory: Some(64_000_000) });
+    let argv = wrapped.get_args().map(|a| a.to_string_lossy().into_owned()).collect::<Vec<_>>();
+    assert_eq!(wrapped.get_program(), "ssh");
+    assert_eq!(argv[4], "me@box");
+    assert_eq!(argv[5], r"cd '/tmp/compdiff' && ulimit -v 64000 && export COMPDIFF_SEED='7' && timeout --signal=KILL 2.000 '/tmp/compdiff/sol.o' 'it'\''s'");
+
+    // a python script is copied, its interpreter is the host's
+    let mut python = Command::new("/usr/bin/python3");
+    python.arg(&binary);
+    let argv = remote.command(python, &[], Limits::default()).get_args().map(|a| a.to_string_lossy().into_owned()).collect::<Vec<_>>();
+    assert!(argv[5].ends_with("'python3' '/tmp/compdiff/sol.o'"));
+}
+
+#[test]
+fn failed_executions_say_what_failed_where_and_why() {
+    use std::os::unix::fs::PermissionsExt;
+    use compdiff::{Cause, Failure, Role, RunError};
+
+    let dir = scratch_dir("run_errors");
+    let sol = fixtures::echo_program();
+    fs::write(dir.join("notes.txt"), "not a programme\n").unwrap();
+    // a binary which may not be executed
+    fs::copy("/bin/true", dir.join("brute")).unwrap();
+    fs::set_permissions(dir.join("brute"), fs::Permissions::from_mode(0o644)).unwrap();
+    let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+    let args = Cli::try_parse_from(["compdiff", "-i", sol.arg(), "-p", sol.arg(), "-r", sol.arg(), "-r", &path("brute")]).unwrap();
+
+    let round = compdiff::run_round_with_input(&RoundCtx::new(&args, 12), "1\n".to_string(), &compdiff::runner::Processes);
+    let Round::ReferenceFails { refs, .. } = round else { panic!("the reference can't be run") };
+    let [Ok(succeeded), Err(Failure::Spawn(e))] = &refs[..] else { panic!("only the second reference can't be started") };
+    assert_eq!(succeeded.stdout, "1\n");
+    assert_eq!(compdiff::failed_references(&args, &refs), format!("reference 2 of 2 ({}) failed, the other succeeded", path("brute")));
+    assert_eq!(e.role, Role::Reference);
+    assert_eq!(e.describe(&args, false), format!("failed to run reference {} in round 12: permission denied (os error 13)", path("brute")));
+
+    let args = Cli::try_parse_from(["compdiff", "-i", sol.arg(), "-p", &path("notes.txt")]).unwrap();
+    let round = compdiff::run_round_with_input(&RoundCtx::new(&args, 3), "1\n".to_string(), &compdiff::runner::Processes);
+    let Round::ProgramFail { fail: Failure::Spawn(e), .. } = round else { panic!("the programme can't be run") };
+    assert_eq!(e.describe(&args, false), format!("failed to run program {} in round 3: textual input is not yet supported.", path("notes.txt")));
+
+    // only the innermost cause is shown unless the whole chain is asked for
+    let cause = Cause::of(&std::io::Error::other(Cause::new("lost the connection to its host").caused_by(Cause::new("Connection reset by peer"))));
+    assert_eq!(cause.chain(), ["lost the connection to its host", "connection reset by peer"]);
+    let e = RunError { role: Role::Generator, path: dir.join("gen.py"), round: 5, action: "run".to_string(), cause };
+    assert_eq!(e.describe(&args, false), format!("failed to run generator {} in round 5: connection reset by peer", path("gen.py")));
+    assert_eq!(e.to_string(), format!("failed to run generator {} in round 5: lost the connection to its host: connection reset by peer", path("gen.py")));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn fixtures_write_the_programmes_they_describe() {
+    use compdiff::runner::{ExecutionBackend, Invocation, Processes};
+
+    let text = "it's \\ \"quoted\"\n\u{e9}\n";
+    let (gen, failing) = (fixtures::generator_with_output(text), fixtures::failing_program(4, "no'pe\n"));
+    let args = Cli::try_parse_from(["compdiff", "-g", gen.arg(), "-p", failing.arg()]).unwrap();
+    let ctx = RoundCtx::new(&args, 0);
+    assert_eq!(Processes.execute(&Invocation::new(gen.path(), Role::Generator), &ctx).unwrap().stdout, text);
+    let Err(compdiff::Failure::Prog(failed)) = Processes.execute(&Invocation::new(failing.path(), Role::Program), &ctx) else { panic!("the programme fails") };
+    assert_eq!((failed.exit_code, failed.stderr.as_str()), (Some(4), "no'pe\n"));
+
+    // the directory of a fixture goes with it
+    let dir = gen.path().parent().unwrap().to_path_buf();
+    drop(gen);
+    assert!(!dir.exists());
+}
+
+#[test]
+fn the_environment_of_a_session_is_recorded_once_prepared() {
+    use std::os::unix::fs::PermissionsExt;
+    use compdiff::save::FailureSaver;
+
+    let (gen, sol) = (fixtures::seeded_generator(10), fixtures::echo_program());
+    let mut args = Cli::try_parse_from(["compdiff", "-g", gen.arg(), "-p", sol.arg(), "-s", "3"]).unwrap();
+    assert!(args.environment.is_none());
+    assert!(args.prepare().iter().all(|(_, built)| built.is_ok()));
+    let environment = args.environment.clone().unwrap();
+    assert_eq!((environment.version.as_str(), environment.os.as_str()), (env!("CARGO_PKG_VERSION"), std::env::consts::OS));
+    assert_eq!(environment.tools.iter().map(|t| t.source.as_path()).collect::<Vec<_>>(), [gen.path(), sol.path()]);
+    assert!(environment.tools.iter().all(|t| !t.compiled && t.path.is_some() && t.version.starts_with("Python")));
+    assert!(environment.config.contains("seed: Some(\n        3,\n    )"));
+    assert!(environment.lines()[1].starts_with(&format!("{}: run by ", gen.path().display())));
+
+    // the saved failures start with it
+    let dir = scratch_dir("environment");
+    let saver = FailureSaver::new(&dir, Some(&environment)).unwrap();
+    let meta = fs::read_to_string(saver.dir().join("meta.txt")).unwrap();
+    assert!(meta.starts_with(&format!("compdiff {} on ", env!("CARGO_PKG_VERSION"))) && meta.contains("configuration: Cli {"));
+
+    // a tool which prints its version to stderr
+    let tool = dir.join("tool");
+    fs::write(&tool, "#!/bin/sh\necho >&2\necho 'tool 1.2' >&2\n").unwrap();
+    fs::set_permissions(&tool, fs::Permissions::from_mode(0o755)).unwrap();
+    assert_eq!(compdiff::tool_version(&tool, &Backend::Local), "tool 1.2");
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn failing_inputs_are_minimized_line_by_line_then_word_by_word() {
+    use compdiff::minimize::minimize;
+
+    // the programme crashes on any number from 10 on
+    let crash = fixtures::script("sol.py", "import os, sys\nnumbers = [int(w) for w in sys.stdin.read().split()]\nif any(n >= 10 for n in numbers): os.abort()\nprint(sum(numbers))\n");
+    let sum = fixtures::script("sum.py", "import sys\nprint(sum(int(w) for w in sys.stdin.read().split()))\n");
+    // every line has two numbers
+    let pairs = fixtures::script("pairs.py", "import sys\nsys.exit(0 if all(len(l.split()) == 2 for l in sys.stdin.read().splitlines()) else 1)\n");
+    let input = "3 1\n5 17\n8 1\n40 4\n";
+    let parse = |options: &[&str]| Cli::try_parse_from(["compdiff", "-i", sum.arg(), "-p", crash.arg(), "-r", sum.arg(), "--minimize"].iter().chain(options)).unwrap();
+
+    let args = parse(&[]);
+    let outcome = compdiff::run_round_with_input(&RoundCtx::new(&args, 0), input.to_string(), &compdiff::runner::Processes);
+    let signature = compdiff::Signature::of_round(&outcome);
+    assert_eq!((signature.verdict, signature.signal), (Verdict::RuntimeError, Some(6)));
+    let minimized = minimize(&args, 0, input, signature);
+    assert_eq!(minimized.input, "10\n");
+    assert!(minimized.runs > 0);
+
+    // the validator keeps the candidates within the constraints
+    let args = parse(&["--minimize-validator", pairs.arg()]);
+    assert_eq!(minimize(&args, 0, input, signature).input, "10 0\n");
+}
+
+#[test]
+fn mutants_of_a_failing_input_are_run_and_the_distinct_failing_ones_kept() {
+    use compdiff::explore::explore;
+
+    let crash = fixtures::script("sol.py", "import os, sys\nif any(int(w) >= 10 for w in sys.stdin.read().split()): os.abort()\n");
+    let quiet = fixtures::script("ref.py", "import sys\nsys.stdin.read()\n");
+    // at most two lines
+    let short = fixtures::script("short.py", "import sys\nsys.exit(len(sys.stdin.read().splitlines()) > 2)\n");
+    let parse = |options: &[&str]| Cli::try_parse_from(["compdiff", "-i", quiet.arg(), "-p", crash.arg(), "-r", quiet.arg(), "--explore", "30"].iter().chain(options)).unwrap();
+
+    let args = parse(&[]);
+    let explored = explore(&args, 0, 7, "9 3\n4\n", 30);
+    assert_eq!((explored.mutants, explored.rejected), (30, 0));
+    assert!(explored.failing > 0 && explored.failing >= explored.distinct.len());
+    assert!(explored.distinct.iter().all(|(_, s)| (s.verdict, s.signal) == (Verdict::RuntimeError, Some(6))));
+    let inputs: Vec<&str> = explored.distinct.iter().map(|(input, _)| input.as_str()).collect();
+    assert!(inputs.iter().enumerate().all(|(i, input)| !inputs[..i].contains(input)));
+    // the mutants only depend on the seed
+    assert_eq!(explore(&args, 0, 7, "9 3\n4\n", 30).failing, explored.failing);
+
+    let args = parse(&["--minimize-validator", short.arg()]);
+    let explored = explore(&args, 0, 7, "9 3\n4\n", 30);
+    assert!(explored.rejected > 0);
+    assert_eq!(explored.mutants + explored.rejected, 30);
+    assert!(explored.distinct.iter().all(|(input, _)| input.lines().count() <= 2));
+}
+
+#[test]
+fn specs_write_the_same_input_for_the_same_seed() {
+    use compdiff::genspec::GenSpec;
+
+    let spec: GenSpec = "n = int(2, 5); print(n)\nrepeat(n) { print(ints(2, -3, 3)) }\nlines(perm(n)); print(string(4, \"ab\"), distinct(3, 1, 3))".parse().unwrap();
+    let input = spec.generate(42).unwrap();
+    assert_eq!(spec.generate(42).unwrap(), input);
+    let lines: Vec<&str> = input.lines().collect();
+    let n: usize = lines[0].parse().unwrap();
+    assert_eq!(lines.len(), 1 + 2 * n + 1);
+    assert!(lines[1..=n].iter().all(|l| l.split(' ').all(|w| (-3..=3).contains(&w.parse::<i64>().unwrap()))));
+    let mut permutation: Vec<usize> = lines[n + 1..=2 * n].iter().map(|l| l.parse().unwrap()).collect();
+    permutation.sort();
+    assert_eq!(permutation, (1..=n).collect::<Vec<_>>());
+    let (text, distinct) = lines[2 * n + 1].split_once(' ').unwrap();
+    assert!(text.len() == 4 && text.chars().all(|c| "ab".contains(c)));
+    let mut distinct: Vec<&str> = distinct.split(' ').collect();
+    distinct.sort();
+    assert_eq!(distinct, ["1", "2", "3"]);
+
+    let error = |source: &str| source.parse::<GenSpec>().and_then(|spec| spec.generate(1)).unwrap_err().to_string();
+    assert_eq!(error("n = 1e5; print(m)"), "at column 16: `m` isn't assigned before it's used");
+    assert_eq!(error("print(distinct(4, 1, 3))"), "at column 7: there aren't 4 distinct numbers from 1 to 3");
+    assert_eq!(error("print(1 +)"), "at column 10: expected a value, found `)`");
+    assert_eq!(error("repeat(2) { print(1)"), "at column 21: the block isn't closed with a `}`");
+
+    // a round's input comes from its seed
+    let echo = fixtures::echo_program();
+    let mut args = Cli::try_parse_from(["compdiff", "--gen-spec", "print(int(1, 1e9))", "-p", echo.arg(), "-r", echo.arg()]).unwrap();
+    args.prepare();
+    let ctx = RoundCtx::new(&args, 3);
+    let Round::Success { input, .. } = compdiff::run_round(&ctx, &compdiff::runner::Processes) else { panic!("the round failed") };
+    assert_eq!(input, args.gen_spec.as_ref().unwrap().generate(ctx.seed).unwrap());
+}
+
+#[test]
+fn specs_are_bounded_in_size_and_stopped_with_their_round() {
+    use compdiff::genspec::{GenSpec, MAX_INPUT};
+    use compdiff::{CancellationToken, Stage};
+
+    let spec: GenSpec = "s = string(1e7, \"a\"); repeat(1e7) { print(s, s, s) }".parse().unwrap();
+    assert_eq!(spec.generate(1).unwrap_err().to_string(), format!("at column 37: the input is longer than {} bytes", MAX_INPUT));
+
+    // repeating for ever writes nothing, but it's stopped like a generator
+    let endless = "repeat(1e7) { repeat(1e7) { x = 1 } }";
+    assert!(endless.parse::<GenSpec>().unwrap().generate_until(1, &|| true).is_none());
+    let echo = fixtures::echo_program();
+    let args = Cli::try_parse_from(["compdiff", "--gen-spec", endless, "-p", echo.arg(), "--round-timeout", "0.3"]).unwrap();
+    let started = std::time::Instant::now();
+    let round = compdiff::run_round(&RoundCtx::new(&args, 0), &compdiff::runner::Processes);
+    assert!(matches!(round, Round::Timeout { stage: Stage::Generator, input: None }));
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    let args = Cli::try_parse_from(["compdiff", "--gen-spec", endless, "-p", echo.arg()]).unwrap();
+    let token = CancellationToken::new();
+    let cancel = { let token = token.clone(); thread::spawn(move || { thread::sleep(Duration::from_millis(300)); token.cancel() }) };
+    let ctx = RoundCtx::new(&args, 0).with_cancellation(token);
+    assert!(matches!(compdiff::generate_input(&ctx, &compdiff::runner::Processes), Err(compdiff::Failure::RoundTimeout(_))));
+    cancel.join().unwrap();
+}
+
+#[test]
+fn generators_take_turns_by_weight_and_those_failing_skip_their_rounds() {
+    use compdiff::session::{run_session, Stop};
+    use compdiff::CancellationToken;
+
+    let (small, big) = (fixtures::generator_with_output("small\n"), fixtures::generator_with_output("big\n"));
+    let echo = fixtures::echo_program();
+    let session = Session::builder().weighted_generator(&small, 2).generator(&big).program(&echo).reference(&echo).rounds(6).build().unwrap();
+    let rounds: Vec<_> = session.rounds().collect();
+    let inputs: Vec<_> = rounds.iter().map(|r| r.outcome.input().unwrap_or_default()).collect();
+    assert_eq!(inputs, ["small\n", "small\n", "big\n", "small\n", "small\n", "big\n"]);
+    assert_eq!(rounds[2].meta.generator_source.as_deref(), Some(big.path()));
+    assert!(Session::builder().weighted_generator(&small, 0).program(&echo).build().is_err());
+
+    // the third generator fails in rounds 2 and 5
+    let broken = fixtures::failing_program(1, "no input today");
+    let run = |options: &[&str]| {
+        let generators = format!("{}:2", small.arg());
+        let mut args = Cli::try_parse_from(["compdiff", "-g", &generators, "-g", broken.arg(), "-p", echo.arg(), "-r", echo.arg(), "-c", "6"]
+            .iter().chain(options)).unwrap();
+        assert!(args.prepare().iter().all(|(_, built)| built.is_ok()));
+        run_session(&args, None, &CancellationToken::new(), &mut ())
+    };
+    let report = run(&["--fail-fast"]);
+    assert_eq!((report.rounds_done, report.skipped_rounds, report.failed_rounds, report.stopped), (6, 2, 0, None));
+    assert_eq!(report.failures.iter().map(|f| (f.round, f.signature.verdict)).collect::<Vec<_>>(), [(2, Verdict::GeneratorFail), (5, Verdict::GeneratorFail)]);
+    let report = run(&["--on-gen-fail", "stop"]);
+    assert_eq!((report.rounds_done, report.failed_rounds, report.stopped), (3, 1, Some(Stop::GeneratorFailed)));
+}
+
+#[test]
+fn generators_are_given_their_arguments_before_the_seed() {
+    use compdiff::{generate_input, generator_invocation};
+
+    let gen = fixtures::script("gen.py", "import sys\nprint(' | '.join(sys.argv[1:]))\n");
+    let args = Cli::try_parse_from(["compdiff", "-g", gen.arg(), "-p", "sol.py", "--gen-args", "--n-max 100 --edges \"very sparse\""]).unwrap();
+    let ctx = RoundCtx::new(&args, 2);
+    let generated = generate_input(&ctx, &compdiff::runner::Processes).unwrap();
+    assert_eq!(generated.stdout, format!("--n-max | 100 | --edges | very sparse | {}\n", ctx.seed).as_str());
+    assert_eq!(generator_invocation(&args, 2, ctx.seed), format!("{} --n-max 100 --edges 'very sparse' {}", gen.arg(), ctx.seed));
+
+    assert!(Cli::try_parse_from(["compdiff", "-g", gen.arg(), "-p", "sol.py", "--gen-args", "--name 'unclosed"]).is_err());
+}
+
+#[test]
+fn corpora_keep_each_input_once_and_the_failing_ones_over_their_size() {
+    use compdiff::corpus::{Corpus, INDEX};
+    use compdiff::session::RoundMeta;
+
+    let dir = scratch_dir("corpus").join("corpus");
+    let meta = RoundMeta {
+        started_at: SystemTime::now(),
+        generator_source: None,
+        generator: Some(Duration::from_millis(20)),
+        program: Some(Duration::from_millis(1500)),
+        references: vec![Some(Duration::from_millis(300)), None],
+        program_memory_kb: None,
+        references_memory_kb: vec![None, None],
+    };
+    let mut corpus = Corpus::open(&dir, Some(20)).unwrap();
+    assert!(corpus.add(1, Verdict::Ok, &meta, "1 2 3\n").unwrap());
+    assert!(!corpus.add(2, Verdict::WrongAnswer, &meta, "1 2 3\n").unwrap());
+    assert!(corpus.add(3, Verdict::WrongAnswer, &meta, "4 5 6\n").unwrap());
+    assert_eq!(fs::read_to_string(dir.join("3.in")).unwrap(), "4 5 6\n");
+    let index = fs::read_to_string(dir.join(INDEX)).unwrap();
+    assert!(index.lines().nth(2).unwrap().starts_with("3\tWA\t"), "{}", index);
+    assert!(index.lines().nth(2).unwrap().ends_with("\t0.020\t1.500\t0.300 -"), "{}", index);
+
+    // a later session goes on with the corpus, the oldest passing inputs making room for the new ones
+    let mut corpus = Corpus::open(&dir, Some(20)).unwrap();
+    assert!(!corpus.add(4, Verdict::Ok, &meta, "4 5 6\n").unwrap());
+    assert!(corpus.add(5, Verdict::Ok, &meta, "7 8 9\n").unwrap());
+    assert!(corpus.add(6, Verdict::RuntimeError, &meta, "10 11\n").unwrap());
+    let seeds = |corpus: &Corpus| corpus.entries().iter().map(|e| e.seed).collect::<Vec<_>>();
+    assert_eq!(seeds(&corpus), [3, 5, 6]);
+    assert!(!dir.join("1.in").exists());
+    assert!(corpus.add(7, Verdict::TimeLimit, &meta, "12 13\n").unwrap());
+    assert_eq!(seeds(&corpus), [3, 6, 7]);
+    assert_eq!(seeds(&Corpus::open(&dir, None).unwrap()), [3, 6, 7]);
+}
+
+#[test]
+fn rounds_are_flagged_when_the_programme_is_much_slower_than_the_fastest_reference() {
+    use compdiff::perf::{self, PerfComparison};
+    use compdiff::session::RoundMeta;
+
+    let ms = |n| Some(Duration::from_millis(n));
+    let meta = |program, references: &[Option<Duration>]| RoundMeta {
+        started_at: SystemTime::now(), generator_source: None, generator: None, program, references: references.to_vec(),
+        program_memory_kb: None, references_memory_kb: vec![],
+    };
+    let mut comparison = PerfComparison::new(3.0, 2);
+    let rounds = [(ms(100), [ms(80), ms(90)]), (ms(900), [None, ms(200)]), (ms(1000), [ms(500), ms(250)]), (ms(50), [ms(10), ms(400)])];
+    let flagged: Vec<_> = rounds.iter().enumerate()
+        .map(|(round, (program, references))| PerfComparison::timed(round as u64, 7, Verdict::Ok, &meta(*program, references), "1\n").unwrap())
+        .map(|timed| comparison.add(timed))
+        .collect();
+    assert_eq!(flagged, [false, true, true, true]);
+    assert_eq!((comparison.compared, comparison.flagged), (4, 3));
+    assert_eq!(comparison.worst().iter().map(|r| (r.round, r.ratio())).collect::<Vec<_>>(), [(3, 5.0), (1, 4.5)]);
+    assert!(PerfComparison::timed(4, 7, Verdict::WrongAnswer, &meta(ms(10), &[None]), "1\n").is_none());
+
+    let dir = scratch_dir("perf").join("flagged");
+    perf::save(&dir, &comparison.worst().iter().collect::<Vec<_>>(), "perf.txt").unwrap();
+    assert_eq!(fs::read_to_string(dir.join("round_3.in")).unwrap(), "1\n");
+    assert!(fs::read_to_string(dir.join("perf.txt")).unwrap().starts_with("round 3: 0.050s against 0.010s, 5.0x, verdict OK"));
+}
+
+#[test]
+fn benchmarks_time_every_run_and_summarize_them_with_their_extremes() {
+    use compdiff::bench::{self, Sample, Stats};
+    use compdiff::CancellationToken;
+
+    let samples: Vec<_> = (1..=100).map(|n| Sample { seed: 1000 + n, value: n as f64 }).collect();
+    let stats = Stats::of(&samples).unwrap();
+    assert_eq!((stats.count, stats.mean, stats.median, stats.p90, stats.p99), (100, 50.5, 50.0, 90.0, 99.0));
+    assert_eq!((stats.min.seed, stats.max.seed), (1001, 1100));
+    assert_eq!(Stats::of(&[Sample { seed: 3, value: 0.5 }]).map(|s| (s.median, s.p99, s.max.seed)), Some((0.5, 0.5, 3)));
+    assert!(Stats::of(&[]).is_none());
+
+    let (gen, echo) = (fixtures::seeded_generator(100), fixtures::echo_program());
+    let broken = fixtures::failing_program(2, "nope");
+    let args = Cli::try_parse_from(["compdiff", "-g", gen.arg(), "-p", echo.arg(), "-r", broken.arg(), "-c", "3", "--bench", "--runs-per-input", "2"]).unwrap();
+    let mut rounds = vec![];
+    let bench = bench::bench(&args, &CancellationToken::new(), |round, _| rounds.push(round));
+    assert_eq!((rounds, bench.inputs, bench.skipped), (vec![0, 1, 2], 3, 0));
+    let (program, reference) = (&bench.programmes[0], &bench.programmes[1]);
+    assert_eq!(program.wall.len(), 6);
+    assert_eq!(program.wall.iter().map(|s| s.seed).collect::<Vec<_>>()[..2], [RoundCtx::new(&args, 0).seed; 2]);
+    if cfg!(unix) {
+        assert!(program.cpu.len() == 6 && program.memory.iter().all(|kb| kb.value > 0.0));
+    }
+    assert_eq!((reference.failed, reference.wall.len(), reference.first_error.as_deref()), (6, 0, Some("exit status: 2")));
+}
+
+#[test]
+fn relative_time_limits_come_from_the_time_of_the_reference_on_the_same_input() {
+    let (gen, echo) = (fixtures::seeded_generator(10), fixtures::echo_program());
+    let (slow, doubling) = (fixtures::slow_program(Duration::from_secs(2)), fixtures::multiplying_program(2));
+    let relative = format!("2x:{}", echo.arg());
+    let round = |program: &Fixture| {
+        let args = Cli::try_parse_from(["compdiff", "-g", gen.arg(), "-p", program.arg(), "-r", doubling.arg(), "-r", echo.arg(), "--time-limit-rel", &relative]).unwrap();
+        assert_eq!(args.relative_reference(), Some(1));
+        compdiff::run_round(&RoundCtx::new(&args, 0), &compdiff::runner::Processes)
+    };
+    let Round::ProgramFail { fail: compdiff::Failure::TimeLimit(killed), diagnostics, .. } = round(&slow) else { panic!("the programme wasn't killed") };
+    assert!(killed.duration < Duration::from_secs(2));
+    assert_eq!(diagnostics[0].title, "relative time limit");
+    assert!(diagnostics[0].body.starts_with(&format!("{} took ", echo.arg())), "{}", diagnostics[0].body);
+    // the reference which ran first keeps its place among the others
+    let Round::Success { refs, .. } = round(&echo) else { panic!("the round failed") };
+    assert_eq!(refs.iter().map(|r| r.path.as_path()).collect::<Vec<_>>(), [doubling.path(), echo.path()]);
+}
+
+#[test]
+fn rounds_are_flagged_when_the_programme_uses_much_more_memory_than_the_leanest_reference() {
+    use compdiff::memory::MemoryComparison;
+    use compdiff::session::RoundMeta;
+
+    let (gen, echo) = (fixtures::generator_with_output("5\n"), fixtures::echo_program());
+    // the block is written to, or its zeroed pages may not all be taken, and is much larger than the margin as
+    // a forked child may start counting from the memory of the tests running alongside
+    let greedy = fixtures::script("greedy.py", "import sys\nblock = b'x' * (160 * 1024 * 1024)\nsys.stdout.write(sys.stdin.read())\n");
+    let args = Cli::try_parse_from(["compdiff", "-g", gen.arg(), "-p", greedy.arg(), "-r", echo.arg(), "--mem-compare"]).unwrap();
+    let Round::Success { prog, refs, .. } = compdiff::run_round(&RoundCtx::new(&args, 0), &compdiff::runner::Processes) else { panic!("the round failed") };
+    if cfg!(unix) {
+        assert!(prog.peak_memory.unwrap() > refs[0].peak_memory.unwrap() + 64 * 1024, "{:?} against {:?}", prog.peak_memory, refs[0].peak_memory);
+    }
+
+    // how the rounds are flagged is that of --perf-compare, only what's measured is the memory's
+    let meta = |program, references: &[Option<u64>]| RoundMeta {
+        started_at: SystemTime::now(), generator_source: None, generator: None, program: None, references: vec![None; references.len()],
+        program_memory_kb: program, references_memory_kb: references.to_vec(),
+    };
+    let (program, references) = (PathBuf::from("sol"), [PathBuf::from("brute"), PathBuf::from("other")]);
+    let mut comparison = MemoryComparison::new(3.0, 2, &program, &references);
+    let rounds = [meta(Some(1000), &[Some(800), Some(900)]), meta(Some(4000), &[None, Some(1000)]), meta(Some(9000), &[Some(2000), Some(3000)]), meta(None, &[Some(500), None])];
+    let ratios: Vec<_> = rounds.iter().enumerate()
+        .map(|(round, meta)| {
+            comparison.record(round as u64, meta);
+            MemoryComparison::measured(round as u64, round as u64, Verdict::Ok, meta, "1\n").map(|measured| measured.ratio())
+        })
+        .collect();
+    assert_eq!(ratios, [Some(1.25), Some(4.0), Some(4.5), None]);
+    let binaries: Vec<_> = comparison.binaries().map(|(path, stats)| (path.to_path_buf(), stats.map(|s| (s.median, s.max.value, s.max.seed)))).collect();
+    assert_eq!(binaries, [(program, Some((4000.0, 9000.0, 2))), (references[0].clone(), Some((800.0, 2000.0, 2))), (references[1].clone(), Some((1000.0, 3000.0, 2)))]);
+}
+
+#[test]
+fn crashing_programmes_are_re_run_under_gdb_for_a_backtrace() {
+    let dir = scratch_dir("backtraces");
+    let source = dir.join("crash.cpp");
+    fs::write(&source, "#include <csignal>\n#include <cstdio>\nvoid crash(){ std::raise(SIGSEGV); }\nint main(){ int n; if (scanf(\"%d\", &n) == 1) crash(); }\n").unwrap();
+    let (gen, echo) = (fixtures::generator_with_output("5\n"), fixtures::echo_program());
+    let mut args = Cli::try_parse_from(["compdiff", "-g", gen.arg(), "-p", source.to_str().unwrap(), "-r", echo.arg(), "--backtrace-on-crash"]).unwrap();
+    assert!(args.prepare().iter().all(|(_, built)| built.is_ok()));
+
+    // two rounds crashing at once don't share the input gdb runs the programme on
+    let rounds: Vec<Round> = thread::scope(|scope| {
+        let rounds: Vec<_> = (0..2).map(|round| scope.spawn({
+            let args = &args;
+            move || compdiff::run_round(&RoundCtx::new(args, round), &compdiff::runner::Processes)
+        })).collect();
+        rounds.into_iter().map(|round| round.join().unwrap()).collect()
+    });
+    let has_gdb = Command::new("gdb").arg("--version").output().is_ok();
+    for round in rounds {
+        let Round::ProgramFail { fail: compdiff::Failure::Prog(crashed), diagnostics, .. } = round else { panic!("the programme didn't crash: {:?}", round) };
+        assert_eq!(crashed.signal.map(compdiff::signal_name).as_deref(), Some("SIGSEGV"));
+        match &diagnostics[..] {
+            [backtrace] if has_gdb => assert!(backtrace.title == "backtrace" && backtrace.body.contains("crash"), "{:?}", backtrace),
+            [backtrace] => assert_eq!((backtrace.title.as_str(), backtrace.body.as_str()), ("backtrace unavailable", "gdb is not on PATH, skipping the backtrace")),
+            diagnostics => panic!("{:?}", diagnostics),
+        }
+    }
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn input_files_are_replayed_instead_of_generating_and_fail_the_round_when_unreadable() {
+    let dir = scratch_dir("input_file");
+    let (sol, brute) = (fixtures::multiplying_program(2), fixtures::multiplying_program(2));
+    let saved = dir.join("round_7.in");
+    fs::write(&saved, "21\n").unwrap();
+    let parse = |file: &Path| Cli::try_parse_from(["compdiff", "-p", sol.arg(), "-r", brute.arg(), "--input-file", file.to_str().unwrap()]).unwrap();
+
+    let args = parse(&saved);
+    let Round::Success { input, prog, .. } = compdiff::run_round(&RoundCtx::new(&args, 0), &compdiff::runner::Processes) else { panic!("the round failed") };
+    assert!(input == "21\n" && prog.stdout == "42\n", "{:?}", prog);
+
+    let args = parse(&dir.join("missing.in"));
+    let Round::GeneratorFail(compdiff::Failure::Spawn(e)) = compdiff::run_round(&RoundCtx::new(&args, 0), &compdiff::runner::Processes) else { panic!("the round didn't fail") };
+    assert_eq!(e.path, dir.join("missing.in"));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn outputs_which_cannot_be_opened_stop_the_session_before_its_rounds() {
+    use compdiff::cli::{handle_cli, EXIT_INFRA};
+
+    let dir = scratch_dir("unopenable_outputs");
+    // nothing can be created under a file
+    let file = dir.join("file");
+    fs::write(&file, "").unwrap();
+    let unopenable = file.join("output");
+    let (gen, sol) = (fixtures::seeded_generator(10), fixtures::echo_program());
+    for option in ["--save-failures", "--events-ndjson", "--log-file", "--report-md", "--report-csv", "--db", "--keep-artifacts", "--session", "--ref-cache", "--corpus-dir"] {
+        let args = Cli::try_parse_from(["compdiff", "-g", gen.arg(), "-p", sol.arg(), "-r", sol.arg(), "-c", "1", "--no-interactive", option, unopenable.to_str().unwrap()]).unwrap();
+        assert_eq!(handle_cli(args), EXIT_INFRA, "{}", option);
+    }
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn sessions_are_only_resumed_with_the_options_and_the_seed_they_were_started_with() {
+    use compdiff::cli::{handle_cli, EXIT_INFRA};
+    use compdiff::resume::SessionFile;
+
+    let dir = scratch_dir("resume_mismatch");
+    let session = dir.join("session.json");
+    let (gen, sol, brute, other) = (fixtures::seeded_generator(10), fixtures::echo_program(), fixtures::echo_program(), fixtures::echo_program());
+    let parse = |extra: &[&str]| {
+        let mut args = vec!["compdiff", "-g", gen.arg(), "-p", sol.arg(), "-c", "1", "--no-interactive", "--session", session.to_str().unwrap()];
+        args.extend(extra);
+        Cli::try_parse_from(args).unwrap()
+    };
+    let started = parse(&["-r", brute.arg(), "-t", "1"]);
+    SessionFile::new(&started, 7).save(&session).unwrap();
+    assert_eq!(SessionFile::resume(&session, &started).unwrap().map(|resumed| resumed.seed), Some(7));
+
+    let refused = |args: &Cli| SessionFile::resume(&session, args).unwrap_err().to_string();
+    assert!(refused(&parse(&["-r", brute.arg(), "-t", "2"])).contains("--time-limit was 1s and is now 2s"));
+    assert!(refused(&parse(&["-r", other.arg(), "-t", "1"])).contains("--reference was"));
+    assert!(refused(&parse(&["-r", brute.arg(), "-t", "1", "-s", "8"])).contains("it was started with the seed 7, not 8"));
+
+    assert_eq!(handle_cli(parse(&["-r", brute.arg(), "-t", "2"])), EXIT_INFRA);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn crashing_inputs_are_re_run_against_the_sanitizer_build() {
+    use compdiff::CrashRerun;
+
+    let (gen, sol) = (fixtures::generator_with_output("5\n"), fixtures::echo_program());
+    let args = Cli::try_parse_from(["compdiff", "-g", gen.arg(), "-p", sol.arg(), "--crash-rerun", "auto"]).unwrap();
+    let report = |spec: &str, input: &str| {
+        let diagnostic = CrashRerun::new(spec, sol.path()).report(input, &args);
+        (diagnostic.title, diagnostic.body)
+    };
+    let asan = fixtures::failing_program(1, "==1==ERROR: AddressSanitizer: heap-use-after-free\n");
+    assert_eq!(report(asan.arg(), "5\n"), ("sanitizer report".to_string(), "==1==ERROR: AddressSanitizer: heap-use-after-free".to_string()));
+    let (title, body) = report(sol.arg(), "5\n");
+    assert!(title == "sanitizer report" && body.contains("did not reproduce the crash"), "{}", body);
+    assert_eq!(report("auto", "5\n"), ("sanitizer re-run unavailable".to_string(), "--crash-rerun auto requires a C++ source programme".to_string()));
+
+    // every input is only re-run once
+    let counting = fixtures::script("count.py", "import sys\nsys.stdin.read()\nopen(sys.argv[0] + '.runs', 'a').write('.')\nsys.exit(1)\n");
+    let mut rerun = CrashRerun::new(counting.arg(), sol.path());
+    for input in ["1\n", "2\n", "1\n"] {
+        assert_eq!(rerun.report(input, &args).title, "sanitizer report");
+    }
+    assert_eq!(fs::read_to_string(format!("{}.runs", counting.arg())).unwrap(), "..");
+}
+
+#[test]
+fn rounds_are_stopped_by_the_round_timeout_in_the_stage_they_reached() {
+    use compdiff::Stage;
+    use std::time::Instant;
+
+    let (gen, echo, slow) = (fixtures::generator_with_output("5\n"), fixtures::echo_program(), fixtures::slow_program(Duration::from_secs(5)));
+    let round = |gen: &Fixture, program: &Fixture, reference: &Fixture| {
+        let args = Cli::try_parse_from(["compdiff", "-g", gen.arg(), "-p", program.arg(), "-r", reference.arg(), "-t", "10", "--round-timeout", "500ms"]).unwrap();
+        let started = Instant::now();
+        let round = compdiff::run_round(&RoundCtx::new(&args, 0), &compdiff::runner::Processes);
+        assert!(started.elapsed() < Duration::from_secs(4), "the round ran for {:?}", started.elapsed());
+        round
+    };
+    assert!(matches!(round(&slow, &echo, &echo), Round::Timeout { stage: Stage::Generator, input: None }));
+    assert!(matches!(round(&gen, &slow, &echo), Round::Timeout { stage: Stage::Program, input: Some(input) } if input == "5\n"));
+    let timed_out = round(&gen, &echo, &slow);
+    assert!(matches!(timed_out, Round::Timeout { stage: Stage::References, .. }));
+    assert_eq!(Verdict::of_round(&timed_out), Verdict::RoundTimeout);
+    assert!(matches!(round(&gen, &echo, &echo), Round::Success { .. }));
+}
+
+#[test]
+fn executions_failing_transiently_are_retried() {
+    let (gen, echo) = (fixtures::generator_with_output("5\n"), fixtures::echo_program());
+    let dir = scratch_dir("retries");
+    // fails with "resource busy" on its first run only
+    let flaky = fixtures::script("flaky.py", &format!("import os, sys\nmark = {:?}\nif not os.path.exists(mark):\n    open(mark, 'w').close()\n    sys.stderr.write('resource busy')\n    sys.exit(1)\nsys.stdout.write(sys.stdin.read())\n", dir.join("ran").to_str().unwrap()));
+    let round = |extra: &[&str]| {
+        let _ = fs::remove_file(dir.join("ran"));
+        let mut argv = vec!["compdiff", "-g", gen.arg(), "-p", flaky.arg(), "-r", echo.arg()];
+        argv.extend(extra);
+        let args = Cli::try_parse_from(argv).unwrap();
+        let ctx = RoundCtx::new(&args, 0);
+        let round = compdiff::run_round(&ctx, &compdiff::runner::Processes);
+        (Verdict::of_round(&round), ctx.retries())
+    };
+    assert_eq!(round(&["--retries", "2", "--retry-pattern", "busy"]), (Verdict::Ok, 1));
+    assert_eq!(round(&["--retries", "0", "--retry-pattern", "busy"]), (Verdict::RuntimeError, 0));
+    // a crash which doesn't match the pattern is the programme's own
+    assert_eq!(round(&["--retries", "2", "--retry-pattern", "disk full"]), (Verdict::RuntimeError, 0));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn generators_are_given_the_seed_of_the_round_the_way_they_read_it() {
+    let echo = fixtures::echo_program();
+    let generators = [
+        ("argv", fixtures::script("gen.py", "import sys\nprint(sys.argv[-1])\n")),
+        ("stdin", fixtures::script("gen.py", "print(input())\n")),
+        ("env", fixtures::script("gen.py", "import os\nprint(os.environ['COMPDIFF_SEED'])\n")),
+    ];
+    for (via, gen) in &generators {
+        let args = Cli::try_parse_from(["compdiff", "-g", gen.arg(), "-p", echo.arg(), "-r", echo.arg(), "-s", "42", "--gen-seed-via", via]).unwrap();
+        let ctx = RoundCtx::new(&args, 3);
+        assert_eq!(ctx.seed, compdiff::round_seed(42, 3));
+        let Round::Success { input, .. } = compdiff::run_round(&ctx, &compdiff::runner::Processes) else { panic!("the round failed with the seed on {}", via) };
+        assert_eq!(input, format!("{}\n", ctx.seed));
+    }
+    // every round has a seed of its own, the same in every session with the same seed
+    let seeds = |seed: u64| (0..3).map(|round| compdiff::round_seed(seed, round)).collect::<Vec<_>>();
+    assert_eq!(seeds(42), seeds(42));
+    assert_ne!(seeds(42)[0], seeds(42)[1]);
+    assert_ne!(seeds(42), seeds(43));
+
+    let args = Cli::try_parse_from(["compdiff", "-g", "gen.py", "-p", "sol.py", "--gen-seed-via", "stdin"]).unwrap();
+    assert_eq!(compdiff::generator_invocation(&args, 0, 17), "echo 17 | gen.py");
+}
+
+#[test]
+fn failing_rounds_are_saved_with_their_outputs_into_a_directory_of_the_session() {
+    use compdiff::cli::{handle_cli, EXIT_FAILURE};
+
+    let dir = scratch_dir("save_failures");
+    // right on 0, wrong on 1
+    let (gen, double, triple) = (fixtures::seeded_generator(2), fixtures::multiplying_program(2), fixtures::multiplying_program(3));
+    let args = Cli::try_parse_from(["compdiff", "-g", gen.arg(), "-p", double.arg(), "-r", triple.arg(), "-s", "7", "-c", "6", "--no-interactive",
+        "--save-failures", dir.to_str().unwrap()]).unwrap();
+    assert_eq!(handle_cli(args), EXIT_FAILURE);
+
+    let sessions: Vec<_> = fs::read_dir(&dir).unwrap().map(|e| e.unwrap().path()).collect();
+    let [session] = &sessions[..] else { panic!("{:?}", sessions) };
+    assert!(session.file_name().unwrap().to_str().unwrap().starts_with("session_"));
+    let meta = fs::read_to_string(session.join("meta.txt")).unwrap();
+    let failed: Vec<u64> = meta.lines().filter_map(|line| line.strip_prefix("round ")?.split(':').next()?.parse().ok()).collect();
+    assert!(!failed.is_empty() && failed.len() < 6, "{}", meta);
+    for round in failed {
+        let file = |suffix: &str| fs::read_to_string(session.join(format!("round_{}.{}", round, suffix))).unwrap();
+        assert_eq!((file("in"), file("program.out"), file("multiply.py.out")), ("1\n".to_string(), "2\n".to_string(), "3\n".to_string()));
+    }
+    assert!(meta.contains("verdict wrong answer, seed ") && meta.contains(&format!("  reproduce the input with: {} ", gen.arg())), "{}", meta);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn sessions_exit_with_what_their_rounds_came_to_and_stop_at_the_first_failure_with_fail_fast() {
+    use compdiff::cli::{handle_cli, EXIT_FAILURE, EXIT_INFRA, EXIT_OK};
+
+    let dir = scratch_dir("fail_fast");
+    let gen = fixtures::script("gen.py", "import sys\nprint(int(sys.argv[1]) % 10 + 1)\n");
+    let (double, triple, broken) = (fixtures::multiplying_program(2), fixtures::multiplying_program(3), fixtures::failing_program(3, "no"));
+    let session = |program: &Fixture, reference: &Fixture, extra: &[&str]| {
+        let mut argv = vec!["compdiff", "-g", gen.arg(), "-p", program.arg(), "-r", reference.arg(), "-c", "3", "--no-interactive"];
+        argv.extend(extra);
+        handle_cli(Cli::try_parse_from(argv).unwrap())
+    };
+    assert_eq!(session(&double, &double, &[]), EXIT_OK);
+    assert_eq!(session(&triple, &double, &[]), EXIT_FAILURE);
+    assert_eq!(session(&double, &broken, &[]), EXIT_INFRA);
+    // the programme failing takes precedence over a reference failing
+    assert_eq!(session(&broken, &broken, &[]), EXIT_FAILURE);
+
+    let report = dir.join("report.json");
+    assert_eq!(session(&triple, &double, &["--fail-fast", "--report-json", report.to_str().unwrap()]), EXIT_FAILURE);
+    let report: serde_json::Value = serde_json::from_str(&fs::read_to_string(&report).unwrap()).unwrap();
+    assert_eq!(report["summary"]["rounds"], 1);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn sessions_stop_once_max_failures_rounds_failed() {
+    use compdiff::cli::{handle_cli, EXIT_FAILURE};
+
+    let dir = scratch_dir("max_failures");
+    let gen = fixtures::script("gen.py", "import sys\nprint(int(sys.argv[1]) % 10 + 1)\n");
+    // wrong on odd numbers only
+    let (odd, double) = (fixtures::script("odd.py", "n = int(input())\nprint(n * 2 + n % 2)\n"), fixtures::multiplying_program(2));
+    let report = dir.join("report.json");
+    let args = Cli::try_parse_from(["compdiff", "-g", gen.arg(), "-p", odd.arg(), "-r", double.arg(), "-s", "3", "-c", "40", "--no-interactive",
+        "--max-failures", "2", "--report-json", report.to_str().unwrap()]).unwrap();
+    assert_eq!(handle_cli(args), EXIT_FAILURE);
+
+    let report: serde_json::Value = serde_json::from_str(&fs::read_to_string(&report).unwrap()).unwrap();
+    let inputs: Vec<_> = report["rounds"].as_array().unwrap().iter().map(|r| r["input"]["text"].as_str().unwrap().trim().parse::<u64>().unwrap()).collect();
+    // the passing rounds don't count, the session stops at the second failing one
+    assert_eq!(inputs.iter().filter(|n| *n % 2 == 1).count(), 2);
+    assert_eq!(inputs.last().map(|n| n % 2), Some(1));
+    assert_eq!((report["summary"]["failed"].as_u64(), report["summary"]["rounds"].as_u64()), (Some(2), Some(inputs.len() as u64)));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn json_reports_describe_every_round_and_the_session() {
+    use compdiff::cli::handle_cli;
+
+    let dir = scratch_dir("report_json");
+    let (gen, double) = (fixtures::seeded_generator(10), fixtures::multiplying_program(2));
+    let (wrong, crash) = (fixtures::script("wrong.py", "print('x' * 100)\n"), fixtures::failing_program(3, &"e".repeat(100)));
+    let report = |program: &Fixture| {
+        let path = dir.join("report.json");
+        let args = Cli::try_parse_from(["compdiff", "-g", gen.arg(), "-p", program.arg(), "-r", double.arg(), "-s", "5", "-c", "2", "--no-interactive",
+            "--report-json", path.to_str().unwrap(), "--report-max-output", "10"]).unwrap();
+        handle_cli(args);
+        serde_json::from_str::<serde_json::Value>(&fs::read_to_string(&path).unwrap()).unwrap()
+    };
+
+    let passed = report(&double);
+    assert_eq!((passed["seed"].as_u64(), passed["summary"]["passed"].as_u64(), passed["summary"]["exit_code"].as_i64()), (Some(5), Some(2), Some(0)));
+    let round = &passed["rounds"][1];
+    assert_eq!((round["round"].as_u64(), round["seed"].as_u64(), round["verdict"].as_str()), (Some(1), Some(compdiff::round_seed(5, 1)), Some("OK")));
+    let n: u64 = round["input"]["text"].as_str().unwrap().trim().parse().unwrap();
+    assert_eq!(round["program"]["output"]["text"].as_str(), Some(format!("{}\n", n * 2).as_str()));
+    assert_eq!(round["references"][0]["reference"].as_u64(), Some(1));
+    assert_eq!(round["reproduce"].as_str(), Some(format!("{} {}", gen.arg(), compdiff::round_seed(5, 1)).as_str()));
+
+    let mismatched = report(&wrong);
+    let output = &mismatched["rounds"][0]["program"]["output"];
+    assert_eq!((mismatched["rounds"][0]["verdict"].as_str(), output["text"].as_str(), output["truncated"].as_bool(), output["bytes"].as_u64()),
+        (Some("WA"), Some("xxxxxxxxxx"), Some(true), Some(101)));
+
+    let crashed = report(&crash);
+    let failure = &crashed["rounds"][0]["failures"][0];
+    assert_eq!((crashed["rounds"][0]["verdict"].as_str(), failure["verdict"].as_str(), failure["stderr"]["text"].as_str()), (Some("RE"), Some("RE"), Some("eeeeeeeeee")));
+    assert_eq!(crashed["summary"]["verdicts"]["RE"].as_u64(), Some(2));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn events_are_streamed_a_line_each_as_the_rounds_run() {
+    use compdiff::cli::handle_cli;
+
+    let dir = scratch_dir("events");
+    let (gen, double, triple) = (fixtures::seeded_generator(10), fixtures::multiplying_program(2), fixtures::multiplying_program(3));
+    let events = dir.join("events.ndjson");
+    let args = Cli::try_parse_from(["compdiff", "-g", gen.arg(), "-p", triple.arg(), "-r", double.arg(), "-s", "5", "-c", "2", "--no-interactive",
+        "--events-ndjson", events.to_str().unwrap()]).unwrap();
+    handle_cli(args);
+
+    let events: Vec<serde_json::Value> = fs::read_to_string(&events).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    let kinds: Vec<_> = events.iter().map(|e| e["event"].as_str().unwrap()).collect();
+    assert_eq!(kinds, ["round_start", "verdict", "round_start", "verdict", "summary"]);
+    assert_eq!((events[2]["round"].as_u64(), events[3]["round"].as_u64(), events[3]["seed"].as_u64()), (Some(1), Some(1), Some(compdiff::round_seed(5, 1))));
+    assert!(events[1]["time_ms"].is_u64() && ["OK", "WA"].contains(&events[1]["verdict"].as_str().unwrap()));
+    assert_eq!((events[4]["rounds"].as_u64(), events[4]["exit_code"].as_i64()), (Some(2), Some(1)));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn rounds_are_printed_as_they_run_without_a_terminal_for_the_progress_bar() {
+    let (gen, double) = (fixtures::seeded_generator(10), fixtures::multiplying_program(2));
+    let odd = fixtures::script("odd.py", "n = int(input())\nprint(n * 2 + n % 2)\n");
+    let out = compdiff(&["-g", gen.arg(), "-p", odd.arg(), "-r", double.arg(), "-s", "5", "-c", "4", "--no-interactive"]).output().unwrap();
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    // the bar is drawn on stderr, which isn't a terminal here
+    assert!(out.stderr.is_empty(), "{}", String::from_utf8_lossy(&out.stderr));
+    let started: Vec<_> = stdout.lines().filter(|line| line.starts_with("== starting round")).collect();
+    assert_eq!(started, ["== starting round 0", "== starting round 1", "== starting round 2", "== starting round 3"]);
+    assert!(stdout.contains("All references match the output!"), "{}", stdout);
+}
+
+#[test]
+fn quiet_sessions_only_print_the_failing_rounds_and_the_summary() {
+    let (gen, double) = (fixtures::seeded_generator(10), fixtures::multiplying_program(2));
+    let odd = fixtures::script("odd.py", "n = int(input())\nprint(n * 2 + n % 2)\n");
+    let inputs: Vec<_> = (0..4).map(|round| compdiff::round_seed(5, round) % 10).collect();
+    assert!(inputs.iter().any(|n| n % 2 == 0) && inputs.iter().any(|n| n % 2 == 1), "{:?}", inputs);
+
+    let out = compdiff(&["-g", gen.arg(), "-p", odd.arg(), "-r", double.arg(), "-s", "5", "-c", "4", "--no-interactive", "-q"]).output().unwrap();
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let started: Vec<_> = stdout.lines().filter_map(|line| line.strip_prefix("== starting round ")?.parse::<u64>().ok()).collect();
+    let failed: Vec<_> = (0..4).filter(|&round| inputs[round as usize] % 2 == 1).collect();
+    assert_eq!(started, failed);
+    assert!(!stdout.contains("All references match the output!") && !stdout.contains("runs with"), "{}", stdout);
+    assert!(stdout.trim_end().ends_with(&format!("== 4 rounds: {} passed, {} failed", 4 - failed.len(), failed.len())), "{}", stdout);
+}
+
+#[test]
+fn markers_are_plain_ascii_and_colours_only_on_demand_or_on_terminals() {
+    use compdiff::output::{Mark, Style};
+
+    let style = |ascii, color| Style { ascii, color };
+    assert_eq!((style(false, false).mark(Mark::Ok), style(true, false).mark(Mark::Bad)), ("✔".to_string(), "[FAIL]".to_string()));
+    assert_eq!(style(true, true).mark(Mark::Warn), "\x1b[33m[WARN]\x1b[0m");
+    assert_eq!((style(true, true).diff_line("+2"), style(true, true).diff_line("+++ sol.py"), style(true, false).diff_line("-3")),
+        ("\x1b[32m+2\x1b[0m".into(), "+++ sol.py".into(), "-3".into()));
+
+    let (gen, double) = (fixtures::seeded_generator(10), fixtures::multiplying_program(2));
+    let stdout = |extra: &[&str], term: &str| {
+        let mut argv = vec!["-g", gen.arg(), "-p", double.arg(), "-r", double.arg(), "-c", "1", "--no-interactive"];
+        argv.extend(extra);
+        String::from_utf8(compdiff(&argv).env("TERM", term).output().unwrap().stdout).unwrap()
+    };
+    let plain = stdout(&["--ascii"], "xterm");
+    assert!(plain.contains("[OK] -- Awesome!") && !plain.contains('✔') && !plain.contains('\x1b'), "{}", plain);
+    assert!(stdout(&[], "dumb").contains("[OK] -- Awesome!"));
+    // stdout isn't a terminal here
+    assert!(!stdout(&[], "xterm").contains('\x1b'));
+    assert!(stdout(&["--color", "always"], "xterm").contains("\x1b[32m✔\x1b[0m -- Awesome!"));
+    assert!(!stdout(&["--color", "always"], "dumb").contains('✔'));
+}
+
+#[test]
+fn options_the_command_line_doesnt_set_are_taken_from_the_configuration() {
+    let dir = scratch_dir("config");
+    let gen = fixtures::script("gen.py", "import sys\nprint(int(sys.argv[1]) % 10 + 1)\n");
+    let (double, triple) = (fixtures::multiplying_program(2), fixtures::multiplying_program(3));
+    let config = dir.join("ci.toml");
+    fs::write(&config, format!("generator = {:?}\nprogram = {:?}\nreferences = [{:?}]\nrounds = 3\nascii = true\nno-interactive = true\nnot-an-option = 1\n",
+        gen.arg(), double.arg(), double.arg())).unwrap();
+    let run = |extra: &[&str]| {
+        let mut argv = vec!["--config", config.to_str().unwrap()];
+        argv.extend(extra);
+        let out = compdiff(&argv).output().unwrap();
+        (out.status.code(), String::from_utf8(out.stdout).unwrap(), String::from_utf8(out.stderr).unwrap())
+    };
+    let (code, stdout, stderr) = run(&[]);
+    assert_eq!(code, Some(0));
+    assert!(stdout.contains("[OK]") && stdout.trim_end().ends_with("== 3 rounds: 3 passed, 0 failed"), "{}", stdout);
+    assert!(stderr.contains("unknown key `not-an-option`"), "{}", stderr);
+
+    // the command line wins
+    let (code, stdout, _) = run(&["-p", triple.arg(), "-c", "1"]);
+    assert_eq!(code, Some(1));
+    assert!(stdout.trim_end().ends_with("== 1 rounds: 0 passed, 1 failed"), "{}", stdout);
+
+    fs::write(&config, "rounds = [\n").unwrap();
+    let (code, _, stderr) = run(&[]);
+    assert!(code == Some(2) && stderr.contains("couldn't parse the configuration"), "{}", stderr);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn watched_sessions_run_again_once_a_source_changes() {
+    use std::io::{BufRead, BufReader};
+
+    let dir = scratch_dir("watch");
+    let (gen, double) = (fixtures::script("gen.py", "import sys\nprint(int(sys.argv[1]) % 10 + 1)\n"), fixtures::multiplying_program(2));
+    let program = dir.join("sol.py");
+    fs::write(&program, "print(int(input()) * 2)\n").unwrap();
+    let mut child = compdiff(&["-g", gen.arg(), "-p", program.to_str().unwrap(), "-r", double.arg(), "-c", "1", "--no-interactive", "--watch"])
+        .stdout(Stdio::piped()).spawn().unwrap();
+    let mut lines = BufReader::new(child.stdout.take().unwrap()).lines().map(Result::unwrap);
+    let mut until_watching = || lines.by_ref().take_while(|line| !line.starts_with("== watching")).collect::<Vec<_>>();
+
+    assert!(until_watching().iter().any(|line| line == "== 1 rounds: 1 passed, 0 failed"));
+    // the sources are compared to how they were once the session ended
+    thread::sleep(Duration::from_millis(500));
+    fs::write(&program, "print(int(input()) * 3)\n").unwrap();
+    let second = until_watching();
+    child.kill().unwrap();
+    let _ = child.wait();
+    assert!(second.iter().any(|line| line.contains("sources changed, re-running")), "{:?}", second);
+    assert!(second.iter().any(|line| line == "== 1 rounds: 0 passed, 1 failed"), "{:?}", second);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn sessions_run_until_the_programme_fails() {
+    use compdiff::cli::EXIT_FAILURE;
+    use compdiff::session::{planned_rounds, run_session, Stop};
+    use compdiff::CancellationToken;
+
+    let (gen, double) = (fixtures::seeded_generator(4), fixtures::multiplying_program(2));
+    // wrong on a single input in 4
+    let rare = fixtures::script("rare.py", "n = int(input())\nprint(n * 2 + (n == 3))\n");
+    let parse = |extra: &[&str]| {
+        let mut argv = vec!["compdiff", "-g", gen.arg(), "-p", rare.arg(), "-r", double.arg(), "-s", "1"];
+        argv.extend(extra);
+        Cli::try_parse_from(argv).unwrap()
+    };
+    assert_eq!(planned_rounds(&parse(&["--until-failure"])), None);
+    assert_eq!(planned_rounds(&parse(&["-c", "0"])), None);
+    assert_eq!(planned_rounds(&parse(&["--until-failure", "-c", "5"])), Some(5));
+
+    let failing = (0..).find(|&round| compdiff::round_seed(1, round) % 4 == 3).unwrap();
+    assert!(failing > 0);
+    for extra in [&["--until-failure"][..], &["-c", "0"]] {
+        let mut args = parse(extra);
+        assert!(args.prepare().iter().all(|(_, built)| built.is_ok()));
+        let report = run_session(&args, None, &CancellationToken::new(), &mut ());
+        assert_eq!((report.stopped, report.rounds_done, report.summary.exit_code), (Some(Stop::FoundFailure), failing + 1, EXIT_FAILURE));
+        assert_eq!((report.summary.passed, report.summary.failed), (failing as usize, 1));
+    }
+}
+
+#[test]
+fn long_inputs_and_outputs_are_cut_in_their_middle_on_the_console() {
+    use compdiff::output::truncate_middle;
+
+    let text = (1..=10).map(|n| n.to_string()).collect::<Vec<_>>().join("\n");
+    assert_eq!(truncate_middle(&text, 4, 1000, ""), "1\n2\n... 6 lines omitted ...\n9\n10");
+    assert_eq!(truncate_middle(&text, 3, 1000, ", see the log"), "1\n2\n... 7 lines omitted, see the log ...\n10");
+    assert_eq!(truncate_middle("abcdefghij", 100, 4, ""), "ab\n... 6 bytes omitted ...\nij");
+    assert_eq!(truncate_middle(&text, 10, 1000, ""), text);
+
+    let (gen, echo) = (fixtures::script("gen.py", "for n in range(1000):\n    print(n)\n"), fixtures::echo_program());
+    let wrong = fixtures::script("wrong.py", "import sys\nsys.stdout.write(sys.stdin.read() + 'extra\\n')\n");
+    let stdout = |extra: &[&str]| {
+        let mut argv = vec!["-g", gen.arg(), "-p", wrong.arg(), "-r", echo.arg(), "-c", "1", "--no-interactive", "--max-lines", "10"];
+        argv.extend(extra);
+        String::from_utf8(compdiff(&argv).output().unwrap().stdout).unwrap()
+    };
+    let cut = stdout(&[]);
+    assert!(cut.contains("0\n1\n2\n3\n4\n... 990 lines omitted ...\n995\n996\n997\n998\n999\n"), "{}", cut);
+    assert!(!cut.contains("\n500\n"));
+    assert!(stdout(&["--full-output"]).contains("\n500\n"));
+}
+
+#[test]
+fn mismatches_show_only_the_differing_lines_unless_the_outputs_are_asked_for_in_full() {
+    let gen = fixtures::script("gen.py", "for n in range(1, 21):\n    print(n)\n");
+    let echo = fixtures::echo_program();
+    let wrong = fixtures::script("wrong.py", "import sys\nfor line in sys.stdin:\n    print('ten' if line.strip() == '10' else line.strip())\n");
+    let stdout = |show: &str| {
+        let out = compdiff(&["-g", gen.arg(), "-p", wrong.arg(), "-r", echo.arg(), "-c", "1", "--no-interactive", "--show", show]).output().unwrap();
+        String::from_utf8(out.stdout).unwrap()
+    };
+    let shown = stdout("diff-only");
+    let diff: Vec<_> = shown.split("::: diff of the program").nth(1).unwrap().lines().skip(1).take_while(|line| !line.starts_with(":::")).collect();
+    assert_eq!(diff[2..], ["@@ -7,7 +7,7 @@", " 7", " 8", " 9", "-10", "+ten", " 11", " 12", " 13"]);
+    assert!(!shown.contains("::: program ("));
+
+    let full = stdout("full");
+    assert!(!full.contains("::: diff of") && full.contains(&format!("::: program ({}) output:\n1\n2\n", wrong.arg())), "{}", full);
+    assert!(full.contains(&format!("::: reference program ({}) output:\n1\n", echo.arg())));
+}
+
+#[test]
+fn outputs_are_shown_side_by_side_in_columns_sharing_the_width() {
+    let gen = fixtures::script("gen.py", "for n in range(1, 7):\n    print(n)\nprint('x' * 40)\n");
+    let echo = fixtures::echo_program();
+    let wrong = fixtures::script("wrong.py", "import sys\nfor line in sys.stdin:\n    line = line.strip()\n    if line != '5':\n        print('three' if line == '3' else line)\nprint('y' * 40)\n");
+    let table = |width: &str| {
+        let out = compdiff(&["-g", gen.arg(), "-p", wrong.arg(), "-r", echo.arg(), "-c", "1", "--no-interactive", "--ascii", "--show", "side-by-side", "--width", width])
+            .output().unwrap();
+        let stdout = String::from_utf8(out.stdout).unwrap();
+        stdout.split(" output next to the program (").nth(1).unwrap().lines().skip(2).take_while(|line| !line.starts_with(":::")).map(str::to_string).collect::<Vec<_>>()
+    };
+    // (30 - 3) / 2 characters a column
+    assert_eq!(table("30"), ["1               1", "2               2", "3             | three", "4               4", "5             <", "6               6",
+        "xxxxxxxxxx...   xxxxxxxxxx...", "              > yyyyyyyyyy..."]);
+    // a width of 0 cuts nothing, the left column is as wide as its longest line
+    assert_eq!(table("0")[6..], [format!("{0}   {0}", "x".repeat(40)), format!("{} > {}", " ".repeat(40), "y".repeat(40))]);
+}
+
+#[test]
+fn the_log_has_everything_said_in_full_and_every_command_run() {
+    let dir = scratch_dir("log_file");
+    let log = dir.join("session.log");
+    let gen = fixtures::script("gen.py", "for n in range(100):\n    print(n)\n");
+    let (echo, wrong) = (fixtures::echo_program(), fixtures::script("wrong.py", "print('nope')\n"));
+    let out = compdiff(&["-g", gen.arg(), "-p", wrong.arg(), "-r", echo.arg(), "-c", "1", "--no-interactive", "--max-lines", "4", "--log-file", log.to_str().unwrap()])
+        .output().unwrap();
+    assert!(String::from_utf8(out.stdout).unwrap().contains("lines omitted"));
+
+    let log = fs::read_to_string(&log).unwrap();
+    let mut lines = log.lines();
+    assert!(lines.next().unwrap().starts_with("compdiff log started at "));
+    // every line is stamped with the seconds since the log started
+    assert!(lines.clone().all(|line| line.starts_with('[') && line[1..11].trim().parse::<f64>().is_ok()), "{}", log);
+    let said: Vec<_> = lines.map(|line| &line[13..]).collect();
+    // the input in full, and the markers without emoji
+    assert!(said.windows(100).any(|lines| lines.iter().copied().eq((0..100).map(|n| n.to_string()))), "{}", log);
+    assert!(said.iter().any(|line| line.starts_with("[FAIL] -- there are 1 mismatched testcases!")), "{}", log);
+    assert!(said.iter().any(|line| line.starts_with("$ ") && line.contains("wrong.py")), "{}", log);
+    assert!(said.last().unwrap().starts_with("== 1 rounds: 0 passed, 1 failed"));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn markdown_reports_have_a_section_for_every_failing_round_under_the_summary() {
+    use compdiff::cli::handle_cli;
+
+    let dir = scratch_dir("report_md");
+    let md = dir.join("report.md");
+    let (gen, double) = (fixtures::seeded_generator(10), fixtures::multiplying_program(2));
+    let odd = fixtures::script("odd.py", "n = int(input())\nprint(n * 2 + n % 2)\n");
+    let report = |program: &Fixture| {
+        let args = Cli::try_parse_from(["compdiff", "-g", gen.arg(), "-p", program.arg(), "-r", double.arg(), "-s", "5", "-c", "4", "--no-interactive",
+            "--report-md", md.to_str().unwrap()]).unwrap();
+        handle_cli(args);
+        fs::read_to_string(&md).unwrap()
+    };
+    let failed: Vec<_> = (0..4).filter(|&round| compdiff::round_seed(5, round) % 10 % 2 == 1).collect();
+    assert!(!failed.is_empty() && failed.len() < 4);
+
+    let text = report(&odd);
+    assert!(text.starts_with("# compdiff report\n\n| verdict | rounds |\n|---|---|\n"), "{}", text);
+    assert!(text.contains(&format!("| wrong answer | {} |\n", failed.len())) && text.contains("| **total** | **4** |\n"), "{}", text);
+    let rounds: Vec<u64> = text.lines().filter_map(|line| line.strip_prefix("## Round ")?.split(':').next()?.parse().ok()).collect();
+    assert_eq!(rounds, failed);
+    let n = compdiff::round_seed(5, failed[0]) % 10;
+    assert!(text.contains(&format!("```diff\n--- {}\n+++ {}\n@@ -1 +1 @@\n-{}\n+{}\n```", double.arg(), odd.arg(), n * 2, n * 2 + 1)), "{}", text);
+    assert!(text.contains("## Environment\n"));
+
+    // backticks in an output don't end its block
+    let ticks = fixtures::failing_program(1, "``` oops");
+    let text = report(&ticks);
+    assert!(text.contains("## Round 0: RE (runtime error)") && text.contains("````text\n``` oops\n````"), "{}", text);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn html_reports_are_a_single_page_with_a_collapsed_panel_for_every_failing_round() {
+    use compdiff::cli::handle_cli;
+
+    let dir = scratch_dir("report_html");
+    let html = dir.join("report.html");
+    let (gen, echo) = (fixtures::generator_with_output(&"<b>&</b>\n".repeat(50)), fixtures::echo_program());
+    let wrong = fixtures::script("wrong.py", "import sys\nsys.stdout.write(sys.stdin.read().upper())\n");
+    let args = Cli::try_parse_from(["compdiff", "-g", gen.arg(), "-p", wrong.arg(), "-r", echo.arg(), "-s", "5", "-c", "2", "--no-interactive",
+        "--report-html", html.to_str().unwrap()]).unwrap();
+    handle_cli(args);
+
+    let html = fs::read_to_string(&html).unwrap();
+    assert!(html.starts_with("<!DOCTYPE html>") && html.contains("<p>2 rounds: <span class=\"ok\">0 passed</span>, <span class=\"fail\">2 failed</span>, exit code 1, session seed 5"));
+    // nothing is loaded from elsewhere
+    assert!(!html.contains("src=") && !html.contains("<link") && !html.contains("http"));
+    assert!(html.contains("<tr><td>1</td><td><a class=\"fail\" href=\"#round-1\">WA</a></td>"));
+    assert_eq!(html.matches("<details id=\"round-").count(), 2);
+    // the texts are escaped and only their beginning is shown until asked for
+    assert!(html.contains("<pre>&lt;b&gt;&amp;&lt;/b&gt;\n") && !html.contains("<b>&</b>"));
+    assert!(html.contains("<span class=\"rest\" hidden>") && html.contains("<button onclick=\"more(this)\">show more</button>"));
+    assert!(html.contains("<span class=\"del\">-&lt;b&gt;&amp;&lt;/b&gt;</span>\n<span class=\"add\">+&lt;B&gt;&amp;&lt;/B&gt;</span>"));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn profiles_of_the_configuration_replace_its_top_level_keys() {
+    use compdiff::config;
+
+    let dir = scratch_dir("config_profiles");
+    let path = dir.join("compdiff.toml");
+    fs::write(&path, "rounds = 100\ntime-limit = \"1s\"\n\n[profile.quick]\nrounds = 5\n\n[profile.ci]\nrounds = 1000\nascii = true\n").unwrap();
+    let keys = |profile: Option<&str>| {
+        let (loaded, table) = config::load(Some(&path), profile).unwrap().unwrap();
+        assert_eq!(loaded, path);
+        let mut keys: Vec<_> = table.into_iter().map(|(key, value)| format!("{} = {}", key, value)).collect();
+        keys.sort();
+        keys
+    };
+    assert_eq!(keys(None), ["rounds = 100", "time-limit = \"1s\""]);
+    assert_eq!(keys(Some("quick")), ["rounds = 5", "time-limit = \"1s\""]);
+    assert_eq!(keys(Some("ci")), ["ascii = true", "rounds = 1000", "time-limit = \"1s\""]);
+    let e = config::load(Some(&path), Some("nightly")).unwrap_err().to_string();
+    assert!(e.contains("unknown profile `nightly`") && e.ends_with("the available ones are: ci, quick"), "{}", e);
+
+    fs::write(&path, "profile = 1\n").unwrap();
+    assert!(config::load(Some(&path), None).unwrap_err().to_string().contains("should be a table of [profile.NAME] sections"));
+
+    // the profile is chosen on the command line
+    let (gen, double) = (fixtures::seeded_generator(10), fixtures::multiplying_program(2));
+    fs::write(&path, format!("generator = {:?}\nprogram = {:?}\nreferences = [{:?}]\nno-interactive = true\nrounds = 1\n\n[profile.more]\nrounds = 3\n",
+        gen.arg(), double.arg(), double.arg())).unwrap();
+    let out = compdiff(&["--config", path.to_str().unwrap(), "--profile", "more"]).output().unwrap();
+    assert!(String::from_utf8(out.stdout).unwrap().trim_end().ends_with("== 3 rounds: 3 passed, 0 failed"));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[cfg(unix)]
+#[test]
+fn failing_rounds_ask_what_to_do_next_on_a_terminal() {
+    use std::fs::File;
+    use std::os::fd::FromRawFd;
+
+    let dir = scratch_dir("interactive");
+    let (gen, double, triple) = (fixtures::script("gen.py", "import sys\nprint(int(sys.argv[1]) % 10 + 1)\n"), fixtures::multiplying_program(2), fixtures::multiplying_program(3));
+    let session = |answers: &str, extra: &[&str]| {
+        // the answers are typed on a terminal of their own
+        let (mut master, mut slave) = (0, 0);
+        assert_eq!(unsafe { libc::openpty(&mut master, &mut slave, std::ptr::null_mut(), std::ptr::null_mut(), std::ptr::null_mut()) }, 0);
+        let (mut master, slave) = unsafe { (File::from_raw_fd(master), File::from_raw_fd(slave)) };
+        master.write_all(answers.as_bytes()).unwrap();
+        let mut argv = vec!["-g", gen.arg(), "-p", triple.arg(), "-r", double.arg(), "-c", "3", "--save-failures", dir.to_str().unwrap()];
+        argv.extend(extra);
+        let out = compdiff(&argv).stdin(slave).output().unwrap();
+        (String::from_utf8(out.stdout).unwrap(), String::from_utf8(out.stderr).unwrap())
+    };
+
+    let (stdout, stderr) = session("r\ns\nx\nq\n", &[]);
+    assert_eq!(stderr.matches("[c]ontinue / [q]uit / [s]ave input / [r]erun?").count(), 4, "{}", stderr);
+    assert!(stdout.contains("rerun: wrong answer again") && stdout.contains("unknown answer `x`"), "{}", stdout);
+    assert!(stdout.lines().any(|line| line.contains("saved the input to ") && line.ends_with("round_0.in")), "{}", stdout);
+    assert!(stdout.trim_end().ends_with("== 1 rounds: 0 passed, 1 failed"), "{}", stdout);
+
+    // going on runs every round, and --no-interactive never asks
+    let (stdout, stderr) = session("c\n\nc\n", &[]);
+    assert_eq!((stderr.matches("[c]ontinue").count(), stdout.trim_end().ends_with("== 3 rounds: 0 passed, 3 failed")), (3, true), "{}", stdout);
+    let (stdout, stderr) = session("q\n", &["--no-interactive"]);
+    assert!(!stderr.contains("[c]ontinue") && stdout.trim_end().ends_with("== 3 rounds: 0 passed, 3 failed"));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn github_actions_get_annotations_of_the_failing_rounds_and_of_the_summary() {
+    let (gen, double) = (fixtures::script("gen.py", "import sys\nprint(int(sys.argv[1]) % 10 + 1)\n"), fixtures::multiplying_program(2));
+    let (triple, broken) = (fixtures::multiplying_program(3), fixtures::failing_program(1, "50% done\nthen broke\n"));
+    let annotations = |program: &Fixture, extra: &[&str]| {
+        let mut argv = vec!["-g", gen.arg(), "-p", program.arg(), "-r", double.arg(), "-s", "5", "-c", "1", "--no-interactive"];
+        argv.extend(extra);
+        let out = compdiff(&argv).env("GITHUB_ACTIONS", "true").output().unwrap();
+        String::from_utf8(out.stdout).unwrap().lines().filter(|line| line.starts_with("::error") || line.starts_with("::notice")).map(str::to_string).collect::<Vec<_>>()
+    };
+    let (n, seed) = (compdiff::round_seed(5, 0) % 10 + 1, compdiff::round_seed(5, 0));
+    assert_eq!(annotations(&triple, &[]), [
+        format!("::error title=Round 0 mismatch::first difference at line 1 against {}: expected `{}`, got `{}` (seed {})", double.arg(), n * 2, n * 3, seed),
+        "::notice title=compdiff summary::1 rounds: 0 passed, 1 failed (1 wrong answer)".to_string(),
+    ]);
+    // the messages are escaped
+    assert_eq!(annotations(&broken, &["--format", "github"])[0],
+        format!("::error title=Round 0 runtime error::{} failed with status exit status: 1: 50%25 done (seed {})", broken.arg(), seed));
+    assert!(annotations(&triple, &["--format", "human"]).is_empty());
+    // nor do they get in the way of the JSON on stdout
+    assert!(annotations(&triple, &["--report-json", "-"]).is_empty());
+}
+
+#[test]
+fn identical_failures_are_shown_once_in_the_summary() {
+    use compdiff::report::{distinct_failures, MismatchedRound};
+    use compdiff::{ExecutionResult, Mismatch};
+
+    let mismatched = |round, input: &str, output: &str| MismatchedRound {
+        round, seed: round, input: input.to_string(),
+        mismatch: Mismatch::ProgMismatch { prog: ExecutionResult::new("sol".into(), output.to_string()), refs: vec![ExecutionResult::new("brute".into(), "0\n".to_string())] },
+    };
+    let rounds = || vec![mismatched(0, "1\n", "2\n"), mismatched(1, "1 \n\n", "2  \n"), mismatched(2, "1\n", "3\n"), mismatched(3, "4\n", "2\n")];
+    let distinct: Vec<_> = distinct_failures(rounds(), true).into_iter().map(|d| (d.first.round, d.occurrences)).collect();
+    // whitespace at the ends of the lines doesn't tell failures apart
+    assert_eq!(distinct, [(0, 2), (2, 1), (3, 1)]);
+    assert_eq!(distinct_failures(rounds(), false).len(), 4);
+
+    let (gen, double, triple) = (fixtures::generator_with_output("5\n"), fixtures::multiplying_program(2), fixtures::multiplying_program(3));
+    let summary = |extra: &[&str]| {
+        let mut argv = vec!["-g", gen.arg(), "-p", triple.arg(), "-r", double.arg(), "-c", "3", "--no-interactive", "-q"];
+        argv.extend(extra);
+        let stdout = String::from_utf8(compdiff(&argv).output().unwrap().stdout).unwrap();
+        stdout.split("Summary of all fails:").nth(1).unwrap().to_string()
+    };
+    let once = summary(&[]);
+    assert!(once.contains("this failure occurred 3 times, first at round 0") && once.matches("mismatched testcases").count() == 1, "{}", once);
+    assert_eq!(summary(&["--no-dedup"]).matches("mismatched testcases").count(), 3);
+}
+
+#[test]
+fn failures_are_grouped_by_their_signature_the_most_frequent_first() {
+    use compdiff::report::{failure_modes, FailedRound};
+    use compdiff::Signature;
+
+    let (wa, segv) = (Signature { verdict: Verdict::WrongAnswer, line: Some(3), signal: None }, Signature { verdict: Verdict::RuntimeError, line: None, signal: Some(11) });
+    let tle = Signature { verdict: Verdict::TimeLimit, line: None, signal: None };
+    assert_eq!((wa.to_string(), segv.to_string(), tle.to_string()), ("WA at line 3".to_string(), "SIGSEGV".to_string(), "TLE".to_string()));
+    let failed = |round, signature| FailedRound { signature, round, seed: round, input: None };
+    let modes: Vec<_> = failure_modes(vec![failed(0, tle), failed(1, segv), failed(2, wa), failed(3, segv), failed(4, segv), failed(5, wa)])
+        .into_iter().map(|m| (m.first.signature, m.first.round, m.rounds)).collect();
+    assert_eq!(modes, [(segv, 1, 3), (wa, 2, 2), (tle, 0, 1)]);
+
+    // aborts on odd numbers, prints a line too many on the others
+    let gen = fixtures::script("gen.py", "import sys\nprint(int(sys.argv[1]) % 10 + 1)\n");
+    let (double, flawed) = (fixtures::multiplying_program(2), fixtures::script("flawed.py", "import os\nn = int(input())\nif n % 2:\n    os.abort()\nprint(n * 2)\nprint(n)\n"));
+    assert_eq!((0..6).map(|round| compdiff::round_seed(5, round) % 10 + 1).collect::<Vec<_>>(), [5, 6, 1, 10, 7, 2]);
+    let out = compdiff(&["-g", gen.arg(), "-p", flawed.arg(), "-r", double.arg(), "-s", "5", "-c", "6", "--no-interactive", "--ascii"]).output().unwrap();
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    // as frequent as each other, the first one first
+    assert!(stdout.contains("\n [WARN] 2 distinct failure modes: SIGABRT (3 rounds), WA at line 2 (3 rounds)\n"), "{}", stdout);
+}
+
+#[test]
+fn csv_reports_have_a_row_for_every_round() {
+    use compdiff::cli::handle_cli;
+
+    let dir = scratch_dir("report_csv");
+    let (csv, brute) = (dir.join("rounds.csv"), dir.join("brute, slow.py"));
+    fs::write(&brute, "print(int(input()) * 2)\n").unwrap();
+    let gen = fixtures::script("gen.py", "import sys\nprint(int(sys.argv[1]) % 10 + 1)\n");
+    let odd = fixtures::script("odd.py", "n = int(input())\nprint(n * 2 + n % 2)\n");
+    let args = Cli::try_parse_from(["compdiff", "-g", gen.arg(), "-p", odd.arg(), "-r", brute.to_str().unwrap(), "-s", "5", "-c", "3", "--no-interactive",
+        "--report-csv", csv.to_str().unwrap()]).unwrap();
+    handle_cli(args);
+
+    let csv = fs::read_to_string(&csv).unwrap();
+    let mut lines = csv.lines();
+    assert_eq!(lines.next().unwrap(), format!("round,seed,verdict,program_ms,\"{}_ms\",peak_memory_kb,input_bytes,output_bytes", brute.display()));
+    let rows: Vec<Vec<&str>> = lines.map(|line| line.split(',').collect()).collect();
+    assert_eq!(rows.len(), 3);
+    for (round, row) in rows.iter().enumerate() {
+        let n = compdiff::round_seed(5, round as u64) % 10 + 1;
+        assert_eq!(row[..3], [round.to_string(), compdiff::round_seed(5, round as u64).to_string(), (if n % 2 == 1 { "WA" } else { "OK" }).to_string()]);
+        assert!(row[3].parse::<f64>().unwrap() > 0.0 && row[4].parse::<f64>().unwrap() > 0.0, "{:?}", row);
+        assert_eq!(row[6..], [format!("{}", n.to_string().len() + 1), format!("{}", (n * 2 + n % 2).to_string().len() + 1)]);
+    }
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn sessions_and_their_rounds_are_kept_in_the_database_across_sessions() {
+    use compdiff::cli::handle_cli;
+
+    let dir = scratch_dir("results_db");
+    let (db, program) = (dir.join("results.db"), dir.join("sol.py"));
+    let (gen, double) = (fixtures::seeded_generator(10), fixtures::multiplying_program(2));
+    let session = |source: &str| {
+        fs::write(&program, source).unwrap();
+        let args = Cli::try_parse_from(["compdiff", "-g", gen.arg(), "-p", program.to_str().unwrap(), "-r", double.arg(), "-s", "5", "-c", "3", "--no-interactive",
+            "--db", db.to_str().unwrap()]).unwrap();
+        handle_cli(args)
+    };
+    session("print(int(input()) * 2)\n");
+    session("# another build\nprint(int(input()) * 3)\n");
+
+    let conn = rusqlite::Connection::open(&db).unwrap();
+    let sessions: Vec<(i64, String, i64, i64)> = conn.prepare("SELECT id, seed, rounds, exit_code FROM sessions ORDER BY id").unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))).unwrap().map(Result::unwrap).collect();
+    assert_eq!(sessions, [(1, "5".to_string(), 3, 0), (2, "5".to_string(), 3, 1)]);
+    let builds: i64 = conn.query_row("SELECT COUNT(DISTINCT build) FROM sessions", [], |row| row.get(0)).unwrap();
+    assert_eq!(builds, 2);
+    let rounds: Vec<(i64, i64, String)> = conn.prepare("SELECT session, round, seed FROM rounds ORDER BY session, round").unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))).unwrap().map(Result::unwrap).collect();
+    assert_eq!(rounds.len(), 6);
+    assert_eq!(rounds[4], (2, 1, compdiff::round_seed(5, 1).to_string()));
+
+    let out = compdiff(&["stats", "--db", db.to_str().unwrap()]).output().unwrap();
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains(": 2 sessions, 6 rounds, ") && stdout.contains(&format!("the latest build of {} (session 2) is ", program.display())), "{}", stdout);
+    assert!(stdout.contains("the previous one (session 1)") && stdout.contains("over 3 common inputs"), "{}", stdout);
+    assert_eq!(compdiff(&["stats", "--db", dir.join("missing.db").to_str().unwrap()]).output().unwrap().status.code(), Some(2));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn failures_and_the_end_of_the_session_are_posted_to_the_notify_url() {
+    use compdiff::cli::handle_cli;
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let server = thread::spawn(move || {
+        let mut bodies = vec![];
+        for stream in listener.incoming().take(3) {
+            let mut stream = BufReader::new(stream.unwrap());
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                stream.read_line(&mut line).unwrap();
+                if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+                if line.trim().is_empty() {
+                    break;
+                }
+            }
+            let mut body = vec![0; length];
+            stream.read_exact(&mut body).unwrap();
+            stream.get_mut().write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").unwrap();
+            bodies.push(serde_json::from_slice::<serde_json::Value>(&body).unwrap());
+        }
+        bodies
+    });
+
+    let gen = fixtures::script("gen.py", "import sys\nprint(int(sys.argv[1]) % 10 + 1)\n");
+    let odd = fixtures::script("odd.py", "n = int(input())\nprint(n * 2 + n % 2)\n");
+    let double = fixtures::multiplying_program(2);
+    // 5, 1 and 7 fail among 5, 6, 1, 10, 7, 2, but only the first failure and the completion are asked for
+    let args = Cli::try_parse_from(["compdiff", "-g", gen.arg(), "-p", odd.arg(), "-r", double.arg(), "-s", "5", "-c", "6", "--no-interactive",
+        "--notify-url", &url, "--notify-on", "first-failure,completion"]).unwrap();
+    assert_eq!(handle_cli(args), compdiff::cli::EXIT_FAILURE);
+    let url = format!("{}/again", url);
+    // every notification is sent once the session is over, a third request of its own stops the server
+    thread::spawn(move || { let _ = Command::new("curl").args(["--silent", "--data-binary", "{\"event\":\"end\"}", &url]).output(); });
+
+    let mut bodies = server.join().unwrap();
+    bodies.sort_by_key(|body| body["event"].as_str().unwrap().to_string());
+    assert_eq!(bodies.iter().map(|body| body["event"].as_str().unwrap()).collect::<Vec<_>>(), ["completion", "end", "failure"]);
+    let failure = &bodies[2];
+    assert_eq!((failure["round"].as_u64(), failure["seed"].as_u64(), failure["verdict"].as_str()), (Some(0), Some(compdiff::round_seed(5, 0)), Some("WA")));
+    assert!(failure["elapsed_seconds"].as_f64().unwrap() >= 0.0 && failure["host"].is_string(), "{}", failure);
+    assert_eq!(bodies[0]["summary"]["exit_code"].as_i64(), Some(1));
+}
+
+#[test]
+fn the_reports_of_failing_rounds_go_through_the_pager() {
+    let dir = scratch_dir("pager");
+    let paged = dir.join("paged.txt");
+    let (gen, double) = (fixtures::seeded_generator(10), fixtures::multiplying_program(2));
+    let odd = fixtures::script("odd.py", "n = int(input())\nprint(n * 2 + n % 2)\n");
+    let failed: Vec<u64> = (0..4).map(|round| compdiff::round_seed(5, round)).filter(|seed| seed % 10 % 2 == 1).collect();
+    let session = |pager: &str, choice: &str| {
+        let out = compdiff(&["-g", gen.arg(), "-p", odd.arg(), "-r", double.arg(), "-s", "5", "-c", "4", "--no-interactive", "--pager", choice])
+            .env("PAGER", pager).output().unwrap();
+        String::from_utf8(out.stdout).unwrap()
+    };
+    // the summary at the end repeats the failures and the first of their mode, without the pager
+    let seeds = |text: &str| -> Vec<u64> { text.lines().filter_map(|line| line.strip_prefix("::: seed ")?.split(',').next()?.parse().ok()).collect() };
+
+    let stdout = session(&format!("cat >> '{}'", paged.display()), "always");
+    assert_eq!(seeds(&fs::read_to_string(&paged).unwrap()), failed);
+    assert_eq!(seeds(&stdout), [&failed[..], &failed[..1]].concat(), "{}", stdout);
+    fs::remove_file(&paged).unwrap();
+
+    // without a pager to run, or when it's not wanted, everything is printed
+    for (pager, choice) in [("cat", "always"), (&*format!("cat >> '{}'", paged.display()), "never"), ("", "always")] {
+        assert_eq!(seeds(&session(pager, choice)), [&failed[..], &failed[..], &failed[..1]].concat(), "{:?}", (pager, choice));
+    }
+    assert!(!paged.exists());
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn every_round_is_kept_with_its_outputs_and_commands_only_the_last_ones_with_keep_last() {
+    use compdiff::cli::handle_cli;
+
+    let dir = scratch_dir("keep_artifacts");
+    let gen = fixtures::script("gen.py", "import sys\nprint(int(sys.argv[1]) % 10 + 1)\n");
+    let odd = fixtures::script("odd.py", "n = int(input())\nprint(n * 2 + n % 2)\n");
+    let double = fixtures::multiplying_program(2);
+    let args = Cli::try_parse_from(["compdiff", "-g", gen.arg(), "-p", odd.arg(), "-r", double.arg(), "-s", "5", "-c", "4", "--no-interactive",
+        "--keep-artifacts", dir.to_str().unwrap(), "--keep-last", "2"]).unwrap();
+    handle_cli(args);
+
+    let mut kept: Vec<_> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name().into_string().unwrap()).filter(|name| name.starts_with("round_")).collect();
+    kept.sort();
+    assert_eq!(kept, ["round_0002", "round_0003"]);
+    // the input of round 2 is 1, which the programme gets wrong, passing rounds are kept all the same
+    let round = dir.join("round_0002");
+    let read = |name: &str| fs::read_to_string(round.join(name)).unwrap();
+    assert_eq!((read("input.in"), read("odd.py.out"), read("multiply.py.out"), read("odd.py.err")), ("1\n".into(), "3\n".into(), "2\n".into(), String::new()));
+    let commands = read("cmd.txt");
+    assert_eq!(commands.lines().count(), 3);
+    assert!(commands.contains(&compdiff::round_seed(5, 2).to_string()) && commands.contains(odd.arg()), "{}", commands);
+    let timing = read("timing.txt");
+    assert!(timing.starts_with(&format!("round 2: verdict WA, seed {}, took ", compdiff::round_seed(5, 2))), "{}", timing);
+    assert!(timing.contains(&format!("{}: ", double.arg())) && timing.lines().all(|line| line.starts_with("round") || line.ends_with(", exit code 0")), "{}", timing);
+    assert!(fs::read_to_string(dir.join("round_0003").join("timing.txt")).unwrap().starts_with("round 3: verdict OK"));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn the_slowest_rounds_are_listed_and_their_inputs_saved() {
+    let dir = scratch_dir("save_slowest");
+    let gen = fixtures::script("gen.py", "import sys\nprint(int(sys.argv[1]) % 10 + 1)\n");
+    let sleepy = fixtures::script("sleepy.py", "import time\nn = int(input())\ntime.sleep(n * 0.1)\nprint(n * 2)\n");
+    let double = fixtures::multiplying_program(2);
+    let out = compdiff(&["-g", gen.arg(), "-p", sleepy.arg(), "-r", double.arg(), "-s", "5", "-c", "6", "--no-interactive", "-q",
+        "--top", "2", "--save-slowest", dir.to_str().unwrap()]).output().unwrap();
+    let stdout = String::from_utf8(out.stdout).unwrap();
+
+    // the inputs are 5, 6, 1, 10, 7 and 2, the programme sleeps for a tenth of them
+    let listed: Vec<_> = stdout.lines().skip_while(|line| !line.contains("the 2 slowest rounds of the programme:")).skip(1).take(2).collect();
+    assert!(listed[0].starts_with("  round 3: ") && listed[1].starts_with("  round 4: "), "{}", stdout);
+    assert!(listed[0].ends_with(&format!(", ok, seed {}", compdiff::round_seed(5, 3))), "{}", stdout);
+    assert!(stdout.contains(&format!("saved their inputs to {}", dir.display())));
+    let mut saved: Vec<_> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name().into_string().unwrap()).filter(|name| !name.starts_with('.')).collect();
+    saved.sort();
+    assert_eq!(saved, ["round_3.in", "round_4.in", "slowest.txt"]);
+    assert_eq!(fs::read_to_string(dir.join("round_3.in")).unwrap(), "10\n");
+    let times = fs::read_to_string(dir.join("slowest.txt")).unwrap();
+    assert!(times.lines().nth(1).unwrap().ends_with(&format!(", verdict OK, seed {}", compdiff::round_seed(5, 4))), "{}", times);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[cfg(unix)]
+#[test]
+fn sources_are_compiled_with_the_chosen_cpp_compiler_which_the_reports_record() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = scratch_dir("cpp_compiler");
+    fs::write(dir.join("sol.cpp"), "#include <cstdio>\nint main(){ int n; scanf(\"%d\", &n); printf(\"%d\\n\", n * 2); }\n").unwrap();
+    // compilers logging what they compile, their versions make them build apart
+    let wrapper = |name: &str| {
+        let path = dir.join(name);
+        fs::write(&path, format!("#!/bin/sh\n[ \"$1\" = --version ] && {{ echo '{0} 1.0'; exit 0; }}\necho \"$@\" >> '{1}'\nexec g++ \"$@\"\n",
+            name, dir.join(format!("{}.log", name)).display())).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    };
+    let (chosen, cxx) = (wrapper("chosen"), wrapper("cxx"));
+    let (gen, double) = (fixtures::seeded_generator(10), fixtures::multiplying_program(2));
+    let (sol, report, build) = (dir.join("sol.cpp"), dir.join("report.json"), dir.join("build"));
+    let session = |extra: &[&str]| {
+        let mut argv = vec!["-g", gen.arg(), "-p", sol.to_str().unwrap(), "-r", double.arg(), "-c", "1", "--no-interactive",
+            "--build-dir", build.to_str().unwrap(), "--report-json", report.to_str().unwrap()];
+        argv.extend(extra);
+        let out = compdiff(&argv).env("CXX", &cxx).output().unwrap();
+        let toolchain = serde_json::from_str::<serde_json::Value>(&fs::read_to_string(&report).unwrap()).unwrap()["toolchain"].clone();
+        (out.status.code(), toolchain)
+    };
+    let logged = |name: &str| fs::read_to_string(dir.join(format!("{}.log", name))).unwrap_or_default();
+
+    let (code, toolchain) = session(&["--cpp-compiler", chosen.to_str().unwrap()]);
+    assert_eq!((code, toolchain["compiler"].as_str(), toolchain["version"].as_str()), (Some(0), chosen.to_str(), Some("chosen 1.0")));
+    assert!(logged("chosen").contains(sol.to_str().unwrap()) && logged("cxx").is_empty());
+
+    // without --cpp-compiler the CXX variable is taken
+    let (code, toolchain) = session(&[]);
+    assert_eq!((code, toolchain["version"].as_str()), (Some(0), Some("cxx 1.0")));
+    assert!(logged("cxx").contains(sol.to_str().unwrap()));
+
+    let missing = compdiff(&["-g", gen.arg(), "-p", sol.to_str().unwrap(), "-c", "1", "--no-interactive", "--cpp-compiler", "no-such-compiler"]).output().unwrap();
+    let stdout = String::from_utf8(missing.stdout).unwrap();
+    assert_eq!(missing.status.code(), Some(2));
+    assert!(stdout.contains(&format!("failed to prepare {}: couldn't find the c++ compiler no-such-compiler", sol.display())), "{}", stdout);
+    let _ = fs::remove_dir_all(&dir);
+}